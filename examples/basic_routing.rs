@@ -6,8 +6,12 @@ use smart_order_router::*;
 async fn main() -> Result<()> {
     env_logger::init();
 
-    // Create exchanges
-    let exchanges = exchanges::create_exchanges();
+    // Create exchanges; pass --demo to use simulated venues
+    let exchanges = if std::env::args().any(|arg| arg == "--demo") {
+        exchanges::create_demo_exchanges()
+    } else {
+        exchanges::create_exchanges()
+    };
     
     // Create router
     let router = router::SmartOrderRouter::new(exchanges);
@@ -79,7 +79,13 @@ impl ExecutionAnalytics {
             total_fees,
             fill_rate,
             routing.estimated_slippage,
-            routing.splits.len()
+            routing.splits.len() + routing.quotes.len()
         )
     }
 }
+
+impl Default for ExecutionAnalytics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -65,3 +65,9 @@ impl BacktestEngine {
         )
     }
 }
+
+impl Default for BacktestEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -3,15 +3,31 @@ use chrono::Utc;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
+/// Fee rate charged when nothing on the split says what the venue charges
+const DEFAULT_FEE_RATE: Decimal = dec!(0.001);
+
+/// The split's own fee rate, else the rate implied by its expected fee,
+/// falling back to the default only when neither is known
+fn fee_rate(split: &OrderSplit) -> Decimal {
+    if let Some(rate) = split.fee_rate {
+        return rate;
+    }
+    let notional = split.quantity * split.expected_price;
+    if split.expected_fee > dec!(0) && notional > dec!(0) {
+        split.expected_fee / notional
+    } else {
+        DEFAULT_FEE_RATE
+    }
+}
+
 /// Simulate order execution with realistic parameters
 pub fn simulate_execution(split: &OrderSplit) -> ExecutionResult {
     // Simulate small price variation
     let price_variation = dec!(0.0001); // 0.01% variation
     let executed_price = split.expected_price * (dec!(1.0) + price_variation);
 
-    // Simulate fees (0.1% maker/taker fee)
-    let fee_rate = dec!(0.001);
-    let fees = split.quantity * executed_price * fee_rate;
+    // Charge the venue's fee rate as priced by the router
+    let fees = split.quantity * executed_price * fee_rate(split);
 
     ExecutionResult {
         order_id: format!("ORDER_{}", Utc::now().timestamp()),
@@ -28,8 +44,7 @@ pub fn simulate_with_slippage(split: &OrderSplit, slippage_bps: Decimal) -> Exec
     let slippage = slippage_bps / dec!(10000.0); // Convert basis points to decimal
     let executed_price = split.expected_price * (dec!(1.0) + slippage);
 
-    let fee_rate = dec!(0.001);
-    let fees = split.quantity * executed_price * fee_rate;
+    let fees = split.quantity * executed_price * fee_rate(split);
 
     ExecutionResult {
         order_id: format!("ORDER_{}", Utc::now().timestamp()),
@@ -52,6 +67,9 @@ mod tests {
             exchange: "TestExchange".to_string(),
             quantity: dec!(1.0),
             expected_price: dec!(50000.0),
+            expected_fee: dec!(0),
+            fee_rate: None,
+            conversion: None,
         };
 
         let result = simulate_execution(&split);
@@ -59,4 +77,34 @@ mod tests {
         assert_eq!(result.executed_quantity, dec!(1.0));
         assert!(result.executed_price > dec!(50000.0));
     }
+
+    #[test]
+    fn test_simulate_uses_expected_fee_rate() {
+        let split = OrderSplit {
+            exchange: "TestExchange".to_string(),
+            quantity: dec!(2.0),
+            expected_price: dec!(100.0),
+            expected_fee: dec!(0.8),
+            fee_rate: None,
+            conversion: None,
+        };
+
+        let result = simulate_with_slippage(&split, dec!(0));
+        assert_eq!(result.fees, dec!(0.8));
+    }
+
+    #[test]
+    fn test_zero_fee_venue_is_simulated_without_fees() {
+        let split = OrderSplit {
+            exchange: "Promo".to_string(),
+            quantity: dec!(2.0),
+            expected_price: dec!(100.0),
+            expected_fee: dec!(0),
+            fee_rate: Some(dec!(0)),
+            conversion: None,
+        };
+
+        let result = simulate_with_slippage(&split, dec!(0));
+        assert_eq!(result.fees, dec!(0));
+    }
 }
@@ -0,0 +1,364 @@
+pub mod pool;
+
+use super::error::ExchangeError;
+use super::fees::FeeSchedule;
+use super::Exchange;
+use crate::types::{OrderBook, OrderReport, OrderRequest, TradingPair, TradingStatus};
+use anyhow::Result;
+use async_trait::async_trait;
+pub use pool::{ConcentratedPool, ConstantProductPool, Pool, PoolSnapshot, Tick};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Where an [`AmmExchange`] reads pool state from: a node, an indexer or,
+/// in tests, a fixed snapshot
+#[async_trait]
+pub trait PoolStateSource: Send + Sync {
+    /// Latest state of the pool trading `pair`, or `None` if there is none
+    async fn pool_state(&self, pair: &TradingPair) -> Result<Option<PoolSnapshot>, ExchangeError>;
+}
+
+/// Sends swaps on-chain for an [`AmmExchange`]: builds, signs and submits
+/// the transaction, and follows it until it settles or reverts
+#[async_trait]
+pub trait SwapSubmitter: Send + Sync {
+    /// Submit a swap of `request.quantity` base on the pool for
+    /// `request.pair`, reporting it as an order
+    async fn submit_swap(&self, request: &OrderRequest) -> Result<OrderReport>;
+
+    /// Current state of a submitted swap
+    async fn swap_status(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport>;
+
+    /// Find a swap by the client order ID it was submitted with; `None`
+    /// means it was never submitted
+    async fn find_swap(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>>;
+}
+
+/// Pool state held in memory and replaced by hand
+#[derive(Default)]
+pub struct StaticPoolSource {
+    pools: Mutex<HashMap<TradingPair, PoolSnapshot>>,
+}
+
+impl StaticPoolSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pool(self, pair: TradingPair, snapshot: PoolSnapshot) -> Self {
+        self.set_pool(pair, snapshot);
+        self
+    }
+
+    /// Replace a pool's state, as a new block would
+    pub fn set_pool(&self, pair: TradingPair, snapshot: PoolSnapshot) {
+        self.pools.lock().unwrap().insert(pair, snapshot);
+    }
+}
+
+#[async_trait]
+impl PoolStateSource for StaticPoolSource {
+    async fn pool_state(&self, pair: &TradingPair) -> Result<Option<PoolSnapshot>, ExchangeError> {
+        Ok(self.pools.lock().unwrap().get(pair).cloned())
+    }
+}
+
+/// On-chain AMM pools presented as an order book.
+///
+/// Each pool's price-impact curve is cut into synthetic levels with the
+/// pool fee already in their prices, so the optimizer walks them like any
+/// other book. What the curve cannot express is gas: it is charged as a
+/// fixed fee per split, which keeps small orders on centralised venues.
+/// Orders are sent through a [`SwapSubmitter`]; without one the venue is
+/// price-only and refuses them.
+pub struct AmmExchange {
+    name: String,
+    source: Arc<dyn PoolStateSource>,
+    curve_steps: usize,
+    max_price_impact: Decimal,
+    /// Gas for one swap, in the quote asset
+    gas_cost: Decimal,
+    /// Pool state only changes once a block, so books age by block time
+    max_quote_age: Duration,
+    submitter: Option<Arc<dyn SwapSubmitter>>,
+}
+
+impl AmmExchange {
+    pub fn new(name: impl Into<String>, source: Arc<dyn PoolStateSource>) -> Self {
+        Self {
+            name: name.into(),
+            source,
+            curve_steps: 20,
+            max_price_impact: dec!(0.02),
+            gas_cost: dec!(0),
+            max_quote_age: Duration::from_secs(30),
+            submitter: None,
+        }
+    }
+
+    /// Send orders on-chain through `submitter`
+    pub fn with_swap_submitter(mut self, submitter: Arc<dyn SwapSubmitter>) -> Self {
+        self.submitter = Some(submitter);
+        self
+    }
+
+    /// Cut the curve into `steps` levels covering `max_price_impact` of
+    /// price movement either side of spot
+    pub fn with_curve(mut self, steps: usize, max_price_impact: Decimal) -> Self {
+        self.curve_steps = steps;
+        self.max_price_impact = max_price_impact;
+        self
+    }
+
+    pub fn with_gas_cost(mut self, gas_cost: Decimal) -> Self {
+        self.gas_cost = gas_cost;
+        self
+    }
+
+    /// Oldest block a pool state is routed on; a couple of blocks by default
+    pub fn with_max_quote_age(mut self, max_quote_age: Duration) -> Self {
+        self.max_quote_age = max_quote_age;
+        self
+    }
+
+    fn submitter(&self) -> Result<&dyn SwapSubmitter, ExchangeError> {
+        self.submitter.as_deref().ok_or_else(|| ExchangeError::Api {
+            exchange: self.name.clone(),
+            code: None,
+            message: "no swap submitter configured; the pool is price-only".to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Exchange for AmmExchange {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        let snapshot =
+            self.source
+                .pool_state(pair)
+                .await?
+                .ok_or_else(|| ExchangeError::UnknownSymbol {
+                    exchange: self.name.clone(),
+                    symbol: pair.to_string(),
+                })?;
+        let pool = &snapshot.pool;
+        Ok(OrderBook::new(
+            self.name.as_str(),
+            pair.clone(),
+            pool.bids(self.curve_steps, self.max_price_impact),
+            pool.asks(self.curve_steps, self.max_price_impact),
+        )
+        .with_exchange_time(snapshot.block_time))
+    }
+
+    fn max_quote_age(&self) -> Option<Duration> {
+        Some(self.max_quote_age)
+    }
+
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        // An unreachable source is no evidence the pool is missing
+        !matches!(self.pair_status(pair).await, Ok(None))
+    }
+
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        Ok(self
+            .source
+            .pool_state(pair)
+            .await?
+            .map(|_| TradingStatus::Trading))
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        // The pool fee is priced into the curve
+        FeeSchedule::flat(dec!(0), dec!(0)).with_per_order_fee(self.gas_cost)
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        self.submitter()?.submit_swap(request).await
+    }
+
+    async fn cancel_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        // A swap settles or reverts in one transaction: there is nothing
+        // left to cancel
+        self.get_order_status(pair, order_id).await
+    }
+
+    async fn get_order_status(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        self.submitter()?.swap_status(pair, order_id).await
+    }
+
+    async fn get_open_orders(&self, _pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        // Swaps never rest
+        Ok(Vec::new())
+    }
+
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        self.submitter()?.find_swap(pair, client_order_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::mock::MockExchange;
+    use crate::executor::Executor;
+    use crate::router::SmartOrderRouter;
+    use crate::types::{Order, OrderSide, OrderStatus, OrderType};
+    use chrono::{TimeZone, Utc};
+
+    fn pair() -> TradingPair {
+        TradingPair::new("BTC", "USDC")
+    }
+
+    fn pool() -> Pool {
+        Pool::ConstantProduct(ConstantProductPool {
+            reserve_base: dec!(100),
+            reserve_quote: dec!(5000000),
+            fee: dec!(0.0005),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pool_state_becomes_a_book() {
+        let block_time = Utc.with_ymd_and_hms(2024, 10, 18, 12, 0, 0).unwrap();
+        let source = StaticPoolSource::new().with_pool(
+            pair(),
+            PoolSnapshot::new(pool()).with_block_time(block_time),
+        );
+        let amm = AmmExchange::new("Uniswap-V2", Arc::new(source)).with_curve(10, dec!(0.01));
+
+        let book = amm.get_liquidity(&pair()).await.unwrap();
+        assert_eq!(book.exchange, "Uniswap-V2");
+        assert_eq!(book.asks.len(), 10);
+        assert_eq!(book.bids.len(), 10);
+        assert!(book.bids[0].price < dec!(50000) && book.asks[0].price > dec!(50000));
+        assert_eq!(book.exchange_time, Some(block_time));
+
+        let missing = TradingPair::new("ETH", "USDC");
+        assert!(matches!(
+            amm.get_liquidity(&missing).await,
+            Err(ExchangeError::UnknownSymbol { .. })
+        ));
+        assert!(!amm.supports_pair(&missing).await);
+    }
+
+    #[tokio::test]
+    async fn test_gas_keeps_small_orders_off_the_pool() {
+        let cex = MockExchange::new("CEX")
+            .with_book(
+                pair(),
+                &[(dec!(49900), dec!(10))],
+                &[(dec!(50100), dec!(0.05)), (dec!(50300), dec!(10))],
+            )
+            .with_fees(FeeSchedule::flat(dec!(0), dec!(0)));
+        let source = StaticPoolSource::new().with_pool(pair(), PoolSnapshot::new(pool()));
+        let amm = AmmExchange::new("Uniswap-V2", Arc::new(source)).with_gas_cost(dec!(20));
+        let router = SmartOrderRouter::new(vec![Box::new(cex), Box::new(amm)]);
+        let buy = |quantity| Order {
+            pair: pair(),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity,
+            limit_price: None,
+        };
+
+        // The pool quotes under 50100 but saves less than its gas
+        let routing = router.route_order(&buy(dec!(0.01))).await.unwrap();
+        assert_eq!(routing.splits.len(), 1);
+        assert_eq!(routing.splits[0].exchange, "CEX");
+
+        // Deep into the CEX book, the pool's curve pays for the swap
+        let routing = router.route_order(&buy(dec!(2))).await.unwrap();
+        let swap = routing
+            .splits
+            .iter()
+            .find(|split| split.exchange == "Uniswap-V2")
+            .expect("pool should take part of a large order");
+        assert_eq!(swap.expected_fee, dec!(20));
+        assert!(swap.expected_price < dec!(50300));
+        assert_eq!(routing.total_quantity, dec!(2));
+    }
+
+    /// Settles every swap in full at a fixed price
+    struct SettlingSubmitter;
+
+    #[async_trait]
+    impl SwapSubmitter for SettlingSubmitter {
+        async fn submit_swap(&self, request: &OrderRequest) -> Result<OrderReport> {
+            Ok(OrderReport {
+                order_id: "0xabc".to_string(),
+                client_order_id: request.client_order_id.clone(),
+                exchange: "Uniswap-V2".to_string(),
+                pair: request.pair.clone(),
+                side: request.side,
+                status: OrderStatus::Filled,
+                quantity: request.quantity,
+                filled_quantity: request.quantity,
+                average_price: dec!(50025),
+                fees: dec!(0),
+                fills: Vec::new(),
+                updated_at: Utc::now(),
+            })
+        }
+
+        async fn swap_status(&self, _pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+            anyhow::bail!("unexpected status query for {}", order_id)
+        }
+
+        async fn find_swap(
+            &self,
+            _pair: &TradingPair,
+            _client_order_id: &str,
+        ) -> Result<Option<OrderReport>> {
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_swaps_need_a_submitter() {
+        let amm = || {
+            let source = StaticPoolSource::new().with_pool(pair(), PoolSnapshot::new(pool()));
+            AmmExchange::new("Uniswap-V2", Arc::new(source))
+        };
+        let order = Order {
+            pair: pair(),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(0.5),
+            limit_price: None,
+        };
+
+        // Price-only: the swap is refused outright, not simulated
+        let router = SmartOrderRouter::new(vec![Box::new(amm())]);
+        let routing = router.route_order(&order).await.unwrap();
+        let report = Executor::new(&router).execute(&routing).await.unwrap();
+        assert_eq!(report.children[0].status, OrderStatus::Rejected);
+        assert_eq!(report.filled_quantity, dec!(0));
+
+        let router = SmartOrderRouter::new(vec![Box::new(
+            amm().with_swap_submitter(Arc::new(SettlingSubmitter)),
+        )]);
+        let routing = router.route_order(&order).await.unwrap();
+        let report = Executor::new(&router).execute(&routing).await.unwrap();
+        assert_eq!(report.status(), OrderStatus::Filled);
+        assert_eq!(report.average_price, dec!(50025));
+    }
+}
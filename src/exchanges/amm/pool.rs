@@ -0,0 +1,329 @@
+use crate::types::PriceLevel;
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, MathematicalOps, RoundingStrategy};
+use rust_decimal_macros::dec;
+
+/// Decimal places synthetic levels are quoted to
+const LEVEL_DP: u32 = 8;
+
+/// Uniswap-v2 style pool holding both assets across the whole price range
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstantProductPool {
+    pub reserve_base: Decimal,
+    pub reserve_quote: Decimal,
+    /// Swap fee taken from the input amount, as a fraction
+    pub fee: Decimal,
+}
+
+/// An initialised tick of a concentrated-liquidity pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tick {
+    /// Price (quote per base) at the tick boundary
+    pub price: Decimal,
+    /// Liquidity added when the price crosses the tick upwards and removed
+    /// when it crosses downwards
+    pub liquidity_net: Decimal,
+}
+
+/// Uniswap-v3 style pool whose liquidity sits in price ranges.
+///
+/// Prices and liquidity are in whole tokens: sources convert on-chain
+/// `sqrtPriceX96`, raw liquidity and tick indices (see [`tick_price`])
+/// before handing state over.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcentratedPool {
+    /// Current price, quote per base
+    pub price: Decimal,
+    /// Liquidity active at the current price
+    pub liquidity: Decimal,
+    /// Initialised ticks on either side of the current price, in any order
+    pub ticks: Vec<Tick>,
+    /// Swap fee taken from the input amount, as a fraction
+    pub fee: Decimal,
+}
+
+/// State of an AMM pool
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pool {
+    ConstantProduct(ConstantProductPool),
+    Concentrated(ConcentratedPool),
+}
+
+/// A pool's state as of a block
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolSnapshot {
+    pub pool: Pool,
+    /// Timestamp of the block the state was read at, if known
+    pub block_time: Option<DateTime<Utc>>,
+}
+
+impl PoolSnapshot {
+    pub fn new(pool: Pool) -> Self {
+        Self {
+            pool,
+            block_time: None,
+        }
+    }
+
+    pub fn with_block_time(mut self, block_time: DateTime<Utc>) -> Self {
+        self.block_time = Some(block_time);
+        self
+    }
+}
+
+/// Price in whole tokens at a Uniswap-v3 tick index, for a pool whose
+/// token0 is the base asset. `None` if the tick is out of range.
+pub fn tick_price(index: i32, base_decimals: u32, quote_decimals: u32) -> Option<Decimal> {
+    let raw = dec!(1.0001).checked_powi(index.into())?;
+    let shift = dec!(10).checked_powi(i64::from(base_decimals) - i64::from(quote_decimals))?;
+    raw.checked_mul(shift)
+}
+
+impl Pool {
+    /// Price-impact curve for buying base, as ask levels best first.
+    ///
+    /// The curve is cut every `max_price_impact / steps` of marginal price
+    /// movement; each level is priced at the average the swap pays over
+    /// its slice, pool fee included.
+    pub fn asks(&self, steps: usize, max_price_impact: Decimal) -> Vec<PriceLevel> {
+        self.curve()
+            .map(|curve| curve.levels(true, steps, max_price_impact))
+            .unwrap_or_default()
+    }
+
+    /// Price-impact curve for selling base, as bid levels best first
+    pub fn bids(&self, steps: usize, max_price_impact: Decimal) -> Vec<PriceLevel> {
+        self.curve()
+            .map(|curve| curve.levels(false, steps, max_price_impact))
+            .unwrap_or_default()
+    }
+
+    /// Both models as liquidity over square-root price; a constant-product
+    /// pool is one range covering every price. `None` for an empty pool.
+    fn curve(&self) -> Option<Curve> {
+        match self {
+            Pool::ConstantProduct(pool) => {
+                if pool.reserve_base <= dec!(0) || pool.reserve_quote <= dec!(0) {
+                    return None;
+                }
+                Some(Curve {
+                    sqrt_price: (pool.reserve_quote / pool.reserve_base).sqrt()?,
+                    liquidity: (pool.reserve_base * pool.reserve_quote).sqrt()?,
+                    ticks: Vec::new(),
+                    fee: pool.fee,
+                })
+            }
+            Pool::Concentrated(pool) => {
+                let mut ticks = pool
+                    .ticks
+                    .iter()
+                    .map(|tick| Some((tick.price.sqrt()?, tick.liquidity_net)))
+                    .collect::<Option<Vec<_>>>()?;
+                ticks.sort_by_key(|&(sqrt_price, _)| sqrt_price);
+                Some(Curve {
+                    sqrt_price: pool.price.sqrt().filter(|p| *p > dec!(0))?,
+                    liquidity: pool.liquidity,
+                    ticks,
+                    fee: pool.fee,
+                })
+            }
+        }
+    }
+}
+
+/// Liquidity over square-root price, with the ticks where it changes
+struct Curve {
+    sqrt_price: Decimal,
+    liquidity: Decimal,
+    /// `(sqrt price, liquidity net)`, ascending
+    ticks: Vec<(Decimal, Decimal)>,
+    fee: Decimal,
+}
+
+impl Curve {
+    /// Swap along the curve in `steps` equal slices of marginal price.
+    ///
+    /// Within a range of liquidity `L`, moving between square-root prices
+    /// `a < b` trades `L * (1/a - 1/b)` base for `L * (b - a)` quote.
+    /// Slices across empty ranges yield no level; the walk stops once no
+    /// liquidity is left in the direction of travel.
+    fn levels(&self, upward: bool, steps: usize, max_price_impact: Decimal) -> Vec<PriceLevel> {
+        let price = self.sqrt_price * self.sqrt_price;
+        let mut crossings: Vec<(Decimal, Decimal)> = if upward {
+            self.ticks
+                .iter()
+                .copied()
+                .filter(|&(tick, _)| tick > self.sqrt_price)
+                .collect()
+        } else {
+            self.ticks
+                .iter()
+                .rev()
+                .copied()
+                .filter(|&(tick, _)| tick <= self.sqrt_price)
+                .collect()
+        };
+        crossings.reverse();
+
+        let mut sqrt_price = self.sqrt_price;
+        let mut liquidity = self.liquidity;
+        let mut levels = Vec::new();
+        for step in 1..=steps {
+            if liquidity <= dec!(0) && crossings.is_empty() {
+                break;
+            }
+            let impact = max_price_impact * Decimal::from(step) / Decimal::from(steps);
+            let target_price = if upward {
+                price * (dec!(1) + impact)
+            } else {
+                price * (dec!(1) - impact)
+            };
+            let Some(target) = target_price.sqrt().filter(|t| *t > dec!(0)) else {
+                break;
+            };
+
+            let (mut base, mut quote) = (dec!(0), dec!(0));
+            loop {
+                let next_tick = crossings.last().map(|&(tick, _)| tick).filter(|&tick| {
+                    if upward {
+                        tick <= target
+                    } else {
+                        tick >= target
+                    }
+                });
+                let stop = next_tick.unwrap_or(target);
+                if liquidity > dec!(0) {
+                    let (low, high) = if upward {
+                        (sqrt_price, stop)
+                    } else {
+                        (stop, sqrt_price)
+                    };
+                    base += liquidity * (dec!(1) / low - dec!(1) / high);
+                    quote += liquidity * (high - low);
+                }
+                sqrt_price = stop;
+
+                if next_tick.is_none() {
+                    break;
+                }
+                if let Some((_, net)) = crossings.pop() {
+                    liquidity += if upward { net } else { -net };
+                }
+            }
+
+            if base <= dec!(0) {
+                continue;
+            }
+            // The fee comes off the input: quote when buying, base when selling
+            let (quantity, level_price) = if upward {
+                (base, quote / (dec!(1) - self.fee) / base)
+            } else {
+                let gross = base / (dec!(1) - self.fee);
+                (gross, quote / gross)
+            };
+            let quantity = quantity.round_dp_with_strategy(LEVEL_DP, RoundingStrategy::ToZero);
+            let level_price = level_price.round_dp_with_strategy(
+                LEVEL_DP,
+                if upward {
+                    RoundingStrategy::AwayFromZero
+                } else {
+                    RoundingStrategy::ToZero
+                },
+            );
+            if quantity > dec!(0) {
+                levels.push(PriceLevel::new(level_price, quantity));
+            }
+        }
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn btc_usdc() -> Pool {
+        Pool::ConstantProduct(ConstantProductPool {
+            reserve_base: dec!(100),
+            reserve_quote: dec!(5000000),
+            fee: dec!(0.003),
+        })
+    }
+
+    fn totals(levels: &[PriceLevel]) -> (Decimal, Decimal) {
+        levels.iter().fold((dec!(0), dec!(0)), |(q, n), level| {
+            (q + level.quantity, n + level.quantity * level.price)
+        })
+    }
+
+    #[test]
+    fn test_constant_product_curve_matches_swap_formula() {
+        let pool = btc_usdc();
+        let k = dec!(100) * dec!(5000000);
+
+        let asks = pool.asks(10, dec!(0.05));
+        assert_eq!(asks.len(), 10);
+        assert!(asks.windows(2).all(|w| w[0].price < w[1].price));
+        // Spot plus fee is the floor; the first slice moves price 0.5%
+        assert!(asks[0].price > dec!(50000) / dec!(0.997));
+        assert!(asks[0].price < dec!(50250) / dec!(0.997));
+
+        // Buying everything moves the pool to 52500
+        let reserve_base = (k / dec!(52500)).sqrt().unwrap();
+        let (quantity, cost) = totals(&asks);
+        assert!((quantity - (dec!(100) - reserve_base)).abs() < dec!(0.000001));
+        let expected_cost = (k / reserve_base - dec!(5000000)) / dec!(0.997);
+        assert!((cost - expected_cost).abs() < dec!(0.01));
+
+        let bids = pool.bids(10, dec!(0.05));
+        assert!(bids.windows(2).all(|w| w[0].price > w[1].price));
+        assert!(bids[0].price < dec!(50000) * dec!(0.997));
+        let reserve_base = (k / dec!(47500)).sqrt().unwrap();
+        let (quantity, proceeds) = totals(&bids);
+        assert!((quantity - (reserve_base - dec!(100)) / dec!(0.997)).abs() < dec!(0.000001));
+        assert!((proceeds - (dec!(5000000) - k / reserve_base)).abs() < dec!(0.01));
+    }
+
+    #[test]
+    fn test_concentrated_curve_ends_with_its_range() {
+        let liquidity = dec!(1000);
+        let pool = Pool::Concentrated(ConcentratedPool {
+            price: dec!(2000),
+            liquidity,
+            ticks: vec![
+                Tick {
+                    price: dec!(2100),
+                    liquidity_net: -liquidity,
+                },
+                Tick {
+                    price: dec!(1900),
+                    liquidity_net: liquidity,
+                },
+            ],
+            fee: dec!(0.0005),
+        });
+
+        // Asking for 10% of impact only finds the 5% the range covers
+        let asks = pool.asks(20, dec!(0.10));
+        assert_eq!(asks.len(), 10);
+        let (quantity, _) = totals(&asks);
+        let spot = dec!(2000).sqrt().unwrap();
+        let upper = dec!(2100).sqrt().unwrap();
+        let expected = liquidity * (dec!(1) / spot - dec!(1) / upper);
+        assert!((quantity - expected).abs() < dec!(0.000001));
+        assert!(asks.last().unwrap().price < dec!(2100) / dec!(0.9995));
+
+        let bids = pool.bids(20, dec!(0.10));
+        assert_eq!(bids.len(), 10);
+        assert!(bids.last().unwrap().price > dec!(1900) * dec!(0.9995));
+    }
+
+    #[test]
+    fn test_tick_price_adjusts_for_token_decimals() {
+        assert_eq!(tick_price(0, 8, 6), Some(dec!(100)));
+        // WETH/USDC around 2000 sits near tick -200311
+        let price = tick_price(-200311, 18, 6).unwrap();
+        assert!((price - dec!(2000)).abs() < dec!(0.2));
+        assert_eq!(tick_price(887272, 18, 6), None);
+    }
+}
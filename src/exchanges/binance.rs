@@ -1,34 +1,516 @@
+use super::auth::{self, Credentials, ServerClock};
+use super::config::ConnectorConfig;
+use super::error::{self, ExchangeError};
+use super::fees::FeeSchedule;
+use super::pairs::PairDirectory;
+use super::rate_limit::{RateLimit, RateLimiter};
 use super::Exchange;
-use crate::types::{Liquidity, TradingPair};
+use crate::types::{
+    Balance, Fill, InstrumentRules, OrderBook, OrderReport, OrderRequest, OrderSide, OrderStatus,
+    OrderType, PairListing, PriceLevel, TradingPair, TradingStatus,
+};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use reqwest::Method;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct BinanceOrderBook {
+    last_update_id: u64,
     bids: Vec<(String, String)>,
     asks: Vec<(String, String)>,
 }
 
+#[derive(Debug, Deserialize)]
+struct BinanceError {
+    code: i64,
+    msg: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BinanceExchangeInfo {
+    symbols: Vec<BinanceSymbol>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceSymbol {
+    symbol: String,
+    status: String,
+    base_asset: String,
+    quote_asset: String,
+    filters: Vec<BinanceFilter>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "filterType")]
+enum BinanceFilter {
+    #[serde(rename = "PRICE_FILTER", rename_all = "camelCase")]
+    Price { tick_size: String },
+    #[serde(rename = "LOT_SIZE", rename_all = "camelCase")]
+    LotSize {
+        min_qty: String,
+        max_qty: String,
+        step_size: String,
+    },
+    #[serde(rename = "NOTIONAL", rename_all = "camelCase")]
+    Notional { min_notional: String },
+    /// Older symbols still carry the pre-2023 filter name
+    #[serde(rename = "MIN_NOTIONAL", rename_all = "camelCase")]
+    MinNotional { min_notional: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct BinanceAccount {
+    balances: Vec<BinanceBalance>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BinanceBalance {
+    asset: String,
+    free: String,
+    locked: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceServerTime {
+    server_time: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceOrder {
+    order_id: u64,
+    client_order_id: String,
+    orig_qty: String,
+    executed_qty: String,
+    cummulative_quote_qty: String,
+    status: String,
+    side: String,
+    #[serde(default)]
+    transact_time: Option<i64>,
+    #[serde(default)]
+    update_time: Option<i64>,
+    #[serde(default)]
+    fills: Vec<BinanceFill>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceFill {
+    price: String,
+    qty: String,
+    commission: String,
+    commission_asset: String,
+}
+
+pub(crate) fn parse_levels(levels: &[(String, String)]) -> Result<Vec<PriceLevel>> {
+    levels
+        .iter()
+        .map(|(price, quantity)| {
+            Ok(PriceLevel::new(
+                Decimal::from_str(price)?,
+                Decimal::from_str(quantity)?,
+            ))
+        })
+        .collect()
+}
+
+/// Binance global spot API
+pub const DEFAULT_BASE_URL: &str = "https://api.binance.com";
+/// Binance.US spot API
+pub const BINANCE_US_BASE_URL: &str = "https://api.binance.us";
+/// Binance spot testnet
+pub const TESTNET_BASE_URL: &str = "https://testnet.binance.vision";
+
+/// How long a signed request stays valid after its timestamp
+const RECV_WINDOW_MS: u64 = 5000;
+
 pub struct BinanceExchange {
     client: reqwest::Client,
-    base_url: String,
+    config: ConnectorConfig,
+    clock: ServerClock,
+    /// Instrument rules change rarely, so each pair is fetched once
+    rules: Mutex<HashMap<TradingPair, InstrumentRules>>,
+    pairs: PairDirectory,
+    limiter: RateLimiter,
 }
 
 impl BinanceExchange {
     pub fn new() -> Self {
-        Self {
-            client: reqwest::Client::new(),
-            base_url: "https://api.binance.com".to_string(),
-        }
+        Self::with_config(Self::default_config()).expect("default Binance configuration is valid")
+    }
+
+    pub fn with_config(config: ConnectorConfig) -> Result<Self> {
+        Ok(Self {
+            client: config.build_client()?,
+            clock: ServerClock::new(),
+            rules: Mutex::new(HashMap::new()),
+            pairs: PairDirectory::new(config.pair_refresh),
+            limiter: config.rate_limiter("Binance"),
+            config,
+        })
+    }
+
+    /// Spot allows 6000 request weight per minute per IP
+    pub fn default_config() -> ConnectorConfig {
+        ConnectorConfig::new(DEFAULT_BASE_URL)
+            .with_rate_limit(RateLimit::new(6000, Duration::from_secs(60)))
+    }
+
+    /// Measure the offset to Binance's clock so signed timestamps fall
+    /// inside the receive window
+    pub async fn sync_clock(&self) -> Result<(), ExchangeError> {
+        let sent = chrono::Utc::now().timestamp_millis();
+        let time: BinanceServerTime = self.send_request(Method::GET, "/api/v3/time", &[]).await?;
+        let received = chrono::Utc::now().timestamp_millis();
+        self.clock.observe(time.server_time, sent, received);
+        Ok(())
+    }
+
+    pub fn clock(&self) -> &ServerClock {
+        &self.clock
+    }
+
+    fn credentials(&self) -> Result<&Credentials, ExchangeError> {
+        self.config
+            .credentials
+            .as_ref()
+            .ok_or_else(|| ExchangeError::missing_credentials(self.name()))
     }
 
-    fn format_symbol(&self, pair: &TradingPair) -> String {
+    pub(crate) fn format_symbol(&self, pair: &TradingPair) -> String {
         format!("{}{}", pair.base.to_uppercase(), pair.quote.to_uppercase())
     }
+
+    /// Send a request and decode the JSON body, classifying Binance's
+    /// `{code, msg}` errors
+    async fn send_request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, ExchangeError> {
+        let url = format!("{}{}", self.config.base_url, path);
+        let weight = request_weight(&method, path, params);
+        self.send(
+            self.client.request(method, &url).query(params),
+            weight,
+            params,
+        )
+        .await
+    }
+
+    /// Send a `SIGNED` (TRADE / USER_DATA) request.
+    ///
+    /// `recvWindow` and `timestamp` are appended and the full query string
+    /// is signed with the account's secret. If Binance rejects the
+    /// timestamp (-1021) the clock is resynchronised and the request retried
+    /// once.
+    async fn send_signed<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, ExchangeError> {
+        match self.try_send_signed(method.clone(), path, params).await {
+            Err(ExchangeError::Api {
+                code: Some(-1021), ..
+            }) => {
+                self.sync_clock().await?;
+                self.try_send_signed(method, path, params).await
+            }
+            result => result,
+        }
+    }
+
+    async fn try_send_signed<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, ExchangeError> {
+        let credentials = self.credentials()?;
+        let weight = request_weight(&method, path, params);
+        let mut url = reqwest::Url::parse(&format!("{}{}", self.config.base_url, path))
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+        url.query_pairs_mut()
+            .extend_pairs(params.iter().map(|(key, value)| (*key, value.as_str())))
+            .append_pair("recvWindow", &RECV_WINDOW_MS.to_string())
+            .append_pair("timestamp", &self.clock.now_millis().to_string());
+        let signature =
+            auth::sign_binance(credentials.api_secret(), url.query().unwrap_or_default());
+        url.query_pairs_mut().append_pair("signature", &signature);
+
+        let request = self
+            .client
+            .request(method, url)
+            .header("X-MBX-APIKEY", &credentials.api_key);
+        self.send(request, weight, params).await
+    }
+
+    /// Spend `weight` from the shared limiter, send, and keep the limiter in
+    /// step with the weight Binance reports as used
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+        weight: u32,
+        params: &[(&str, String)],
+    ) -> Result<T, ExchangeError> {
+        self.limiter.acquire(weight).await?;
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+
+        let status = response.status();
+        let retry_after = error::retry_after(response.headers());
+        if let Some(used) = response
+            .headers()
+            .get("X-MBX-USED-WEIGHT-1M")
+            .and_then(|value| value.to_str().ok()?.parse().ok())
+        {
+            self.limiter.observe_usage(used);
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+        if !status.is_success() {
+            let error = self.classify_error(status, retry_after, &body, params);
+            self.limiter.observe(&error);
+            return Err(error);
+        }
+
+        serde_json::from_str(&body).map_err(|e| ExchangeError::parse(self.name(), e))
+    }
+
+    fn classify_error(
+        &self,
+        status: reqwest::StatusCode,
+        retry_after: Option<Duration>,
+        body: &str,
+        params: &[(&str, String)],
+    ) -> ExchangeError {
+        let exchange = self.name().to_string();
+        if matches!(status.as_u16(), 429 | 418) {
+            return ExchangeError::RateLimited {
+                exchange,
+                retry_after,
+            };
+        }
+
+        match serde_json::from_str::<BinanceError>(body) {
+            // -1121: Invalid symbol
+            Ok(error) if error.code == -1121 => ExchangeError::UnknownSymbol {
+                exchange,
+                symbol: params
+                    .iter()
+                    .find(|(key, _)| *key == "symbol")
+                    .map(|(_, symbol)| symbol.clone())
+                    .unwrap_or_default(),
+            },
+            Ok(_) if status.as_u16() == 503 => ExchangeError::Maintenance { exchange },
+            // -2014/-2015: API key format invalid / rejected
+            Ok(error) if matches!(error.code, -2014 | -2015) => ExchangeError::Auth {
+                exchange,
+                message: error.msg,
+            },
+            Ok(error) => ExchangeError::Api {
+                exchange,
+                code: Some(error.code),
+                message: error.msg,
+            },
+            Err(_) => ExchangeError::from_status(&exchange, status, body.to_string()),
+        }
+    }
+
+    /// Depth snapshot together with the `lastUpdateId` it was taken at,
+    /// for synchronising a diff stream
+    pub async fn depth_snapshot(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<(u64, OrderBook), ExchangeError> {
+        let params = [
+            ("symbol", self.format_symbol(pair)),
+            ("limit", self.config.depth_limit.to_string()),
+        ];
+        let order_book: BinanceOrderBook = self
+            .send_request(Method::GET, "/api/v3/depth", &params)
+            .await?;
+
+        // REST depth carries no timestamp, so the book is as old as our receipt
+        let book = OrderBook::new(
+            self.name(),
+            pair.clone(),
+            parse_levels(&order_book.bids).map_err(|e| ExchangeError::parse(self.name(), e))?,
+            parse_levels(&order_book.asks).map_err(|e| ExchangeError::parse(self.name(), e))?,
+        );
+        Ok((order_book.last_update_id, book))
+    }
+
+    /// Every symbol in `exchangeInfo`
+    async fn fetch_listings(&self) -> Result<Vec<PairListing>, ExchangeError> {
+        let info: BinanceExchangeInfo = self
+            .send_request(Method::GET, "/api/v3/exchangeInfo", &[])
+            .await?;
+        Ok(info
+            .symbols
+            .into_iter()
+            .map(|symbol| PairListing {
+                pair: TradingPair::new(symbol.base_asset, symbol.quote_asset),
+                status: parse_trading_status(&symbol.status),
+                symbol: symbol.symbol,
+            })
+            .collect())
+    }
+
+    fn to_report(&self, pair: &TradingPair, order: BinanceOrder) -> Result<OrderReport> {
+        let quantity = Decimal::from_str(&order.orig_qty)?;
+        let filled_quantity = Decimal::from_str(&order.executed_qty)?;
+        let quote_quantity = Decimal::from_str(&order.cummulative_quote_qty)?;
+        let updated_at = order
+            .transact_time
+            .or(order.update_time)
+            .and_then(|ms| Utc.timestamp_millis_opt(ms).single())
+            .unwrap_or_else(Utc::now);
+
+        let fills = order
+            .fills
+            .iter()
+            .map(|fill| {
+                let price = Decimal::from_str(&fill.price)?;
+                let commission = Decimal::from_str(&fill.commission)?;
+                // Commission charged in the base asset is converted to quote;
+                // quote or third-asset (BNB) commissions are taken as reported
+                let fee = if fill.commission_asset.eq_ignore_ascii_case(&pair.base) {
+                    commission * price
+                } else {
+                    commission
+                };
+                Ok(Fill {
+                    price,
+                    quantity: Decimal::from_str(&fill.qty)?,
+                    fee,
+                    timestamp: updated_at,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(OrderReport {
+            order_id: order.order_id.to_string(),
+            client_order_id: order.client_order_id,
+            exchange: self.name().to_string(),
+            pair: pair.clone(),
+            side: parse_side(&order.side)?,
+            status: parse_status(&order.status)?,
+            quantity,
+            filled_quantity,
+            average_price: if filled_quantity > dec!(0) {
+                quote_quantity / filled_quantity
+            } else {
+                dec!(0)
+            },
+            fees: fills.iter().map(|f| f.fee).sum(),
+            fills,
+            updated_at,
+        })
+    }
+}
+
+fn parse_rules(symbol: &BinanceSymbol) -> Result<InstrumentRules> {
+    let mut rules = InstrumentRules::default();
+    for filter in &symbol.filters {
+        match filter {
+            BinanceFilter::Price { tick_size } => rules.tick_size = Decimal::from_str(tick_size)?,
+            BinanceFilter::LotSize {
+                min_qty,
+                max_qty,
+                step_size,
+            } => {
+                rules.step_size = Decimal::from_str(step_size)?;
+                rules.min_quantity = Decimal::from_str(min_qty)?;
+                rules.max_quantity = Some(Decimal::from_str(max_qty)?);
+            }
+            BinanceFilter::Notional { min_notional }
+            | BinanceFilter::MinNotional { min_notional } => {
+                rules.min_notional = Decimal::from_str(min_notional)?
+            }
+            BinanceFilter::Other => {}
+        }
+    }
+    Ok(rules)
+}
+
+/// Request weight Binance charges for an endpoint
+fn request_weight(method: &Method, path: &str, params: &[(&str, String)]) -> u32 {
+    let param = |key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value.as_str())
+    };
+    match (method.as_str(), path) {
+        (_, "/api/v3/depth") => match param("limit").and_then(|limit| limit.parse().ok()) {
+            Some(0..=100) | None => 5,
+            Some(101..=500) => 25,
+            Some(501..=1000) => 50,
+            Some(_) => 250,
+        },
+        (_, "/api/v3/exchangeInfo") | (_, "/api/v3/account") => 20,
+        ("GET", "/api/v3/order") => 4,
+        (_, "/api/v3/openOrders") if param("symbol").is_some() => 6,
+        (_, "/api/v3/openOrders") => 80,
+        _ => 1,
+    }
+}
+
+/// Binance keeps delisted symbols in `exchangeInfo` with status `BREAK`,
+/// indistinguishable from a temporary break, so both read as halted
+fn parse_trading_status(status: &str) -> TradingStatus {
+    match status {
+        "TRADING" => TradingStatus::Trading,
+        _ => TradingStatus::Halted,
+    }
+}
+
+fn parse_side(side: &str) -> Result<OrderSide> {
+    match side {
+        "BUY" => Ok(OrderSide::Buy),
+        "SELL" => Ok(OrderSide::Sell),
+        other => anyhow::bail!("Unknown Binance order side {}", other),
+    }
+}
+
+fn parse_status(status: &str) -> Result<OrderStatus> {
+    match status {
+        "NEW" | "PENDING_NEW" => Ok(OrderStatus::New),
+        "PARTIALLY_FILLED" => Ok(OrderStatus::PartiallyFilled),
+        // Whatever filled before the cancel is in executedQty
+        "PENDING_CANCEL" => Ok(OrderStatus::PendingCancel),
+        "FILLED" => Ok(OrderStatus::Filled),
+        "CANCELED" => Ok(OrderStatus::Cancelled),
+        "REJECTED" => Ok(OrderStatus::Rejected),
+        "EXPIRED" | "EXPIRED_IN_MATCH" => Ok(OrderStatus::Expired),
+        other => anyhow::bail!("Unknown Binance order status {}", other),
+    }
+}
+
+impl Default for BinanceExchange {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[async_trait]
@@ -37,49 +519,480 @@ impl Exchange for BinanceExchange {
         "Binance"
     }
 
-    async fn get_liquidity(&self, pair: &TradingPair) -> Result<Liquidity> {
-        let symbol = self.format_symbol(pair);
-        let url = format!("{}/api/v3/depth?symbol={}&limit=10", self.base_url, symbol);
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        let (_, book) = self.depth_snapshot(pair).await?;
+        Ok(book)
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        // An unreachable listing is no evidence the pair is missing
+        !matches!(self.pair_status(pair).await, Ok(None))
+    }
+
+    async fn list_pairs(&self) -> Result<Vec<PairListing>> {
+        Ok(self
+            .pairs
+            .listings(self.name(), || self.fetch_listings())
+            .await?)
+    }
+
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        self.pairs
+            .status(self.name(), pair, || self.fetch_listings())
             .await
-            .context("Failed to fetch Binance order book")?;
-
-        if !response.status().is_success() {
-            // Return mock data if API fails
-            return Ok(Liquidity {
-                exchange: self.name().to_string(),
-                pair: pair.clone(),
-                bid_price: dec!(50000.0),
-                bid_quantity: dec!(1.5),
-                ask_price: dec!(50050.0),
-                ask_quantity: dec!(2.0),
-            });
+    }
+
+    async fn instrument_rules(&self, pair: &TradingPair) -> Result<InstrumentRules, ExchangeError> {
+        if let Some(rules) = self.rules.lock().unwrap().get(pair) {
+            return Ok(rules.clone());
         }
 
-        let order_book: BinanceOrderBook = response
-            .json()
+        let params = [("symbol", self.format_symbol(pair))];
+        let info: BinanceExchangeInfo = self
+            .send_request(Method::GET, "/api/v3/exchangeInfo", &params)
+            .await?;
+        let symbol = info
+            .symbols
+            .first()
+            .ok_or_else(|| ExchangeError::parse(self.name(), "exchangeInfo has no symbol"))?;
+        let rules = parse_rules(symbol).map_err(|e| ExchangeError::parse(self.name(), e))?;
+
+        self.rules
+            .lock()
+            .unwrap()
+            .insert(pair.clone(), rules.clone());
+        Ok(rules)
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        FeeSchedule::flat(dec!(0.001), dec!(0.001))
+            .with_tier(dec!(1000000), dec!(0.0009), dec!(0.001))
+            .with_tier(dec!(5000000), dec!(0.0008), dec!(0.001))
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        let mut params = vec![
+            ("symbol", self.format_symbol(&request.pair)),
+            (
+                "side",
+                match request.side {
+                    OrderSide::Buy => "BUY",
+                    OrderSide::Sell => "SELL",
+                }
+                .to_string(),
+            ),
+            ("quantity", request.quantity.normalize().to_string()),
+            ("newClientOrderId", request.client_order_id.clone()),
+            ("newOrderRespType", "FULL".to_string()),
+        ];
+        match request.order_type {
+            OrderType::Market => params.push(("type", "MARKET".to_string())),
+            OrderType::Limit => {
+                let price = request
+                    .limit_price
+                    .context("Limit order has no limit price")?;
+                params.push(("type", "LIMIT".to_string()));
+                params.push(("timeInForce", "GTC".to_string()));
+                params.push(("price", price.normalize().to_string()));
+            }
+        }
+
+        let order: BinanceOrder = self
+            .send_signed(Method::POST, "/api/v3/order", &params)
+            .await?;
+        self.to_report(&request.pair, order)
+    }
+
+    async fn cancel_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let params = [
+            ("symbol", self.format_symbol(pair)),
+            ("orderId", order_id.to_string()),
+        ];
+        let order: BinanceOrder = self
+            .send_signed(Method::DELETE, "/api/v3/order", &params)
+            .await?;
+        self.to_report(pair, order)
+    }
+
+    async fn get_order_status(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let params = [
+            ("symbol", self.format_symbol(pair)),
+            ("orderId", order_id.to_string()),
+        ];
+        let order: BinanceOrder = self
+            .send_signed(Method::GET, "/api/v3/order", &params)
+            .await?;
+        self.to_report(pair, order)
+    }
+
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        let params = [
+            ("symbol", self.format_symbol(pair)),
+            ("origClientOrderId", client_order_id.to_string()),
+        ];
+        match self
+            .send_signed::<BinanceOrder>(Method::GET, "/api/v3/order", &params)
             .await
-            .context("Failed to parse Binance response")?;
+        {
+            Ok(order) => self.to_report(pair, order).map(Some),
+            // Order does not exist
+            Err(ExchangeError::Api {
+                code: Some(-2013), ..
+            }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
 
-        let best_bid = order_book.bids.first().context("No bids available")?;
-        let best_ask = order_book.asks.first().context("No asks available")?;
+    async fn get_open_orders(&self, pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        let params = [("symbol", self.format_symbol(pair))];
+        let orders: Vec<BinanceOrder> = self
+            .send_signed(Method::GET, "/api/v3/openOrders", &params)
+            .await?;
+        orders
+            .into_iter()
+            .map(|order| self.to_report(pair, order))
+            .collect()
+    }
 
-        Ok(Liquidity {
-            exchange: self.name().to_string(),
-            pair: pair.clone(),
-            bid_price: Decimal::from_str(&best_bid.0)?,
-            bid_quantity: Decimal::from_str(&best_bid.1)?,
-            ask_price: Decimal::from_str(&best_ask.0)?,
-            ask_quantity: Decimal::from_str(&best_ask.1)?,
-        })
+    async fn get_balances(&self) -> Result<Vec<Balance>> {
+        let params = [("omitZeroBalances", "true".to_string())];
+        let account: BinanceAccount = self
+            .send_signed(Method::GET, "/api/v3/account", &params)
+            .await?;
+        account
+            .balances
+            .into_iter()
+            .map(|balance| {
+                let free = Decimal::from_str(&balance.free)?;
+                let locked = Decimal::from_str(&balance.locked)?;
+                Ok(Balance::new(balance.asset, free + locked, locked))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(server: &mockito::Server) -> BinanceExchange {
+        BinanceExchange::with_config(
+            BinanceExchange::default_config()
+                .with_base_url(server.url())
+                .with_credentials(Credentials::new("test-key", "test-secret")),
+        )
+        .unwrap()
     }
 
-    async fn supports_pair(&self, _pair: &TradingPair) -> bool {
-        // In a real implementation, this would check against supported pairs
-        true
+    fn market_buy() -> OrderRequest {
+        OrderRequest {
+            client_order_id: "child-1".to_string(),
+            pair: TradingPair::new("BTC", "USDT"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(0.50),
+            limit_price: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_place_order_parses_full_response() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/v3/order")
+            .match_header("X-MBX-APIKEY", "test-key")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("symbol".into(), "BTCUSDT".into()),
+                mockito::Matcher::UrlEncoded("type".into(), "MARKET".into()),
+                mockito::Matcher::UrlEncoded("quantity".into(), "0.5".into()),
+                mockito::Matcher::UrlEncoded("recvWindow".into(), "5000".into()),
+                mockito::Matcher::Regex("&signature=[0-9a-f]{64}$".into()),
+            ]))
+            .with_body(
+                r#"{"symbol":"BTCUSDT","orderId":28,"clientOrderId":"child-1",
+                    "transactTime":1507725176595,"price":"0.00000000",
+                    "origQty":"0.50000000","executedQty":"0.50000000",
+                    "cummulativeQuoteQty":"25005.00000000","status":"FILLED",
+                    "type":"MARKET","side":"BUY","fills":[
+                    {"price":"50000.00","qty":"0.30","commission":"15.00","commissionAsset":"USDT"},
+                    {"price":"50025.00","qty":"0.20","commission":"0.0002","commissionAsset":"BTC"}]}"#,
+            )
+            .create_async()
+            .await;
+
+        let report = exchange(&server).place_order(&market_buy()).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(report.order_id, "28");
+        assert_eq!(report.status, OrderStatus::Filled);
+        assert_eq!(report.filled_quantity, dec!(0.5));
+        assert_eq!(report.average_price, dec!(50010));
+        assert_eq!(report.fees, dec!(25.005));
+        assert_eq!(report.fills.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pending_cancel_is_still_open() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v3/order")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{"symbol":"BTCUSDT","orderId":29,"clientOrderId":"child-2",
+                    "origQty":"1.00000000","executedQty":"0.00000000",
+                    "cummulativeQuoteQty":"0.00000000","status":"PENDING_CANCEL",
+                    "side":"SELL","updateTime":1507725176595}"#,
+            )
+            .create_async()
+            .await;
+
+        let report = exchange(&server)
+            .get_order_status(&TradingPair::new("BTC", "USDT"), "29")
+            .await
+            .unwrap();
+
+        assert_eq!(report.status, OrderStatus::PendingCancel);
+        assert!(report.status.is_open());
+        assert_eq!(report.filled_quantity, dec!(0));
+    }
+
+    #[tokio::test]
+    async fn test_place_order_surfaces_binance_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/v3/order")
+            .match_query(mockito::Matcher::Any)
+            .with_status(400)
+            .with_body(r#"{"code":-2010,"msg":"Account has insufficient balance."}"#)
+            .create_async()
+            .await;
+
+        let error = exchange(&server)
+            .place_order(&market_buy())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<ExchangeError>(),
+            Some(ExchangeError::Api {
+                code: Some(-2010),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_rejection_resyncs_clock_and_retries() {
+        let mut server = mockito::Server::new_async().await;
+        let server_time = chrono::Utc::now().timestamp_millis() + 60_000;
+        let time = server
+            .mock("GET", "/api/v3/time")
+            .with_body(format!(r#"{{"serverTime":{}}}"#, server_time))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/v3/openOrders")
+            .match_query(mockito::Matcher::Any)
+            .with_status(400)
+            .with_body(r#"{"code":-1021,"msg":"Timestamp for this request was 1000ms ahead of the server's time."}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let retried = server
+            .mock("GET", "/api/v3/openOrders")
+            .match_query(mockito::Matcher::Any)
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let binance = exchange(&server);
+        let orders = binance
+            .get_open_orders(&TradingPair::new("BTC", "USDT"))
+            .await
+            .unwrap();
+
+        assert!(orders.is_empty());
+        time.assert_async().await;
+        retried.assert_async().await;
+        assert!(binance.clock().offset_millis() > 55_000);
+    }
+
+    #[tokio::test]
+    async fn test_signed_endpoints_require_credentials() {
+        let server = mockito::Server::new_async().await;
+        let binance = BinanceExchange::with_config(ConnectorConfig::new(server.url())).unwrap();
+
+        let error = binance.place_order(&market_buy()).await.unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<ExchangeError>(),
+            Some(ExchangeError::Auth { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_instrument_rules_are_parsed_and_cached() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/api/v3/exchangeInfo")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "symbol".into(),
+                "BTCUSDT".into(),
+            ))
+            .with_body(include_str!(
+                "../../tests/fixtures/binance/exchange_info_btcusdt.json"
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+        let binance = exchange(&server);
+        let pair = TradingPair::new("BTC", "USDT");
+
+        let rules = binance.instrument_rules(&pair).await.unwrap();
+        assert_eq!(binance.instrument_rules(&pair).await.unwrap(), rules);
+
+        mock.assert_async().await;
+        assert_eq!(rules.tick_size, dec!(0.01));
+        assert_eq!(rules.step_size, dec!(0.00001));
+        assert_eq!(rules.min_quantity, dec!(0.00001));
+        assert_eq!(rules.max_quantity, Some(dec!(9000)));
+        assert_eq!(rules.min_notional, dec!(5));
+    }
+
+    #[tokio::test]
+    async fn test_pair_listing_is_cached() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/api/v3/exchangeInfo")
+            .with_body(include_str!(
+                "../../tests/fixtures/binance/exchange_info_listing.json"
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+        let binance = exchange(&server);
+
+        let symbols: Vec<_> = binance
+            .list_pairs()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|listing| listing.symbol)
+            .collect();
+        assert_eq!(symbols, vec!["BTCUSDT", "ETHBTC", "LUNAUSDT"]);
+        assert_eq!(
+            binance
+                .pair_status(&TradingPair::new("btc", "usdt"))
+                .await
+                .unwrap(),
+            Some(TradingStatus::Trading)
+        );
+        assert_eq!(
+            binance
+                .pair_status(&TradingPair::new("LUNA", "USDT"))
+                .await
+                .unwrap(),
+            Some(TradingStatus::Halted)
+        );
+        assert!(!binance.supports_pair(&TradingPair::new("BTC", "USD")).await);
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_requests_spend_weight_and_respect_throttling() {
+        let mut server = mockito::Server::new_async().await;
+        let depth = server
+            .mock("GET", "/api/v3/depth")
+            .match_query(mockito::Matcher::Any)
+            .with_header("X-MBX-USED-WEIGHT-1M", "45")
+            .with_body(r#"{"lastUpdateId":1,"bids":[],"asks":[]}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let time = server
+            .mock("GET", "/api/v3/time")
+            .with_status(418)
+            .with_header("Retry-After", "30")
+            .expect(1)
+            .create_async()
+            .await;
+        let config = ConnectorConfig::new(server.url())
+            .with_rate_limit(RateLimit::new(50, Duration::from_secs(60)))
+            .with_max_throttle_wait(Duration::ZERO);
+        let binance = BinanceExchange::with_config(config.clone()).unwrap();
+        let pair = TradingPair::new("BTC", "USDT");
+
+        // Depth costs 5 and Binance reports 45 of 50 used after the first
+        binance.get_liquidity(&pair).await.unwrap();
+        binance.get_liquidity(&pair).await.unwrap();
+        assert!(matches!(
+            binance.get_liquidity(&pair).await.unwrap_err(),
+            ExchangeError::RateLimited { .. }
+        ));
+        depth.assert_async().await;
+
+        // A ban stops further requests until it expires
+        let binance = BinanceExchange::with_config(config).unwrap();
+        assert!(binance.sync_clock().await.is_err());
+        let error = binance.sync_clock().await.unwrap_err();
+        assert!(matches!(
+            error,
+            ExchangeError::RateLimited { retry_after: Some(wait), .. } if wait.as_secs() >= 29
+        ));
+        time.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_liquidity_classifies_errors() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v3/depth")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "symbol".into(),
+                "FOOUSDT".into(),
+            ))
+            .with_status(400)
+            .with_body(r#"{"code":-1121,"msg":"Invalid symbol."}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/v3/depth")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "symbol".into(),
+                "BTCUSDT".into(),
+            ))
+            .with_status(429)
+            .with_header("Retry-After", "7")
+            .create_async()
+            .await;
+        let binance = exchange(&server);
+
+        let unknown = binance
+            .get_liquidity(&TradingPair::new("FOO", "USDT"))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(unknown, ExchangeError::UnknownSymbol { symbol, .. } if symbol == "FOOUSDT")
+        );
+
+        let limited = binance
+            .get_liquidity(&TradingPair::new("BTC", "USDT"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            limited,
+            ExchangeError::RateLimited {
+                retry_after: Some(d),
+                ..
+            } if d.as_secs() == 7
+        ));
     }
 }
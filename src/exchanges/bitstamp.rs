@@ -0,0 +1,782 @@
+use super::auth::{self, Credentials};
+use super::config::ConnectorConfig;
+use super::error::ExchangeError;
+use super::fees::FeeSchedule;
+use super::pairs::PairDirectory;
+use super::rate_limit::{RateLimit, RateLimiter};
+use super::Exchange;
+use crate::types::{
+    Fill, InstrumentRules, OrderBook, OrderReport, OrderRequest, OrderSide, OrderStatus, OrderType,
+    PairListing, PriceLevel, TradingPair, TradingStatus,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Depth levels are `[price, amount]`; the whole book is returned
+#[derive(Debug, Deserialize)]
+struct BitstampOrderBook {
+    bids: Vec<(String, String)>,
+    asks: Vec<(String, String)>,
+}
+
+/// Body of a failed call
+#[derive(Debug, Deserialize)]
+struct BitstampError {
+    #[serde(default)]
+    reason: serde_json::Value,
+    #[serde(default)]
+    code: Option<String>,
+}
+
+/// Entry of `/api/v2/trading-pairs-info/`
+#[derive(Debug, Deserialize)]
+struct BitstampPairInfo {
+    /// `BTC/USD`
+    name: String,
+    /// `btcusd`
+    url_symbol: String,
+    base_decimals: u32,
+    counter_decimals: u32,
+    /// `"10.00000000 USD"`
+    minimum_order: String,
+    /// `Enabled` or `Disabled`
+    trading: String,
+    instant_and_market_orders: String,
+}
+
+impl BitstampPairInfo {
+    fn pair(&self) -> Option<TradingPair> {
+        let (base, quote) = self.name.split_once('/')?;
+        Some(TradingPair::new(base, quote))
+    }
+
+    fn rules(&self) -> Result<InstrumentRules> {
+        let minimum = self
+            .minimum_order
+            .split_whitespace()
+            .next()
+            .context("empty minimum_order")?;
+        Ok(InstrumentRules::new(
+            Decimal::new(1, self.counter_decimals),
+            Decimal::new(1, self.base_decimals),
+        )
+        .with_min_notional(Decimal::from_str(minimum)?))
+    }
+
+    fn trading_status(&self) -> TradingStatus {
+        match (
+            self.trading.as_str(),
+            self.instant_and_market_orders.as_str(),
+        ) {
+            ("Enabled", "Enabled") => TradingStatus::Trading,
+            ("Enabled", _) => TradingStatus::LimitOnly,
+            _ => TradingStatus::Halted,
+        }
+    }
+}
+
+/// Answer to `buy/`, `sell/` and their market variants. IDs and order
+/// types are numbers on some endpoints and strings on others.
+#[derive(Debug, Deserialize)]
+struct BitstampOrderAck {
+    id: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct BitstampOpenOrder {
+    id: serde_json::Value,
+}
+
+/// Answer to `order_status/`
+#[derive(Debug, Deserialize)]
+struct BitstampOrderStatus {
+    id: serde_json::Value,
+    /// `Open`, `Finished`, `Canceled` or `Expired`
+    status: String,
+    /// `0`/`Buy` or `1`/`Sell`
+    #[serde(rename = "type")]
+    side: serde_json::Value,
+    #[serde(default)]
+    client_order_id: Option<String>,
+    amount_remaining: String,
+    transactions: Vec<BitstampTransaction>,
+}
+
+/// A fill of the order. Amounts are keyed by currency, e.g. `"btc"` and
+/// `"usd"`.
+#[derive(Debug, Deserialize)]
+struct BitstampTransaction {
+    price: serde_json::Value,
+    /// In the counter currency
+    fee: serde_json::Value,
+    datetime: String,
+    #[serde(flatten)]
+    amounts: HashMap<String, serde_json::Value>,
+}
+
+/// Bitstamp sends decimals as strings on most endpoints and numbers on a few
+fn decimal(value: &serde_json::Value) -> Result<Decimal> {
+    match value {
+        serde_json::Value::String(text) => Ok(Decimal::from_str(text)?),
+        serde_json::Value::Number(number) => Ok(Decimal::from_str(&number.to_string())?),
+        other => anyhow::bail!("expected a decimal, got {}", other),
+    }
+}
+
+/// An ID or code as text, whether Bitstamp sent a string or a number
+fn id_string(id: &serde_json::Value) -> String {
+    match id {
+        serde_json::Value::String(id) => id.clone(),
+        id => id.to_string(),
+    }
+}
+
+impl BitstampOrderStatus {
+    fn into_report(self, exchange: &str, pair: &TradingPair) -> Result<OrderReport> {
+        let base = pair.base.to_lowercase();
+        let fills = self
+            .transactions
+            .iter()
+            .map(|transaction| {
+                let quantity = transaction
+                    .amounts
+                    .get(&base)
+                    .with_context(|| format!("transaction has no {} amount", base))?;
+                Ok(Fill {
+                    price: decimal(&transaction.price)?,
+                    quantity: decimal(quantity)?.abs(),
+                    fee: decimal(&transaction.fee)?,
+                    timestamp: NaiveDateTime::parse_from_str(
+                        &transaction.datetime,
+                        "%Y-%m-%d %H:%M:%S%.f",
+                    )
+                    .map(|time| time.and_utc())
+                    .unwrap_or_else(|_| Utc::now()),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let filled_quantity: Decimal = fills.iter().map(|fill| fill.quantity).sum();
+        let notional: Decimal = fills.iter().map(|fill| fill.price * fill.quantity).sum();
+        let status = match self.status.as_str() {
+            "Open" if filled_quantity > dec!(0) => OrderStatus::PartiallyFilled,
+            "Open" => OrderStatus::New,
+            "Finished" => OrderStatus::Filled,
+            "Canceled" => OrderStatus::Cancelled,
+            "Expired" => OrderStatus::Expired,
+            other => anyhow::bail!("Unknown Bitstamp order status {}", other),
+        };
+        let side = match id_string(&self.side).as_str() {
+            "0" | "Buy" | "buy" => OrderSide::Buy,
+            "1" | "Sell" | "sell" => OrderSide::Sell,
+            other => anyhow::bail!("Unknown Bitstamp order type {}", other),
+        };
+        Ok(OrderReport {
+            order_id: id_string(&self.id),
+            client_order_id: self.client_order_id.unwrap_or_default(),
+            exchange: exchange.to_string(),
+            pair: pair.clone(),
+            side,
+            status,
+            quantity: filled_quantity + Decimal::from_str(&self.amount_remaining)?,
+            filled_quantity,
+            average_price: if filled_quantity > dec!(0) {
+                notional / filled_quantity
+            } else {
+                dec!(0)
+            },
+            fees: fills.iter().map(|fill| fill.fee).sum(),
+            updated_at: fills
+                .iter()
+                .map(|fill| fill.timestamp)
+                .max()
+                .unwrap_or_else(Utc::now),
+            fills,
+        })
+    }
+}
+
+/// Bitstamp URL symbol used in requests (BTC/USD -> btcusd)
+pub fn to_bitstamp_symbol(pair: &TradingPair) -> String {
+    format!("{}{}", pair.base.to_lowercase(), pair.quote.to_lowercase())
+}
+
+fn parse_levels(levels: &[(String, String)]) -> Result<Vec<PriceLevel>> {
+    levels
+        .iter()
+        .map(|(price, amount)| {
+            Ok(PriceLevel::new(
+                Decimal::from_str(price)?,
+                Decimal::from_str(amount)?,
+            ))
+        })
+        .collect()
+}
+
+/// Bitstamp REST API
+pub const DEFAULT_BASE_URL: &str = "https://www.bitstamp.net";
+
+pub struct BitstampExchange {
+    client: reqwest::Client,
+    config: ConnectorConfig,
+    /// Instrument rules change rarely, so each pair is fetched once
+    rules: Mutex<HashMap<TradingPair, InstrumentRules>>,
+    pairs: PairDirectory,
+    limiter: RateLimiter,
+}
+
+impl BitstampExchange {
+    pub fn new() -> Self {
+        Self::with_config(Self::default_config()).expect("default Bitstamp configuration is valid")
+    }
+
+    pub fn with_config(config: ConnectorConfig) -> Result<Self> {
+        Ok(Self {
+            client: config.build_client()?,
+            rules: Mutex::new(HashMap::new()),
+            pairs: PairDirectory::new(config.pair_refresh),
+            limiter: config.rate_limiter("Bitstamp"),
+            config,
+        })
+    }
+
+    /// Bitstamp allows 400 requests per second, and 10000 per 10 minutes
+    /// by default; the sustained limit is the one that bites
+    pub fn default_config() -> ConnectorConfig {
+        ConnectorConfig::new(DEFAULT_BASE_URL)
+            .with_rate_limit(RateLimit::new(10000, Duration::from_secs(600)))
+    }
+
+    fn credentials(&self) -> Result<&Credentials, ExchangeError> {
+        self.config
+            .credentials
+            .as_ref()
+            .ok_or_else(|| ExchangeError::missing_credentials(self.name()))
+    }
+
+    /// `GET /api/v2/{path}`
+    async fn public_request<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        symbol: &str,
+    ) -> Result<T, ExchangeError> {
+        self.limiter.acquire(1).await?;
+        self.send(
+            self.client
+                .get(format!("{}/api/v2/{}", self.config.base_url, path)),
+            symbol,
+        )
+        .await
+    }
+
+    /// `POST /api/v2/{path}` with `params` as the form body, signed with a
+    /// single-use nonce. Bitstamp has no time endpoint; requests are
+    /// accepted within 150 seconds of its clock.
+    async fn private_request<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, String)],
+        symbol: &str,
+    ) -> Result<T, ExchangeError> {
+        let credentials = self.credentials()?;
+        self.limiter.acquire(1).await?;
+        let url = format!("{}/api/v2/{}", self.config.base_url, path);
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params.iter().map(|(key, value)| (*key, value.as_str())))
+            .finish();
+        let nonce = auth::bitstamp_nonce().map_err(|e| ExchangeError::Auth {
+            exchange: self.name().to_string(),
+            message: e.to_string(),
+        })?;
+        let timestamp = Utc::now().timestamp_millis().to_string();
+        let signature = auth::sign_bitstamp(
+            credentials.api_secret(),
+            &credentials.api_key,
+            "POST",
+            url.split_once("://").map_or(url.as_str(), |(_, rest)| rest),
+            &nonce,
+            &timestamp,
+            &body,
+        );
+
+        let mut request = self
+            .client
+            .post(&url)
+            .header("X-Auth", format!("BITSTAMP {}", credentials.api_key))
+            .header("X-Auth-Signature", signature)
+            .header("X-Auth-Nonce", nonce)
+            .header("X-Auth-Timestamp", timestamp)
+            .header("X-Auth-Version", "v2");
+        if !body.is_empty() {
+            request = request
+                .header(
+                    reqwest::header::CONTENT_TYPE,
+                    "application/x-www-form-urlencoded",
+                )
+                .body(body);
+        }
+        self.send(request, symbol).await
+    }
+
+    /// Send a request and decode the answer, or the error it carries
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+        symbol: &str,
+    ) -> Result<T, ExchangeError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+        let error = if status.is_success() {
+            match serde_json::from_str::<T>(&body) {
+                Ok(value) => return Ok(value),
+                // Some failures still come back as 200 with an error body
+                Err(e) => match serde_json::from_str::<BitstampError>(&body) {
+                    Ok(error) if error.code.is_some() || !error.reason.is_null() => {
+                        self.classify_error(status, error, symbol)
+                    }
+                    _ => ExchangeError::parse(self.name(), e),
+                },
+            }
+        } else {
+            let error = serde_json::from_str(&body).unwrap_or(BitstampError {
+                reason: serde_json::Value::String(body),
+                code: None,
+            });
+            self.classify_error(status, error, symbol)
+        };
+        self.limiter.observe(&error);
+        Err(error)
+    }
+
+    /// Every pair in `trading-pairs-info`
+    async fn fetch_listings(&self) -> Result<Vec<PairListing>, ExchangeError> {
+        let pairs: Vec<BitstampPairInfo> = self.public_request("trading-pairs-info/", "").await?;
+        Ok(pairs
+            .into_iter()
+            .filter_map(|info| {
+                Some(PairListing {
+                    pair: info.pair()?,
+                    status: info.trading_status(),
+                    symbol: info.url_symbol,
+                })
+            })
+            .collect())
+    }
+
+    /// Map a failed call to an [`ExchangeError`]. Unknown pairs are plain
+    /// 404s; other failures carry a `reason` and sometimes a `code` such as
+    /// `API0005`.
+    fn classify_error(
+        &self,
+        status: reqwest::StatusCode,
+        error: BitstampError,
+        symbol: &str,
+    ) -> ExchangeError {
+        let exchange = self.name().to_string();
+        let message = match error.reason {
+            serde_json::Value::String(reason) => reason,
+            serde_json::Value::Null => error.code.clone().unwrap_or_default(),
+            // Validation failures map field names to messages
+            reason => reason.to_string(),
+        };
+        match (status.as_u16(), error.code.as_deref()) {
+            (404, _) => ExchangeError::UnknownSymbol {
+                exchange,
+                symbol: symbol.to_string(),
+            },
+            // Missing, invalid or unauthorised key, signature or nonce
+            (_, Some("API0001" | "API0002" | "API0003" | "API0004" | "API0005")) => {
+                ExchangeError::Auth { exchange, message }
+            }
+            (200, _) => ExchangeError::Api {
+                exchange,
+                code: None,
+                message,
+            },
+            _ => ExchangeError::from_status(self.name(), status, message),
+        }
+    }
+}
+
+impl Default for BitstampExchange {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Exchange for BitstampExchange {
+    fn name(&self) -> &str {
+        "Bitstamp"
+    }
+
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        let symbol = to_bitstamp_symbol(pair);
+        let mut book: BitstampOrderBook = self
+            .public_request(&format!("order_book/{}/", symbol), &symbol)
+            .await?;
+        // The full book can run to thousands of levels; keep the configured depth
+        book.bids.truncate(self.config.depth_limit);
+        book.asks.truncate(self.config.depth_limit);
+
+        // `microtimestamp` is on Bitstamp's clock, which has no endpoint to
+        // measure it against, so the book is aged from when it arrived
+        Ok(OrderBook::new(
+            self.name(),
+            pair.clone(),
+            parse_levels(&book.bids).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid bid level: {}", e))
+            })?,
+            parse_levels(&book.asks).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid ask level: {}", e))
+            })?,
+        ))
+    }
+
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        // An unreachable listing is no evidence the pair is missing
+        !matches!(self.pair_status(pair).await, Ok(None))
+    }
+
+    async fn list_pairs(&self) -> Result<Vec<PairListing>> {
+        Ok(self
+            .pairs
+            .listings(self.name(), || self.fetch_listings())
+            .await?)
+    }
+
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        self.pairs
+            .status(self.name(), pair, || self.fetch_listings())
+            .await
+    }
+
+    async fn instrument_rules(&self, pair: &TradingPair) -> Result<InstrumentRules, ExchangeError> {
+        if let Some(rules) = self.rules.lock().unwrap().get(pair) {
+            return Ok(rules.clone());
+        }
+
+        // There is no per-pair endpoint; the full list is small
+        let symbol = to_bitstamp_symbol(pair);
+        let pairs: Vec<BitstampPairInfo> =
+            self.public_request("trading-pairs-info/", &symbol).await?;
+        let rules = pairs
+            .iter()
+            .find(|info| info.url_symbol == symbol)
+            .ok_or_else(|| ExchangeError::UnknownSymbol {
+                exchange: self.name().to_string(),
+                symbol: symbol.clone(),
+            })?
+            .rules()
+            .map_err(|e| ExchangeError::parse(self.name(), e))?;
+
+        self.rules
+            .lock()
+            .unwrap()
+            .insert(pair.clone(), rules.clone());
+        Ok(rules)
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        FeeSchedule::flat(dec!(0.003), dec!(0.004))
+            .with_tier(dec!(10000), dec!(0.002), dec!(0.003))
+            .with_tier(dec!(100000), dec!(0.0015), dec!(0.0025))
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        let symbol = to_bitstamp_symbol(&request.pair);
+        let side = match request.side {
+            OrderSide::Buy => "buy",
+            OrderSide::Sell => "sell",
+        };
+        let mut params = vec![
+            ("amount", request.quantity.normalize().to_string()),
+            ("client_order_id", request.client_order_id.clone()),
+        ];
+        let path = match request.order_type {
+            OrderType::Market => format!("{}/market/{}/", side, symbol),
+            OrderType::Limit => {
+                let price = request
+                    .limit_price
+                    .context("Limit order has no limit price")?;
+                params.push(("price", price.normalize().to_string()));
+                format!("{}/{}/", side, symbol)
+            }
+        };
+
+        let ack: BitstampOrderAck = self.private_request(&path, &params, &symbol).await?;
+        let mut report = self
+            .get_order_status(&request.pair, &id_string(&ack.id))
+            .await?;
+        report.client_order_id = request.client_order_id.clone();
+        Ok(report)
+    }
+
+    async fn cancel_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let _: serde_json::Value = self
+            .private_request("cancel_order/", &[("id", order_id.to_string())], "")
+            .await?;
+        self.get_order_status(pair, order_id).await
+    }
+
+    async fn get_order_status(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let status: BitstampOrderStatus = self
+            .private_request("order_status/", &[("id", order_id.to_string())], "")
+            .await?;
+        status.into_report(self.name(), pair)
+    }
+
+    async fn get_open_orders(&self, pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        let symbol = to_bitstamp_symbol(pair);
+        // The listing has no fills, so each order is queried for them
+        let open: Vec<BitstampOpenOrder> = self
+            .private_request(&format!("open_orders/{}/", symbol), &[], &symbol)
+            .await?;
+        let mut reports = Vec::with_capacity(open.len());
+        for order in open {
+            reports.push(self.get_order_status(pair, &id_string(&order.id)).await?);
+        }
+        Ok(reports)
+    }
+
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        let params = [("client_order_id", client_order_id.to_string())];
+        let status: BitstampOrderStatus =
+            match self.private_request("order_status/", &params, "").await {
+                Ok(status) => status,
+                // An unknown order is a 404 or an "Order not found" reason
+                Err(ExchangeError::UnknownSymbol { .. }) => return Ok(None),
+                Err(ExchangeError::Api { message, .. })
+                    if message.to_lowercase().contains("not found") =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e.into()),
+            };
+        let mut report = status.into_report(self.name(), pair)?;
+        report.client_order_id = client_order_id.to_string();
+        Ok(Some(report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(server: &mockito::Server) -> BitstampExchange {
+        BitstampExchange::with_config(ConnectorConfig::new(server.url()).with_depth_limit(2))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_liquidity_truncates_full_book() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/api/v2/order_book/btcusd/")
+            .with_body(include_str!(
+                "../../tests/fixtures/bitstamp/order_book_btcusd.json"
+            ))
+            .create_async()
+            .await;
+
+        let book = exchange(&server)
+            .get_liquidity(&TradingPair::new("BTC", "USD"))
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(
+            book.best_bid(),
+            Some(&PriceLevel::new(dec!(67008), dec!(0.14925373)))
+        );
+        assert_eq!(book.best_ask().unwrap().price, dec!(67012));
+        assert_eq!(book.bids.len(), 2);
+        assert_eq!(book.asks.len(), 2);
+        assert_eq!(book.exchange_time, None);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_classified() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v2/order_book/foousd/")
+            .with_status(404)
+            .with_body(include_str!("../../tests/fixtures/bitstamp/not_found.html"))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/v2/order_book/btcusd/")
+            .with_status(429)
+            .with_body(r#"{"status":"error","reason":"Rate limit exceeded","code":"API0021"}"#)
+            .create_async()
+            .await;
+        let bitstamp = exchange(&server);
+
+        let error = bitstamp
+            .get_liquidity(&TradingPair::new("FOO", "USD"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ExchangeError::UnknownSymbol { symbol, .. } if symbol == "foousd"
+        ));
+
+        let error = bitstamp
+            .get_liquidity(&TradingPair::new("BTC", "USD"))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ExchangeError::RateLimited { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_trading_pairs_info_gives_rules_and_listings() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v2/trading-pairs-info/")
+            .with_body(include_str!(
+                "../../tests/fixtures/bitstamp/trading_pairs_info.json"
+            ))
+            .create_async()
+            .await;
+        let bitstamp = exchange(&server);
+
+        let listings = bitstamp.list_pairs().await.unwrap();
+        assert_eq!(
+            listings,
+            vec![
+                PairListing {
+                    pair: TradingPair::new("BTC", "USD"),
+                    symbol: "btcusd".to_string(),
+                    status: TradingStatus::Trading,
+                },
+                PairListing {
+                    pair: TradingPair::new("ETH", "EUR"),
+                    symbol: "etheur".to_string(),
+                    status: TradingStatus::LimitOnly,
+                },
+                PairListing {
+                    pair: TradingPair::new("XRP", "GBP"),
+                    symbol: "xrpgbp".to_string(),
+                    status: TradingStatus::Halted,
+                },
+            ]
+        );
+
+        let rules = bitstamp
+            .instrument_rules(&TradingPair::new("BTC", "USD"))
+            .await
+            .unwrap();
+        assert_eq!(
+            rules,
+            InstrumentRules::new(dec!(1), dec!(0.00000001)).with_min_notional(dec!(10))
+        );
+    }
+
+    fn trading_exchange(server: &mockito::Server) -> BitstampExchange {
+        BitstampExchange::with_config(
+            ConnectorConfig::new(server.url())
+                .with_credentials(Credentials::new("bitstamp-key", "bitstamp-secret")),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_place_order_is_signed_and_reports_the_fills() {
+        let mut server = mockito::Server::new_async().await;
+        let buy = server
+            .mock("POST", "/api/v2/buy/btcusd/")
+            .match_header("X-Auth", "BITSTAMP bitstamp-key")
+            .match_header("X-Auth-Version", "v2")
+            .match_header(
+                "X-Auth-Signature",
+                mockito::Matcher::Regex("^[0-9a-f]{64}$".into()),
+            )
+            .match_header(
+                "X-Auth-Nonce",
+                mockito::Matcher::Regex("^[0-9a-f]{36}$".into()),
+            )
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("amount".into(), "0.5".into()),
+                mockito::Matcher::UrlEncoded("price".into(), "67000".into()),
+                mockito::Matcher::UrlEncoded("client_order_id".into(), "sor-1-0".into()),
+            ]))
+            .with_body(
+                r#"{"id":"1694012345678901","market":"BTC/USD","type":"0","price":"67000.00",
+                    "amount":"0.50000000","client_order_id":"sor-1-0"}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/api/v2/order_status/")
+            .match_body(mockito::Matcher::UrlEncoded(
+                "id".into(),
+                "1694012345678901".into(),
+            ))
+            .with_body(include_str!(
+                "../../tests/fixtures/bitstamp/order_status_partially_filled.json"
+            ))
+            .create_async()
+            .await;
+
+        let request = OrderRequest {
+            client_order_id: "sor-1-0".to_string(),
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Limit,
+            quantity: dec!(0.5),
+            limit_price: Some(dec!(67000)),
+        };
+        let report = trading_exchange(&server)
+            .place_order(&request)
+            .await
+            .unwrap();
+
+        buy.assert_async().await;
+        assert_eq!(report.order_id, "1694012345678901");
+        assert_eq!(report.side, OrderSide::Buy);
+        assert_eq!(report.status, OrderStatus::PartiallyFilled);
+        assert_eq!(report.quantity, dec!(0.5));
+        assert_eq!(report.filled_quantity, dec!(0.2));
+        assert_eq!(report.average_price, dec!(66995));
+        assert_eq!(report.fees, dec!(53.596));
+        assert_eq!(report.fills.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_client_order_id_is_not_found() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/v2/order_status/")
+            .with_body(r#"{"status":"error","reason":"Order not found."}"#)
+            .create_async()
+            .await;
+
+        let found = trading_exchange(&server)
+            .find_order(&TradingPair::new("BTC", "USD"), "sor-1-0")
+            .await
+            .unwrap();
+        assert!(found.is_none());
+    }
+}
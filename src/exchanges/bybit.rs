@@ -0,0 +1,916 @@
+use super::auth::{self, Credentials, ServerClock};
+use super::config::ConnectorConfig;
+use super::error::ExchangeError;
+use super::fees::FeeSchedule;
+use super::pairs::PairDirectory;
+use super::rate_limit::{RateLimit, RateLimiter};
+use super::Exchange;
+use crate::types::{
+    InstrumentRules, OrderBook, OrderReport, OrderRequest, OrderSide, OrderStatus, OrderType,
+    PairListing, PriceLevel, TradingPair, TradingStatus,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Bybit wraps every response in `{retCode, retMsg, result}`; `retCode` is
+/// 0 on success
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitResponse {
+    ret_code: i64,
+    ret_msg: String,
+    /// Only decoded once `retCode` says the call succeeded
+    #[serde(default)]
+    result: serde_json::Value,
+}
+
+/// Depth levels are `[price, size]`
+#[derive(Debug, Deserialize)]
+struct BybitOrderBook {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "b")]
+    bids: Vec<(String, String)>,
+    #[serde(rename = "a")]
+    asks: Vec<(String, String)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BybitInstruments {
+    list: Vec<BybitInstrument>,
+}
+
+/// Spot entry of `/v5/market/instruments-info`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitInstrument {
+    symbol: String,
+    base_coin: String,
+    quote_coin: String,
+    status: String,
+    lot_size_filter: BybitLotSizeFilter,
+    price_filter: BybitPriceFilter,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitLotSizeFilter {
+    base_precision: String,
+    min_order_qty: String,
+    max_order_qty: String,
+    min_order_amt: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitPriceFilter {
+    tick_size: String,
+}
+
+impl BybitInstrument {
+    fn rules(&self) -> Result<InstrumentRules> {
+        let lot = &self.lot_size_filter;
+        Ok(InstrumentRules::new(
+            Decimal::from_str(&self.price_filter.tick_size)?,
+            Decimal::from_str(&lot.base_precision)?,
+        )
+        .with_min_quantity(Decimal::from_str(&lot.min_order_qty)?)
+        .with_max_quantity(Decimal::from_str(&lot.max_order_qty)?)
+        .with_min_notional(Decimal::from_str(&lot.min_order_amt)?))
+    }
+
+    fn trading_status(&self) -> TradingStatus {
+        match self.status.as_str() {
+            "Trading" => TradingStatus::Trading,
+            "Closed" => TradingStatus::Delisted,
+            // PreLaunch and anything newer
+            _ => TradingStatus::Halted,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitTime {
+    time_nano: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitOrderAck {
+    order_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BybitOrders {
+    list: Vec<BybitOrder>,
+}
+
+/// Order as returned by `/v5/order/realtime` and `/v5/order/history`.
+/// Amounts Bybit has no value for yet, such as `avgPrice` before a fill,
+/// may be empty strings.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitOrder {
+    order_id: String,
+    order_link_id: String,
+    side: String,
+    qty: String,
+    cum_exec_qty: String,
+    avg_price: String,
+    cum_exec_fee: String,
+    order_status: String,
+    /// Milliseconds since the epoch, as a string
+    updated_time: String,
+}
+
+impl BybitOrder {
+    fn into_report(self, exchange: &str, pair: &TradingPair) -> Result<OrderReport> {
+        let amount = |value: &str| -> Result<Decimal> {
+            if value.is_empty() {
+                Ok(dec!(0))
+            } else {
+                Ok(Decimal::from_str(value)?)
+            }
+        };
+        let filled_quantity = amount(&self.cum_exec_qty)?;
+        let average_price = amount(&self.avg_price)?;
+        let status = match self.order_status.as_str() {
+            "New" | "Untriggered" | "Triggered" if filled_quantity > dec!(0) => {
+                OrderStatus::PartiallyFilled
+            }
+            "New" | "Untriggered" | "Triggered" => OrderStatus::New,
+            "PartiallyFilled" => OrderStatus::PartiallyFilled,
+            "Filled" => OrderStatus::Filled,
+            "Cancelled" | "PartiallyFilledCanceled" | "Deactivated" => OrderStatus::Cancelled,
+            "Rejected" => OrderStatus::Rejected,
+            other => anyhow::bail!("Unknown Bybit order status {}", other),
+        };
+        let side = match self.side.as_str() {
+            "Buy" => OrderSide::Buy,
+            "Sell" => OrderSide::Sell,
+            other => anyhow::bail!("Unknown Bybit order side {}", other),
+        };
+        // Spot fees come out of the asset received: base on buys
+        let fee = amount(&self.cum_exec_fee)?;
+        let fees = match side {
+            OrderSide::Buy => fee * average_price,
+            OrderSide::Sell => fee,
+        };
+        Ok(OrderReport {
+            order_id: self.order_id,
+            client_order_id: self.order_link_id,
+            exchange: exchange.to_string(),
+            pair: pair.clone(),
+            side,
+            status,
+            quantity: amount(&self.qty)?,
+            filled_quantity,
+            average_price,
+            fees,
+            fills: Vec::new(),
+            updated_at: self
+                .updated_time
+                .parse()
+                .ok()
+                .and_then(DateTime::from_timestamp_millis)
+                .unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+/// `orderLinkId` Bybit accepts for `client_order_id`: up to 36 letters,
+/// digits, `-` and `_`, or else a digest of it
+pub fn bybit_client_id(client_order_id: &str) -> String {
+    let accepted = !client_order_id.is_empty()
+        && client_order_id.len() <= 36
+        && client_order_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if accepted {
+        client_order_id.to_string()
+    } else {
+        auth::client_id_digest(client_order_id)
+    }
+}
+
+/// Bybit symbol used in requests (BTC/USDT -> BTCUSDT)
+pub fn to_bybit_symbol(pair: &TradingPair) -> String {
+    format!("{}{}", pair.base.to_uppercase(), pair.quote.to_uppercase())
+}
+
+fn parse_levels(levels: &[(String, String)]) -> Result<Vec<PriceLevel>> {
+    levels
+        .iter()
+        .map(|(price, size)| {
+            Ok(PriceLevel::new(
+                Decimal::from_str(price)?,
+                Decimal::from_str(size)?,
+            ))
+        })
+        .collect()
+}
+
+/// Bybit v5 REST API
+pub const DEFAULT_BASE_URL: &str = "https://api.bybit.com";
+/// Bybit testnet
+pub const TESTNET_BASE_URL: &str = "https://api-testnet.bybit.com";
+/// How long after its timestamp a signed request is still accepted, in ms
+const RECV_WINDOW: u64 = 5000;
+
+pub struct BybitExchange {
+    client: reqwest::Client,
+    config: ConnectorConfig,
+    /// Instrument rules change rarely, so each pair is fetched once
+    rules: Mutex<HashMap<TradingPair, InstrumentRules>>,
+    pairs: PairDirectory,
+    limiter: RateLimiter,
+    /// Signed requests must be timestamped within the receive window of
+    /// Bybit's clock
+    clock: ServerClock,
+}
+
+impl BybitExchange {
+    pub fn new() -> Self {
+        Self::with_config(Self::default_config()).expect("default Bybit configuration is valid")
+    }
+
+    pub fn with_config(config: ConnectorConfig) -> Result<Self> {
+        Ok(Self {
+            client: config.build_client()?,
+            rules: Mutex::new(HashMap::new()),
+            pairs: PairDirectory::new(config.pair_refresh),
+            limiter: config.rate_limiter("Bybit"),
+            config,
+            clock: ServerClock::new(),
+        })
+    }
+
+    /// Bybit allows 600 requests per 5 seconds per IP across all endpoints
+    pub fn default_config() -> ConnectorConfig {
+        ConnectorConfig::new(DEFAULT_BASE_URL)
+            .with_rate_limit(RateLimit::new(600, Duration::from_secs(5)))
+    }
+
+    /// Measure the offset to Bybit's clock
+    pub async fn sync_clock(&self) -> Result<(), ExchangeError> {
+        let sent = Utc::now().timestamp_millis();
+        let time: BybitTime = self.public_request("/v5/market/time", "").await?;
+        let received = Utc::now().timestamp_millis();
+        let nanos: i64 = time
+            .time_nano
+            .parse()
+            .map_err(|e| ExchangeError::parse(self.name(), e))?;
+        self.clock.observe(nanos / 1_000_000, sent, received);
+        Ok(())
+    }
+
+    pub fn clock(&self) -> &ServerClock {
+        &self.clock
+    }
+
+    fn credentials(&self) -> Result<&Credentials, ExchangeError> {
+        self.config
+            .credentials
+            .as_ref()
+            .ok_or_else(|| ExchangeError::missing_credentials(self.name()))
+    }
+
+    /// `GET {path}`, unwrapping Bybit's `{retCode, retMsg, result}` envelope
+    async fn public_request<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        symbol: &str,
+    ) -> Result<T, ExchangeError> {
+        self.limiter.acquire(1).await?;
+        self.send(
+            self.client.get(format!("{}{}", self.config.base_url, path)),
+            symbol,
+        )
+        .await
+    }
+
+    /// Call a private endpoint with `params` as the query string of a GET
+    /// or the JSON body of a POST. A request outside the receive window of
+    /// Bybit's clock is retried once after resynchronising.
+    async fn private_request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &serde_json::Value,
+        symbol: &str,
+    ) -> Result<T, ExchangeError> {
+        match self
+            .try_private_request(method.clone(), path, params, symbol)
+            .await
+        {
+            Err(ExchangeError::Api {
+                code: Some(10002), ..
+            }) => {
+                self.sync_clock().await?;
+                self.try_private_request(method, path, params, symbol).await
+            }
+            result => result,
+        }
+    }
+
+    async fn try_private_request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &serde_json::Value,
+        symbol: &str,
+    ) -> Result<T, ExchangeError> {
+        let credentials = self.credentials()?;
+        // Before signing, so a wait cannot age the timestamp
+        self.limiter.acquire(1).await?;
+        let timestamp = self.clock.now_millis();
+        let mut url = format!("{}{}", self.config.base_url, path);
+        let payload = if method == Method::GET {
+            let query = form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(
+                    params
+                        .as_object()
+                        .into_iter()
+                        .flatten()
+                        .map(|(key, value)| (key, value.as_str().unwrap_or_default())),
+                )
+                .finish();
+            url = format!("{}?{}", url, query);
+            query
+        } else {
+            params.to_string()
+        };
+        let signature = auth::sign_bybit(
+            credentials.api_secret(),
+            timestamp,
+            &credentials.api_key,
+            RECV_WINDOW,
+            &payload,
+        );
+
+        let mut request = self
+            .client
+            .request(method.clone(), url)
+            .header("X-BAPI-API-KEY", &credentials.api_key)
+            .header("X-BAPI-TIMESTAMP", timestamp.to_string())
+            .header("X-BAPI-RECV-WINDOW", RECV_WINDOW.to_string())
+            .header("X-BAPI-SIGN", signature);
+        if method != Method::GET {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(payload);
+        }
+        self.send(request, symbol).await
+    }
+
+    /// Send a request and unwrap the envelope
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+        symbol: &str,
+    ) -> Result<T, ExchangeError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+        let error = if !status.is_success() {
+            // Bybit answers an IP over its limit with a bare 403
+            if status == reqwest::StatusCode::FORBIDDEN {
+                ExchangeError::RateLimited {
+                    exchange: self.name().to_string(),
+                    retry_after: None,
+                }
+            } else {
+                ExchangeError::from_status(self.name(), status, body)
+            }
+        } else {
+            match serde_json::from_str::<BybitResponse>(&body) {
+                Ok(envelope) if envelope.ret_code == 0 => {
+                    return serde_json::from_value(envelope.result)
+                        .map_err(|e| ExchangeError::parse(self.name(), e));
+                }
+                Ok(envelope) => self.classify_error(envelope.ret_code, envelope.ret_msg, symbol),
+                Err(e) => ExchangeError::parse(self.name(), e),
+            }
+        };
+        self.limiter.observe(&error);
+        Err(error)
+    }
+
+    /// One order by `orderId` or `orderLinkId`. Open orders are served by
+    /// the realtime endpoint and finished ones by the history endpoint.
+    async fn query_order(
+        &self,
+        pair: &TradingPair,
+        key: &str,
+        id: &str,
+    ) -> Result<Option<OrderReport>> {
+        let symbol = to_bybit_symbol(pair);
+        let params = serde_json::json!({ "category": "spot", "symbol": symbol, key: id });
+        for path in ["/v5/order/realtime", "/v5/order/history"] {
+            let orders: BybitOrders = self
+                .private_request(Method::GET, path, &params, &symbol)
+                .await?;
+            if let Some(order) = orders.list.into_iter().next() {
+                return order.into_report(self.name(), pair).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every spot instrument
+    async fn fetch_listings(&self) -> Result<Vec<PairListing>, ExchangeError> {
+        let instruments: BybitInstruments = self
+            .public_request("/v5/market/instruments-info?category=spot", "")
+            .await?;
+        Ok(instruments
+            .list
+            .into_iter()
+            .map(|instrument| PairListing {
+                pair: TradingPair::new(&instrument.base_coin, &instrument.quote_coin),
+                status: instrument.trading_status(),
+                symbol: instrument.symbol,
+            })
+            .collect())
+    }
+
+    /// Map a Bybit `retCode` (e.g. `10006`) to an [`ExchangeError`]
+    fn classify_error(&self, code: i64, message: String, symbol: &str) -> ExchangeError {
+        let exchange = self.name().to_string();
+        match code {
+            // Parameter errors only mean an unknown pair when they say so
+            10001 if message.to_lowercase().contains("symbol") => ExchangeError::UnknownSymbol {
+                exchange,
+                symbol: symbol.to_string(),
+            },
+            170121 => ExchangeError::UnknownSymbol {
+                exchange,
+                symbol: symbol.to_string(),
+            },
+            10006 | 10018 => ExchangeError::RateLimited {
+                exchange,
+                retry_after: None,
+            },
+            10000 | 10016 => ExchangeError::Maintenance { exchange },
+            // Outside the receive window: the clock needs resynchronising
+            10002 => ExchangeError::Api {
+                exchange,
+                code: Some(10002),
+                message,
+            },
+            10003..=10005 | 10007 | 10009 | 10010 => ExchangeError::Auth { exchange, message },
+            code => ExchangeError::Api {
+                exchange,
+                code: Some(code),
+                message,
+            },
+        }
+    }
+}
+
+impl Default for BybitExchange {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Exchange for BybitExchange {
+    fn name(&self) -> &str {
+        "Bybit"
+    }
+
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        let symbol = to_bybit_symbol(pair);
+        let book: BybitOrderBook = self
+            .public_request(
+                &format!(
+                    "/v5/market/orderbook?category=spot&symbol={}&limit={}",
+                    symbol, self.config.depth_limit
+                ),
+                &symbol,
+            )
+            .await?;
+        if book.symbol != symbol {
+            return Err(ExchangeError::parse(
+                self.name(),
+                format!("returned {} when {} was requested", book.symbol, symbol),
+            ));
+        }
+
+        // Aged from receipt rather than `ts`, which is on Bybit's clock
+        Ok(OrderBook::new(
+            self.name(),
+            pair.clone(),
+            parse_levels(&book.bids).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid bid level: {}", e))
+            })?,
+            parse_levels(&book.asks).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid ask level: {}", e))
+            })?,
+        ))
+    }
+
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        // An unreachable listing is no evidence the pair is missing
+        !matches!(self.pair_status(pair).await, Ok(None))
+    }
+
+    async fn list_pairs(&self) -> Result<Vec<PairListing>> {
+        Ok(self
+            .pairs
+            .listings(self.name(), || self.fetch_listings())
+            .await?)
+    }
+
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        self.pairs
+            .status(self.name(), pair, || self.fetch_listings())
+            .await
+    }
+
+    async fn instrument_rules(&self, pair: &TradingPair) -> Result<InstrumentRules, ExchangeError> {
+        if let Some(rules) = self.rules.lock().unwrap().get(pair) {
+            return Ok(rules.clone());
+        }
+
+        let symbol = to_bybit_symbol(pair);
+        let instruments: BybitInstruments = self
+            .public_request(
+                &format!(
+                    "/v5/market/instruments-info?category=spot&symbol={}",
+                    symbol
+                ),
+                &symbol,
+            )
+            .await?;
+        let rules = instruments
+            .list
+            .iter()
+            .find(|instrument| instrument.symbol == symbol)
+            .ok_or_else(|| ExchangeError::UnknownSymbol {
+                exchange: self.name().to_string(),
+                symbol: symbol.clone(),
+            })?
+            .rules()
+            .map_err(|e| ExchangeError::parse(self.name(), e))?;
+
+        self.rules
+            .lock()
+            .unwrap()
+            .insert(pair.clone(), rules.clone());
+        Ok(rules)
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        // Non-VIP spot rates
+        FeeSchedule::flat(dec!(0.001), dec!(0.001))
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        let symbol = to_bybit_symbol(&request.pair);
+        let mut params = serde_json::json!({
+            "category": "spot",
+            "symbol": symbol,
+            "side": match request.side {
+                OrderSide::Buy => "Buy",
+                OrderSide::Sell => "Sell",
+            },
+            "qty": request.quantity.normalize().to_string(),
+            "orderLinkId": bybit_client_id(&request.client_order_id),
+        });
+        match request.order_type {
+            OrderType::Market => {
+                params["orderType"] = "Market".into();
+                // Spot market buys are sized in quote unless told otherwise
+                params["marketUnit"] = "baseCoin".into();
+            }
+            OrderType::Limit => {
+                let price = request
+                    .limit_price
+                    .context("Limit order has no limit price")?;
+                params["orderType"] = "Limit".into();
+                params["price"] = price.normalize().to_string().into();
+                params["timeInForce"] = "GTC".into();
+            }
+        }
+
+        let ack: BybitOrderAck = self
+            .private_request(Method::POST, "/v5/order/create", &params, &symbol)
+            .await?;
+        let mut report = self.get_order_status(&request.pair, &ack.order_id).await?;
+        report.client_order_id = request.client_order_id.clone();
+        Ok(report)
+    }
+
+    async fn cancel_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let symbol = to_bybit_symbol(pair);
+        let params = serde_json::json!({
+            "category": "spot",
+            "symbol": symbol,
+            "orderId": order_id,
+        });
+        let _: BybitOrderAck = self
+            .private_request(Method::POST, "/v5/order/cancel", &params, &symbol)
+            .await?;
+        self.get_order_status(pair, order_id).await
+    }
+
+    async fn get_order_status(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        self.query_order(pair, "orderId", order_id)
+            .await?
+            .with_context(|| format!("Bybit did not return order {}", order_id))
+    }
+
+    async fn get_open_orders(&self, pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        let symbol = to_bybit_symbol(pair);
+        let params = serde_json::json!({ "category": "spot", "symbol": symbol });
+        let orders: BybitOrders = self
+            .private_request(Method::GET, "/v5/order/realtime", &params, &symbol)
+            .await?;
+        orders
+            .list
+            .into_iter()
+            .map(|order| order.into_report(self.name(), pair))
+            .collect()
+    }
+
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        let report = self
+            .query_order(pair, "orderLinkId", &bybit_client_id(client_order_id))
+            .await?;
+        Ok(report.map(|mut report| {
+            report.client_order_id = client_order_id.to_string();
+            report
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(server: &mockito::Server) -> BybitExchange {
+        BybitExchange::with_config(ConnectorConfig::new(server.url())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_liquidity_parses_orderbook() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                "/v5/market/orderbook?category=spot&symbol=BTCUSDT&limit=10",
+            )
+            .with_body(include_str!(
+                "../../tests/fixtures/bybit/orderbook_btcusdt.json"
+            ))
+            .create_async()
+            .await;
+
+        let book = exchange(&server)
+            .get_liquidity(&TradingPair::new("BTC", "USDT"))
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(
+            book.best_bid(),
+            Some(&PriceLevel::new(dec!(67011.52), dec!(0.839262)))
+        );
+        assert_eq!(book.best_ask().unwrap().price, dec!(67011.53));
+        assert_eq!(book.bids.len(), 3);
+        assert_eq!(book.exchange_time, None);
+    }
+
+    #[tokio::test]
+    async fn test_error_codes_are_classified() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock(
+                "GET",
+                "/v5/market/orderbook?category=spot&symbol=FOOUSDT&limit=10",
+            )
+            .with_body(include_str!(
+                "../../tests/fixtures/bybit/invalid_symbol.json"
+            ))
+            .create_async()
+            .await;
+        server
+            .mock(
+                "GET",
+                "/v5/market/orderbook?category=spot&symbol=BTCUSDT&limit=10",
+            )
+            .with_status(403)
+            .with_body("access too frequent")
+            .create_async()
+            .await;
+        let bybit = exchange(&server);
+
+        let error = bybit
+            .get_liquidity(&TradingPair::new("FOO", "USDT"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ExchangeError::UnknownSymbol { symbol, .. } if symbol == "FOOUSDT"
+        ));
+
+        let error = bybit
+            .get_liquidity(&TradingPair::new("BTC", "USDT"))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ExchangeError::RateLimited { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_instruments_give_rules_and_listings() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/v5/market/instruments-info")
+            .match_query(mockito::Matcher::Any)
+            .with_body(include_str!(
+                "../../tests/fixtures/bybit/instruments_info.json"
+            ))
+            .create_async()
+            .await;
+        let bybit = exchange(&server);
+
+        let listings = bybit.list_pairs().await.unwrap();
+        assert_eq!(
+            listings,
+            vec![
+                PairListing {
+                    pair: TradingPair::new("BTC", "USDT"),
+                    symbol: "BTCUSDT".to_string(),
+                    status: TradingStatus::Trading,
+                },
+                PairListing {
+                    pair: TradingPair::new("ETH", "USDC"),
+                    symbol: "ETHUSDC".to_string(),
+                    status: TradingStatus::Halted,
+                },
+            ]
+        );
+
+        let rules = bybit
+            .instrument_rules(&TradingPair::new("BTC", "USDT"))
+            .await
+            .unwrap();
+        assert_eq!(
+            rules,
+            InstrumentRules::new(dec!(0.01), dec!(0.000001))
+                .with_min_quantity(dec!(0.000048))
+                .with_max_quantity(dec!(71.73956243))
+                .with_min_notional(dec!(1))
+        );
+    }
+
+    fn trading_exchange(server: &mockito::Server) -> BybitExchange {
+        BybitExchange::with_config(
+            ConnectorConfig::new(server.url())
+                .with_credentials(Credentials::new("bybit-key", "bybit-secret")),
+        )
+        .unwrap()
+    }
+
+    fn limit_buy() -> OrderRequest {
+        OrderRequest {
+            client_order_id: "sor-1-0".to_string(),
+            pair: TradingPair::new("BTC", "USDT"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Limit,
+            quantity: dec!(0.5),
+            limit_price: Some(dec!(67000)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_place_order_is_signed_and_reports_the_order() {
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/v5/order/create")
+            .match_header("X-BAPI-API-KEY", "bybit-key")
+            .match_header("X-BAPI-RECV-WINDOW", "5000")
+            .match_header("X-BAPI-SIGN", mockito::Matcher::Regex("^[0-9a-f]{64}$".into()))
+            .match_header("X-BAPI-TIMESTAMP", mockito::Matcher::Regex(r"^\d{13}$".into()))
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "category": "spot",
+                "symbol": "BTCUSDT",
+                "side": "Buy",
+                "orderType": "Limit",
+                "qty": "0.5",
+                "price": "67000",
+                "orderLinkId": "sor-1-0",
+            })))
+            .with_body(
+                r#"{"retCode":0,"retMsg":"OK","result":{"orderId":"1793418244183937280","orderLinkId":"sor-1-0"}}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/v5/order/realtime")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "orderId".into(),
+                "1793418244183937280".into(),
+            ))
+            .with_body(include_str!(
+                "../../tests/fixtures/bybit/order_partially_filled.json"
+            ))
+            .create_async()
+            .await;
+
+        let report = trading_exchange(&server)
+            .place_order(&limit_buy())
+            .await
+            .unwrap();
+
+        create.assert_async().await;
+        assert_eq!(report.order_id, "1793418244183937280");
+        assert_eq!(report.status, OrderStatus::PartiallyFilled);
+        assert_eq!(report.quantity, dec!(0.5));
+        assert_eq!(report.filled_quantity, dec!(0.2));
+        assert_eq!(report.average_price, dec!(66990));
+        // 0.0002 BTC at the fill price
+        assert_eq!(report.fees, dec!(13.398));
+    }
+
+    #[tokio::test]
+    async fn test_finished_orders_are_found_in_history() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/v5/order/realtime")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"retCode":0,"retMsg":"OK","result":{"list":[]}}"#)
+            .create_async()
+            .await;
+        let history = server
+            .mock("GET", "/v5/order/history")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "orderLinkId".into(),
+                "sor-1-0".into(),
+            ))
+            .with_body(
+                include_str!("../../tests/fixtures/bybit/order_partially_filled.json")
+                    .replace("\"PartiallyFilled\"", "\"PartiallyFilledCanceled\""),
+            )
+            .create_async()
+            .await;
+        let bybit = trading_exchange(&server);
+
+        let report = bybit
+            .find_order(&TradingPair::new("BTC", "USDT"), "sor-1-0")
+            .await
+            .unwrap()
+            .unwrap();
+
+        history.assert_async().await;
+        assert_eq!(report.status, OrderStatus::Cancelled);
+        assert_eq!(report.filled_quantity, dec!(0.2));
+    }
+
+    #[tokio::test]
+    async fn test_refused_order_is_an_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v5/order/create")
+            .with_body(r#"{"retCode":170131,"retMsg":"Insufficient balance.","result":{}}"#)
+            .create_async()
+            .await;
+
+        let error = trading_exchange(&server)
+            .place_order(&limit_buy())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ExchangeError>(),
+            Some(ExchangeError::Api {
+                code: Some(170131),
+                ..
+            })
+        ));
+    }
+}
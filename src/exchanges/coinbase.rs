@@ -1,19 +1,547 @@
+use super::auth::{self, Credentials, ServerClock};
+use super::config::ConnectorConfig;
+use super::error::{self, ExchangeError};
+use super::fees::FeeSchedule;
+use super::pairs::PairDirectory;
+use super::rate_limit::{RateLimit, RateLimiter};
 use super::Exchange;
-use crate::types::{Liquidity, TradingPair};
-use anyhow::Result;
+use crate::types::{
+    Balance, InstrumentRules, OrderBook, OrderReport, OrderRequest, OrderSide, OrderStatus,
+    OrderType, PairListing, PriceLevel, TradingPair, TradingStatus,
+};
+use anyhow::{Context, Result};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Level 2 product book: `[price, size, num_orders]` per level
+#[derive(Debug, Deserialize)]
+struct CoinbaseProductBook {
+    bids: Vec<(String, String, serde_json::Value)>,
+    asks: Vec<(String, String, serde_json::Value)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinbaseError {
+    message: String,
+}
+
+/// `GET /products/{id}`
+#[derive(Debug, Deserialize)]
+struct CoinbaseProduct {
+    quote_increment: String,
+    base_increment: String,
+    #[serde(default)]
+    base_min_size: Option<String>,
+    #[serde(default)]
+    base_max_size: Option<String>,
+    #[serde(default)]
+    min_market_funds: Option<String>,
+}
+
+impl CoinbaseProduct {
+    fn rules(&self) -> Result<InstrumentRules> {
+        let optional = |value: &Option<String>| value.as_deref().map(Decimal::from_str).transpose();
+        let mut rules = InstrumentRules::new(
+            Decimal::from_str(&self.quote_increment)?,
+            Decimal::from_str(&self.base_increment)?,
+        );
+        if let Some(min) = optional(&self.base_min_size)? {
+            rules = rules.with_min_quantity(min);
+        }
+        if let Some(max) = optional(&self.base_max_size)? {
+            rules = rules.with_max_quantity(max);
+        }
+        if let Some(funds) = optional(&self.min_market_funds)? {
+            rules = rules.with_min_notional(funds);
+        }
+        Ok(rules)
+    }
+}
+
+/// One entry of `GET /products`
+#[derive(Debug, Deserialize)]
+struct CoinbaseListedProduct {
+    id: String,
+    base_currency: String,
+    quote_currency: String,
+    status: String,
+    #[serde(default)]
+    trading_disabled: bool,
+    #[serde(default)]
+    cancel_only: bool,
+    #[serde(default)]
+    post_only: bool,
+    #[serde(default)]
+    limit_only: bool,
+}
+
+impl CoinbaseListedProduct {
+    fn trading_status(&self) -> TradingStatus {
+        if self.status == "delisted" {
+            TradingStatus::Delisted
+        } else if self.status != "online" || self.trading_disabled || self.cancel_only {
+            TradingStatus::Halted
+        } else if self.post_only {
+            TradingStatus::PostOnly
+        } else if self.limit_only {
+            TradingStatus::LimitOnly
+        } else {
+            TradingStatus::Trading
+        }
+    }
+}
+
+/// One currency account from `GET /accounts`
+#[derive(Debug, Deserialize)]
+struct CoinbaseAccount {
+    currency: String,
+    balance: String,
+    hold: String,
+}
+
+/// `GET /time`; `epoch` is fractional seconds
+#[derive(Debug, Deserialize)]
+struct CoinbaseTime {
+    epoch: f64,
+}
+
+/// `POST /api/v3/brokerage/orders`; a refused order still answers 200
+/// with `success: false`
+#[derive(Debug, Deserialize)]
+struct CoinbaseCreateOrder {
+    success: bool,
+    #[serde(default)]
+    success_response: Option<CoinbaseOrderId>,
+    #[serde(default)]
+    error_response: Option<CoinbaseOrderError>,
+    #[serde(default)]
+    failure_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinbaseOrderId {
+    order_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinbaseOrderError {
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    message: String,
+}
+
+/// `POST /api/v3/brokerage/orders/batch_cancel`
+#[derive(Debug, Deserialize)]
+struct CoinbaseCancelResults {
+    results: Vec<CoinbaseCancelResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinbaseCancelResult {
+    success: bool,
+    #[serde(default)]
+    failure_reason: Option<String>,
+}
+
+/// `GET /api/v3/brokerage/orders/historical/{order_id}`
+#[derive(Debug, Deserialize)]
+struct CoinbaseOrderEnvelope {
+    order: CoinbaseOrder,
+}
+
+/// `GET /api/v3/brokerage/orders/historical/batch`
+#[derive(Debug, Deserialize)]
+struct CoinbaseOrders {
+    orders: Vec<CoinbaseOrder>,
+}
+
+/// Advanced Trade order; numeric fields are strings, empty until they apply
+#[derive(Debug, Deserialize)]
+struct CoinbaseOrder {
+    order_id: String,
+    client_order_id: String,
+    side: String,
+    status: String,
+    /// `{"<kind>": {"base_size": ..., ...}}`
+    order_configuration: serde_json::Value,
+    #[serde(default)]
+    filled_size: String,
+    #[serde(default)]
+    average_filled_price: String,
+    #[serde(default)]
+    total_fees: String,
+    created_time: DateTime<Utc>,
+    #[serde(default)]
+    last_fill_time: Option<DateTime<Utc>>,
+}
+
+impl CoinbaseOrder {
+    fn into_report(self, exchange: &str, pair: &TradingPair) -> Result<OrderReport> {
+        let amount = |value: &str| -> Result<Decimal> {
+            if value.is_empty() {
+                Ok(dec!(0))
+            } else {
+                Ok(Decimal::from_str(value)?)
+            }
+        };
+        let filled_quantity = amount(&self.filled_size)?;
+        // Orders sized in quote have no base size until they fill
+        let quantity = match self
+            .order_configuration
+            .as_object()
+            .and_then(|kinds| kinds.values().next())
+            .and_then(|kind| kind.get("base_size"))
+            .and_then(|size| size.as_str())
+        {
+            Some(size) => Decimal::from_str(size)?,
+            None => filled_quantity,
+        };
+        let status = match self.status.as_str() {
+            "PENDING" | "QUEUED" | "OPEN" if filled_quantity > dec!(0) => {
+                OrderStatus::PartiallyFilled
+            }
+            "PENDING" | "QUEUED" | "OPEN" => OrderStatus::New,
+            "CANCEL_QUEUED" => OrderStatus::PendingCancel,
+            "FILLED" => OrderStatus::Filled,
+            "CANCELLED" => OrderStatus::Cancelled,
+            "EXPIRED" => OrderStatus::Expired,
+            "FAILED" => OrderStatus::Rejected,
+            other => anyhow::bail!("Unknown Coinbase order status {}", other),
+        };
+        Ok(OrderReport {
+            order_id: self.order_id,
+            client_order_id: self.client_order_id,
+            exchange: exchange.to_string(),
+            pair: pair.clone(),
+            side: match self.side.as_str() {
+                "BUY" => OrderSide::Buy,
+                "SELL" => OrderSide::Sell,
+                other => anyhow::bail!("Unknown Coinbase order side {}", other),
+            },
+            status,
+            quantity,
+            filled_quantity,
+            average_price: amount(&self.average_filled_price)?,
+            fees: amount(&self.total_fees)?,
+            fills: Vec::new(),
+            updated_at: self.last_fill_time.unwrap_or(self.created_time),
+        })
+    }
+}
+
+fn parse_levels(levels: &[(String, String, serde_json::Value)]) -> Result<Vec<PriceLevel>> {
+    levels
+        .iter()
+        .map(|(price, size, _)| {
+            Ok(PriceLevel::new(
+                Decimal::from_str(price)?,
+                Decimal::from_str(size)?,
+            ))
+        })
+        .collect()
+}
+
+/// Coinbase Exchange production API
+pub const DEFAULT_BASE_URL: &str = "https://api.exchange.coinbase.com";
+/// Coinbase Exchange public sandbox
+pub const SANDBOX_BASE_URL: &str = "https://api-public.sandbox.exchange.coinbase.com";
+/// Coinbase Advanced Trade API, where orders are placed
+pub const ADVANCED_TRADE_BASE_URL: &str = "https://api.coinbase.com";
 
 pub struct CoinbaseExchange {
-    #[allow(dead_code)]
     client: reqwest::Client,
+    config: ConnectorConfig,
+    /// Base URL of the Advanced Trade order endpoints
+    trading_url: String,
+    clock: ServerClock,
+    /// Instrument rules change rarely, so each product is fetched once
+    rules: Mutex<HashMap<TradingPair, InstrumentRules>>,
+    pairs: PairDirectory,
+    limiter: RateLimiter,
 }
 
 impl CoinbaseExchange {
     pub fn new() -> Self {
-        Self {
-            client: reqwest::Client::new(),
+        Self::with_config(Self::default_config()).expect("default Coinbase configuration is valid")
+    }
+
+    /// Coinbase rejects requests without a User-Agent, so keep one configured
+    pub fn with_config(config: ConnectorConfig) -> Result<Self> {
+        Ok(Self {
+            client: config.build_client()?,
+            clock: ServerClock::new(),
+            rules: Mutex::new(HashMap::new()),
+            pairs: PairDirectory::new(config.pair_refresh),
+            limiter: config.rate_limiter("Coinbase"),
+            config,
+            trading_url: ADVANCED_TRADE_BASE_URL.to_string(),
+        })
+    }
+
+    /// Send orders to another Advanced Trade host, e.g. a sandbox
+    pub fn with_trading_url(mut self, trading_url: impl Into<String>) -> Self {
+        self.trading_url = trading_url.into();
+        self
+    }
+
+    /// Public endpoints allow 10 requests per second per IP
+    pub fn default_config() -> ConnectorConfig {
+        ConnectorConfig::new(DEFAULT_BASE_URL)
+            .with_rate_limit(RateLimit::new(10, Duration::from_secs(1)))
+    }
+
+    fn format_product_id(&self, pair: &TradingPair) -> String {
+        format!("{}-{}", pair.base.to_uppercase(), pair.quote.to_uppercase())
+    }
+
+    /// Measure the offset to Coinbase's clock; signed requests more than 30
+    /// seconds out are rejected
+    pub async fn sync_clock(&self) -> Result<(), ExchangeError> {
+        self.limiter.acquire(1).await?;
+        let sent = chrono::Utc::now().timestamp_millis();
+        let response = self
+            .client
+            .get(format!("{}/time", self.config.base_url))
+            .send()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let error = ExchangeError::from_status(self.name(), status, body);
+            self.limiter.observe(&error);
+            return Err(error);
+        }
+        let time: CoinbaseTime = response
+            .json()
+            .await
+            .map_err(|e| ExchangeError::parse(self.name(), e))?;
+        let received = chrono::Utc::now().timestamp_millis();
+        self.clock
+            .observe((time.epoch * 1000.0) as i64, sent, received);
+        Ok(())
+    }
+
+    pub fn clock(&self) -> &ServerClock {
+        &self.clock
+    }
+
+    /// `GET /products/{product_id}{suffix}`, mapping 404 to an unknown
+    /// product
+    async fn get_product_resource(
+        &self,
+        product_id: &str,
+        suffix: &str,
+    ) -> Result<String, ExchangeError> {
+        self.get_public(
+            &format!("/products/{}{}", product_id, suffix),
+            Some(product_id),
+        )
+        .await
+    }
+
+    /// Every product on `GET /products`
+    async fn fetch_listings(&self) -> Result<Vec<PairListing>, ExchangeError> {
+        let body = self.get_public("/products", None).await?;
+        let products: Vec<CoinbaseListedProduct> =
+            serde_json::from_str(&body).map_err(|e| ExchangeError::parse(self.name(), e))?;
+        Ok(products
+            .into_iter()
+            .map(|product| PairListing {
+                pair: TradingPair::new(&product.base_currency, &product.quote_currency),
+                status: product.trading_status(),
+                symbol: product.id,
+            })
+            .collect())
+    }
+
+    /// Unauthenticated `GET`; a 404 means `product_id` is not listed
+    async fn get_public(
+        &self,
+        path: &str,
+        product_id: Option<&str>,
+    ) -> Result<String, ExchangeError> {
+        self.limiter.acquire(1).await?;
+        let url = format!("{}{}", self.config.base_url, path);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+
+        let status = response.status();
+        let retry_after = error::retry_after(response.headers());
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<CoinbaseError>(&body)
+                .map(|error| error.message)
+                .unwrap_or_else(|_| body.clone());
+            let error = match (status.as_u16(), product_id) {
+                (404, Some(product_id)) => ExchangeError::UnknownSymbol {
+                    exchange: self.name().to_string(),
+                    symbol: product_id.to_string(),
+                },
+                (429, _) => ExchangeError::RateLimited {
+                    exchange: self.name().to_string(),
+                    retry_after,
+                },
+                _ => ExchangeError::from_status(self.name(), status, message),
+            };
+            self.limiter.observe(&error);
+            return Err(error);
+        }
+        Ok(body)
+    }
+
+    fn credentials(&self) -> Result<&Credentials, ExchangeError> {
+        self.config
+            .credentials
+            .as_ref()
+            .ok_or_else(|| ExchangeError::missing_credentials(self.name()))
+    }
+
+    /// Call a private endpoint, e.g. `GET /accounts`.
+    ///
+    /// Exchange API keys (base64 secret plus passphrase) are signed with the
+    /// `CB-ACCESS-*` HMAC headers. Developer Platform keys, whose secret is a
+    /// PEM EC private key, get a per-request JWT bearer token instead.
+    pub async fn signed_request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        request_path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T, ExchangeError> {
+        self.signed_request_to(&self.config.base_url, method, request_path, body)
+            .await
+    }
+
+    /// Call an Advanced Trade order endpoint, signed like [`Self::signed_request`]
+    async fn trading_request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        request_path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T, ExchangeError> {
+        self.signed_request_to(&self.trading_url, method, request_path, body)
+            .await
+    }
+
+    async fn signed_request_to<T: serde::de::DeserializeOwned>(
+        &self,
+        base_url: &str,
+        method: Method,
+        request_path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T, ExchangeError> {
+        let credentials = self.credentials()?;
+        // Before signing, so a wait cannot age the timestamp
+        self.limiter.acquire(1).await?;
+        let auth_error = |e: anyhow::Error| ExchangeError::Auth {
+            exchange: self.name().to_string(),
+            message: e.to_string(),
+        };
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let now_secs = self.clock.now_millis() / 1000;
+
+        let mut request = self
+            .client
+            .request(method.clone(), format!("{}{}", base_url, request_path));
+        if credentials.api_secret().contains("-----BEGIN") {
+            let host = base_url
+                .split_once("://")
+                .map_or(base_url, |(_, host)| host);
+            // The token covers the path only, not the query string
+            let path = request_path.split('?').next().unwrap_or(request_path);
+            let token = auth::coinbase_jwt(
+                &credentials.api_key,
+                credentials.api_secret(),
+                &format!("{} {}{}", method, host, path),
+                now_secs,
+            )
+            .map_err(auth_error)?;
+            request = request.bearer_auth(token);
+        } else {
+            let timestamp = now_secs.to_string();
+            let signature = auth::sign_coinbase(
+                credentials.api_secret(),
+                &timestamp,
+                method.as_str(),
+                request_path,
+                &body,
+            )
+            .map_err(auth_error)?;
+            request = request
+                .header("CB-ACCESS-KEY", &credentials.api_key)
+                .header("CB-ACCESS-SIGN", signature)
+                .header("CB-ACCESS-TIMESTAMP", timestamp);
+            if let Some(passphrase) = &credentials.passphrase {
+                request = request.header("CB-ACCESS-PASSPHRASE", passphrase);
+            }
+        }
+        if !body.is_empty() {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body);
         }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+        let status = response.status();
+        let retry_after = error::retry_after(response.headers());
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+        if !status.is_success() {
+            if status.as_u16() == 429 {
+                let error = ExchangeError::RateLimited {
+                    exchange: self.name().to_string(),
+                    retry_after,
+                };
+                self.limiter.observe(&error);
+                return Err(error);
+            }
+            let message = serde_json::from_str::<CoinbaseError>(&body)
+                .map(|error| error.message)
+                .unwrap_or(body);
+            return Err(ExchangeError::from_status(self.name(), status, message));
+        }
+
+        serde_json::from_str(&body).map_err(|e| ExchangeError::parse(self.name(), e))
+    }
+
+    /// `GET /api/v3/brokerage/orders/historical/{order_id}`
+    async fn fetch_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let envelope: CoinbaseOrderEnvelope = self
+            .trading_request(
+                Method::GET,
+                &format!("/api/v3/brokerage/orders/historical/{}", order_id),
+                None,
+            )
+            .await?;
+        envelope.order.into_report(self.name(), pair)
+    }
+}
+
+impl Default for CoinbaseExchange {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -23,19 +551,494 @@ impl Exchange for CoinbaseExchange {
         "Coinbase"
     }
 
-    async fn get_liquidity(&self, pair: &TradingPair) -> Result<Liquidity> {
-        // Mock implementation - in production, this would call Coinbase API
-        Ok(Liquidity {
-            exchange: self.name().to_string(),
-            pair: pair.clone(),
-            bid_price: dec!(49950.0),
-            bid_quantity: dec!(1.2),
-            ask_price: dec!(50000.0),
-            ask_quantity: dec!(1.8),
-        })
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        let product_id = self.format_product_id(pair);
+        let body = self
+            .get_product_resource(&product_id, "/book?level=2")
+            .await?;
+
+        let mut book: CoinbaseProductBook =
+            serde_json::from_str(&body).map_err(|e| ExchangeError::parse(self.name(), e))?;
+        // Level 2 always returns the top 50 levels; keep the configured depth
+        book.bids.truncate(self.config.depth_limit);
+        book.asks.truncate(self.config.depth_limit);
+
+        // REST snapshots are aged from receipt, so Coinbase's `time` (on its
+        // own clock) is not carried over
+        Ok(OrderBook::new(
+            self.name(),
+            pair.clone(),
+            parse_levels(&book.bids).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid bid level: {}", e))
+            })?,
+            parse_levels(&book.asks).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid ask level: {}", e))
+            })?,
+        ))
+    }
+
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        // An unreachable listing is no evidence the product is missing
+        !matches!(self.pair_status(pair).await, Ok(None))
+    }
+
+    async fn list_pairs(&self) -> Result<Vec<PairListing>> {
+        Ok(self
+            .pairs
+            .listings(self.name(), || self.fetch_listings())
+            .await?)
+    }
+
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        self.pairs
+            .status(self.name(), pair, || self.fetch_listings())
+            .await
+    }
+
+    async fn instrument_rules(&self, pair: &TradingPair) -> Result<InstrumentRules, ExchangeError> {
+        if let Some(rules) = self.rules.lock().unwrap().get(pair) {
+            return Ok(rules.clone());
+        }
+
+        let body = self
+            .get_product_resource(&self.format_product_id(pair), "")
+            .await?;
+        let rules = serde_json::from_str::<CoinbaseProduct>(&body)
+            .map_err(anyhow::Error::from)
+            .and_then(|product| product.rules())
+            .map_err(|e| ExchangeError::parse(self.name(), e))?;
+
+        self.rules
+            .lock()
+            .unwrap()
+            .insert(pair.clone(), rules.clone());
+        Ok(rules)
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        FeeSchedule::flat(dec!(0.004), dec!(0.006))
+            .with_tier(dec!(10000), dec!(0.0025), dec!(0.004))
+            .with_tier(dec!(50000), dec!(0.0015), dec!(0.0025))
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        let base_size = request.quantity.normalize().to_string();
+        let configuration = match request.order_type {
+            OrderType::Market => serde_json::json!({
+                "market_market_ioc": {"base_size": base_size}
+            }),
+            OrderType::Limit => {
+                let price = request
+                    .limit_price
+                    .context("Limit order has no limit price")?;
+                serde_json::json!({
+                    "limit_limit_gtc": {
+                        "base_size": base_size,
+                        "limit_price": price.normalize().to_string(),
+                        "post_only": false,
+                    }
+                })
+            }
+        };
+        let body = serde_json::json!({
+            "client_order_id": request.client_order_id,
+            "product_id": self.format_product_id(&request.pair),
+            "side": match request.side {
+                OrderSide::Buy => "BUY",
+                OrderSide::Sell => "SELL",
+            },
+            "order_configuration": configuration,
+        });
+
+        let created: CoinbaseCreateOrder = self
+            .trading_request(Method::POST, "/api/v3/brokerage/orders", Some(&body))
+            .await?;
+        let order_id = match created.success_response {
+            Some(accepted) if created.success => accepted.order_id,
+            _ => {
+                let message = match created.error_response {
+                    Some(error) if !error.message.is_empty() => {
+                        format!("{}: {}", error.error, error.message)
+                    }
+                    Some(error) => error.error,
+                    None => created.failure_reason.unwrap_or_default(),
+                };
+                return Err(ExchangeError::Api {
+                    exchange: self.name().to_string(),
+                    code: None,
+                    message,
+                }
+                .into());
+            }
+        };
+
+        // The create response only acknowledges the order; its state comes
+        // from the order itself
+        self.fetch_order(&request.pair, &order_id).await
+    }
+
+    async fn cancel_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let body = serde_json::json!({ "order_ids": [order_id] });
+        let cancelled: CoinbaseCancelResults = self
+            .trading_request(
+                Method::POST,
+                "/api/v3/brokerage/orders/batch_cancel",
+                Some(&body),
+            )
+            .await?;
+        match cancelled.results.first() {
+            Some(result) if result.success => {}
+            result => {
+                return Err(ExchangeError::Api {
+                    exchange: self.name().to_string(),
+                    code: None,
+                    message: format!(
+                        "cancel of {} failed: {}",
+                        order_id,
+                        result
+                            .and_then(|result| result.failure_reason.as_deref())
+                            .unwrap_or("no result")
+                    ),
+                }
+                .into())
+            }
+        }
+        self.fetch_order(pair, order_id).await
     }
 
-    async fn supports_pair(&self, _pair: &TradingPair) -> bool {
-        true
+    async fn get_order_status(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        self.fetch_order(pair, order_id).await
+    }
+
+    async fn get_open_orders(&self, pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        let orders: CoinbaseOrders = self
+            .trading_request(
+                Method::GET,
+                &format!(
+                    "/api/v3/brokerage/orders/historical/batch?product_ids={}&order_status=OPEN",
+                    self.format_product_id(pair)
+                ),
+                None,
+            )
+            .await?;
+        orders
+            .orders
+            .into_iter()
+            .map(|order| order.into_report(self.name(), pair))
+            .collect()
+    }
+
+    async fn get_balances(&self) -> Result<Vec<Balance>> {
+        let accounts: Vec<CoinbaseAccount> =
+            self.signed_request(Method::GET, "/accounts", None).await?;
+        accounts
+            .into_iter()
+            .map(|account| {
+                Ok(Balance::new(
+                    account.currency,
+                    Decimal::from_str(&account.balance)?,
+                    Decimal::from_str(&account.hold)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(server: &mockito::Server) -> CoinbaseExchange {
+        CoinbaseExchange::with_config(ConnectorConfig::new(server.url())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_signed_request_uses_key_type_specific_auth() {
+        let mut server = mockito::Server::new_async().await;
+        let hmac = server
+            .mock("GET", "/accounts")
+            .match_header("CB-ACCESS-KEY", "exchange-key")
+            .match_header("CB-ACCESS-PASSPHRASE", "phrase")
+            .match_header(
+                "CB-ACCESS-SIGN",
+                mockito::Matcher::Regex("^[A-Za-z0-9+/]{43}=$".into()),
+            )
+            .match_header(
+                "CB-ACCESS-TIMESTAMP",
+                mockito::Matcher::Regex(r"^\d+$".into()),
+            )
+            .with_body("[]")
+            .create_async()
+            .await;
+        let jwt = server
+            .mock("GET", "/api/v3/brokerage/accounts")
+            .match_header(
+                "Authorization",
+                mockito::Matcher::Regex(r"^Bearer ey[\w-]+\.[\w-]+\.[\w-]+$".into()),
+            )
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let exchange_key = CoinbaseExchange::with_config(
+            ConnectorConfig::new(server.url()).with_credentials(
+                Credentials::new("exchange-key", "Y29pbmJhc2UtdGVzdC1zZWNyZXQ=")
+                    .with_passphrase("phrase"),
+            ),
+        )
+        .unwrap();
+        let accounts: Vec<serde_json::Value> = exchange_key
+            .signed_request(Method::GET, "/accounts", None)
+            .await
+            .unwrap();
+        assert!(accounts.is_empty());
+
+        let cdp_key = CoinbaseExchange::with_config(
+            ConnectorConfig::new(server.url()).with_credentials(Credentials::new(
+                "organizations/org/apiKeys/key",
+                include_str!("../../tests/fixtures/coinbase/cdp_key.pem"),
+            )),
+        )
+        .unwrap();
+        let _: Vec<serde_json::Value> = cdp_key
+            .signed_request(Method::GET, "/api/v3/brokerage/accounts", None)
+            .await
+            .unwrap();
+
+        hmac.assert_async().await;
+        jwt.assert_async().await;
+
+        let error = exchange(&server)
+            .signed_request::<serde_json::Value>(Method::GET, "/accounts", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ExchangeError::Auth { .. }));
+    }
+
+    fn trading_exchange(server: &mockito::Server) -> CoinbaseExchange {
+        CoinbaseExchange::with_config(
+            ConnectorConfig::new(server.url()).with_credentials(
+                Credentials::new("exchange-key", "Y29pbmJhc2UtdGVzdC1zZWNyZXQ=")
+                    .with_passphrase("phrase"),
+            ),
+        )
+        .unwrap()
+        .with_trading_url(server.url())
+    }
+
+    #[tokio::test]
+    async fn test_place_order_reports_the_venues_order_state() {
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/api/v3/brokerage/orders")
+            .match_header("CB-ACCESS-KEY", "exchange-key")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "client_order_id": "sor-1-0",
+                "product_id": "BTC-USD",
+                "side": "BUY",
+                "order_configuration": {
+                    "limit_limit_gtc": {"base_size": "0.5", "limit_price": "50000"}
+                }
+            })))
+            .with_body(r#"{"success":true,"success_response":{"order_id":"0000-000000-000000"}}"#)
+            .create_async()
+            .await;
+        server
+            .mock(
+                "GET",
+                "/api/v3/brokerage/orders/historical/0000-000000-000000",
+            )
+            .with_body(include_str!(
+                "../../tests/fixtures/coinbase/order_partially_filled.json"
+            ))
+            .create_async()
+            .await;
+
+        let request = OrderRequest {
+            client_order_id: "sor-1-0".to_string(),
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Limit,
+            quantity: dec!(0.5),
+            limit_price: Some(dec!(50000)),
+        };
+        let report = trading_exchange(&server)
+            .place_order(&request)
+            .await
+            .unwrap();
+
+        create.assert_async().await;
+        assert_eq!(report.order_id, "0000-000000-000000");
+        assert_eq!(report.exchange, "Coinbase");
+        assert_eq!(report.status, OrderStatus::PartiallyFilled);
+        assert_eq!(report.quantity, dec!(0.5));
+        assert_eq!(report.filled_quantity, dec!(0.2));
+        assert_eq!(report.average_price, dec!(49990.00));
+        assert_eq!(report.fees, dec!(11.9976));
+    }
+
+    #[tokio::test]
+    async fn test_refused_order_is_an_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/v3/brokerage/orders")
+            .with_body(
+                r#"{"success":false,"failure_reason":"UNKNOWN_FAILURE_REASON",
+                    "error_response":{"error":"INSUFFICIENT_FUND","message":"Insufficient balance in source account"}}"#,
+            )
+            .create_async()
+            .await;
+
+        let request = OrderRequest {
+            client_order_id: "sor-1-0".to_string(),
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Sell,
+            order_type: OrderType::Market,
+            quantity: dec!(1),
+            limit_price: None,
+        };
+        let error = trading_exchange(&server)
+            .place_order(&request)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("INSUFFICIENT_FUND"));
+
+        // Without credentials nothing is sent at all
+        let error = exchange(&server).place_order(&request).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ExchangeError>(),
+            Some(ExchangeError::Auth { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_instrument_rules_from_product() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/products/BTC-USD")
+            .with_body(include_str!(
+                "../../tests/fixtures/coinbase/product_btc_usd.json"
+            ))
+            .create_async()
+            .await;
+
+        let rules = exchange(&server)
+            .instrument_rules(&TradingPair::new("BTC", "USD"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            rules,
+            InstrumentRules::new(dec!(0.01), dec!(0.00000001)).with_min_notional(dec!(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_pairs_reports_product_status() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/products")
+            .with_body(include_str!("../../tests/fixtures/coinbase/products.json"))
+            .expect(1)
+            .create_async()
+            .await;
+        let coinbase = exchange(&server);
+
+        let listings = coinbase.list_pairs().await.unwrap();
+        let statuses: Vec<_> = listings
+            .iter()
+            .map(|listing| (listing.symbol.as_str(), listing.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("BTC-USD", TradingStatus::Trading),
+                ("ETH-USD", TradingStatus::PostOnly),
+                ("RNDR-USD", TradingStatus::Delisted),
+            ]
+        );
+        assert_eq!(listings[1].pair, TradingPair::new("ETH", "USD"));
+        assert!(
+            coinbase
+                .supports_pair(&TradingPair::new("BTC", "USD"))
+                .await
+        );
+        assert!(
+            !coinbase
+                .supports_pair(&TradingPair::new("BTC", "EUR"))
+                .await
+        );
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_liquidity_parses_product_book() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/products/BTC-USD/book?level=2")
+            .with_body(include_str!(
+                "../../tests/fixtures/coinbase/book_btc_usd.json"
+            ))
+            .create_async()
+            .await;
+
+        let book = exchange(&server)
+            .get_liquidity(&TradingPair::new("btc", "usd"))
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(book.bids.len(), 3);
+        assert_eq!(book.asks.len(), 3);
+        assert_eq!(
+            book.best_bid(),
+            Some(&PriceLevel::new(dec!(67012.45), dec!(0.4182)))
+        );
+        assert_eq!(book.best_ask().unwrap().price, dec!(67013.10));
+        assert_eq!(book.exchange_time, None);
+    }
+
+    #[tokio::test]
+    async fn test_get_liquidity_surfaces_http_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/products/FOO-USD/book?level=2")
+            .with_status(404)
+            .with_body(include_str!("../../tests/fixtures/coinbase/not_found.json"))
+            .create_async()
+            .await;
+
+        let error = exchange(&server)
+            .get_liquidity(&TradingPair::new("FOO", "USD"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            ExchangeError::UnknownSymbol { symbol, .. } if symbol == "FOO-USD"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_liquidity_rejects_malformed_level() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/products/BTC-USD/book?level=2")
+            .with_body(r#"{"bids":[["abc","1.0",1]],"asks":[]}"#)
+            .create_async()
+            .await;
+
+        let error = exchange(&server)
+            .get_liquidity(&TradingPair::new("BTC", "USD"))
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(error, ExchangeError::Parse { message, .. } if message.contains("bid level"))
+        );
     }
 }
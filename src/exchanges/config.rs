@@ -0,0 +1,303 @@
+use super::auth::{Credentials, CredentialsSource};
+use super::rate_limit::{RateLimit, RateLimiter};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// User agent sent by every connector unless overridden
+pub const DEFAULT_USER_AGENT: &str = concat!("smart-order-router/", env!("CARGO_PKG_VERSION"));
+
+/// Connection settings for a REST connector
+#[derive(Debug, Clone)]
+pub struct ConnectorConfig {
+    pub base_url: String,
+    /// Per-request timeout
+    pub timeout: Duration,
+    /// Number of price levels requested per side
+    pub depth_limit: usize,
+    /// HTTP(S) proxy URL all requests are sent through
+    pub proxy: Option<String>,
+    pub user_agent: String,
+    /// Needed for account and trading endpoints only
+    pub credentials: Option<Credentials>,
+    /// How long the venue's list of pairs is trusted before it is refetched
+    pub pair_refresh: Duration,
+    /// Request weight the venue allows; shared by every call the connector makes
+    pub rate_limit: RateLimit,
+    /// Longest a request may queue for rate-limit capacity before failing
+    pub max_throttle_wait: Duration,
+}
+
+impl ConnectorConfig {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            timeout: Duration::from_secs(10),
+            depth_limit: 10,
+            proxy: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            credentials: None,
+            pair_refresh: Duration::from_secs(3600),
+            rate_limit: RateLimit::new(10, Duration::from_secs(1)),
+            max_throttle_wait: Duration::from_millis(250),
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_depth_limit(mut self, depth_limit: usize) -> Self {
+        self.depth_limit = depth_limit;
+        self
+    }
+
+    pub fn with_proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    pub fn with_pair_refresh(mut self, pair_refresh: Duration) -> Self {
+        self.pair_refresh = pair_refresh;
+        self
+    }
+
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    pub fn with_max_throttle_wait(mut self, max_throttle_wait: Duration) -> Self {
+        self.max_throttle_wait = max_throttle_wait;
+        self
+    }
+
+    /// Limiter enforcing `rate_limit` for `exchange`
+    pub fn rate_limiter(&self, exchange: &str) -> RateLimiter {
+        RateLimiter::new(exchange, self.rate_limit, self.max_throttle_wait)
+    }
+
+    /// Build an HTTP client honouring the timeout, proxy and user agent
+    pub fn build_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .user_agent(&self.user_agent);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(
+                reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy {}", proxy))?,
+            );
+        }
+        builder.build().context("Failed to build HTTP client")
+    }
+}
+
+/// Supported connector kinds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Venue {
+    Binance,
+    Coinbase,
+    Kraken,
+    Okx,
+    Bybit,
+    Bitstamp,
+}
+
+/// Settings for one venue in an [`ExchangesConfig`]; unset fields keep the
+/// connector's defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VenueConfig {
+    pub venue: Venue,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub depth_limit: Option<usize>,
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub credentials: Option<CredentialsSource>,
+    #[serde(default)]
+    pub pair_refresh_secs: Option<u64>,
+    /// Request weight allowed per `rate_limit_window_ms`
+    #[serde(default)]
+    pub rate_limit_weight: Option<u32>,
+    #[serde(default)]
+    pub rate_limit_window_ms: Option<u64>,
+    #[serde(default)]
+    pub max_throttle_wait_ms: Option<u64>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl VenueConfig {
+    pub fn new(venue: Venue) -> Self {
+        Self {
+            venue,
+            enabled: true,
+            base_url: None,
+            timeout_ms: None,
+            depth_limit: None,
+            proxy: None,
+            user_agent: None,
+            credentials: None,
+            pair_refresh_secs: None,
+            rate_limit_weight: None,
+            rate_limit_window_ms: None,
+            max_throttle_wait_ms: None,
+        }
+    }
+
+    /// Apply the overrides in this entry on top of `defaults`, loading
+    /// credentials if a source is configured
+    pub fn connector_config(&self, defaults: ConnectorConfig) -> Result<ConnectorConfig> {
+        let credentials = match &self.credentials {
+            Some(source) => Some(
+                source
+                    .load()
+                    .with_context(|| format!("Failed to load {:?} credentials", self.venue))?,
+            ),
+            None => defaults.credentials,
+        };
+        Ok(ConnectorConfig {
+            base_url: self.base_url.clone().unwrap_or(defaults.base_url),
+            timeout: self
+                .timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.timeout),
+            depth_limit: self.depth_limit.unwrap_or(defaults.depth_limit),
+            proxy: self.proxy.clone().or(defaults.proxy),
+            user_agent: self.user_agent.clone().unwrap_or(defaults.user_agent),
+            credentials,
+            pair_refresh: self
+                .pair_refresh_secs
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.pair_refresh),
+            rate_limit: RateLimit::new(
+                self.rate_limit_weight.unwrap_or(defaults.rate_limit.weight),
+                self.rate_limit_window_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(defaults.rate_limit.window),
+            ),
+            max_throttle_wait: self
+                .max_throttle_wait_ms
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.max_throttle_wait),
+        })
+    }
+}
+
+/// Which venues to connect to and how
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangesConfig {
+    pub venues: Vec<VenueConfig>,
+}
+
+impl ExchangesConfig {
+    /// Parse a JSON configuration
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("Invalid exchanges configuration")
+    }
+
+    /// Load a JSON configuration file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_json(&json)
+    }
+}
+
+impl Default for ExchangesConfig {
+    fn default() -> Self {
+        Self {
+            venues: vec![
+                VenueConfig::new(Venue::Binance),
+                VenueConfig::new(Venue::Coinbase),
+                VenueConfig::new(Venue::Kraken),
+                VenueConfig::new(Venue::Okx),
+                VenueConfig::new(Venue::Bybit),
+                VenueConfig::new(Venue::Bitstamp),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_apply_on_top_of_defaults() {
+        let config = ExchangesConfig::from_json(
+            r#"{"venues": [
+                {"venue": "binance", "base_url": "https://api.binance.us", "depth_limit": 50,
+                 "rate_limit_weight": 1200},
+                {"venue": "kraken", "enabled": false}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.venues.len(), 2);
+        assert!(config.venues[0].enabled);
+        assert!(!config.venues[1].enabled);
+
+        let binance = config.venues[0]
+            .connector_config(
+                ConnectorConfig::new("https://api.binance.com")
+                    .with_timeout(Duration::from_secs(3)),
+            )
+            .unwrap();
+        assert_eq!(binance.base_url, "https://api.binance.us");
+        assert_eq!(binance.depth_limit, 50);
+        assert_eq!(binance.timeout, Duration::from_secs(3));
+        assert_eq!(
+            binance.rate_limit,
+            RateLimit::new(1200, Duration::from_secs(1))
+        );
+        assert_eq!(binance.user_agent, DEFAULT_USER_AGENT);
+        assert!(binance.credentials.is_none());
+    }
+
+    #[test]
+    fn test_missing_credentials_fail_loudly() {
+        let config = ExchangesConfig::from_json(
+            r#"{"venues": [{"venue": "kraken", "credentials": {"env": "SOR_CONFIG_TEST_UNSET"}}]}"#,
+        )
+        .unwrap();
+        let error = config.venues[0]
+            .connector_config(ConnectorConfig::new("https://api.kraken.com"))
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("SOR_CONFIG_TEST_UNSET_API_KEY"));
+    }
+
+    #[test]
+    fn test_invalid_proxy_is_rejected() {
+        let config = ConnectorConfig::new("http://localhost").with_proxy("not a url");
+        assert!(config.build_client().is_err());
+    }
+}
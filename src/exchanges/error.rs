@@ -0,0 +1,124 @@
+use std::time::Duration;
+use thiserror::Error;
+
+/// Failure talking to an exchange
+#[derive(Debug, Clone, Error)]
+pub enum ExchangeError {
+    #[error("{exchange} rate limited the request")]
+    RateLimited {
+        exchange: String,
+        /// How long the exchange asked us to wait, if it said
+        retry_after: Option<Duration>,
+    },
+
+    #[error("{exchange} does not list {symbol}")]
+    UnknownSymbol { exchange: String, symbol: String },
+
+    #[error("{exchange} is unavailable for maintenance")]
+    Maintenance { exchange: String },
+
+    #[error("{exchange} rejected the request credentials: {message}")]
+    Auth { exchange: String, message: String },
+
+    #[error("network error talking to {exchange}: {message}")]
+    Network { exchange: String, message: String },
+
+    #[error("could not parse {exchange} response: {message}")]
+    Parse { exchange: String, message: String },
+
+    #[error("{exchange} returned an error: {message}")]
+    Api {
+        exchange: String,
+        /// HTTP status or venue-specific error code
+        code: Option<i64>,
+        message: String,
+    },
+}
+
+impl ExchangeError {
+    pub fn network(exchange: &str, error: impl std::fmt::Display) -> Self {
+        ExchangeError::Network {
+            exchange: exchange.to_string(),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn parse(exchange: &str, error: impl std::fmt::Display) -> Self {
+        ExchangeError::Parse {
+            exchange: exchange.to_string(),
+            message: error.to_string(),
+        }
+    }
+
+    /// A private endpoint was called on a connector without credentials
+    pub fn missing_credentials(exchange: &str) -> Self {
+        ExchangeError::Auth {
+            exchange: exchange.to_string(),
+            message: "no API credentials configured".to_string(),
+        }
+    }
+
+    /// Classify a non-success HTTP response that carried no venue-specific code
+    pub fn from_status(exchange: &str, status: reqwest::StatusCode, message: String) -> Self {
+        match status.as_u16() {
+            // Binance answers 418 once an IP is banned for ignoring 429s
+            429 | 418 => ExchangeError::RateLimited {
+                exchange: exchange.to_string(),
+                retry_after: None,
+            },
+            401 | 403 => ExchangeError::Auth {
+                exchange: exchange.to_string(),
+                message,
+            },
+            503 => ExchangeError::Maintenance {
+                exchange: exchange.to_string(),
+            },
+            code => ExchangeError::Api {
+                exchange: exchange.to_string(),
+                code: Some(code as i64),
+                message,
+            },
+        }
+    }
+
+    /// Whether the venue certainly did not act on the request.
+    ///
+    /// A dropped connection, an unreadable answer or a server-side failure
+    /// can come after the venue accepted an order, so such an order may
+    /// still exist and must be looked up before it is sent again.
+    pub fn is_definite(&self) -> bool {
+        match self {
+            ExchangeError::Network { .. }
+            | ExchangeError::Parse { .. }
+            | ExchangeError::Maintenance { .. } => false,
+            ExchangeError::Api {
+                code: Some(code), ..
+            } => !(500..600).contains(code),
+            _ => true,
+        }
+    }
+
+    /// Name of the exchange that failed
+    pub fn exchange(&self) -> &str {
+        match self {
+            ExchangeError::RateLimited { exchange, .. }
+            | ExchangeError::UnknownSymbol { exchange, .. }
+            | ExchangeError::Maintenance { exchange }
+            | ExchangeError::Auth { exchange, .. }
+            | ExchangeError::Network { exchange, .. }
+            | ExchangeError::Parse { exchange, .. }
+            | ExchangeError::Api { exchange, .. } => exchange,
+        }
+    }
+}
+
+/// `Retry-After` header value, in whole seconds
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
@@ -0,0 +1,157 @@
+use crate::types::TradingPair;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Maker/taker fee rates expressed as fractions (0.001 = 10 bps)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeRates {
+    pub maker: Decimal,
+    pub taker: Decimal,
+    /// Fixed charge per order in the quote asset, on top of the rates
+    /// (e.g. gas for an on-chain swap)
+    #[serde(default)]
+    pub per_order: Decimal,
+}
+
+impl FeeRates {
+    pub fn new(maker: Decimal, taker: Decimal) -> Self {
+        Self {
+            maker,
+            taker,
+            per_order: dec!(0),
+        }
+    }
+
+    pub fn with_per_order(mut self, per_order: Decimal) -> Self {
+        self.per_order = per_order;
+        self
+    }
+}
+
+/// Fee rates that apply once 30-day traded volume reaches `min_volume`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeTier {
+    pub min_volume: Decimal,
+    pub rates: FeeRates,
+}
+
+/// Fee schedule for a venue: base rates, volume tiers and per-pair overrides
+#[derive(Debug, Clone)]
+pub struct FeeSchedule {
+    base: FeeRates,
+    tiers: Vec<VolumeTier>,
+    pair_overrides: HashMap<TradingPair, FeeRates>,
+    thirty_day_volume: Decimal,
+    per_order: Decimal,
+}
+
+impl FeeSchedule {
+    /// Schedule with the same rates for every pair and volume
+    pub fn flat(maker: Decimal, taker: Decimal) -> Self {
+        Self {
+            base: FeeRates::new(maker, taker),
+            tiers: Vec::new(),
+            pair_overrides: HashMap::new(),
+            thirty_day_volume: dec!(0),
+            per_order: dec!(0),
+        }
+    }
+
+    /// Add a volume tier
+    pub fn with_tier(mut self, min_volume: Decimal, maker: Decimal, taker: Decimal) -> Self {
+        self.tiers.push(VolumeTier {
+            min_volume,
+            rates: FeeRates::new(maker, taker),
+        });
+        self.tiers.sort_by_key(|tier| tier.min_volume);
+        self
+    }
+
+    /// Override rates for a single pair (e.g. zero-fee promotions)
+    pub fn with_pair_override(mut self, pair: TradingPair, maker: Decimal, taker: Decimal) -> Self {
+        self.pair_overrides
+            .insert(pair, FeeRates::new(maker, taker));
+        self
+    }
+
+    /// Set the account's 30-day traded volume used to pick a tier
+    pub fn with_volume(mut self, thirty_day_volume: Decimal) -> Self {
+        self.thirty_day_volume = thirty_day_volume;
+        self
+    }
+
+    /// Charge a fixed quote-asset amount on every order, whatever its size
+    pub fn with_per_order_fee(mut self, per_order: Decimal) -> Self {
+        self.per_order = per_order;
+        self
+    }
+
+    /// Resolve the rates that apply to a pair.
+    ///
+    /// Pair overrides win over volume tiers, which win over base rates.
+    /// The per-order fee applies whichever rates are picked.
+    pub fn rates_for(&self, pair: &TradingPair) -> FeeRates {
+        let rates = match self.pair_overrides.get(pair) {
+            Some(rates) => *rates,
+            None => self
+                .tiers
+                .iter()
+                .rev()
+                .find(|tier| self.thirty_day_volume >= tier.min_volume)
+                .map(|tier| tier.rates)
+                .unwrap_or(self.base),
+        };
+        rates.with_per_order(self.per_order)
+    }
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self::flat(dec!(0.001), dec!(0.001))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tier_selection() {
+        let schedule = FeeSchedule::flat(dec!(0.004), dec!(0.006))
+            .with_tier(dec!(10000), dec!(0.0025), dec!(0.004))
+            .with_tier(dec!(50000), dec!(0.0015), dec!(0.0025));
+        let pair = TradingPair::new("BTC", "USD");
+
+        assert_eq!(schedule.rates_for(&pair).taker, dec!(0.006));
+        let schedule = schedule.with_volume(dec!(20000));
+        assert_eq!(schedule.rates_for(&pair).taker, dec!(0.004));
+        let schedule = schedule.with_volume(dec!(50000));
+        assert_eq!(schedule.rates_for(&pair).taker, dec!(0.0025));
+    }
+
+    #[test]
+    fn test_pair_override() {
+        let pair = TradingPair::new("BTC", "USDT");
+        let schedule = FeeSchedule::default().with_pair_override(pair.clone(), dec!(0), dec!(0));
+
+        assert_eq!(schedule.rates_for(&pair).taker, dec!(0));
+        assert_eq!(
+            schedule.rates_for(&TradingPair::new("ETH", "USDT")).taker,
+            dec!(0.001)
+        );
+    }
+
+    #[test]
+    fn test_per_order_fee_applies_over_overrides() {
+        let pair = TradingPair::new("ETH", "USDC");
+        let schedule = FeeSchedule::flat(dec!(0), dec!(0))
+            .with_pair_override(pair.clone(), dec!(0), dec!(0.0005))
+            .with_per_order_fee(dec!(4.5));
+
+        let rates = schedule.rates_for(&pair);
+        assert_eq!(rates.taker, dec!(0.0005));
+        assert_eq!(rates.per_order, dec!(4.5));
+    }
+}
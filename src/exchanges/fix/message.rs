@@ -0,0 +1,364 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// Field separator
+pub const SOH: u8 = 0x01;
+pub const BEGIN_STRING: &str = "FIX.4.4";
+
+/// Tags used by the session layer and the connector
+pub mod tags {
+    pub const AVG_PX: u32 = 6;
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECKSUM: u32 = 10;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const COMMISSION: u32 = 12;
+    pub const CUM_QTY: u32 = 14;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const EXEC_ID: u32 = 17;
+    pub const LAST_PX: u32 = 31;
+    pub const LAST_QTY: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const PRICE: u32 = 44;
+    pub const REF_SEQ_NUM: u32 = 45;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const TIME_IN_FORCE: u32 = 59;
+    pub const TRANSACT_TIME: u32 = 60;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const ORIG_SENDING_TIME: u32 = 122;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const RESET_SEQ_NUM_FLAG: u32 = 141;
+    pub const NO_RELATED_SYM: u32 = 146;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const LEAVES_QTY: u32 = 151;
+    pub const MD_REQ_ID: u32 = 262;
+    pub const SUBSCRIPTION_REQUEST_TYPE: u32 = 263;
+    pub const MARKET_DEPTH: u32 = 264;
+    pub const MD_UPDATE_TYPE: u32 = 265;
+    pub const NO_MD_ENTRY_TYPES: u32 = 267;
+    pub const NO_MD_ENTRIES: u32 = 268;
+    pub const MD_ENTRY_TYPE: u32 = 269;
+    pub const MD_ENTRY_PX: u32 = 270;
+    pub const MD_ENTRY_SIZE: u32 = 271;
+    pub const MD_UPDATE_ACTION: u32 = 279;
+    pub const MD_REQ_REJ_REASON: u32 = 281;
+    pub const USERNAME: u32 = 553;
+    pub const PASSWORD: u32 = 554;
+}
+
+/// MsgType (35) values
+pub mod msg_type {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const LOGON: &str = "A";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+    pub const MARKET_DATA_REQUEST: &str = "V";
+    pub const MARKET_DATA_SNAPSHOT: &str = "W";
+    pub const MARKET_DATA_INCREMENTAL: &str = "X";
+    pub const MARKET_DATA_REQUEST_REJECT: &str = "Y";
+
+    /// Session-level messages, which are gap-filled rather than resent
+    pub fn is_admin(msg_type: &str) -> bool {
+        matches!(
+            msg_type,
+            HEARTBEAT | TEST_REQUEST | RESEND_REQUEST | REJECT | SEQUENCE_RESET | LOGOUT | LOGON
+        )
+    }
+}
+
+/// A FIX message: its type and every other field in wire order.
+///
+/// BeginString, BodyLength and CheckSum are computed on encoding and
+/// checked on decoding, so they are never stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    msg_type: String,
+    fields: Vec<(u32, String)>,
+}
+
+impl Message {
+    pub fn new(msg_type: &str) -> Self {
+        Self {
+            msg_type: msg_type.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, tag: u32, value: impl ToString) -> Self {
+        self.push(tag, value);
+        self
+    }
+
+    pub fn push(&mut self, tag: u32, value: impl ToString) {
+        self.fields.push((tag, value.to_string()));
+    }
+
+    /// Drop every occurrence of `tag`
+    pub fn remove(&mut self, tag: u32) {
+        self.fields.retain(|(t, _)| *t != tag);
+    }
+
+    pub fn msg_type(&self) -> &str {
+        &self.msg_type
+    }
+
+    pub fn fields(&self) -> &[(u32, String)] {
+        &self.fields
+    }
+
+    /// First value of `tag`
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// First value of `tag`, failing if it is missing
+    pub fn require(&self, tag: u32) -> Result<&str> {
+        self.get(tag)
+            .with_context(|| format!("{} message has no tag {}", self.msg_type, tag))
+    }
+
+    pub fn decimal(&self, tag: u32) -> Result<Option<Decimal>> {
+        self.get(tag)
+            .map(|value| {
+                Decimal::from_str(value).with_context(|| format!("bad decimal in tag {tag}"))
+            })
+            .transpose()
+    }
+
+    pub fn seq_num(&self) -> Option<u64> {
+        self.get(tags::MSG_SEQ_NUM)?.parse().ok()
+    }
+
+    pub fn is_poss_dup(&self) -> bool {
+        self.get(tags::POSS_DUP_FLAG) == Some("Y")
+    }
+
+    /// Entries of the repeating group counted by `count_tag`.
+    ///
+    /// `members` lists the tags an entry may hold, delimiter first: each
+    /// delimiter starts a new entry, and the group ends at the first field
+    /// that is not a member.
+    pub fn group(&self, count_tag: u32, members: &[u32]) -> Vec<Vec<(u32, &str)>> {
+        let Some(start) = self.fields.iter().position(|(t, _)| *t == count_tag) else {
+            return Vec::new();
+        };
+        let mut entries: Vec<Vec<(u32, &str)>> = Vec::new();
+        for (tag, value) in &self.fields[start + 1..] {
+            if !members.contains(tag) {
+                break;
+            }
+            if *tag == members[0] || entries.is_empty() {
+                entries.push(Vec::new());
+            }
+            if let Some(entry) = entries.last_mut() {
+                entry.push((*tag, value.as_str()));
+            }
+        }
+        entries
+    }
+
+    /// Serialise with BeginString, BodyLength and CheckSum
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        write_field(&mut body, tags::MSG_TYPE, &self.msg_type);
+        for (tag, value) in &self.fields {
+            write_field(&mut body, *tag, value);
+        }
+
+        let mut frame = Vec::with_capacity(body.len() + 32);
+        write_field(&mut frame, tags::BEGIN_STRING, BEGIN_STRING);
+        write_field(&mut frame, tags::BODY_LENGTH, &body.len().to_string());
+        frame.extend_from_slice(&body);
+        let checksum = checksum(&frame);
+        write_field(&mut frame, tags::CHECKSUM, &format!("{checksum:03}"));
+        frame
+    }
+
+    /// Parse one complete frame, verifying its length and checksum
+    pub fn decode(frame: &[u8]) -> Result<Message> {
+        let text = std::str::from_utf8(frame).context("FIX frame is not UTF-8")?;
+        let mut fields = text
+            .strip_suffix('\x01')
+            .context("FIX frame does not end with SOH")?
+            .split('\x01')
+            .map(|field| {
+                let (tag, value) = field
+                    .split_once('=')
+                    .with_context(|| format!("malformed FIX field {field:?}"))?;
+                let tag: u32 = tag
+                    .parse()
+                    .with_context(|| format!("malformed FIX tag {tag:?}"))?;
+                Ok((tag, value.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        match fields.first() {
+            Some((tags::BEGIN_STRING, version)) if version == BEGIN_STRING => {}
+            other => anyhow::bail!("expected BeginString {BEGIN_STRING}, got {other:?}"),
+        }
+        let (body_start, body_length) = match fields.get(1) {
+            Some((tags::BODY_LENGTH, length)) => (
+                // "8=FIX.4.4<SOH>9=" + length + SOH
+                BEGIN_STRING.len() + 5 + length.len() + 1,
+                length.parse::<usize>().context("malformed BodyLength")?,
+            ),
+            other => anyhow::bail!("expected BodyLength, got {other:?}"),
+        };
+        let checksum_at = text.rfind("\x0110=").context("FIX frame has no CheckSum")? + 1;
+        if checksum_at.checked_sub(body_start) != Some(body_length) {
+            anyhow::bail!("BodyLength {body_length} does not match the frame");
+        }
+        let expected = checksum(&frame[..checksum_at]);
+        match fields.last() {
+            Some((tags::CHECKSUM, value)) if value.parse::<u8>().ok() == Some(expected) => {}
+            other => anyhow::bail!("bad CheckSum {other:?}, expected {expected:03}"),
+        }
+
+        fields.pop();
+        let mut fields = fields.split_off(2);
+        if fields.first().map(|(tag, _)| *tag) != Some(tags::MSG_TYPE) {
+            anyhow::bail!("MsgType must follow BodyLength");
+        }
+        let (_, msg_type) = fields.remove(0);
+        Ok(Message { msg_type, fields })
+    }
+}
+
+/// Split the first complete frame off the front of `buffer`, or `None` if
+/// more bytes are needed
+pub fn take_frame(buffer: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+    const PREFIX: &[u8] = b"8=FIX.4.4\x019=";
+    if buffer.len() < PREFIX.len() {
+        return Ok(None);
+    }
+    if !buffer.starts_with(PREFIX) {
+        anyhow::bail!("stream is out of frame");
+    }
+    let Some(length_end) = buffer[PREFIX.len()..].iter().position(|&b| b == SOH) else {
+        return Ok(None);
+    };
+    let body_length: usize = std::str::from_utf8(&buffer[PREFIX.len()..PREFIX.len() + length_end])?
+        .parse()
+        .context("malformed BodyLength")?;
+    // Body, then "10=NNN<SOH>"
+    let frame_length = PREFIX.len() + length_end + 1 + body_length + 7;
+    if buffer.len() < frame_length {
+        return Ok(None);
+    }
+    let rest = buffer.split_off(frame_length);
+    Ok(Some(std::mem::replace(buffer, rest)))
+}
+
+/// UTCTimestamp with milliseconds, as used for SendingTime and TransactTime
+pub fn format_timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%d-%H:%M:%S%.3f").to_string()
+}
+
+pub fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%d-%H:%M:%S%.f")
+        .with_context(|| format!("bad UTCTimestamp {value:?}"))?;
+    Ok(time.and_utc())
+}
+
+fn write_field(out: &mut Vec<u8>, tag: u32, value: &str) {
+    out.extend_from_slice(tag.to_string().as_bytes());
+    out.push(b'=');
+    out.extend_from_slice(value.as_bytes());
+    out.push(SOH);
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_matches_reference_frame() {
+        let message = Message::new(msg_type::HEARTBEAT)
+            .with(tags::SENDER_COMP_ID, "CLIENT")
+            .with(tags::TARGET_COMP_ID, "VENUE")
+            .with(tags::MSG_SEQ_NUM, 2)
+            .with(tags::SENDING_TIME, "20241018-12:00:00.000");
+
+        let frame = message.encode();
+        assert_eq!(
+            String::from_utf8(frame.clone())
+                .unwrap()
+                .replace('\x01', "|"),
+            "8=FIX.4.4|9=54|35=0|49=CLIENT|56=VENUE|34=2|52=20241018-12:00:00.000|10=253|"
+        );
+        assert_eq!(Message::decode(&frame).unwrap(), message);
+
+        let mut corrupted = frame.clone();
+        let at = corrupted.len() - 5;
+        corrupted[at] = b'9';
+        assert!(Message::decode(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_frames_are_split_from_a_stream() {
+        let first = Message::new(msg_type::TEST_REQUEST)
+            .with(tags::TEST_REQ_ID, "a")
+            .encode();
+        let second = Message::new(msg_type::HEARTBEAT).encode();
+        let mut buffer = [first.clone(), second[..10].to_vec()].concat();
+
+        assert_eq!(take_frame(&mut buffer).unwrap(), Some(first));
+        assert_eq!(take_frame(&mut buffer).unwrap(), None);
+        buffer.extend_from_slice(&second[10..]);
+        assert_eq!(take_frame(&mut buffer).unwrap(), Some(second));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_repeating_group() {
+        let message = Message::new(msg_type::MARKET_DATA_SNAPSHOT)
+            .with(tags::SYMBOL, "BTC/USD")
+            .with(tags::NO_MD_ENTRIES, 2)
+            .with(tags::MD_ENTRY_TYPE, "0")
+            .with(tags::MD_ENTRY_PX, "49990")
+            .with(tags::MD_ENTRY_SIZE, "1.5")
+            .with(tags::MD_ENTRY_TYPE, "1")
+            .with(tags::MD_ENTRY_PX, "50010")
+            .with(tags::TEXT, "not in the group");
+
+        let entries = message.group(
+            tags::NO_MD_ENTRIES,
+            &[tags::MD_ENTRY_TYPE, tags::MD_ENTRY_PX, tags::MD_ENTRY_SIZE],
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], vec![(269, "0"), (270, "49990"), (271, "1.5")]);
+        assert_eq!(entries[1], vec![(269, "1"), (270, "50010")]);
+    }
+}
@@ -0,0 +1,926 @@
+pub mod message;
+pub mod session;
+pub mod store;
+#[cfg(test)]
+pub(crate) mod test_acceptor;
+
+use super::auth::ServerClock;
+use super::error::ExchangeError;
+use super::fees::FeeSchedule;
+use super::Exchange;
+use crate::streaming::book::{BookUpdate, LocalOrderBook};
+use crate::types::{
+    Fill, OrderBook, OrderReport, OrderRequest, OrderSide, OrderStatus, OrderType, PriceLevel,
+    TradingPair,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use message::{format_timestamp, msg_type, parse_timestamp, tags, Message};
+use rust_decimal_macros::dec;
+use session::{FixSession, SessionConfig};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use store::MessageStore;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+
+/// What the venue has told us over the session so far
+#[derive(Default)]
+struct VenueState {
+    /// Books by FIX symbol
+    books: HashMap<String, LocalOrderBook>,
+    /// Symbols with a market data subscription sent
+    subscribed: HashSet<String>,
+    /// MarketDataRequestReject reason code and text by MDReqID
+    md_rejects: HashMap<String, (Option<i64>, String)>,
+    /// Latest view of each order, by the venue's OrderID
+    orders: HashMap<String, OrderReport>,
+    /// ExecIDs already applied, so resent reports do not add fills twice
+    exec_ids: HashSet<String>,
+    /// OrderCancelReject text by the ClOrdID of the cancel
+    cancel_rejects: HashMap<String, String>,
+    /// Session-level Reject text by the MsgSeqNum it refers to
+    session_rejects: HashMap<u64, String>,
+    /// Venue clock, so SendingTime can be compared with ours
+    clock: Arc<ServerClock>,
+}
+
+/// Venue reached over a FIX 4.4 session, such as an institutional venue
+/// or OTC desk without a REST API.
+///
+/// Books are built from a market data subscription: the first request
+/// for a pair subscribes to full snapshots plus incremental refreshes,
+/// and later requests read the locally maintained book. Orders go out as
+/// NewOrderSingle and OrderCancelRequest; ExecutionReports keep each
+/// order's state, fills and commission current, and order queries answer
+/// from that state.
+pub struct FixExchange {
+    name: String,
+    session: FixSession,
+    state: Arc<Mutex<VenueState>>,
+    /// Bumped whenever an application message has been applied
+    updates: Arc<watch::Sender<u64>>,
+    fees: FeeSchedule,
+    depth: usize,
+    request_timeout: Duration,
+    dispatcher: JoinHandle<()>,
+}
+
+impl FixExchange {
+    /// Log on to the venue and start tracking its messages
+    pub async fn connect(
+        name: impl Into<String>,
+        config: SessionConfig,
+        store: Box<dyn MessageStore>,
+    ) -> Result<Self> {
+        let session = FixSession::connect(config, store).await?;
+        Ok(Self::new(name, session))
+    }
+
+    /// Track an established session
+    pub fn new(name: impl Into<String>, session: FixSession) -> Self {
+        let name = name.into();
+        let state = Arc::new(Mutex::new(VenueState {
+            clock: session.clock(),
+            ..VenueState::default()
+        }));
+        let updates = Arc::new(watch::Sender::new(0));
+        let dispatcher = tokio::spawn(dispatch(
+            name.clone(),
+            session.subscribe(),
+            state.clone(),
+            updates.clone(),
+        ));
+        Self {
+            name,
+            session,
+            state,
+            updates,
+            fees: FeeSchedule::default(),
+            depth: 20,
+            request_timeout: Duration::from_secs(5),
+            dispatcher,
+        }
+    }
+
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// Levels per side requested and returned
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// How long to wait for the venue to answer a request
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    pub fn session(&self) -> &FixSession {
+        &self.session
+    }
+
+    /// Wait until `check` finds what it is looking for in the venue state,
+    /// or the request timeout passes. Subscribe to `updates` before sending
+    /// the request so no answer is missed.
+    async fn wait_for<T>(
+        &self,
+        mut updates: watch::Receiver<u64>,
+        mut check: impl FnMut(&VenueState) -> Option<T>,
+    ) -> Option<T> {
+        tokio::time::timeout(self.request_timeout, async {
+            loop {
+                if let Some(found) = check(&self.state.lock().unwrap()) {
+                    return Some(found);
+                }
+                if updates.changed().await.is_err() {
+                    return None;
+                }
+            }
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+}
+
+impl Drop for FixExchange {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
+/// Instrument symbol as FIX venues usually quote it
+pub fn to_fix_symbol(pair: &TradingPair) -> String {
+    format!("{}/{}", pair.base.to_uppercase(), pair.quote.to_uppercase())
+}
+
+fn from_fix_symbol(symbol: &str) -> Result<TradingPair> {
+    let (base, quote) = symbol
+        .split_once('/')
+        .with_context(|| format!("Unrecognised FIX symbol {symbol:?}"))?;
+    Ok(TradingPair::new(base, quote))
+}
+
+fn side_code(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Buy => "1",
+        OrderSide::Sell => "2",
+    }
+}
+
+/// Apply application messages to the venue state as the session delivers them
+async fn dispatch(
+    name: String,
+    mut messages: broadcast::Receiver<Message>,
+    state: Arc<Mutex<VenueState>>,
+    updates: Arc<watch::Sender<u64>>,
+) {
+    loop {
+        match messages.recv().await {
+            Ok(message) => {
+                let mut state = state.lock().unwrap();
+                if let Err(error) = apply(&name, &mut state, &message) {
+                    log::warn!(
+                        "{name}: could not apply {} message: {error:#}",
+                        message.msg_type()
+                    );
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                // Missed increments leave every book suspect; resubscribing
+                // brings fresh snapshots
+                log::warn!("{name}: fell {missed} messages behind; resubscribing market data");
+                let mut state = state.lock().unwrap();
+                state.subscribed.clear();
+                for book in state.books.values_mut() {
+                    book.invalidate();
+                }
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+        updates.send_modify(|version| *version += 1);
+    }
+}
+
+fn apply(name: &str, state: &mut VenueState, message: &Message) -> Result<()> {
+    match message.msg_type() {
+        msg_type::MARKET_DATA_SNAPSHOT => apply_snapshot(name, state, message),
+        msg_type::MARKET_DATA_INCREMENTAL => apply_incremental(state, message),
+        msg_type::MARKET_DATA_REQUEST_REJECT => {
+            let request_id = message.require(tags::MD_REQ_ID)?;
+            let reason = message
+                .get(tags::MD_REQ_REJ_REASON)
+                .and_then(|reason| reason.parse().ok());
+            let text = message.get(tags::TEXT).unwrap_or("request rejected");
+            state
+                .md_rejects
+                .insert(request_id.to_string(), (reason, text.to_string()));
+            Ok(())
+        }
+        msg_type::EXECUTION_REPORT => apply_execution_report(name, state, message),
+        msg_type::ORDER_CANCEL_REJECT => {
+            let cl_ord_id = message.require(tags::CL_ORD_ID)?;
+            let text = message.get(tags::TEXT).unwrap_or("cancel rejected");
+            state
+                .cancel_rejects
+                .insert(cl_ord_id.to_string(), text.to_string());
+            Ok(())
+        }
+        msg_type::REJECT => {
+            let ref_seq = message.require(tags::REF_SEQ_NUM)?.parse()?;
+            let text = message.get(tags::TEXT).unwrap_or("message rejected");
+            state.session_rejects.insert(ref_seq, text.to_string());
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Price and size of an MD entry, with size zero when it is a deletion
+fn md_level(entry: &[(u32, &str)], deleted: bool) -> Result<PriceLevel> {
+    let field = |tag| entry.iter().find(|(t, _)| *t == tag).map(|(_, v)| *v);
+    let price = field(tags::MD_ENTRY_PX).context("MD entry has no price")?;
+    let quantity = if deleted {
+        dec!(0)
+    } else {
+        field(tags::MD_ENTRY_SIZE)
+            .context("MD entry has no size")?
+            .parse()?
+    };
+    Ok(PriceLevel::new(price.parse()?, quantity))
+}
+
+fn apply_snapshot(name: &str, state: &mut VenueState, message: &Message) -> Result<()> {
+    let symbol = message.require(tags::SYMBOL)?;
+    let (mut bids, mut asks) = (Vec::new(), Vec::new());
+    for entry in message.group(
+        tags::NO_MD_ENTRIES,
+        &[tags::MD_ENTRY_TYPE, tags::MD_ENTRY_PX, tags::MD_ENTRY_SIZE],
+    ) {
+        match entry.first() {
+            Some((_, "0")) => bids.push(md_level(&entry, false)?),
+            Some((_, "1")) => asks.push(md_level(&entry, false)?),
+            // Trades and statistics are not book levels
+            _ => {}
+        }
+    }
+
+    let event_time = sending_time(state, message);
+    let pair = from_fix_symbol(symbol)?;
+    let book = state
+        .books
+        .entry(symbol.to_string())
+        .or_insert_with(|| LocalOrderBook::new(name, pair));
+    book.apply_snapshot(book.sequence() + 1, &bids, &asks, event_time);
+    Ok(())
+}
+
+/// When the venue sent `message`, on our clock
+fn sending_time(state: &VenueState, message: &Message) -> Option<DateTime<Utc>> {
+    message
+        .get(tags::SENDING_TIME)
+        .and_then(|time| parse_timestamp(time).ok())
+        .map(|time| state.clock.to_local(time))
+}
+
+fn apply_incremental(state: &mut VenueState, message: &Message) -> Result<()> {
+    let event_time = sending_time(state, message);
+    let mut changes: HashMap<&str, (Vec<PriceLevel>, Vec<PriceLevel>)> = HashMap::new();
+    for entry in message.group(
+        tags::NO_MD_ENTRIES,
+        &[
+            tags::MD_UPDATE_ACTION,
+            tags::MD_ENTRY_TYPE,
+            tags::SYMBOL,
+            tags::MD_ENTRY_PX,
+            tags::MD_ENTRY_SIZE,
+        ],
+    ) {
+        let field = |tag| entry.iter().find(|(t, _)| *t == tag).map(|(_, v)| *v);
+        let symbol = field(tags::SYMBOL)
+            .or(message.get(tags::SYMBOL))
+            .context("MD entry has no symbol")?;
+        let deleted = field(tags::MD_UPDATE_ACTION) == Some("2");
+        let (bids, asks) = changes.entry(symbol).or_default();
+        match field(tags::MD_ENTRY_TYPE) {
+            Some("0") => bids.push(md_level(&entry, deleted)?),
+            Some("1") => asks.push(md_level(&entry, deleted)?),
+            _ => {}
+        }
+    }
+
+    // The session delivers messages gap-free and in order, so each refresh
+    // simply follows the last one applied
+    for (symbol, (bids, asks)) in changes {
+        if let Some(book) = state.books.get_mut(symbol) {
+            let sequence = book.sequence() + 1;
+            let _ = book.apply_update(&BookUpdate {
+                first_sequence: sequence,
+                last_sequence: sequence,
+                bids,
+                asks,
+                event_time,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn apply_execution_report(name: &str, state: &mut VenueState, message: &Message) -> Result<()> {
+    let status = match message.require(tags::ORD_STATUS)? {
+        "0" => Some(OrderStatus::New),
+        "1" => Some(OrderStatus::PartiallyFilled),
+        "2" => Some(OrderStatus::Filled),
+        "4" => Some(OrderStatus::Cancelled),
+        "6" => Some(OrderStatus::PendingCancel),
+        "8" => Some(OrderStatus::Rejected),
+        "C" => Some(OrderStatus::Expired),
+        // Other pending and replaced states keep the order's status but
+        // still carry quantities and fills
+        _ => None,
+    };
+    if let Some(exec_id) = message.get(tags::EXEC_ID) {
+        if !state.exec_ids.insert(exec_id.to_string()) {
+            return Ok(());
+        }
+    }
+
+    let order_id = message.require(tags::ORDER_ID)?;
+    let updated_at = message
+        .get(tags::TRANSACT_TIME)
+        .map(parse_timestamp)
+        .transpose()?
+        .unwrap_or_else(Utc::now);
+    let report = match state.orders.entry(order_id.to_string()) {
+        std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+        std::collections::hash_map::Entry::Vacant(entry) => {
+            let side = match message.require(tags::SIDE)? {
+                "1" => OrderSide::Buy,
+                "2" => OrderSide::Sell,
+                other => anyhow::bail!("Unknown side {other}"),
+            };
+            entry.insert(OrderReport {
+                order_id: order_id.to_string(),
+                client_order_id: message.require(tags::CL_ORD_ID)?.to_string(),
+                exchange: name.to_string(),
+                pair: from_fix_symbol(message.require(tags::SYMBOL)?)?,
+                side,
+                status: status.unwrap_or(OrderStatus::New),
+                quantity: dec!(0),
+                filled_quantity: dec!(0),
+                average_price: dec!(0),
+                fees: dec!(0),
+                fills: Vec::new(),
+                updated_at,
+            })
+        }
+    };
+
+    if let Some(status) = status {
+        report.status = status;
+    }
+    report.updated_at = updated_at;
+    if let Some(quantity) = message.decimal(tags::ORDER_QTY)? {
+        report.quantity = quantity;
+    }
+    if let Some(filled) = message.decimal(tags::CUM_QTY)? {
+        report.filled_quantity = filled;
+    }
+    if let Some(average_price) = message.decimal(tags::AVG_PX)? {
+        report.average_price = average_price;
+    }
+    // ExecType F: a trade, priced and sized by LastPx/LastQty
+    if message.get(tags::EXEC_TYPE) == Some("F") {
+        let fee = message.decimal(tags::COMMISSION)?.unwrap_or(dec!(0));
+        report.fills.push(Fill {
+            price: message
+                .decimal(tags::LAST_PX)?
+                .context("Trade has no LastPx")?,
+            quantity: message
+                .decimal(tags::LAST_QTY)?
+                .context("Trade has no LastQty")?,
+            fee,
+            timestamp: updated_at,
+        });
+        report.fees += fee;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Exchange for FixExchange {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        if !self.session.is_logged_on() {
+            return Err(ExchangeError::network(
+                &self.name,
+                "FIX session is not logged on",
+            ));
+        }
+        let symbol = to_fix_symbol(pair);
+        let request_id = format!("md-{symbol}");
+        let updates = self.updates.subscribe();
+        let subscribe = {
+            let mut state = self.state.lock().unwrap();
+            if let Some(book) = state.books.get(&symbol).filter(|book| book.is_synced()) {
+                return Ok(book.to_order_book(self.depth));
+            }
+            state.md_rejects.remove(&request_id);
+            state.subscribed.insert(symbol.clone())
+        };
+
+        if subscribe {
+            let request = Message::new(msg_type::MARKET_DATA_REQUEST)
+                .with(tags::MD_REQ_ID, &request_id)
+                // Snapshot plus updates, as incremental refreshes
+                .with(tags::SUBSCRIPTION_REQUEST_TYPE, 1)
+                .with(tags::MARKET_DEPTH, self.depth)
+                .with(tags::MD_UPDATE_TYPE, 1)
+                .with(tags::NO_MD_ENTRY_TYPES, 2)
+                .with(tags::MD_ENTRY_TYPE, 0)
+                .with(tags::MD_ENTRY_TYPE, 1)
+                .with(tags::NO_RELATED_SYM, 1)
+                .with(tags::SYMBOL, &symbol);
+            if let Err(error) = self.session.send(request).await {
+                self.state.lock().unwrap().subscribed.remove(&symbol);
+                return Err(ExchangeError::network(&self.name, error));
+            }
+        }
+
+        let answer = self
+            .wait_for(updates, |state| {
+                if let Some(reject) = state.md_rejects.get(&request_id) {
+                    return Some(Err(reject.clone()));
+                }
+                state
+                    .books
+                    .get(&symbol)
+                    .filter(|book| book.is_synced())
+                    .map(|book| Ok(book.to_order_book(self.depth)))
+            })
+            .await;
+        match answer {
+            Some(Ok(book)) => Ok(book),
+            Some(Err((reason, message))) => {
+                self.state.lock().unwrap().subscribed.remove(&symbol);
+                // MDReqRejReason 0: unknown symbol
+                Err(if reason == Some(0) {
+                    ExchangeError::UnknownSymbol {
+                        exchange: self.name.clone(),
+                        symbol,
+                    }
+                } else {
+                    ExchangeError::Api {
+                        exchange: self.name.clone(),
+                        code: reason,
+                        message,
+                    }
+                })
+            }
+            None => Err(ExchangeError::network(
+                &self.name,
+                format!(
+                    "no market data for {symbol} within {:?}",
+                    self.request_timeout
+                ),
+            )),
+        }
+    }
+
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        // FIX venues rarely publish a security list; the market data
+        // request is the test
+        !matches!(
+            self.get_liquidity(pair).await,
+            Err(ExchangeError::UnknownSymbol { .. })
+        )
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        self.fees.clone()
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        let mut message = Message::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tags::CL_ORD_ID, &request.client_order_id)
+            .with(tags::SYMBOL, to_fix_symbol(&request.pair))
+            .with(tags::SIDE, side_code(request.side))
+            .with(tags::TRANSACT_TIME, format_timestamp(Utc::now()))
+            .with(tags::ORDER_QTY, request.quantity);
+        match request.order_type {
+            OrderType::Market => message.push(tags::ORD_TYPE, 1),
+            OrderType::Limit => {
+                let price = request
+                    .limit_price
+                    .context("Limit order has no limit price")?;
+                message.push(tags::ORD_TYPE, 2);
+                message.push(tags::PRICE, price);
+                // Good till cancel
+                message.push(tags::TIME_IN_FORCE, 1);
+            }
+        }
+
+        let updates = self.updates.subscribe();
+        let seq = self.session.send(message).await?;
+        let market = request.order_type == OrderType::Market;
+        let answer = self
+            .wait_for(updates, |state| {
+                if let Some(text) = state.session_rejects.get(&seq) {
+                    return Some(Err(text.clone()));
+                }
+                let report = state
+                    .orders
+                    .values()
+                    .find(|report| report.client_order_id == request.client_order_id)?;
+                // Market orders are reported once they have stopped trading
+                if market && report.status.is_open() {
+                    return None;
+                }
+                Some(Ok(report.clone()))
+            })
+            .await;
+        match answer {
+            Some(Ok(report)) => Ok(report),
+            Some(Err(text)) => Err(ExchangeError::Api {
+                exchange: self.name.clone(),
+                code: None,
+                message: format!("order {} rejected: {}", request.client_order_id, text),
+            }
+            .into()),
+            None => anyhow::bail!(
+                "{} did not acknowledge order {} within {:?}",
+                self.name,
+                request.client_order_id,
+                self.request_timeout
+            ),
+        }
+    }
+
+    async fn cancel_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let original = self
+            .state
+            .lock()
+            .unwrap()
+            .orders
+            .get(order_id)
+            .cloned()
+            .with_context(|| format!("{} has no order {}", self.name, order_id))?;
+        if !original.status.is_open() {
+            anyhow::bail!("Order {} is not open", order_id);
+        }
+
+        let cl_ord_id = format!(
+            "{}-cancel-{}",
+            original.client_order_id,
+            Utc::now().timestamp_millis()
+        );
+        let message = Message::new(msg_type::ORDER_CANCEL_REQUEST)
+            .with(tags::ORIG_CL_ORD_ID, &original.client_order_id)
+            .with(tags::ORDER_ID, order_id)
+            .with(tags::CL_ORD_ID, &cl_ord_id)
+            .with(tags::SYMBOL, to_fix_symbol(pair))
+            .with(tags::SIDE, side_code(original.side))
+            .with(tags::TRANSACT_TIME, format_timestamp(Utc::now()))
+            .with(tags::ORDER_QTY, original.quantity);
+
+        let updates = self.updates.subscribe();
+        let seq = self.session.send(message).await?;
+        let answer = self
+            .wait_for(updates, |state| {
+                if let Some(text) = state
+                    .session_rejects
+                    .get(&seq)
+                    .or(state.cancel_rejects.get(&cl_ord_id))
+                {
+                    return Some(Err(text.clone()));
+                }
+                state
+                    .orders
+                    .get(order_id)
+                    .filter(|report| !report.status.is_open())
+                    .map(|report| Ok(report.clone()))
+            })
+            .await;
+        match answer {
+            Some(Ok(report)) => Ok(report),
+            Some(Err(text)) => {
+                anyhow::bail!("{} refused to cancel {}: {}", self.name, order_id, text)
+            }
+            None => anyhow::bail!(
+                "{} did not confirm cancelling {} within {:?}",
+                self.name,
+                order_id,
+                self.request_timeout
+            ),
+        }
+    }
+
+    async fn get_order_status(&self, _pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        self.state
+            .lock()
+            .unwrap()
+            .orders
+            .get(order_id)
+            .cloned()
+            .with_context(|| format!("{} has no order {}", self.name, order_id))
+    }
+
+    async fn get_open_orders(&self, pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .orders
+            .values()
+            .filter(|report| &report.pair == pair && report.status.is_open())
+            .cloned()
+            .collect())
+    }
+
+    /// Only execution reports received this session are known; an order
+    /// without one may still be on its way
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        let state = self.state.lock().unwrap();
+        match state
+            .orders
+            .values()
+            .find(|report| &report.pair == pair && report.client_order_id == client_order_id)
+        {
+            Some(report) => Ok(Some(report.clone())),
+            None => anyhow::bail!(
+                "{} has sent no execution report for {}",
+                self.name,
+                client_order_id
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use store::MemoryStore;
+
+    /// Venue that quotes BTC/USD, rejects other symbols, fills market
+    /// orders in two trades and rests limit orders until cancelled
+    fn venue(message: &Message) -> Vec<Message> {
+        static NEXT_ORDER: AtomicU64 = AtomicU64::new(1);
+        let report = |order_id: &str, status: &str, exec_type: &str, cum_qty: &str| {
+            Message::new(msg_type::EXECUTION_REPORT)
+                .with(tags::ORDER_ID, order_id)
+                .with(tags::CL_ORD_ID, message.get(tags::CL_ORD_ID).unwrap())
+                .with(tags::EXEC_ID, format!("{order_id}-{exec_type}-{cum_qty}"))
+                .with(tags::EXEC_TYPE, exec_type)
+                .with(tags::ORD_STATUS, status)
+                .with(tags::SYMBOL, message.get(tags::SYMBOL).unwrap())
+                .with(tags::SIDE, message.get(tags::SIDE).unwrap())
+                .with(tags::ORDER_QTY, message.get(tags::ORDER_QTY).unwrap())
+                .with(tags::CUM_QTY, cum_qty)
+        };
+
+        match message.msg_type() {
+            msg_type::MARKET_DATA_REQUEST if message.get(tags::SYMBOL) == Some("BTC/USD") => {
+                vec![Message::new(msg_type::MARKET_DATA_SNAPSHOT)
+                    .with(tags::MD_REQ_ID, message.get(tags::MD_REQ_ID).unwrap())
+                    .with(tags::SYMBOL, "BTC/USD")
+                    .with(tags::NO_MD_ENTRIES, 4)
+                    .with(tags::MD_ENTRY_TYPE, 0)
+                    .with(tags::MD_ENTRY_PX, "49990")
+                    .with(tags::MD_ENTRY_SIZE, "1.5")
+                    .with(tags::MD_ENTRY_TYPE, 0)
+                    .with(tags::MD_ENTRY_PX, "49980")
+                    .with(tags::MD_ENTRY_SIZE, "2")
+                    .with(tags::MD_ENTRY_TYPE, 1)
+                    .with(tags::MD_ENTRY_PX, "50010")
+                    .with(tags::MD_ENTRY_SIZE, "1")
+                    .with(tags::MD_ENTRY_TYPE, 1)
+                    .with(tags::MD_ENTRY_PX, "50020")
+                    .with(tags::MD_ENTRY_SIZE, "3")]
+            }
+            msg_type::MARKET_DATA_REQUEST => {
+                vec![Message::new(msg_type::MARKET_DATA_REQUEST_REJECT)
+                    .with(tags::MD_REQ_ID, message.get(tags::MD_REQ_ID).unwrap())
+                    .with(tags::MD_REQ_REJ_REASON, 0)
+                    .with(tags::TEXT, "Unknown symbol")]
+            }
+            msg_type::NEW_ORDER_SINGLE => {
+                let order_id = format!("V{}", NEXT_ORDER.fetch_add(1, Ordering::SeqCst));
+                let mut replies = vec![report(&order_id, "0", "0", "0")];
+                if message.get(tags::ORD_TYPE) == Some("1") {
+                    replies.push(
+                        report(&order_id, "1", "F", "0.4")
+                            .with(tags::LAST_PX, "50010")
+                            .with(tags::LAST_QTY, "0.4")
+                            .with(tags::AVG_PX, "50010")
+                            .with(tags::COMMISSION, "20.004"),
+                    );
+                    replies.push(
+                        report(&order_id, "2", "F", "0.5")
+                            .with(tags::LAST_PX, "50020")
+                            .with(tags::LAST_QTY, "0.1")
+                            .with(tags::AVG_PX, "50012")
+                            .with(tags::COMMISSION, "5.002"),
+                    );
+                }
+                replies
+            }
+            msg_type::ORDER_CANCEL_REQUEST => {
+                let order_id = message.get(tags::ORDER_ID).unwrap();
+                vec![report(order_id, "4", "4", "0").with(
+                    tags::ORIG_CL_ORD_ID,
+                    message.get(tags::ORIG_CL_ORD_ID).unwrap(),
+                )]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    async fn exchange(acceptor: &test_acceptor::Acceptor) -> FixExchange {
+        let config = SessionConfig::new(acceptor.address(), "CLIENT", "VENUE");
+        FixExchange::connect("FIX-Venue", config, Box::new(MemoryStore::new()))
+            .await
+            .unwrap()
+            .with_request_timeout(Duration::from_secs(2))
+    }
+
+    #[tokio::test]
+    async fn test_market_data_snapshot_and_incremental_refresh() {
+        let acceptor = test_acceptor::spawn(venue).await;
+        let exchange = exchange(&acceptor).await;
+        let pair = TradingPair::new("BTC", "USD");
+
+        let book = exchange.get_liquidity(&pair).await.unwrap();
+        assert_eq!(book.exchange, "FIX-Venue");
+        assert_eq!(book.bids[0], PriceLevel::new(dec!(49990), dec!(1.5)));
+        assert_eq!(book.asks[1], PriceLevel::new(dec!(50020), dec!(3)));
+        assert!(book.exchange_time.is_some());
+        let request = acceptor.wait_for(msg_type::MARKET_DATA_REQUEST).await;
+        assert_eq!(request.get(tags::SUBSCRIPTION_REQUEST_TYPE), Some("1"));
+
+        // Best ask is taken out, the bid grows and a new bid joins
+        acceptor.send(
+            Message::new(msg_type::MARKET_DATA_INCREMENTAL)
+                .with(tags::NO_MD_ENTRIES, 3)
+                .with(tags::MD_UPDATE_ACTION, 2)
+                .with(tags::MD_ENTRY_TYPE, 1)
+                .with(tags::SYMBOL, "BTC/USD")
+                .with(tags::MD_ENTRY_PX, "50010")
+                .with(tags::MD_UPDATE_ACTION, 1)
+                .with(tags::MD_ENTRY_TYPE, 0)
+                .with(tags::SYMBOL, "BTC/USD")
+                .with(tags::MD_ENTRY_PX, "49990")
+                .with(tags::MD_ENTRY_SIZE, "2.5")
+                .with(tags::MD_UPDATE_ACTION, 0)
+                .with(tags::MD_ENTRY_TYPE, 0)
+                .with(tags::SYMBOL, "BTC/USD")
+                .with(tags::MD_ENTRY_PX, "49995")
+                .with(tags::MD_ENTRY_SIZE, "0.2"),
+        );
+        let mut book = book;
+        for _ in 0..100 {
+            book = exchange.get_liquidity(&pair).await.unwrap();
+            if book.asks.len() == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            book.bids,
+            vec![
+                PriceLevel::new(dec!(49995), dec!(0.2)),
+                PriceLevel::new(dec!(49990), dec!(2.5)),
+                PriceLevel::new(dec!(49980), dec!(2)),
+            ]
+        );
+        assert_eq!(book.asks, vec![PriceLevel::new(dec!(50020), dec!(3))]);
+        // The subscription is only sent once
+        assert_eq!(
+            acceptor
+                .received()
+                .iter()
+                .filter(|m| m.msg_type() == msg_type::MARKET_DATA_REQUEST)
+                .count(),
+            1
+        );
+
+        let unknown = TradingPair::new("DOGE", "USD");
+        assert!(matches!(
+            exchange.get_liquidity(&unknown).await,
+            Err(ExchangeError::UnknownSymbol { .. })
+        ));
+        assert!(!exchange.supports_pair(&unknown).await);
+    }
+
+    #[tokio::test]
+    async fn test_orders_follow_execution_reports() {
+        let acceptor = test_acceptor::spawn(venue).await;
+        let exchange = exchange(&acceptor).await;
+        let pair = TradingPair::new("BTC", "USD");
+        let request = |id: &str, order_type, limit_price| OrderRequest {
+            client_order_id: id.to_string(),
+            pair: pair.clone(),
+            side: OrderSide::Buy,
+            order_type,
+            quantity: dec!(0.5),
+            limit_price,
+        };
+
+        let report = exchange
+            .place_order(&request("mkt-1", OrderType::Market, None))
+            .await
+            .unwrap();
+        assert_eq!(report.status, OrderStatus::Filled);
+        assert_eq!(report.client_order_id, "mkt-1");
+        assert_eq!(report.filled_quantity, dec!(0.5));
+        assert_eq!(report.average_price, dec!(50012));
+        assert_eq!(report.fills.len(), 2);
+        assert_eq!(report.fees, dec!(25.006));
+        let execution = report.execution_result();
+        assert_eq!(execution.executed_quantity, dec!(0.5));
+        assert_eq!(execution.exchange, "FIX-Venue");
+        let sent = acceptor.wait_for(msg_type::NEW_ORDER_SINGLE).await;
+        assert_eq!(sent.get(tags::ORD_TYPE), Some("1"));
+        assert_eq!(sent.get(tags::SIDE), Some("1"));
+
+        let resting = exchange
+            .place_order(&request("lmt-1", OrderType::Limit, Some(dec!(49000))))
+            .await
+            .unwrap();
+        assert_eq!(resting.status, OrderStatus::New);
+        assert_eq!(exchange.get_open_orders(&pair).await.unwrap().len(), 1);
+
+        let cancelled = exchange
+            .cancel_order(&pair, &resting.order_id)
+            .await
+            .unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        let cancel = acceptor.wait_for(msg_type::ORDER_CANCEL_REQUEST).await;
+        assert_eq!(cancel.get(tags::ORIG_CL_ORD_ID), Some("lmt-1"));
+        assert!(exchange.get_open_orders(&pair).await.unwrap().is_empty());
+        assert_eq!(
+            exchange
+                .get_order_status(&pair, &resting.order_id)
+                .await
+                .unwrap()
+                .status,
+            OrderStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_fills_while_a_cancel_is_pending_are_kept() {
+        let mut state = VenueState::default();
+        let report = |exec_id: &str, status: &str, exec_type: &str, cum_qty: &str| {
+            Message::new(msg_type::EXECUTION_REPORT)
+                .with(tags::ORDER_ID, "V1")
+                .with(tags::CL_ORD_ID, "lmt-1")
+                .with(tags::EXEC_ID, exec_id)
+                .with(tags::EXEC_TYPE, exec_type)
+                .with(tags::ORD_STATUS, status)
+                .with(tags::SYMBOL, "BTC/USD")
+                .with(tags::SIDE, "1")
+                .with(tags::ORDER_QTY, "0.5")
+                .with(tags::CUM_QTY, cum_qty)
+        };
+
+        apply_execution_report("FIX-Venue", &mut state, &report("E1", "0", "0", "0")).unwrap();
+        apply_execution_report("FIX-Venue", &mut state, &report("E2", "6", "6", "0")).unwrap();
+        assert_eq!(state.orders["V1"].status, OrderStatus::PendingCancel);
+
+        let fill = report("E3", "6", "F", "0.2")
+            .with(tags::LAST_PX, "49000")
+            .with(tags::LAST_QTY, "0.2")
+            .with(tags::AVG_PX, "49000")
+            .with(tags::COMMISSION, "9.8");
+        apply_execution_report("FIX-Venue", &mut state, &fill).unwrap();
+        apply_execution_report("FIX-Venue", &mut state, &fill).unwrap();
+        // A state with no mapping still records the trade
+        let replace = report("E4", "E", "F", "0.3")
+            .with(tags::LAST_PX, "49010")
+            .with(tags::LAST_QTY, "0.1")
+            .with(tags::AVG_PX, "49003.33");
+        apply_execution_report("FIX-Venue", &mut state, &replace).unwrap();
+
+        let order = &state.orders["V1"];
+        assert_eq!(order.status, OrderStatus::PendingCancel);
+        assert_eq!(order.filled_quantity, dec!(0.3));
+        assert_eq!(order.fills.len(), 2);
+        assert_eq!(order.fills[0].price, dec!(49000));
+        assert_eq!(order.fees, dec!(9.8));
+    }
+}
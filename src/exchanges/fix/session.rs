@@ -0,0 +1,670 @@
+use super::message::{format_timestamp, msg_type, parse_timestamp, tags, take_frame, Message};
+use super::store::MessageStore;
+use crate::exchanges::auth::ServerClock;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+
+/// Header fields stamped by the session on every outgoing message
+const HEADER_TAGS: [u32; 6] = [
+    tags::SENDER_COMP_ID,
+    tags::TARGET_COMP_ID,
+    tags::MSG_SEQ_NUM,
+    tags::SENDING_TIME,
+    tags::POSS_DUP_FLAG,
+    tags::ORIG_SENDING_TIME,
+];
+
+/// Connection settings for a FIX initiator session
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// Acceptor `host:port`
+    pub address: String,
+    pub sender_comp_id: String,
+    pub target_comp_id: String,
+    /// Sent as whole seconds in the Logon
+    pub heartbeat_interval: Duration,
+    /// Ask the acceptor to restart both sequences at 1 on logon
+    pub reset_on_logon: bool,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// How long to wait for the connection and the acceptor's Logon
+    pub logon_timeout: Duration,
+}
+
+impl SessionConfig {
+    pub fn new(
+        address: impl Into<String>,
+        sender_comp_id: impl Into<String>,
+        target_comp_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            address: address.into(),
+            sender_comp_id: sender_comp_id.into(),
+            target_comp_id: target_comp_id.into(),
+            heartbeat_interval: Duration::from_secs(30),
+            reset_on_logon: false,
+            username: None,
+            password: None,
+            logon_timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn with_heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    pub fn with_reset_on_logon(mut self, reset_on_logon: bool) -> Self {
+        self.reset_on_logon = reset_on_logon;
+        self
+    }
+
+    pub fn with_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Name the session's store files are kept under
+    pub fn session_id(&self) -> String {
+        format!("{}-{}", self.sender_comp_id, self.target_comp_id)
+    }
+}
+
+/// A logged-on FIX 4.4 initiator session.
+///
+/// The session owns everything below the application: it stamps and
+/// stores outgoing messages, sends heartbeats and test requests, checks
+/// incoming sequence numbers, asks for resends when it spots a gap and
+/// answers the counterparty's resend requests from its store.
+/// Application messages arrive in sequence order on [`FixSession::subscribe`].
+pub struct FixSession {
+    inner: Arc<Inner>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+struct Inner {
+    config: SessionConfig,
+    writer: tokio::sync::Mutex<OwnedWriteHalf>,
+    state: Mutex<SessionState>,
+    messages: broadcast::Sender<Message>,
+    logged_on: watch::Sender<bool>,
+    /// Counterparty clock, measured over the Logon round trip
+    clock: Arc<ServerClock>,
+}
+
+struct SessionState {
+    store: Box<dyn MessageStore>,
+    last_sent: Instant,
+    last_received: Instant,
+    /// TestReqID of a TestRequest not answered yet
+    pending_test: Option<String>,
+    /// While a ResendRequest is outstanding, the highest sequence number
+    /// seen ahead of the gap
+    resend_until: Option<u64>,
+    /// We sent the Logout, so the counterparty's is the acknowledgement
+    logging_out: bool,
+}
+
+/// What to do with an incoming message once its sequence number is checked
+enum Disposition {
+    Process,
+    Discard,
+    RequestResend(u64),
+    TooLow(u64),
+}
+
+impl FixSession {
+    /// Connect, log on and start the session's reader and heartbeat tasks
+    pub async fn connect(config: SessionConfig, mut store: Box<dyn MessageStore>) -> Result<Self> {
+        let stream =
+            tokio::time::timeout(config.logon_timeout, TcpStream::connect(&config.address))
+                .await
+                .with_context(|| format!("Timed out connecting to {}", config.address))?
+                .with_context(|| format!("Failed to connect to {}", config.address))?;
+        stream.set_nodelay(true)?;
+        let (mut reader, writer) = stream.into_split();
+
+        if config.reset_on_logon {
+            store.reset()?;
+        }
+        let (messages, _) = broadcast::channel(1024);
+        let inner = Arc::new(Inner {
+            config,
+            writer: tokio::sync::Mutex::new(writer),
+            state: Mutex::new(SessionState {
+                store,
+                last_sent: Instant::now(),
+                last_received: Instant::now(),
+                pending_test: None,
+                resend_until: None,
+                logging_out: false,
+            }),
+            messages,
+            logged_on: watch::Sender::new(false),
+            clock: Arc::new(ServerClock::new()),
+        });
+        let config = &inner.config;
+
+        let mut logon = Message::new(msg_type::LOGON)
+            .with(tags::ENCRYPT_METHOD, 0)
+            .with(
+                tags::HEART_BT_INT,
+                config.heartbeat_interval.as_secs().max(1),
+            );
+        if config.reset_on_logon {
+            logon.push(tags::RESET_SEQ_NUM_FLAG, "Y");
+        }
+        if let Some(username) = &config.username {
+            logon.push(tags::USERNAME, username);
+        }
+        if let Some(password) = &config.password {
+            logon.push(tags::PASSWORD, password);
+        }
+        let sent = Utc::now().timestamp_millis();
+        inner.write(logon).await?;
+
+        let mut buffer = Vec::new();
+        let response =
+            tokio::time::timeout(config.logon_timeout, read_message(&mut reader, &mut buffer))
+                .await
+                .context("Timed out waiting for FIX logon")??;
+        let received = Utc::now().timestamp_millis();
+        match response.msg_type() {
+            msg_type::LOGON => {
+                if let Some(Ok(time)) = response.get(tags::SENDING_TIME).map(parse_timestamp) {
+                    inner.clock.observe(time.timestamp_millis(), sent, received);
+                }
+            }
+            msg_type::LOGOUT => anyhow::bail!(
+                "{} refused logon: {}",
+                config.target_comp_id,
+                response.get(tags::TEXT).unwrap_or("no reason given")
+            ),
+            other => anyhow::bail!("Expected Logon, got MsgType {other}"),
+        }
+
+        let seq = response.seq_num().context("Logon has no MsgSeqNum")?;
+        let gap_from = {
+            let mut state = inner.state.lock().unwrap();
+            state.last_received = Instant::now();
+            let expected = state.store.next_target_seq();
+            if seq < expected {
+                anyhow::bail!("Logon MsgSeqNum {seq} is lower than expected {expected}");
+            }
+            if seq > expected {
+                // The counterparty will resend from `expected`, gap-filling
+                // over its Logon
+                state.resend_until = Some(seq);
+                Some(expected)
+            } else {
+                state.store.set_next_target_seq(seq + 1)?;
+                None
+            }
+        };
+        inner.logged_on.send_replace(true);
+
+        let tasks = vec![
+            tokio::spawn(run_reader(inner.clone(), reader, buffer)),
+            tokio::spawn(run_heartbeats(inner.clone())),
+        ];
+        if let Some(from) = gap_from {
+            inner.request_resend(from).await?;
+        }
+        Ok(Self { inner, tasks })
+    }
+
+    /// Stamp, store and send an application message, returning its MsgSeqNum
+    pub async fn send(&self, message: Message) -> Result<u64> {
+        if !self.is_logged_on() {
+            anyhow::bail!(
+                "FIX session {} is not logged on",
+                self.inner.config.session_id()
+            );
+        }
+        self.inner.write(message).await
+    }
+
+    /// Application messages received from now on, in sequence order
+    pub fn subscribe(&self) -> broadcast::Receiver<Message> {
+        self.inner.messages.subscribe()
+    }
+
+    /// Counterparty clock, for reading the venue's timestamps as local time
+    pub fn clock(&self) -> Arc<ServerClock> {
+        self.inner.clock.clone()
+    }
+
+    pub fn is_logged_on(&self) -> bool {
+        *self.inner.logged_on.borrow()
+    }
+
+    pub fn next_sender_seq(&self) -> u64 {
+        self.inner.state.lock().unwrap().store.next_sender_seq()
+    }
+
+    pub fn next_target_seq(&self) -> u64 {
+        self.inner.state.lock().unwrap().store.next_target_seq()
+    }
+
+    /// Send a Logout and wait for the counterparty to acknowledge it
+    pub async fn logout(&self) -> Result<()> {
+        self.inner.state.lock().unwrap().logging_out = true;
+        self.inner.write(Message::new(msg_type::LOGOUT)).await?;
+        let mut logged_on = self.inner.logged_on.subscribe();
+        tokio::time::timeout(
+            self.inner.config.logon_timeout,
+            logged_on.wait_for(|on| !on),
+        )
+        .await
+        .context("Timed out waiting for Logout acknowledgement")?
+        .ok();
+        Ok(())
+    }
+}
+
+impl Drop for FixSession {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Inner {
+    /// Give a message the next sequence number, store it and send it
+    async fn write(&self, message: Message) -> Result<u64> {
+        // Holding the writer while numbering keeps the wire in sequence order
+        let mut writer = self.writer.lock().await;
+        let (seq, frame) = {
+            let mut state = self.state.lock().unwrap();
+            let seq = state.store.next_sender_seq();
+            let frame = self.stamp(&message, seq, None).encode();
+            state.store.store(seq, &frame)?;
+            state.store.set_next_sender_seq(seq + 1)?;
+            state.last_sent = Instant::now();
+            (seq, frame)
+        };
+        writer.write_all(&frame).await?;
+        Ok(seq)
+    }
+
+    /// Header plus `message`'s body. Resent messages keep their sequence
+    /// number and carry PossDupFlag with the original SendingTime.
+    fn stamp(&self, message: &Message, seq: u64, orig_sending_time: Option<&str>) -> Message {
+        let mut stamped = Message::new(message.msg_type())
+            .with(tags::SENDER_COMP_ID, &self.config.sender_comp_id)
+            .with(tags::TARGET_COMP_ID, &self.config.target_comp_id)
+            .with(tags::MSG_SEQ_NUM, seq)
+            .with(tags::SENDING_TIME, format_timestamp(Utc::now()));
+        if let Some(orig_sending_time) = orig_sending_time {
+            stamped.push(tags::POSS_DUP_FLAG, "Y");
+            stamped.push(tags::ORIG_SENDING_TIME, orig_sending_time);
+        }
+        for (tag, value) in message.fields() {
+            if !HEADER_TAGS.contains(tag) {
+                stamped.push(*tag, value);
+            }
+        }
+        stamped
+    }
+
+    async fn request_resend(&self, from: u64) -> Result<()> {
+        let request = Message::new(msg_type::RESEND_REQUEST)
+            .with(tags::BEGIN_SEQ_NO, from)
+            .with(tags::END_SEQ_NO, 0);
+        self.write(request).await.map(|_| ())
+    }
+
+    /// Check an incoming message's sequence number and act on it. Returns
+    /// `false` once the session has logged out.
+    async fn handle(&self, message: Message) -> Result<bool> {
+        let seq = message.seq_num().context("Message has no MsgSeqNum")?;
+        let kind = message.msg_type().to_string();
+        // Resend requests are answered whatever their own sequence number
+        if kind == msg_type::RESEND_REQUEST {
+            self.resend(&message).await?;
+        }
+
+        let disposition = {
+            let mut state = self.state.lock().unwrap();
+            state.last_received = Instant::now();
+            let expected = state.store.next_target_seq();
+            let new_seq = if kind == msg_type::SEQUENCE_RESET {
+                let new_seq: u64 = message
+                    .require(tags::NEW_SEQ_NO)?
+                    .parse()
+                    .context("Malformed NewSeqNo")?;
+                if message.get(tags::GAP_FILL_FLAG) != Some("Y") {
+                    // Reset mode moves the sequence whatever it says it is
+                    state.store.set_next_target_seq(new_seq)?;
+                    state.resend_until = None;
+                    return Ok(true);
+                }
+                Some(new_seq)
+            } else {
+                None
+            };
+
+            match seq.cmp(&expected) {
+                std::cmp::Ordering::Greater => match state.resend_until {
+                    // Already asked; the resend will bring this one again
+                    Some(until) => {
+                        state.resend_until = Some(until.max(seq));
+                        Disposition::Discard
+                    }
+                    None => {
+                        state.resend_until = Some(seq);
+                        Disposition::RequestResend(expected)
+                    }
+                },
+                std::cmp::Ordering::Less if message.is_poss_dup() => Disposition::Discard,
+                std::cmp::Ordering::Less => Disposition::TooLow(expected),
+                std::cmp::Ordering::Equal => {
+                    let next = new_seq.unwrap_or(seq + 1);
+                    state.store.set_next_target_seq(next)?;
+                    if state.resend_until.is_some_and(|until| next > until) {
+                        state.resend_until = None;
+                    }
+                    Disposition::Process
+                }
+            }
+        };
+
+        match disposition {
+            Disposition::Discard => return Ok(true),
+            Disposition::RequestResend(from) => {
+                self.request_resend(from).await?;
+                return Ok(true);
+            }
+            Disposition::TooLow(expected) => {
+                let text = format!("MsgSeqNum too low, expecting {expected} but received {seq}");
+                self.write(Message::new(msg_type::LOGOUT).with(tags::TEXT, &text))
+                    .await?;
+                anyhow::bail!(text);
+            }
+            Disposition::Process => {}
+        }
+
+        match kind.as_str() {
+            msg_type::HEARTBEAT => {
+                let mut state = self.state.lock().unwrap();
+                if state.pending_test.as_deref() == message.get(tags::TEST_REQ_ID) {
+                    state.pending_test = None;
+                }
+            }
+            msg_type::TEST_REQUEST => {
+                let id = message.require(tags::TEST_REQ_ID)?;
+                self.write(Message::new(msg_type::HEARTBEAT).with(tags::TEST_REQ_ID, id))
+                    .await?;
+            }
+            msg_type::LOGOUT => {
+                let acknowledged = self.state.lock().unwrap().logging_out;
+                if !acknowledged {
+                    log::warn!(
+                        "{} logged out: {}",
+                        self.config.target_comp_id,
+                        message.get(tags::TEXT).unwrap_or("no reason given")
+                    );
+                    self.write(Message::new(msg_type::LOGOUT)).await?;
+                }
+                return Ok(false);
+            }
+            msg_type::LOGON | msg_type::RESEND_REQUEST | msg_type::SEQUENCE_RESET => {}
+            _ => {
+                if kind == msg_type::REJECT {
+                    log::warn!(
+                        "{} rejected message {}: {}",
+                        self.config.target_comp_id,
+                        message.get(tags::REF_SEQ_NUM).unwrap_or("?"),
+                        message.get(tags::TEXT).unwrap_or("no reason given")
+                    );
+                }
+                // Nobody listening is not an error
+                let _ = self.messages.send(message);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Answer a ResendRequest from the store: application messages go out
+    /// again as possible duplicates, session messages and anything missing
+    /// are skipped with gap fills
+    async fn resend(&self, request: &Message) -> Result<()> {
+        let begin: u64 = request.require(tags::BEGIN_SEQ_NO)?.parse()?;
+        let end: u64 = request.require(tags::END_SEQ_NO)?.parse()?;
+
+        let mut writer = self.writer.lock().await;
+        let (stored, next_seq) = {
+            let state = self.state.lock().unwrap();
+            let next_seq = state.store.next_sender_seq();
+            let last = if end == 0 {
+                next_seq - 1
+            } else {
+                end.min(next_seq - 1)
+            };
+            (state.store.fetch(begin, last)?, last + 1)
+        };
+
+        let gap_fill = |from: u64, to: u64| {
+            let fill = Message::new(msg_type::SEQUENCE_RESET)
+                .with(tags::GAP_FILL_FLAG, "Y")
+                .with(tags::NEW_SEQ_NO, to);
+            let now = format_timestamp(Utc::now());
+            self.stamp(&fill, from, Some(&now)).encode()
+        };
+        let mut frames = Vec::new();
+        let mut cursor = begin;
+        for (seq, frame) in stored {
+            let original = Message::decode(&frame)?;
+            if msg_type::is_admin(original.msg_type()) {
+                continue;
+            }
+            if cursor < seq {
+                frames.push(gap_fill(cursor, seq));
+            }
+            let sent_at = original.require(tags::SENDING_TIME)?;
+            frames.push(self.stamp(&original, seq, Some(sent_at)).encode());
+            cursor = seq + 1;
+        }
+        if cursor < next_seq {
+            frames.push(gap_fill(cursor, next_seq));
+        }
+
+        for frame in frames {
+            writer.write_all(&frame).await?;
+        }
+        self.state.lock().unwrap().last_sent = Instant::now();
+        Ok(())
+    }
+}
+
+/// Read the next complete message, buffering partial frames
+async fn read_message(reader: &mut OwnedReadHalf, buffer: &mut Vec<u8>) -> Result<Message> {
+    loop {
+        if let Some(frame) = take_frame(buffer)? {
+            return Message::decode(&frame);
+        }
+        if reader.read_buf(buffer).await? == 0 {
+            anyhow::bail!("connection closed");
+        }
+    }
+}
+
+async fn run_reader(inner: Arc<Inner>, mut reader: OwnedReadHalf, mut buffer: Vec<u8>) {
+    let result: Result<()> = async {
+        loop {
+            let message = read_message(&mut reader, &mut buffer).await?;
+            if !inner.handle(message).await? {
+                return Ok(());
+            }
+        }
+    }
+    .await;
+    if let Err(error) = result {
+        log::warn!("FIX session {} ended: {error:#}", inner.config.session_id());
+    }
+    inner.logged_on.send_replace(false);
+}
+
+/// Keep the session alive: heartbeat when we have been quiet, test the
+/// counterparty when it has, and drop the connection if it stays silent
+async fn run_heartbeats(inner: Arc<Inner>) {
+    enum Due {
+        Nothing,
+        Heartbeat,
+        TestRequest(String),
+        Dead,
+    }
+
+    let interval = inner.config.heartbeat_interval;
+    let grace = interval / 5;
+    let mut ticker = tokio::time::interval(interval / 4);
+    loop {
+        ticker.tick().await;
+        if !*inner.logged_on.borrow() {
+            return;
+        }
+        let due = {
+            let mut state = inner.state.lock().unwrap();
+            let silent = state.last_received.elapsed();
+            if state.pending_test.is_some() && silent >= interval * 2 + grace {
+                Due::Dead
+            } else if state.pending_test.is_none() && silent >= interval + grace {
+                let id = format!("TEST-{}", Utc::now().timestamp_millis());
+                state.pending_test = Some(id.clone());
+                Due::TestRequest(id)
+            } else if state.last_sent.elapsed() >= interval {
+                Due::Heartbeat
+            } else {
+                Due::Nothing
+            }
+        };
+
+        let result = match due {
+            Due::Nothing => Ok(0),
+            Due::Heartbeat => inner.write(Message::new(msg_type::HEARTBEAT)).await,
+            Due::TestRequest(id) => {
+                inner
+                    .write(Message::new(msg_type::TEST_REQUEST).with(tags::TEST_REQ_ID, id))
+                    .await
+            }
+            Due::Dead => {
+                log::warn!(
+                    "FIX session {} did not answer a test request; disconnecting",
+                    inner.config.session_id()
+                );
+                let _ = inner.writer.lock().await.shutdown().await;
+                inner.logged_on.send_replace(false);
+                return;
+            }
+        };
+        if let Err(error) = result {
+            log::warn!(
+                "FIX session {} heartbeat failed: {error:#}",
+                inner.config.session_id()
+            );
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::fix::store::MemoryStore;
+    use crate::exchanges::fix::test_acceptor;
+
+    async fn connect(acceptor: &test_acceptor::Acceptor) -> FixSession {
+        let config = SessionConfig::new(acceptor.address(), "CLIENT", "VENUE")
+            .with_credentials("trader", "secret");
+        FixSession::connect(config, Box::new(MemoryStore::new()))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_logon_and_test_request() {
+        let acceptor = test_acceptor::spawn(|_| Vec::new()).await;
+        let session = connect(&acceptor).await;
+        assert!(session.is_logged_on());
+
+        let logon = acceptor.wait_for(msg_type::LOGON).await;
+        assert_eq!(logon.get(tags::HEART_BT_INT), Some("30"));
+        assert_eq!(logon.get(tags::USERNAME), Some("trader"));
+        assert_eq!(logon.seq_num(), Some(1));
+
+        acceptor.send(Message::new(msg_type::TEST_REQUEST).with(tags::TEST_REQ_ID, "ping"));
+        let heartbeat = acceptor.wait_for(msg_type::HEARTBEAT).await;
+        assert_eq!(heartbeat.get(tags::TEST_REQ_ID), Some("ping"));
+        assert_eq!(heartbeat.seq_num(), Some(2));
+
+        session.logout().await.unwrap();
+        assert!(!session.is_logged_on());
+    }
+
+    #[tokio::test]
+    async fn test_gap_is_filled_by_resend_request() {
+        let acceptor = test_acceptor::spawn(|_| Vec::new()).await;
+        let session = connect(&acceptor).await;
+        let mut messages = session.subscribe();
+        let news = |text: &str| Message::new("B").with(tags::TEXT, text);
+
+        // The acceptor's 2 is lost on the way; 3 arrives first
+        acceptor.skip(news("first"));
+        acceptor.send(news("second"));
+
+        let request = acceptor.wait_for(msg_type::RESEND_REQUEST).await;
+        assert_eq!(request.get(tags::BEGIN_SEQ_NO), Some("2"));
+        assert_eq!(request.get(tags::END_SEQ_NO), Some("0"));
+
+        for expected in ["first", "second"] {
+            let message = tokio::time::timeout(Duration::from_secs(2), messages.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(message.get(tags::TEXT), Some(expected));
+        }
+        assert_eq!(session.next_target_seq(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_resend_request_is_answered_from_the_store() {
+        let acceptor = test_acceptor::spawn(|_| Vec::new()).await;
+        let session = connect(&acceptor).await;
+        let seq = session
+            .send(Message::new("B").with(tags::TEXT, "hello"))
+            .await
+            .unwrap();
+        assert_eq!(seq, 2);
+        acceptor.wait_for("B").await;
+
+        acceptor.send(
+            Message::new(msg_type::RESEND_REQUEST)
+                .with(tags::BEGIN_SEQ_NO, 1)
+                .with(tags::END_SEQ_NO, 0),
+        );
+
+        // The Logon is skipped with a gap fill; the news goes out again
+        let gap_fill = acceptor.wait_for(msg_type::SEQUENCE_RESET).await;
+        assert_eq!(gap_fill.seq_num(), Some(1));
+        assert_eq!(gap_fill.get(tags::NEW_SEQ_NO), Some("2"));
+        assert_eq!(gap_fill.get(tags::GAP_FILL_FLAG), Some("Y"));
+        let resent = acceptor.wait_for_nth("B", 2).await;
+        assert_eq!(resent.seq_num(), Some(2));
+        assert!(resent.is_poss_dup());
+        assert!(resent.get(tags::ORIG_SENDING_TIME).is_some());
+        assert_eq!(session.next_sender_seq(), 3);
+    }
+}
@@ -0,0 +1,228 @@
+use super::message::{take_frame, Message};
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Sequence numbers and sent messages of a FIX session, kept so a
+/// reconnect carries on where the last connection stopped and so resend
+/// requests can be answered
+pub trait MessageStore: Send {
+    /// MsgSeqNum the next outgoing message will carry
+    fn next_sender_seq(&self) -> u64;
+
+    /// MsgSeqNum expected on the next incoming message
+    fn next_target_seq(&self) -> u64;
+
+    fn set_next_sender_seq(&mut self, seq: u64) -> Result<()>;
+
+    fn set_next_target_seq(&mut self, seq: u64) -> Result<()>;
+
+    /// Keep an encoded outgoing message for resending
+    fn store(&mut self, seq: u64, frame: &[u8]) -> Result<()>;
+
+    /// Stored messages with sequence numbers in `begin..=end`, in order
+    fn fetch(&self, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>>;
+
+    /// Start over from sequence number 1 with nothing stored
+    fn reset(&mut self) -> Result<()>;
+}
+
+/// Store that forgets everything when the process exits
+#[derive(Debug)]
+pub struct MemoryStore {
+    next_sender_seq: u64,
+    next_target_seq: u64,
+    messages: BTreeMap<u64, Vec<u8>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            next_sender_seq: 1,
+            next_target_seq: 1,
+            messages: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageStore for MemoryStore {
+    fn next_sender_seq(&self) -> u64 {
+        self.next_sender_seq
+    }
+
+    fn next_target_seq(&self) -> u64 {
+        self.next_target_seq
+    }
+
+    fn set_next_sender_seq(&mut self, seq: u64) -> Result<()> {
+        self.next_sender_seq = seq;
+        Ok(())
+    }
+
+    fn set_next_target_seq(&mut self, seq: u64) -> Result<()> {
+        self.next_target_seq = seq;
+        Ok(())
+    }
+
+    fn store(&mut self, seq: u64, frame: &[u8]) -> Result<()> {
+        self.messages.insert(seq, frame.to_vec());
+        Ok(())
+    }
+
+    fn fetch(&self, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>> {
+        Ok(self
+            .messages
+            .range(begin..=end)
+            .map(|(&seq, frame)| (seq, frame.clone()))
+            .collect())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        *self = Self::new();
+        Ok(())
+    }
+}
+
+/// Store persisted under a directory, one pair of files per session.
+///
+/// `<session>.seqnums` holds the next sender and target sequence numbers;
+/// `<session>.messages` is an append-only log of the raw frames sent, which
+/// carry their own sequence numbers and framing.
+#[derive(Debug)]
+pub struct FileStore {
+    seqnums_path: PathBuf,
+    messages_path: PathBuf,
+    memory: MemoryStore,
+}
+
+impl FileStore {
+    /// Open the store for `session`, creating it if needed and loading
+    /// whatever a previous run left
+    pub fn open(dir: impl AsRef<Path>, session: &str) -> Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create FIX store at {}", dir.display()))?;
+        let mut store = Self {
+            seqnums_path: dir.join(format!("{session}.seqnums")),
+            messages_path: dir.join(format!("{session}.messages")),
+            memory: MemoryStore::new(),
+        };
+
+        if let Ok(seqnums) = fs::read_to_string(&store.seqnums_path) {
+            let (sender, target) = seqnums
+                .trim()
+                .split_once(' ')
+                .context("Malformed FIX sequence number file")?;
+            store.memory.next_sender_seq = sender.parse()?;
+            store.memory.next_target_seq = target.parse()?;
+        }
+        if let Ok(mut log) = fs::read(&store.messages_path) {
+            while let Some(frame) = take_frame(&mut log)? {
+                let seq = Message::decode(&frame)?
+                    .seq_num()
+                    .context("Stored FIX message has no MsgSeqNum")?;
+                store.memory.messages.insert(seq, frame);
+            }
+        }
+        Ok(store)
+    }
+
+    fn save_seqnums(&self) -> Result<()> {
+        fs::write(
+            &self.seqnums_path,
+            format!(
+                "{} {}\n",
+                self.memory.next_sender_seq, self.memory.next_target_seq
+            ),
+        )
+        .context("Failed to save FIX sequence numbers")
+    }
+}
+
+impl MessageStore for FileStore {
+    fn next_sender_seq(&self) -> u64 {
+        self.memory.next_sender_seq
+    }
+
+    fn next_target_seq(&self) -> u64 {
+        self.memory.next_target_seq
+    }
+
+    fn set_next_sender_seq(&mut self, seq: u64) -> Result<()> {
+        self.memory.next_sender_seq = seq;
+        self.save_seqnums()
+    }
+
+    fn set_next_target_seq(&mut self, seq: u64) -> Result<()> {
+        self.memory.next_target_seq = seq;
+        self.save_seqnums()
+    }
+
+    fn store(&mut self, seq: u64, frame: &[u8]) -> Result<()> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.messages_path)
+            .and_then(|mut log| log.write_all(frame))
+            .context("Failed to store FIX message")?;
+        self.memory.store(seq, frame)
+    }
+
+    fn fetch(&self, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>> {
+        self.memory.fetch(begin, end)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.memory.reset()?;
+        File::create(&self.messages_path).context("Failed to truncate FIX message log")?;
+        self.save_seqnums()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::fix::message::{msg_type, tags};
+
+    #[test]
+    fn test_file_store_survives_reopening() {
+        let dir = std::env::temp_dir().join(format!("sor-fix-store-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let frame = |seq: u64| {
+            Message::new(msg_type::NEW_ORDER_SINGLE)
+                .with(tags::MSG_SEQ_NUM, seq)
+                .with(tags::CL_ORD_ID, format!("order-{seq}"))
+                .encode()
+        };
+
+        let mut store = FileStore::open(&dir, "CLIENT-VENUE").unwrap();
+        for seq in 1..=3 {
+            store.store(seq, &frame(seq)).unwrap();
+        }
+        store.set_next_sender_seq(4).unwrap();
+        store.set_next_target_seq(7).unwrap();
+        drop(store);
+
+        let mut store = FileStore::open(&dir, "CLIENT-VENUE").unwrap();
+        assert_eq!(store.next_sender_seq(), 4);
+        assert_eq!(store.next_target_seq(), 7);
+        assert_eq!(
+            store.fetch(2, 10).unwrap(),
+            vec![(2, frame(2)), (3, frame(3))]
+        );
+
+        store.reset().unwrap();
+        let store = FileStore::open(&dir, "CLIENT-VENUE").unwrap();
+        assert_eq!(store.next_sender_seq(), 1);
+        assert!(store.fetch(1, 10).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
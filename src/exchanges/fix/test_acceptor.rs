@@ -0,0 +1,202 @@
+//! Local FIX acceptor standing in for a venue in tests.
+//!
+//! It answers the session layer itself (logon, test requests, resend
+//! requests, logout) and hands every application message to a responder
+//! whose replies it sends back. Tests can also push messages unprompted,
+//! or burn a sequence number to make the client see a gap.
+
+use super::message::{format_timestamp, msg_type, tags, take_frame, Message};
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+enum Command {
+    Send(Message),
+    /// Number and store the message but never deliver it
+    Skip(Message),
+}
+
+pub struct Acceptor {
+    address: String,
+    commands: mpsc::UnboundedSender<Command>,
+    received: Arc<Mutex<Vec<Message>>>,
+}
+
+/// Outgoing side of the acceptor's session
+struct Outbound {
+    sender: String,
+    target: String,
+    next_seq: u64,
+    /// Body and SendingTime of everything sent, for resends
+    sent: BTreeMap<u64, (Message, String)>,
+}
+
+impl Outbound {
+    fn header(&self, body: &Message, seq: u64, sending_time: &str) -> Message {
+        let mut message = Message::new(body.msg_type())
+            .with(tags::SENDER_COMP_ID, &self.sender)
+            .with(tags::TARGET_COMP_ID, &self.target)
+            .with(tags::MSG_SEQ_NUM, seq)
+            .with(tags::SENDING_TIME, sending_time);
+        for (tag, value) in body.fields() {
+            message.push(*tag, value);
+        }
+        message
+    }
+
+    fn stamp(&mut self, body: Message) -> Vec<u8> {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let now = format_timestamp(Utc::now());
+        let frame = self.header(&body, seq, &now).encode();
+        self.sent.insert(seq, (body, now));
+        frame
+    }
+
+    fn resend(&self, begin: u64) -> Vec<Vec<u8>> {
+        let now = format_timestamp(Utc::now());
+        self.sent
+            .range(begin..)
+            .map(|(&seq, (body, sent_at))| {
+                let mut message = self.header(body, seq, &now);
+                message.push(tags::POSS_DUP_FLAG, "Y");
+                message.push(tags::ORIG_SENDING_TIME, sent_at);
+                message.encode()
+            })
+            .collect()
+    }
+}
+
+/// Listen on a local port; each connection is served in turn with the
+/// acceptor's sequence numbers carried over
+pub async fn spawn(
+    responder: impl Fn(&Message) -> Vec<Message> + Send + Sync + 'static,
+) -> Acceptor {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (commands, mut command_rx) = mpsc::unbounded_channel();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+
+    tokio::spawn(async move {
+        let mut outbound = Outbound {
+            sender: String::new(),
+            target: String::new(),
+            next_seq: 1,
+            sent: BTreeMap::new(),
+        };
+        while let Ok((stream, _)) = listener.accept().await {
+            let (mut reader, mut writer) = stream.into_split();
+            let mut buffer = Vec::new();
+            loop {
+                let mut frames = Vec::new();
+                tokio::select! {
+                    read = reader.read_buf(&mut buffer) => {
+                        if !matches!(read, Ok(n) if n > 0) {
+                            break;
+                        }
+                        while let Ok(Some(frame)) = take_frame(&mut buffer) {
+                            let message = Message::decode(&frame).unwrap();
+                            log.lock().unwrap().push(message.clone());
+                            let replies = match message.msg_type() {
+                                msg_type::LOGON => {
+                                    if message.get(tags::RESET_SEQ_NUM_FLAG) == Some("Y") {
+                                        outbound.next_seq = 1;
+                                        outbound.sent.clear();
+                                    }
+                                    outbound.sender = message.get(tags::TARGET_COMP_ID).unwrap().to_string();
+                                    outbound.target = message.get(tags::SENDER_COMP_ID).unwrap().to_string();
+                                    let mut logon = Message::new(msg_type::LOGON)
+                                        .with(tags::ENCRYPT_METHOD, 0)
+                                        .with(tags::HEART_BT_INT, message.get(tags::HEART_BT_INT).unwrap());
+                                    if let Some(reset) = message.get(tags::RESET_SEQ_NUM_FLAG) {
+                                        logon.push(tags::RESET_SEQ_NUM_FLAG, reset);
+                                    }
+                                    vec![logon]
+                                }
+                                msg_type::TEST_REQUEST => vec![Message::new(msg_type::HEARTBEAT)
+                                    .with(tags::TEST_REQ_ID, message.get(tags::TEST_REQ_ID).unwrap())],
+                                msg_type::RESEND_REQUEST => {
+                                    let begin = message.get(tags::BEGIN_SEQ_NO).unwrap().parse().unwrap();
+                                    frames.extend(outbound.resend(begin));
+                                    Vec::new()
+                                }
+                                msg_type::LOGOUT => vec![Message::new(msg_type::LOGOUT)],
+                                msg_type::HEARTBEAT | msg_type::SEQUENCE_RESET => Vec::new(),
+                                _ => responder(&message),
+                            };
+                            frames.extend(replies.into_iter().map(|reply| outbound.stamp(reply)));
+                        }
+                    }
+                    command = command_rx.recv() => match command {
+                        Some(Command::Send(message)) => frames.push(outbound.stamp(message)),
+                        Some(Command::Skip(message)) => {
+                            outbound.stamp(message);
+                        }
+                        None => return,
+                    },
+                }
+                for frame in frames {
+                    if writer.write_all(&frame).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    Acceptor {
+        address,
+        commands,
+        received,
+    }
+}
+
+impl Acceptor {
+    pub fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    /// Send a message to the client now
+    pub fn send(&self, message: Message) {
+        let _ = self.commands.send(Command::Send(message));
+    }
+
+    /// Use up a sequence number on a message the client never receives
+    pub fn skip(&self, message: Message) {
+        let _ = self.commands.send(Command::Skip(message));
+    }
+
+    /// Everything the client has sent so far
+    pub fn received(&self) -> Vec<Message> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Wait for the first message of a type from the client
+    pub async fn wait_for(&self, msg_type: &str) -> Message {
+        self.wait_for_nth(msg_type, 1).await
+    }
+
+    /// Wait for the `n`th message of a type from the client
+    pub async fn wait_for_nth(&self, msg_type: &str, n: usize) -> Message {
+        for _ in 0..200 {
+            let found = self
+                .received
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|message| message.msg_type() == msg_type)
+                .nth(n - 1)
+                .cloned();
+            if let Some(message) = found {
+                return message;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("client never sent message #{n} of type {msg_type}");
+    }
+}
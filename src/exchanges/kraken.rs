@@ -1,19 +1,493 @@
+use super::auth::{self, Credentials, ServerClock};
+use super::config::ConnectorConfig;
+use super::error::ExchangeError;
+use super::fees::FeeSchedule;
+use super::pairs::PairDirectory;
+use super::rate_limit::{RateLimit, RateLimiter};
 use super::Exchange;
-use crate::types::{Liquidity, TradingPair};
-use anyhow::Result;
+use crate::types::{
+    Balance, InstrumentRules, OrderBook, OrderReport, OrderRequest, OrderSide, OrderStatus,
+    OrderType, PairListing, PriceLevel, TradingPair, TradingStatus,
+};
+use anyhow::{Context, Result};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Kraken wraps every response in `{error, result}`
+#[derive(Debug, Deserialize)]
+struct KrakenResponse<T> {
+    error: Vec<String>,
+    result: Option<T>,
+}
+
+/// Depth levels are `[price, volume, timestamp]`
+#[derive(Debug, Deserialize)]
+struct KrakenDepth {
+    asks: Vec<(String, String, serde_json::Value)>,
+    bids: Vec<(String, String, serde_json::Value)>,
+}
+
+/// Trading rules from `AssetPairs`
+#[derive(Debug, Deserialize)]
+struct KrakenAssetPair {
+    lot_decimals: u32,
+    ordermin: String,
+    #[serde(default)]
+    costmin: Option<String>,
+    tick_size: String,
+}
+
+impl KrakenAssetPair {
+    fn rules(&self) -> Result<InstrumentRules> {
+        let mut rules = InstrumentRules::new(
+            Decimal::from_str(&self.tick_size)?,
+            Decimal::new(1, self.lot_decimals),
+        )
+        .with_min_quantity(Decimal::from_str(&self.ordermin)?);
+        if let Some(costmin) = &self.costmin {
+            rules = rules.with_min_notional(Decimal::from_str(costmin)?);
+        }
+        Ok(rules)
+    }
+}
+
+/// Listing fields of an unfiltered `AssetPairs` entry
+#[derive(Debug, Deserialize)]
+struct KrakenListedPair {
+    altname: String,
+    base: String,
+    quote: String,
+    /// Absent from older responses, which only listed tradable pairs
+    #[serde(default)]
+    status: Option<String>,
+}
+
+impl KrakenListedPair {
+    fn trading_status(&self) -> TradingStatus {
+        match self.status.as_deref() {
+            None | Some("online") => TradingStatus::Trading,
+            Some("limit_only") => TradingStatus::LimitOnly,
+            Some("post_only") => TradingStatus::PostOnly,
+            // cancel_only, reduce_only and anything newer
+            Some(_) => TradingStatus::Halted,
+        }
+    }
+}
+
+/// Per-asset entry of `BalanceEx`
+#[derive(Debug, Deserialize)]
+struct KrakenBalance {
+    balance: String,
+    #[serde(default)]
+    hold_trade: Option<String>,
+}
+
+/// `AddOrder` result; one txid per order placed
+#[derive(Debug, Deserialize)]
+struct KrakenAddOrder {
+    txid: Vec<String>,
+}
+
+/// `OpenOrders` result
+#[derive(Debug, Deserialize)]
+struct KrakenOpenOrders {
+    open: HashMap<String, KrakenOrder>,
+}
+
+/// `ClosedOrders` result
+#[derive(Debug, Deserialize)]
+struct KrakenClosedOrders {
+    closed: HashMap<String, KrakenOrder>,
+}
+
+/// Order as returned by `QueryOrders` and `OpenOrders`
+#[derive(Debug, Deserialize)]
+struct KrakenOrder {
+    status: String,
+    /// Unix seconds, fractional
+    opentm: f64,
+    #[serde(default)]
+    closetm: Option<f64>,
+    vol: String,
+    vol_exec: String,
+    /// Quote spent or received by the executed volume
+    cost: String,
+    fee: String,
+    #[serde(default)]
+    cl_ord_id: Option<String>,
+    descr: KrakenOrderDescription,
+}
+
+#[derive(Debug, Deserialize)]
+struct KrakenOrderDescription {
+    pair: String,
+    #[serde(rename = "type")]
+    side: String,
+}
+
+impl KrakenOrder {
+    fn into_report(self, exchange: &str, txid: String, pair: &TradingPair) -> Result<OrderReport> {
+        let quantity = Decimal::from_str(&self.vol)?;
+        let filled_quantity = Decimal::from_str(&self.vol_exec)?;
+        let cost = Decimal::from_str(&self.cost)?;
+        let status = match self.status.as_str() {
+            "pending" | "open" if filled_quantity > dec!(0) => OrderStatus::PartiallyFilled,
+            "pending" | "open" => OrderStatus::New,
+            "closed" if filled_quantity >= quantity => OrderStatus::Filled,
+            // Closed short of its volume, e.g. a market order out of liquidity
+            "closed" | "canceled" => OrderStatus::Cancelled,
+            "expired" => OrderStatus::Expired,
+            other => anyhow::bail!("Unknown Kraken order status {}", other),
+        };
+        let seconds = self.closetm.unwrap_or(self.opentm);
+        Ok(OrderReport {
+            order_id: txid,
+            client_order_id: self.cl_ord_id.unwrap_or_default(),
+            exchange: exchange.to_string(),
+            pair: pair.clone(),
+            side: match self.descr.side.as_str() {
+                "buy" => OrderSide::Buy,
+                "sell" => OrderSide::Sell,
+                other => anyhow::bail!("Unknown Kraken order side {}", other),
+            },
+            status,
+            quantity,
+            filled_quantity,
+            average_price: if filled_quantity > dec!(0) {
+                cost / filled_quantity
+            } else {
+                dec!(0)
+            },
+            fees: Decimal::from_str(&self.fee)?,
+            fills: Vec::new(),
+            updated_at: DateTime::from_timestamp_millis((seconds * 1000.0) as i64)
+                .unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+/// `cl_ord_id` Kraken accepts for `client_order_id`: free text of up to 18
+/// characters, or else a digest of it in the short UUID form
+pub fn kraken_client_id(client_order_id: &str) -> String {
+    let free_text = client_order_id.len() <= 18
+        && client_order_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if free_text {
+        client_order_id.to_string()
+    } else {
+        auth::client_id_digest(client_order_id)
+    }
+}
+
+/// Asset codes Kraken spells differently from everyone else
+const ASSET_ALIASES: &[(&str, &str)] = &[("BTC", "XBT"), ("DOGE", "XDG")];
+
+/// Kraken's original assets, whose codes carry an X (crypto) or Z (fiat)
+/// prefix. Assets listed later keep their plain code even when it starts
+/// with X or Z, such as ZEUS or XCN.
+const LEGACY_ASSETS: &[&str] = &[
+    "XETC", "XETH", "XLTC", "XMLN", "XREP", "XXBT", "XXDG", "XXLM", "XXMR", "XXRP", "XZEC", "ZAUD",
+    "ZCAD", "ZEUR", "ZGBP", "ZJPY", "ZUSD",
+];
+
+/// Quote currencies recognised when splitting an unprefixed pair name
+const KNOWN_QUOTES: &[&str] = &[
+    "USDT", "USDC", "USD", "EUR", "GBP", "CAD", "JPY", "CHF", "AUD", "XBT", "ETH",
+];
+
+/// Map a standard asset code to Kraken's (BTC -> XBT)
+pub fn to_kraken_asset(asset: &str) -> String {
+    let asset = asset.to_uppercase();
+    ASSET_ALIASES
+        .iter()
+        .find(|(standard, _)| *standard == asset)
+        .map(|(_, kraken)| kraken.to_string())
+        .unwrap_or(asset)
+}
+
+/// Map a Kraken asset code back to the standard one (XXBT -> BTC, ZUSD -> USD)
+pub fn from_kraken_asset(asset: &str) -> String {
+    let asset = asset.to_uppercase();
+    let stripped = if LEGACY_ASSETS.contains(&asset.as_str()) {
+        asset[1..].to_string()
+    } else {
+        asset
+    };
+    ASSET_ALIASES
+        .iter()
+        .find(|(_, kraken)| *kraken == stripped)
+        .map(|(standard, _)| standard.to_string())
+        .unwrap_or(stripped)
+}
+
+/// Kraken pair name used in requests (BTC/USD -> XBTUSD)
+pub fn to_kraken_pair(pair: &TradingPair) -> String {
+    format!(
+        "{}{}",
+        to_kraken_asset(&pair.base),
+        to_kraken_asset(&pair.quote)
+    )
+}
+
+/// Parse a Kraken pair name such as `XXBTZUSD`, `XBTUSDT` or `SOLUSD`
+pub fn from_kraken_pair(name: &str) -> Option<TradingPair> {
+    let name = name.to_uppercase();
+
+    // Legacy form: two prefixed four-letter codes
+    if name.len() == 8 && LEGACY_ASSETS.contains(&&name[..4]) && LEGACY_ASSETS.contains(&&name[4..])
+    {
+        return Some(TradingPair::new(
+            from_kraken_asset(&name[..4]),
+            from_kraken_asset(&name[4..]),
+        ));
+    }
+
+    KNOWN_QUOTES.iter().find_map(|quote| {
+        let base = name.strip_suffix(quote)?;
+        (!base.is_empty())
+            .then(|| TradingPair::new(from_kraken_asset(base), from_kraken_asset(quote)))
+    })
+}
+
+fn parse_levels(levels: &[(String, String, serde_json::Value)]) -> Result<Vec<PriceLevel>> {
+    levels
+        .iter()
+        .map(|(price, volume, _)| {
+            Ok(PriceLevel::new(
+                Decimal::from_str(price)?,
+                Decimal::from_str(volume)?,
+            ))
+        })
+        .collect()
+}
+
+/// Kraken spot REST API
+pub const DEFAULT_BASE_URL: &str = "https://api.kraken.com";
 
 pub struct KrakenExchange {
-    #[allow(dead_code)]
     client: reqwest::Client,
+    config: ConnectorConfig,
+    /// Source of strictly increasing nonces for private calls
+    clock: ServerClock,
+    /// Instrument rules change rarely, so each pair is fetched once
+    rules: Mutex<HashMap<TradingPair, InstrumentRules>>,
+    pairs: PairDirectory,
+    limiter: RateLimiter,
 }
 
 impl KrakenExchange {
     pub fn new() -> Self {
-        Self {
-            client: reqwest::Client::new(),
+        Self::with_config(Self::default_config()).expect("default Kraken configuration is valid")
+    }
+
+    pub fn with_config(config: ConnectorConfig) -> Result<Self> {
+        Ok(Self {
+            client: config.build_client()?,
+            clock: ServerClock::new(),
+            rules: Mutex::new(HashMap::new()),
+            pairs: PairDirectory::new(config.pair_refresh),
+            limiter: config.rate_limiter("Kraken"),
+            config,
+        })
+    }
+
+    /// Public calls are held to about one per second; the burst matches
+    /// the starter tier's private call counter
+    pub fn default_config() -> ConnectorConfig {
+        ConnectorConfig::new(DEFAULT_BASE_URL)
+            .with_rate_limit(RateLimit::new(15, Duration::from_secs(15)))
+    }
+
+    /// `GET /0/public/{endpoint}`, unwrapping Kraken's `{error, result}`
+    /// envelope
+    async fn public_request<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        kraken_pair: &str,
+    ) -> Result<T, ExchangeError> {
+        self.limiter.acquire(1).await?;
+        let url = format!("{}/0/public/{}", self.config.base_url, endpoint);
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let error = ExchangeError::from_status(self.name(), status, body);
+            self.limiter.observe(&error);
+            return Err(error);
+        }
+
+        let body: KrakenResponse<T> = response
+            .json()
+            .await
+            .map_err(|e| ExchangeError::parse(self.name(), e))?;
+
+        // Kraken reports failures with HTTP 200 and a non-empty error array
+        if !body.error.is_empty() {
+            let error = self.classify_errors(&body.error, kraken_pair);
+            self.limiter.observe(&error);
+            return Err(error);
+        }
+        body.result
+            .ok_or_else(|| ExchangeError::parse(self.name(), "response has no result"))
+    }
+
+    /// Every pair in `AssetPairs`, keyed by Kraken's canonical name
+    async fn fetch_listings(&self) -> Result<Vec<PairListing>, ExchangeError> {
+        let pairs: HashMap<String, KrakenListedPair> =
+            self.public_request("AssetPairs", "").await?;
+        Ok(pairs
+            .into_iter()
+            // Dark pool books share the lit pair's assets and are not routable
+            .filter(|(_, listed)| !listed.altname.ends_with(".d"))
+            .map(|(name, listed)| PairListing {
+                pair: TradingPair::new(
+                    from_kraken_asset(&listed.base),
+                    from_kraken_asset(&listed.quote),
+                ),
+                status: listed.trading_status(),
+                symbol: name,
+            })
+            .collect())
+    }
+
+    fn credentials(&self) -> Result<&Credentials, ExchangeError> {
+        self.config
+            .credentials
+            .as_ref()
+            .ok_or_else(|| ExchangeError::missing_credentials(self.name()))
+    }
+
+    /// Call a private endpoint, e.g. `private_request("Balance", &[])`.
+    ///
+    /// The form body carries a millisecond nonce that must increase on every
+    /// call made with the key, so one connector instance should own a key.
+    pub async fn private_request<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, ExchangeError> {
+        let credentials = self.credentials()?;
+        let path = format!("/0/private/{}", endpoint);
+        // Before taking a nonce, so queued calls still send them in order
+        self.limiter.acquire(1).await?;
+        let nonce = self.clock.next_nonce();
+        let post_data = form_urlencoded::Serializer::new(String::new())
+            .append_pair("nonce", &nonce.to_string())
+            .extend_pairs(params.iter().map(|(key, value)| (*key, value.as_str())))
+            .finish();
+        let signature = auth::sign_kraken(credentials.api_secret(), &path, nonce, &post_data)
+            .map_err(|e| ExchangeError::Auth {
+                exchange: self.name().to_string(),
+                message: e.to_string(),
+            })?;
+
+        let response = self
+            .client
+            .post(format!("{}{}", self.config.base_url, path))
+            .header("API-Key", &credentials.api_key)
+            .header("API-Sign", signature)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+            .body(post_data)
+            .send()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let error = ExchangeError::from_status(self.name(), status, body);
+            self.limiter.observe(&error);
+            return Err(error);
+        }
+
+        let body: KrakenResponse<T> = response
+            .json()
+            .await
+            .map_err(|e| ExchangeError::parse(self.name(), e))?;
+        if !body.error.is_empty() {
+            let pair = params
+                .iter()
+                .find(|(key, _)| *key == "pair")
+                .map(|(_, pair)| pair.as_str())
+                .unwrap_or_default();
+            let error = self.classify_errors(&body.error, pair);
+            self.limiter.observe(&error);
+            return Err(error);
         }
+        body.result
+            .ok_or_else(|| ExchangeError::parse(self.name(), "response has no result"))
+    }
+
+    /// `QueryOrders` for a single txid
+    async fn query_order(&self, pair: &TradingPair, txid: &str) -> Result<OrderReport> {
+        let mut orders: HashMap<String, KrakenOrder> = self
+            .private_request("QueryOrders", &[("txid", txid.to_string())])
+            .await?;
+        let order = orders
+            .remove(txid)
+            .with_context(|| format!("Kraken did not return order {}", txid))?;
+        order.into_report(self.name(), txid.to_string(), pair)
+    }
+}
+
+impl KrakenExchange {
+    /// Map Kraken's `error` array (e.g. `EQuery:Unknown asset pair`) to an
+    /// [`ExchangeError`]
+    fn classify_errors(&self, errors: &[String], kraken_pair: &str) -> ExchangeError {
+        let exchange = self.name().to_string();
+        let has = |prefix: &str| errors.iter().any(|e| e.starts_with(prefix));
+
+        if has("EQuery:Unknown asset pair") {
+            ExchangeError::UnknownSymbol {
+                exchange,
+                symbol: kraken_pair.to_string(),
+            }
+        } else if has("EAPI:Rate limit exceeded") || has("EGeneral:Too many requests") {
+            ExchangeError::RateLimited {
+                exchange,
+                retry_after: None,
+            }
+        } else if has("EService:Unavailable") || has("EService:Busy") {
+            ExchangeError::Maintenance { exchange }
+        } else if has("EAPI:Invalid key")
+            || has("EAPI:Invalid signature")
+            || has("EAPI:Invalid nonce")
+            || has("EGeneral:Permission denied")
+        {
+            ExchangeError::Auth {
+                exchange,
+                message: errors.join(", "),
+            }
+        } else {
+            ExchangeError::Api {
+                exchange,
+                code: None,
+                message: errors.join(", "),
+            }
+        }
+    }
+}
+
+impl Default for KrakenExchange {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -23,19 +497,534 @@ impl Exchange for KrakenExchange {
         "Kraken"
     }
 
-    async fn get_liquidity(&self, pair: &TradingPair) -> Result<Liquidity> {
-        // Mock implementation - in production, this would call Kraken API
-        Ok(Liquidity {
-            exchange: self.name().to_string(),
-            pair: pair.clone(),
-            bid_price: dec!(49980.0),
-            bid_quantity: dec!(2.5),
-            ask_price: dec!(50030.0),
-            ask_quantity: dec!(1.5),
-        })
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        let kraken_pair = to_kraken_pair(pair);
+        let depth: HashMap<String, KrakenDepth> = self
+            .public_request(
+                &format!(
+                    "Depth?pair={}&count={}",
+                    kraken_pair, self.config.depth_limit
+                ),
+                &kraken_pair,
+            )
+            .await?;
+
+        // The result is keyed by Kraken's canonical name, e.g. XXBTZUSD for XBTUSD
+        let (name, book) = depth
+            .into_iter()
+            .next()
+            .ok_or_else(|| ExchangeError::parse(self.name(), "response has no order book"))?;
+        if let Some(returned) = from_kraken_pair(&name) {
+            if &returned != pair {
+                return Err(ExchangeError::parse(
+                    self.name(),
+                    format!("returned {} when {} was requested", returned, pair),
+                ));
+            }
+        }
+
+        Ok(OrderBook::new(
+            self.name(),
+            pair.clone(),
+            parse_levels(&book.bids).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid bid level: {}", e))
+            })?,
+            parse_levels(&book.asks).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid ask level: {}", e))
+            })?,
+        ))
+    }
+
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        // An unreachable listing is no evidence the pair is missing
+        !matches!(self.pair_status(pair).await, Ok(None))
+    }
+
+    async fn list_pairs(&self) -> Result<Vec<PairListing>> {
+        Ok(self
+            .pairs
+            .listings(self.name(), || self.fetch_listings())
+            .await?)
+    }
+
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        self.pairs
+            .status(self.name(), pair, || self.fetch_listings())
+            .await
+    }
+
+    async fn instrument_rules(&self, pair: &TradingPair) -> Result<InstrumentRules, ExchangeError> {
+        if let Some(rules) = self.rules.lock().unwrap().get(pair) {
+            return Ok(rules.clone());
+        }
+
+        let kraken_pair = to_kraken_pair(pair);
+        let pairs: HashMap<String, KrakenAssetPair> = self
+            .public_request(&format!("AssetPairs?pair={}", kraken_pair), &kraken_pair)
+            .await?;
+        let rules = pairs
+            .values()
+            .next()
+            .ok_or_else(|| ExchangeError::parse(self.name(), "response has no asset pair"))?
+            .rules()
+            .map_err(|e| ExchangeError::parse(self.name(), e))?;
+
+        self.rules
+            .lock()
+            .unwrap()
+            .insert(pair.clone(), rules.clone());
+        Ok(rules)
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        FeeSchedule::flat(dec!(0.0025), dec!(0.004))
+            .with_tier(dec!(50000), dec!(0.002), dec!(0.0035))
+            .with_tier(dec!(100000), dec!(0.0014), dec!(0.0024))
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        let mut params = vec![
+            ("pair", to_kraken_pair(&request.pair)),
+            (
+                "type",
+                match request.side {
+                    OrderSide::Buy => "buy",
+                    OrderSide::Sell => "sell",
+                }
+                .to_string(),
+            ),
+            ("volume", request.quantity.normalize().to_string()),
+            ("cl_ord_id", kraken_client_id(&request.client_order_id)),
+        ];
+        match request.order_type {
+            OrderType::Market => params.push(("ordertype", "market".to_string())),
+            OrderType::Limit => {
+                let price = request
+                    .limit_price
+                    .context("Limit order has no limit price")?;
+                params.push(("ordertype", "limit".to_string()));
+                params.push(("price", price.normalize().to_string()));
+            }
+        }
+
+        let added: KrakenAddOrder = self.private_request("AddOrder", &params).await?;
+        let txid = added
+            .txid
+            .into_iter()
+            .next()
+            .context("Kraken accepted the order without a txid")?;
+        let mut report = self.query_order(&request.pair, &txid).await?;
+        report.client_order_id = request.client_order_id.clone();
+        Ok(report)
+    }
+
+    async fn cancel_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let _: serde_json::Value = self
+            .private_request("CancelOrder", &[("txid", order_id.to_string())])
+            .await?;
+        self.query_order(pair, order_id).await
+    }
+
+    async fn get_order_status(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        self.query_order(pair, order_id).await
+    }
+
+    async fn get_open_orders(&self, pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        let orders: KrakenOpenOrders = self.private_request("OpenOrders", &[]).await?;
+        let kraken_pair = to_kraken_pair(pair);
+        orders
+            .open
+            .into_iter()
+            .filter(|(_, order)| order.descr.pair == kraken_pair)
+            .map(|(txid, order)| order.into_report(self.name(), txid, pair))
+            .collect()
+    }
+
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        let params = [("cl_ord_id", kraken_client_id(client_order_id))];
+        let open: KrakenOpenOrders = self.private_request("OpenOrders", &params).await?;
+        let order = match open.open.into_iter().next() {
+            Some(order) => Some(order),
+            None => {
+                let closed: KrakenClosedOrders =
+                    self.private_request("ClosedOrders", &params).await?;
+                closed.closed.into_iter().next()
+            }
+        };
+        order
+            .map(|(txid, order)| {
+                let mut report = order.into_report(self.name(), txid, pair)?;
+                report.client_order_id = client_order_id.to_string();
+                Ok(report)
+            })
+            .transpose()
+    }
+
+    async fn get_balances(&self) -> Result<Vec<Balance>> {
+        let balances: HashMap<String, KrakenBalance> =
+            self.private_request("BalanceEx", &[]).await?;
+        balances
+            .into_iter()
+            .map(|(asset, balance)| {
+                let reserved = match &balance.hold_trade {
+                    Some(hold) => Decimal::from_str(hold)?,
+                    None => dec!(0),
+                };
+                Ok(Balance::new(
+                    from_kraken_asset(&asset),
+                    Decimal::from_str(&balance.balance)?,
+                    reserved,
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(server: &mockito::Server) -> KrakenExchange {
+        KrakenExchange::with_config(ConnectorConfig::new(server.url())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_private_request_is_signed() {
+        let mut server = mockito::Server::new_async().await;
+        let balance = server
+            .mock("POST", "/0/private/Balance")
+            .match_header("API-Key", "kraken-key")
+            .match_header(
+                "API-Sign",
+                mockito::Matcher::Regex("^[A-Za-z0-9+/]{86}==$".into()),
+            )
+            .match_body(mockito::Matcher::Regex(r"^nonce=\d{13}$".into()))
+            .with_body(r#"{"error":[],"result":{"ZUSD":"1500.00","XXBT":"0.25"}}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/0/private/OpenOrders")
+            .with_body(r#"{"error":["EAPI:Invalid nonce"]}"#)
+            .create_async()
+            .await;
+        let kraken = KrakenExchange::with_config(
+            ConnectorConfig::new(server.url())
+                .with_credentials(Credentials::new("kraken-key", "a3Jha2VuLXNlY3JldA==")),
+        )
+        .unwrap();
+
+        let balances: HashMap<String, String> =
+            kraken.private_request("Balance", &[]).await.unwrap();
+        balance.assert_async().await;
+        assert_eq!(balances["XXBT"], "0.25");
+
+        let error = kraken
+            .private_request::<serde_json::Value>("OpenOrders", &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ExchangeError::Auth { .. }));
+    }
+
+    fn trading_exchange(server: &mockito::Server) -> KrakenExchange {
+        KrakenExchange::with_config(
+            ConnectorConfig::new(server.url())
+                .with_credentials(Credentials::new("kraken-key", "a3Jha2VuLXNlY3JldA==")),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_place_order_reports_the_venues_order_state() {
+        let mut server = mockito::Server::new_async().await;
+        let add = server
+            .mock("POST", "/0/private/AddOrder")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::Regex("pair=XBTUSD".into()),
+                mockito::Matcher::Regex("type=buy".into()),
+                mockito::Matcher::Regex("volume=0.5".into()),
+                mockito::Matcher::Regex("ordertype=limit".into()),
+                mockito::Matcher::Regex("price=50000".into()),
+                mockito::Matcher::Regex("cl_ord_id=sor-1-0".into()),
+            ]))
+            .with_body(
+                r#"{"error":[],"result":{"descr":{"order":"buy 0.5 XBTUSD @ limit 50000"},
+                    "txid":["OUF4EM-FRGI2-MQMWZD"]}}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/0/private/QueryOrders")
+            .match_body(mockito::Matcher::Regex("txid=OUF4EM-FRGI2-MQMWZD".into()))
+            .with_body(
+                r#"{"error":[],"result":{"OUF4EM-FRGI2-MQMWZD":{
+                    "status":"open","opentm":1714564800.5,"vol":"0.5","vol_exec":"0.2",
+                    "cost":"9998.0","fee":"25.995","cl_ord_id":"sor-1-0",
+                    "descr":{"pair":"XBTUSD","type":"buy","ordertype":"limit","price":"50000"}}}}"#,
+            )
+            .create_async()
+            .await;
+
+        let request = OrderRequest {
+            client_order_id: "sor-1-0".to_string(),
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Limit,
+            quantity: dec!(0.5),
+            limit_price: Some(dec!(50000)),
+        };
+        let report = trading_exchange(&server)
+            .place_order(&request)
+            .await
+            .unwrap();
+
+        add.assert_async().await;
+        assert_eq!(report.order_id, "OUF4EM-FRGI2-MQMWZD");
+        assert_eq!(report.client_order_id, "sor-1-0");
+        assert_eq!(report.status, OrderStatus::PartiallyFilled);
+        assert_eq!(report.filled_quantity, dec!(0.2));
+        assert_eq!(report.average_price, dec!(49990));
+        assert_eq!(report.fees, dec!(25.995));
+    }
+
+    #[tokio::test]
+    async fn test_refused_order_is_an_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/0/private/AddOrder")
+            .with_body(r#"{"error":["EOrder:Insufficient funds"]}"#)
+            .create_async()
+            .await;
+
+        let request = OrderRequest {
+            client_order_id: "sor-1-0".to_string(),
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Sell,
+            order_type: OrderType::Market,
+            quantity: dec!(1),
+            limit_price: None,
+        };
+        let error = trading_exchange(&server)
+            .place_order(&request)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Insufficient funds"));
+    }
+
+    #[test]
+    fn test_long_client_ids_are_sent_as_a_digest() {
+        assert_eq!(kraken_client_id("sor-1-0"), "sor-1-0");
+        let long = kraken_client_id("sor-0190a1b2c3d4-12-0");
+        assert_eq!(long.len(), 32);
+        assert_eq!(long, kraken_client_id("sor-0190a1b2c3d4-12-0"));
+    }
+
+    #[tokio::test]
+    async fn test_get_balances_maps_assets_and_holds() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/0/private/BalanceEx")
+            .with_body(
+                r#"{"error":[],"result":{
+                    "ZUSD":{"balance":"1500.00","hold_trade":"400.00"},
+                    "XXBT":{"balance":"0.25","hold_trade":"0"}}}"#,
+            )
+            .create_async()
+            .await;
+        let kraken = KrakenExchange::with_config(
+            ConnectorConfig::new(server.url())
+                .with_credentials(Credentials::new("kraken-key", "a3Jha2VuLXNlY3JldA==")),
+        )
+        .unwrap();
+
+        let mut balances = kraken.get_balances().await.unwrap();
+        balances.sort_by(|a, b| a.asset.cmp(&b.asset));
+
+        assert_eq!(
+            balances,
+            vec![
+                Balance::new("BTC", dec!(0.25), dec!(0)),
+                Balance::new("USD", dec!(1500.00), dec!(400.00)),
+            ]
+        );
+        assert_eq!(balances[1].available(), dec!(1100));
+    }
+
+    #[tokio::test]
+    async fn test_instrument_rules_from_asset_pairs() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/0/public/AssetPairs?pair=XBTUSD")
+            .with_body(include_str!(
+                "../../tests/fixtures/kraken/asset_pairs_xxbtzusd.json"
+            ))
+            .create_async()
+            .await;
+
+        let rules = exchange(&server)
+            .instrument_rules(&TradingPair::new("BTC", "USD"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            rules,
+            InstrumentRules::new(dec!(0.1), dec!(0.00000001))
+                .with_min_quantity(dec!(0.0001))
+                .with_min_notional(dec!(0.5))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_pairs_maps_assets_and_status() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/0/public/AssetPairs")
+            .with_body(include_str!("../../tests/fixtures/kraken/asset_pairs.json"))
+            .create_async()
+            .await;
+        let kraken = exchange(&server);
+
+        let listings = kraken.list_pairs().await.unwrap();
+        assert_eq!(
+            listings,
+            vec![
+                PairListing {
+                    pair: TradingPair::new("LUNA", "USD"),
+                    symbol: "LUNAUSD".to_string(),
+                    status: TradingStatus::Halted,
+                },
+                PairListing {
+                    pair: TradingPair::new("ETH", "BTC"),
+                    symbol: "XETHXXBT".to_string(),
+                    status: TradingStatus::Trading,
+                },
+                PairListing {
+                    pair: TradingPair::new("BTC", "USD"),
+                    symbol: "XXBTZUSD".to_string(),
+                    status: TradingStatus::Trading,
+                },
+            ]
+        );
+        assert_eq!(
+            kraken
+                .pair_status(&TradingPair::new("BTC", "USD"))
+                .await
+                .unwrap(),
+            Some(TradingStatus::Trading)
+        );
+    }
+
+    #[test]
+    fn test_asset_code_mapping() {
+        assert_eq!(to_kraken_pair(&TradingPair::new("BTC", "USD")), "XBTUSD");
+        assert_eq!(to_kraken_pair(&TradingPair::new("doge", "eur")), "XDGEUR");
+        assert_eq!(from_kraken_asset("XXBT"), "BTC");
+        assert_eq!(from_kraken_asset("ZUSD"), "USD");
+        assert_eq!(from_kraken_asset("XBT"), "BTC");
+        assert_eq!(from_kraken_asset("USDT"), "USDT");
+        assert_eq!(from_kraken_asset("XXDG"), "DOGE");
+        // Newer assets starting with X or Z keep their code
+        assert_eq!(from_kraken_asset("ZEUS"), "ZEUS");
+        assert_eq!(from_kraken_asset("XCN"), "XCN");
+        assert_eq!(from_kraken_asset("ZRX"), "ZRX");
+        assert_eq!(
+            from_kraken_pair("ZEUSUSD"),
+            Some(TradingPair::new("ZEUS", "USD"))
+        );
+        assert_eq!(
+            from_kraken_pair("XXBTZUSD"),
+            Some(TradingPair::new("BTC", "USD"))
+        );
+        assert_eq!(
+            from_kraken_pair("XETHXXBT"),
+            Some(TradingPair::new("ETH", "BTC"))
+        );
+        assert_eq!(
+            from_kraken_pair("XBTUSDT"),
+            Some(TradingPair::new("BTC", "USDT"))
+        );
+        assert_eq!(
+            from_kraken_pair("SOLUSD"),
+            Some(TradingPair::new("SOL", "USD"))
+        );
     }
 
-    async fn supports_pair(&self, _pair: &TradingPair) -> bool {
-        true
+    #[tokio::test]
+    async fn test_get_liquidity_parses_depth() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/0/public/Depth?pair=XBTUSD&count=10")
+            .with_body(include_str!(
+                "../../tests/fixtures/kraken/depth_xxbtzusd.json"
+            ))
+            .create_async()
+            .await;
+
+        let book = exchange(&server)
+            .get_liquidity(&TradingPair::new("BTC", "USD"))
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(book.pair, TradingPair::new("BTC", "USD"));
+        assert_eq!(
+            book.best_bid(),
+            Some(&PriceLevel::new(dec!(67014.1), dec!(3.105)))
+        );
+        assert_eq!(book.best_ask().unwrap().price, dec!(67014.2));
+        assert_eq!(book.asks.len(), 3);
+        // Level timestamps say when a level last changed, not how old the
+        // snapshot is
+        assert_eq!(book.exchange_time, None);
+    }
+
+    #[tokio::test]
+    async fn test_get_liquidity_surfaces_error_array() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/0/public/Depth?pair=FOOUSD&count=10")
+            .with_body(include_str!(
+                "../../tests/fixtures/kraken/unknown_pair.json"
+            ))
+            .create_async()
+            .await;
+
+        let error = exchange(&server)
+            .get_liquidity(&TradingPair::new("FOO", "USD"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            ExchangeError::UnknownSymbol { symbol, .. } if symbol == "FOOUSD"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_error_pauses_requests() {
+        let mut server = mockito::Server::new_async().await;
+        let depth = server
+            .mock("GET", "/0/public/Depth?pair=XBTUSD&count=10")
+            .with_body(r#"{"error":["EAPI:Rate limit exceeded"]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let kraken = exchange(&server);
+        let pair = TradingPair::new("BTC", "USD");
+
+        for _ in 0..2 {
+            assert!(matches!(
+                kraken.get_liquidity(&pair).await.unwrap_err(),
+                ExchangeError::RateLimited { .. }
+            ));
+        }
+        // The second attempt never reached Kraken
+        depth.assert_async().await;
     }
 }
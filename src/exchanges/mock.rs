@@ -0,0 +1,455 @@
+use super::error::ExchangeError;
+use super::fees::FeeSchedule;
+use super::paper::PaperBackend;
+use super::Exchange;
+use crate::types::{
+    Balance, InstrumentRules, OrderBook, OrderReport, OrderRequest, OrderSide, PairListing,
+    PriceLevel, TradingPair, TradingStatus,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// One scripted answer to a book request
+#[derive(Debug, Clone)]
+enum Step {
+    Book(OrderBook),
+    Fail(ExchangeError),
+}
+
+/// How a [`MockExchange`] treats orders sent to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FillBehavior {
+    /// Match against the current book, like a paper venue
+    Book,
+    /// Fill at most this fraction of each order. Market remainders expire
+    /// and limit remainders rest.
+    Partial(Decimal),
+    /// Reject every order with this message
+    Reject(String),
+    /// Match against the book but lose the acknowledgement, as if the
+    /// connection dropped after the venue took the order
+    Unacknowledged,
+}
+
+/// Exchange backed entirely by books supplied up front.
+///
+/// Never touches the network. Used for demos and tests; it must be opted
+/// into explicitly so invented prices cannot leak into live routing.
+///
+/// Each pair follows a script: book requests walk through the scripted
+/// books and failures in order, and the last step repeats once the script
+/// runs out. Nothing is random, so the same script always yields the same
+/// routing.
+pub struct MockExchange {
+    name: String,
+    scripts: HashMap<TradingPair, Vec<Step>>,
+    /// Book requests answered so far, per pair
+    calls: Mutex<HashMap<TradingPair, usize>>,
+    rules: HashMap<TradingPair, InstrumentRules>,
+    /// Pairs with a book trade unless given another status
+    statuses: HashMap<TradingPair, TradingStatus>,
+    fees: FeeSchedule,
+    /// `None` behaves like a venue without balance queries
+    balances: Option<Vec<Balance>>,
+    /// How far behind the venue clock every quote is; fresh when `None`
+    quote_age: Option<Duration>,
+    /// The venue's own quote age limit, if it sets one
+    max_quote_age: Option<Duration>,
+    /// Delay before every book request and order is answered
+    latency: Duration,
+    fill_behavior: FillBehavior,
+    /// Refuse every cancel, leaving the order working
+    cancels_fail: bool,
+    /// Every order received, in arrival order
+    received_orders: Mutex<Vec<OrderRequest>>,
+    paper: PaperBackend,
+}
+
+impl MockExchange {
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            paper: PaperBackend::new(name.clone()),
+            name,
+            scripts: HashMap::new(),
+            calls: Mutex::new(HashMap::new()),
+            rules: HashMap::new(),
+            statuses: HashMap::new(),
+            fees: FeeSchedule::default(),
+            balances: None,
+            quote_age: None,
+            max_quote_age: None,
+            latency: Duration::ZERO,
+            fill_behavior: FillBehavior::Book,
+            cancels_fail: false,
+            received_orders: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Quote a pair with the given `(price, quantity)` levels, best first,
+    /// replacing any script for it
+    pub fn with_book(
+        mut self,
+        pair: TradingPair,
+        bids: &[(Decimal, Decimal)],
+        asks: &[(Decimal, Decimal)],
+    ) -> Self {
+        let book = self.book(&pair, bids, asks);
+        self.scripts.insert(pair, vec![Step::Book(book)]);
+        self
+    }
+
+    /// Answer the next book request for a pair with this book
+    pub fn then_book(
+        mut self,
+        pair: TradingPair,
+        bids: &[(Decimal, Decimal)],
+        asks: &[(Decimal, Decimal)],
+    ) -> Self {
+        let book = self.book(&pair, bids, asks);
+        self.scripts.entry(pair).or_default().push(Step::Book(book));
+        self
+    }
+
+    /// Answer the next book request for a pair with `error`
+    pub fn then_fail(mut self, pair: TradingPair, error: ExchangeError) -> Self {
+        self.scripts
+            .entry(pair)
+            .or_default()
+            .push(Step::Fail(error));
+        self
+    }
+
+    /// Serve every book as if the venue produced it `age` ago
+    pub fn with_quote_age(mut self, age: Duration) -> Self {
+        self.quote_age = Some(age);
+        self
+    }
+
+    /// Route on quotes up to `age` old, whatever the router's default
+    pub fn with_max_quote_age(mut self, age: Duration) -> Self {
+        self.max_quote_age = Some(age);
+        self
+    }
+
+    /// Wait `latency` before answering each book request or order
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_fill_behavior(mut self, fill_behavior: FillBehavior) -> Self {
+        self.fill_behavior = fill_behavior;
+        self
+    }
+
+    /// Refuse every cancel request
+    pub fn with_failing_cancels(mut self) -> Self {
+        self.cancels_fail = true;
+        self
+    }
+
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// Enforce `rules` on a pair; pairs without rules are unrestricted
+    pub fn with_rules(mut self, pair: TradingPair, rules: InstrumentRules) -> Self {
+        self.rules.insert(pair, rules);
+        self
+    }
+
+    /// List a pair with `status` instead of as trading
+    pub fn with_status(mut self, pair: TradingPair, status: TradingStatus) -> Self {
+        self.statuses.insert(pair, status);
+        self
+    }
+
+    /// Report `balance` from [`Exchange::get_balances`]
+    pub fn with_balance(mut self, balance: Balance) -> Self {
+        self.balances.get_or_insert_with(Vec::new).push(balance);
+        self
+    }
+
+    /// Number of book requests made for a pair
+    pub fn liquidity_calls(&self, pair: &TradingPair) -> usize {
+        self.calls.lock().unwrap().get(pair).copied().unwrap_or(0)
+    }
+
+    /// Every order received so far, including rejected ones
+    pub fn received_orders(&self) -> Vec<OrderRequest> {
+        self.received_orders.lock().unwrap().clone()
+    }
+
+    fn book(
+        &self,
+        pair: &TradingPair,
+        bids: &[(Decimal, Decimal)],
+        asks: &[(Decimal, Decimal)],
+    ) -> OrderBook {
+        let levels = |levels: &[(Decimal, Decimal)]| {
+            levels
+                .iter()
+                .map(|&(price, quantity)| PriceLevel::new(price, quantity))
+                .collect()
+        };
+        OrderBook::new(self.name.clone(), pair.clone(), levels(bids), levels(asks))
+    }
+
+    /// The scripted answer for request number `call`
+    fn step(&self, pair: &TradingPair, call: usize) -> Result<OrderBook, ExchangeError> {
+        let step = self
+            .scripts
+            .get(pair)
+            .and_then(|script| script.get(call).or(script.last()))
+            .ok_or_else(|| ExchangeError::UnknownSymbol {
+                exchange: self.name.clone(),
+                symbol: pair.to_string(),
+            })?;
+        let mut book = match step {
+            Step::Book(book) => book.clone(),
+            Step::Fail(error) => return Err(error.clone()),
+        };
+        let now = chrono::Utc::now();
+        book.received_at = now;
+        book.exchange_time = self
+            .quote_age
+            .and_then(|age| chrono::Duration::from_std(age).ok())
+            .map(|age| now - age);
+        Ok(book)
+    }
+
+    /// The book last served for a pair, which orders are matched against
+    fn current_book(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        self.step(pair, self.liquidity_calls(pair).saturating_sub(1))
+    }
+}
+
+/// Keep only the first `quantity` of depth on the side `side` takes from
+fn clip_book(mut book: OrderBook, side: OrderSide, quantity: Decimal) -> OrderBook {
+    let levels = match side {
+        OrderSide::Buy => &mut book.asks,
+        OrderSide::Sell => &mut book.bids,
+    };
+    let mut remaining = quantity;
+    levels.retain_mut(|level| {
+        level.quantity = level.quantity.min(remaining);
+        remaining -= level.quantity;
+        level.quantity > dec!(0)
+    });
+    book
+}
+
+#[async_trait]
+impl Exchange for MockExchange {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        let call = {
+            let mut calls = self.calls.lock().unwrap();
+            let count = calls.entry(pair.clone()).or_default();
+            *count += 1;
+            *count - 1
+        };
+        self.step(pair, call)
+    }
+
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        self.scripts.contains_key(pair)
+    }
+
+    async fn list_pairs(&self) -> Result<Vec<PairListing>> {
+        let mut listings: Vec<_> = self
+            .scripts
+            .keys()
+            .map(|pair| PairListing {
+                pair: pair.clone(),
+                symbol: pair.to_string(),
+                status: self
+                    .statuses
+                    .get(pair)
+                    .copied()
+                    .unwrap_or(TradingStatus::Trading),
+            })
+            .collect();
+        listings.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        Ok(listings)
+    }
+
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        Ok(self.scripts.contains_key(pair).then(|| {
+            self.statuses
+                .get(pair)
+                .copied()
+                .unwrap_or(TradingStatus::Trading)
+        }))
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        self.fees.clone()
+    }
+
+    fn max_quote_age(&self) -> Option<Duration> {
+        self.max_quote_age
+    }
+
+    async fn instrument_rules(&self, pair: &TradingPair) -> Result<InstrumentRules, ExchangeError> {
+        Ok(self.rules.get(pair).cloned().unwrap_or_default())
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        self.received_orders.lock().unwrap().push(request.clone());
+
+        let book = self.current_book(&request.pair)?;
+        let book = match &self.fill_behavior {
+            FillBehavior::Book | FillBehavior::Unacknowledged => book,
+            FillBehavior::Partial(fraction) => {
+                clip_book(book, request.side, request.quantity * fraction)
+            }
+            FillBehavior::Reject(message) => {
+                return Err(ExchangeError::Api {
+                    exchange: self.name.clone(),
+                    code: None,
+                    message: message.clone(),
+                }
+                .into())
+            }
+        };
+        let fees = self.fees.rates_for(&request.pair);
+        let report = self.paper.place_order(request, &book, fees)?;
+        if matches!(self.fill_behavior, FillBehavior::Unacknowledged) {
+            return Err(ExchangeError::network(&self.name, "connection reset").into());
+        }
+        Ok(report)
+    }
+
+    async fn cancel_order(&self, _pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        if self.cancels_fail {
+            return Err(ExchangeError::network(&self.name, "cancel timed out").into());
+        }
+        self.paper.cancel_order(order_id)
+    }
+
+    async fn get_order_status(&self, _pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        self.paper.get_order_status(order_id)
+    }
+
+    async fn get_open_orders(&self, pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        Ok(self.paper.get_open_orders(pair))
+    }
+
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        Ok(self.paper.find_order(pair, client_order_id))
+    }
+
+    async fn get_balances(&self) -> Result<Vec<Balance>> {
+        match &self.balances {
+            Some(balances) => Ok(balances.clone()),
+            None => anyhow::bail!("{} does not support balance queries", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{OrderStatus, OrderType};
+
+    fn request(order_type: OrderType, quantity: Decimal) -> OrderRequest {
+        OrderRequest {
+            client_order_id: "child-1".to_string(),
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type,
+            quantity,
+            limit_price: (order_type == OrderType::Limit).then_some(dec!(101)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_script_plays_in_order_and_repeats_last_step() {
+        let pair = TradingPair::new("BTC", "USD");
+        let venue = MockExchange::new("Venue")
+            .with_book(pair.clone(), &[], &[(dec!(100), dec!(1))])
+            .then_fail(pair.clone(), ExchangeError::network("Venue", "reset"))
+            .then_book(pair.clone(), &[], &[(dec!(101), dec!(1))]);
+
+        let ask = |book: OrderBook| book.best_ask().unwrap().price;
+        assert_eq!(ask(venue.get_liquidity(&pair).await.unwrap()), dec!(100));
+        assert!(matches!(
+            venue.get_liquidity(&pair).await,
+            Err(ExchangeError::Network { .. })
+        ));
+        assert_eq!(ask(venue.get_liquidity(&pair).await.unwrap()), dec!(101));
+        assert_eq!(ask(venue.get_liquidity(&pair).await.unwrap()), dec!(101));
+        assert_eq!(venue.liquidity_calls(&pair), 4);
+
+        assert!(matches!(
+            venue.get_liquidity(&TradingPair::new("ETH", "USD")).await,
+            Err(ExchangeError::UnknownSymbol { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fill_behaviors() {
+        let pair = TradingPair::new("BTC", "USD");
+        let venue = |behavior| {
+            MockExchange::new("Venue")
+                .with_book(
+                    pair.clone(),
+                    &[],
+                    &[(dec!(100), dec!(1)), (dec!(101), dec!(1))],
+                )
+                .with_fill_behavior(behavior)
+        };
+
+        let report = venue(FillBehavior::Book)
+            .place_order(&request(OrderType::Market, dec!(2)))
+            .await
+            .unwrap();
+        assert_eq!(report.status, OrderStatus::Filled);
+        assert_eq!(report.average_price, dec!(100.5));
+
+        let partial = venue(FillBehavior::Partial(dec!(0.75)));
+        let report = partial
+            .place_order(&request(OrderType::Market, dec!(2)))
+            .await
+            .unwrap();
+        assert_eq!(report.status, OrderStatus::Expired);
+        assert_eq!(report.filled_quantity, dec!(1.5));
+        let report = partial
+            .place_order(&request(OrderType::Limit, dec!(2)))
+            .await
+            .unwrap();
+        assert_eq!(report.status, OrderStatus::PartiallyFilled);
+
+        let rejecting = venue(FillBehavior::Reject("insufficient balance".to_string()));
+        let error = rejecting
+            .place_order(&request(OrderType::Market, dec!(1)))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("insufficient balance"));
+        assert_eq!(rejecting.received_orders().len(), 1);
+    }
+}
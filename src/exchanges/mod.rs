@@ -1,10 +1,32 @@
+pub mod amm;
+pub mod auth;
 pub mod binance;
+pub mod bitstamp;
+pub mod bybit;
 pub mod coinbase;
+pub mod config;
+pub mod error;
+pub mod fees;
+pub mod fix;
 pub mod kraken;
+pub mod mock;
+pub mod okx;
+pub mod pairs;
+pub mod paper;
+pub mod rate_limit;
+pub mod rfq;
 
-use crate::types::{Liquidity, TradingPair};
+use crate::types::{
+    Balance, InstrumentRules, OrderBook, OrderReport, OrderRequest, PairListing, TradingPair,
+    TradingStatus,
+};
 use anyhow::Result;
 use async_trait::async_trait;
+use config::{ExchangesConfig, Venue};
+pub use error::ExchangeError;
+use fees::FeeSchedule;
+use rust_decimal_macros::dec;
+use std::time::Duration;
 
 /// Trait for exchange connectors
 #[async_trait]
@@ -12,18 +34,172 @@ pub trait Exchange: Send + Sync {
     /// Get the exchange name
     fn name(&self) -> &str;
 
-    /// Fetch current liquidity for a trading pair
-    async fn get_liquidity(&self, pair: &TradingPair) -> Result<Liquidity>;
+    /// Fetch the current order book for a trading pair
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError>;
 
     /// Check if the exchange supports a trading pair
     async fn supports_pair(&self, pair: &TradingPair) -> bool;
+
+    /// Every pair the venue lists, with its current trading status
+    async fn list_pairs(&self) -> Result<Vec<PairListing>> {
+        anyhow::bail!("{} does not support pair listing", self.name())
+    }
+
+    /// Trading status of a pair, or `None` if the venue does not list it.
+    ///
+    /// Connectors without a listing report every supported pair as trading.
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        Ok(self
+            .supports_pair(pair)
+            .await
+            .then_some(TradingStatus::Trading))
+    }
+
+    /// Fee schedule charged by the exchange
+    fn fee_schedule(&self) -> FeeSchedule {
+        FeeSchedule::default()
+    }
+
+    /// Oldest quote from this venue worth routing on, where the venue's
+    /// books age differently from the router's `max_quote_age`
+    fn max_quote_age(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Tick, lot and minimum-size rules for a pair; unrestricted unless the
+    /// connector knows better
+    async fn instrument_rules(
+        &self,
+        _pair: &TradingPair,
+    ) -> Result<InstrumentRules, ExchangeError> {
+        Ok(InstrumentRules::default())
+    }
+
+    /// Submit an order
+    async fn place_order(&self, _request: &OrderRequest) -> Result<OrderReport> {
+        anyhow::bail!("{} does not support order placement", self.name())
+    }
+
+    /// Cancel an open order
+    async fn cancel_order(&self, _pair: &TradingPair, _order_id: &str) -> Result<OrderReport> {
+        anyhow::bail!("{} does not support order cancellation", self.name())
+    }
+
+    /// Fetch the current state of an order
+    async fn get_order_status(&self, _pair: &TradingPair, _order_id: &str) -> Result<OrderReport> {
+        anyhow::bail!("{} does not support order queries", self.name())
+    }
+
+    /// List orders still working for a pair
+    async fn get_open_orders(&self, _pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        anyhow::bail!("{} does not support order queries", self.name())
+    }
+
+    /// Find an order by the client order ID it was sent with; `None` means
+    /// the venue has no such order.
+    ///
+    /// Venues that only list open orders cannot tell a missing order from
+    /// a finished one, so unless it is still open this fails rather than
+    /// answering `None`.
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        match self
+            .get_open_orders(pair)
+            .await?
+            .into_iter()
+            .find(|report| report.client_order_id == client_order_id)
+        {
+            Some(report) => Ok(Some(report)),
+            None => anyhow::bail!(
+                "{} cannot look up finished orders by client order ID",
+                self.name()
+            ),
+        }
+    }
+
+    /// Account balances, with funds held for open orders reported as reserved
+    async fn get_balances(&self) -> Result<Vec<Balance>> {
+        anyhow::bail!("{} does not support balance queries", self.name())
+    }
 }
 
-/// Factory to create exchange instances
+/// Factory to create live exchange instances with default settings
 pub fn create_exchanges() -> Vec<Box<dyn Exchange>> {
+    create_exchanges_from_config(&ExchangesConfig::default())
+        .expect("default exchanges configuration is valid")
+}
+
+/// Factory to create the enabled live exchanges described by `config`
+pub fn create_exchanges_from_config(config: &ExchangesConfig) -> Result<Vec<Box<dyn Exchange>>> {
+    let mut exchanges: Vec<Box<dyn Exchange>> = Vec::new();
+    for venue in config.venues.iter().filter(|venue| venue.enabled) {
+        let exchange: Box<dyn Exchange> = match venue.venue {
+            Venue::Binance => Box::new(binance::BinanceExchange::with_config(
+                venue.connector_config(binance::BinanceExchange::default_config())?,
+            )?),
+            Venue::Coinbase => Box::new(coinbase::CoinbaseExchange::with_config(
+                venue.connector_config(coinbase::CoinbaseExchange::default_config())?,
+            )?),
+            Venue::Kraken => Box::new(kraken::KrakenExchange::with_config(
+                venue.connector_config(kraken::KrakenExchange::default_config())?,
+            )?),
+            Venue::Okx => Box::new(okx::OkxExchange::with_config(
+                venue.connector_config(okx::OkxExchange::default_config())?,
+            )?),
+            Venue::Bybit => Box::new(bybit::BybitExchange::with_config(
+                venue.connector_config(bybit::BybitExchange::default_config())?,
+            )?),
+            Venue::Bitstamp => Box::new(bitstamp::BitstampExchange::with_config(
+                venue.connector_config(bitstamp::BitstampExchange::default_config())?,
+            )?),
+        };
+        exchanges.push(exchange);
+    }
+    Ok(exchanges)
+}
+
+/// Factory for simulated exchanges quoting a fixed BTC/USD market.
+///
+/// Demo venues are named so they can never be mistaken for live ones, and
+/// answer with a small fixed latency like a nearby venue would.
+pub fn create_demo_exchanges() -> Vec<Box<dyn Exchange>> {
+    let pair = TradingPair::new("BTC", "USD");
     vec![
-        Box::new(binance::BinanceExchange::new()),
-        Box::new(coinbase::CoinbaseExchange::new()),
-        Box::new(kraken::KrakenExchange::new()),
+        Box::new(
+            mock::MockExchange::new("Demo-Binance")
+                .with_book(
+                    pair.clone(),
+                    &[(dec!(50000.0), dec!(1.5)), (dec!(49990.0), dec!(2.0))],
+                    &[(dec!(50050.0), dec!(2.0)), (dec!(50060.0), dec!(2.5))],
+                )
+                .with_fees(FeeSchedule::flat(dec!(0.001), dec!(0.001)))
+                .with_latency(Duration::from_millis(15)),
+        ),
+        Box::new(
+            mock::MockExchange::new("Demo-Coinbase")
+                .with_book(
+                    pair.clone(),
+                    &[(dec!(49950.0), dec!(1.2)), (dec!(49940.0), dec!(2.0))],
+                    &[(dec!(50000.0), dec!(1.8)), (dec!(50020.0), dec!(2.2))],
+                )
+                .with_fees(FeeSchedule::flat(dec!(0.004), dec!(0.006)))
+                .with_latency(Duration::from_millis(40)),
+        ),
+        Box::new(
+            mock::MockExchange::new("Demo-Kraken")
+                .with_book(
+                    pair,
+                    &[(dec!(49980.0), dec!(2.5)), (dec!(49960.0), dec!(1.0))],
+                    &[(dec!(50030.0), dec!(1.5)), (dec!(50045.0), dec!(3.0))],
+                )
+                .with_fees(FeeSchedule::flat(dec!(0.0025), dec!(0.004)))
+                .with_latency(Duration::from_millis(25)),
+        ),
     ]
 }
@@ -0,0 +1,860 @@
+use super::auth::{self, Credentials, ServerClock};
+use super::config::ConnectorConfig;
+use super::error::ExchangeError;
+use super::fees::FeeSchedule;
+use super::pairs::PairDirectory;
+use super::rate_limit::{RateLimit, RateLimiter};
+use super::Exchange;
+use crate::types::{
+    InstrumentRules, OrderBook, OrderReport, OrderRequest, OrderSide, OrderStatus, OrderType,
+    PairListing, PriceLevel, TradingPair, TradingStatus,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Method;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// OKX wraps every response in `{code, msg, data}`; `code` is `"0"` on
+/// success
+#[derive(Debug, Deserialize)]
+struct OkxResponse {
+    code: String,
+    msg: String,
+    /// Only decoded once `code` says the call succeeded
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+/// Depth levels are `[price, size, deprecated, order_count]`
+#[derive(Debug, Deserialize)]
+struct OkxBook {
+    asks: Vec<(String, String, String, String)>,
+    bids: Vec<(String, String, String, String)>,
+}
+
+/// Spot entry of `/api/v5/public/instruments`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxInstrument {
+    inst_id: String,
+    base_ccy: String,
+    quote_ccy: String,
+    tick_sz: String,
+    lot_sz: String,
+    min_sz: String,
+    state: String,
+}
+
+impl OkxInstrument {
+    fn rules(&self) -> Result<InstrumentRules> {
+        Ok(InstrumentRules::new(
+            Decimal::from_str(&self.tick_sz)?,
+            Decimal::from_str(&self.lot_sz)?,
+        )
+        .with_min_quantity(Decimal::from_str(&self.min_sz)?))
+    }
+
+    fn trading_status(&self) -> TradingStatus {
+        match self.state.as_str() {
+            "live" => TradingStatus::Trading,
+            // preopen, suspend, test and anything newer
+            _ => TradingStatus::Halted,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OkxTime {
+    /// Milliseconds since the epoch, as a string
+    ts: String,
+}
+
+/// Per-order result of `/api/v5/trade/order` and `/cancel-order`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxOrderAck {
+    ord_id: String,
+}
+
+/// Order as returned by `/api/v5/trade/order` and `/orders-pending`.
+/// Amounts OKX has no value for yet, such as `avgPx` before a fill, are
+/// empty strings.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxOrder {
+    ord_id: String,
+    cl_ord_id: String,
+    side: String,
+    sz: String,
+    acc_fill_sz: String,
+    avg_px: String,
+    state: String,
+    /// Negative when charged, positive for a rebate
+    fee: String,
+    fee_ccy: String,
+    /// Milliseconds since the epoch, as a string
+    u_time: String,
+}
+
+impl OkxOrder {
+    fn into_report(self, exchange: &str, pair: &TradingPair) -> Result<OrderReport> {
+        let amount = |value: &str| -> Result<Decimal> {
+            if value.is_empty() {
+                Ok(dec!(0))
+            } else {
+                Ok(Decimal::from_str(value)?)
+            }
+        };
+        let filled_quantity = amount(&self.acc_fill_sz)?;
+        let average_price = amount(&self.avg_px)?;
+        let status = match self.state.as_str() {
+            "live" => OrderStatus::New,
+            "partially_filled" => OrderStatus::PartiallyFilled,
+            "filled" => OrderStatus::Filled,
+            "canceled" | "mmp_canceled" => OrderStatus::Cancelled,
+            other => anyhow::bail!("Unknown OKX order state {}", other),
+        };
+        // Buys pay their fee in the base asset they receive
+        let fee = -amount(&self.fee)?;
+        let fees = if self.fee_ccy.eq_ignore_ascii_case(&pair.base) {
+            fee * average_price
+        } else {
+            fee
+        };
+        Ok(OrderReport {
+            order_id: self.ord_id,
+            client_order_id: self.cl_ord_id,
+            exchange: exchange.to_string(),
+            pair: pair.clone(),
+            side: match self.side.as_str() {
+                "buy" => OrderSide::Buy,
+                "sell" => OrderSide::Sell,
+                other => anyhow::bail!("Unknown OKX order side {}", other),
+            },
+            status,
+            quantity: amount(&self.sz)?,
+            filled_quantity,
+            average_price,
+            fees,
+            fills: Vec::new(),
+            updated_at: self
+                .u_time
+                .parse()
+                .ok()
+                .and_then(DateTime::from_timestamp_millis)
+                .unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+/// `clOrdId` OKX accepts for `client_order_id`: up to 32 letters and
+/// digits, or else a digest of it
+pub fn okx_client_id(client_order_id: &str) -> String {
+    let accepted = !client_order_id.is_empty()
+        && client_order_id.len() <= 32
+        && client_order_id.chars().all(|c| c.is_ascii_alphanumeric());
+    if accepted {
+        client_order_id.to_string()
+    } else {
+        auth::client_id_digest(client_order_id)
+    }
+}
+
+/// OKX instrument id used in requests (BTC/USDT -> BTC-USDT)
+pub fn to_okx_inst_id(pair: &TradingPair) -> String {
+    format!("{}-{}", pair.base.to_uppercase(), pair.quote.to_uppercase())
+}
+
+fn parse_levels(levels: &[(String, String, String, String)]) -> Result<Vec<PriceLevel>> {
+    levels
+        .iter()
+        .map(|(price, size, _, _)| {
+            Ok(PriceLevel::new(
+                Decimal::from_str(price)?,
+                Decimal::from_str(size)?,
+            ))
+        })
+        .collect()
+}
+
+/// OKX v5 REST API
+pub const DEFAULT_BASE_URL: &str = "https://www.okx.com";
+
+pub struct OkxExchange {
+    client: reqwest::Client,
+    config: ConnectorConfig,
+    /// Instrument rules change rarely, so each pair is fetched once
+    rules: Mutex<HashMap<TradingPair, InstrumentRules>>,
+    pairs: PairDirectory,
+    limiter: RateLimiter,
+    /// Signed requests are rejected more than 30 seconds off OKX's clock
+    clock: ServerClock,
+}
+
+impl OkxExchange {
+    pub fn new() -> Self {
+        Self::with_config(Self::default_config()).expect("default OKX configuration is valid")
+    }
+
+    pub fn with_config(config: ConnectorConfig) -> Result<Self> {
+        Ok(Self {
+            client: config.build_client()?,
+            rules: Mutex::new(HashMap::new()),
+            pairs: PairDirectory::new(config.pair_refresh),
+            limiter: config.rate_limiter("OKX"),
+            config,
+            clock: ServerClock::new(),
+        })
+    }
+
+    /// The instruments endpoint, the tightest public one used, allows 20
+    /// requests per 2 seconds per IP
+    pub fn default_config() -> ConnectorConfig {
+        ConnectorConfig::new(DEFAULT_BASE_URL)
+            .with_rate_limit(RateLimit::new(20, Duration::from_secs(2)))
+    }
+
+    /// Measure the offset to OKX's clock, which signed requests are
+    /// timestamped with
+    pub async fn sync_clock(&self) -> Result<(), ExchangeError> {
+        let sent = Utc::now().timestamp_millis();
+        let times: Vec<OkxTime> = self.public_request("/api/v5/public/time", "").await?;
+        let received = Utc::now().timestamp_millis();
+        let server = times
+            .first()
+            .and_then(|time| time.ts.parse().ok())
+            .ok_or_else(|| ExchangeError::parse(self.name(), "response has no server time"))?;
+        self.clock.observe(server, sent, received);
+        Ok(())
+    }
+
+    pub fn clock(&self) -> &ServerClock {
+        &self.clock
+    }
+
+    fn credentials(&self) -> Result<&Credentials, ExchangeError> {
+        self.config
+            .credentials
+            .as_ref()
+            .ok_or_else(|| ExchangeError::missing_credentials(self.name()))
+    }
+
+    /// `GET {path}`, unwrapping OKX's `{code, msg, data}` envelope
+    async fn public_request<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        inst_id: &str,
+    ) -> Result<Vec<T>, ExchangeError> {
+        self.limiter.acquire(1).await?;
+        self.send(
+            self.client.get(format!("{}{}", self.config.base_url, path)),
+            inst_id,
+        )
+        .await
+    }
+
+    /// Call a private endpoint, signed with the key, secret and passphrase.
+    /// A request OKX finds too far off its clock is retried once after
+    /// resynchronising.
+    async fn private_request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        request_path: &str,
+        body: Option<&serde_json::Value>,
+        inst_id: &str,
+    ) -> Result<Vec<T>, ExchangeError> {
+        match self
+            .try_private_request(method.clone(), request_path, body, inst_id)
+            .await
+        {
+            Err(ExchangeError::Api {
+                code: Some(50102), ..
+            }) => {
+                self.sync_clock().await?;
+                self.try_private_request(method, request_path, body, inst_id)
+                    .await
+            }
+            result => result,
+        }
+    }
+
+    async fn try_private_request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        request_path: &str,
+        body: Option<&serde_json::Value>,
+        inst_id: &str,
+    ) -> Result<Vec<T>, ExchangeError> {
+        let credentials = self.credentials()?;
+        let passphrase = credentials
+            .passphrase
+            .as_deref()
+            .ok_or_else(|| ExchangeError::Auth {
+                exchange: self.name().to_string(),
+                message: "OKX API keys need a passphrase".to_string(),
+            })?;
+        // Before signing, so a wait cannot age the timestamp
+        self.limiter.acquire(1).await?;
+        let timestamp = DateTime::from_timestamp_millis(self.clock.now_millis())
+            .unwrap_or_else(Utc::now)
+            .to_rfc3339_opts(SecondsFormat::Millis, true);
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let signature = auth::sign_okx(
+            credentials.api_secret(),
+            &timestamp,
+            method.as_str(),
+            request_path,
+            &body,
+        );
+
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.config.base_url, request_path))
+            .header("OK-ACCESS-KEY", &credentials.api_key)
+            .header("OK-ACCESS-SIGN", signature)
+            .header("OK-ACCESS-TIMESTAMP", timestamp)
+            .header("OK-ACCESS-PASSPHRASE", passphrase);
+        if !body.is_empty() {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body);
+        }
+        self.send(request, inst_id).await
+    }
+
+    /// Send a request and unwrap the envelope
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+        inst_id: &str,
+    ) -> Result<Vec<T>, ExchangeError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ExchangeError::network(self.name(), e))?;
+        // Errors usually come with a JSON envelope whatever the HTTP status
+        let error = match serde_json::from_str::<OkxResponse>(&body) {
+            Ok(envelope) if envelope.code == "0" && status.is_success() => {
+                return serde_json::from_value(envelope.data)
+                    .map_err(|e| ExchangeError::parse(self.name(), e));
+            }
+            Ok(envelope) => {
+                // Order endpoints fail with code 1 and give the reason per order
+                let rejection = envelope
+                    .data
+                    .get(0)
+                    .and_then(|order| Some((order.get("sCode")?.as_str()?, order.get("sMsg")?)));
+                match rejection {
+                    Some((code, message)) if code != "0" => self.classify_error(
+                        code,
+                        message.as_str().unwrap_or_default().to_string(),
+                        inst_id,
+                    ),
+                    _ => self.classify_error(&envelope.code, envelope.msg, inst_id),
+                }
+            }
+            Err(_) if !status.is_success() => ExchangeError::from_status(self.name(), status, body),
+            Err(e) => ExchangeError::parse(self.name(), e),
+        };
+        self.limiter.observe(&error);
+        Err(error)
+    }
+
+    /// Every spot instrument
+    async fn fetch_listings(&self) -> Result<Vec<PairListing>, ExchangeError> {
+        let instruments: Vec<OkxInstrument> = self
+            .public_request("/api/v5/public/instruments?instType=SPOT", "")
+            .await?;
+        Ok(instruments
+            .into_iter()
+            .map(|instrument| PairListing {
+                pair: TradingPair::new(&instrument.base_ccy, &instrument.quote_ccy),
+                status: instrument.trading_status(),
+                symbol: instrument.inst_id,
+            })
+            .collect())
+    }
+
+    /// Map an OKX error code (e.g. `51001`) to an [`ExchangeError`]
+    fn classify_error(&self, code: &str, message: String, inst_id: &str) -> ExchangeError {
+        let exchange = self.name().to_string();
+        match code {
+            // Instrument does not exist / is not yet live
+            "51001" | "51014" => ExchangeError::UnknownSymbol {
+                exchange,
+                symbol: inst_id.to_string(),
+            },
+            "50011" | "50061" => ExchangeError::RateLimited {
+                exchange,
+                retry_after: None,
+            },
+            // Service unavailable / busy / under maintenance
+            "50001" | "50013" | "50026" => ExchangeError::Maintenance { exchange },
+            // Timestamp expired: the clock needs resynchronising, the key is fine
+            "50102" => ExchangeError::Api {
+                exchange,
+                code: Some(50102),
+                message,
+            },
+            code if code.starts_with("501") => ExchangeError::Auth { exchange, message },
+            code => ExchangeError::Api {
+                exchange,
+                code: code.parse().ok(),
+                message,
+            },
+        }
+    }
+}
+
+impl Default for OkxExchange {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Exchange for OkxExchange {
+    fn name(&self) -> &str {
+        "OKX"
+    }
+
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        let inst_id = to_okx_inst_id(pair);
+        let books: Vec<OkxBook> = self
+            .public_request(
+                &format!(
+                    "/api/v5/market/books?instId={}&sz={}",
+                    inst_id, self.config.depth_limit
+                ),
+                &inst_id,
+            )
+            .await?;
+        let book = books
+            .into_iter()
+            .next()
+            .ok_or_else(|| ExchangeError::parse(self.name(), "response has no order book"))?;
+
+        // A REST snapshot is as old as our copy of it; `ts` would need
+        // OKX's clock offset to compare with ours
+        Ok(OrderBook::new(
+            self.name(),
+            pair.clone(),
+            parse_levels(&book.bids).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid bid level: {}", e))
+            })?,
+            parse_levels(&book.asks).map_err(|e| {
+                ExchangeError::parse(self.name(), format!("invalid ask level: {}", e))
+            })?,
+        ))
+    }
+
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        // An unreachable listing is no evidence the pair is missing
+        !matches!(self.pair_status(pair).await, Ok(None))
+    }
+
+    async fn list_pairs(&self) -> Result<Vec<PairListing>> {
+        Ok(self
+            .pairs
+            .listings(self.name(), || self.fetch_listings())
+            .await?)
+    }
+
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        self.pairs
+            .status(self.name(), pair, || self.fetch_listings())
+            .await
+    }
+
+    async fn instrument_rules(&self, pair: &TradingPair) -> Result<InstrumentRules, ExchangeError> {
+        if let Some(rules) = self.rules.lock().unwrap().get(pair) {
+            return Ok(rules.clone());
+        }
+
+        let inst_id = to_okx_inst_id(pair);
+        let instruments: Vec<OkxInstrument> = self
+            .public_request(
+                &format!(
+                    "/api/v5/public/instruments?instType=SPOT&instId={}",
+                    inst_id
+                ),
+                &inst_id,
+            )
+            .await?;
+        let rules = instruments
+            .first()
+            .ok_or_else(|| ExchangeError::UnknownSymbol {
+                exchange: self.name().to_string(),
+                symbol: inst_id.clone(),
+            })?
+            .rules()
+            .map_err(|e| ExchangeError::parse(self.name(), e))?;
+
+        self.rules
+            .lock()
+            .unwrap()
+            .insert(pair.clone(), rules.clone());
+        Ok(rules)
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        // Regular-user spot rates
+        FeeSchedule::flat(dec!(0.0008), dec!(0.001))
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        let inst_id = to_okx_inst_id(&request.pair);
+        let mut body = serde_json::json!({
+            "instId": inst_id,
+            "tdMode": "cash",
+            "clOrdId": okx_client_id(&request.client_order_id),
+            "side": match request.side {
+                OrderSide::Buy => "buy",
+                OrderSide::Sell => "sell",
+            },
+            "sz": request.quantity.normalize().to_string(),
+        });
+        match request.order_type {
+            OrderType::Market => {
+                body["ordType"] = "market".into();
+                // Spot market buys are sized in quote unless told otherwise
+                body["tgtCcy"] = "base_ccy".into();
+            }
+            OrderType::Limit => {
+                let price = request
+                    .limit_price
+                    .context("Limit order has no limit price")?;
+                body["ordType"] = "limit".into();
+                body["px"] = price.normalize().to_string().into();
+            }
+        }
+
+        let acks: Vec<OkxOrderAck> = self
+            .private_request(Method::POST, "/api/v5/trade/order", Some(&body), &inst_id)
+            .await?;
+        let order_id = acks
+            .into_iter()
+            .next()
+            .context("OKX accepted the order without an ordId")?
+            .ord_id;
+        let mut report = self.get_order_status(&request.pair, &order_id).await?;
+        report.client_order_id = request.client_order_id.clone();
+        Ok(report)
+    }
+
+    async fn cancel_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let inst_id = to_okx_inst_id(pair);
+        let body = serde_json::json!({ "instId": inst_id, "ordId": order_id });
+        let _: Vec<OkxOrderAck> = self
+            .private_request(
+                Method::POST,
+                "/api/v5/trade/cancel-order",
+                Some(&body),
+                &inst_id,
+            )
+            .await?;
+        self.get_order_status(pair, order_id).await
+    }
+
+    async fn get_order_status(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        let inst_id = to_okx_inst_id(pair);
+        let orders: Vec<OkxOrder> = self
+            .private_request(
+                Method::GET,
+                &format!("/api/v5/trade/order?instId={}&ordId={}", inst_id, order_id),
+                None,
+                &inst_id,
+            )
+            .await?;
+        orders
+            .into_iter()
+            .next()
+            .with_context(|| format!("OKX did not return order {}", order_id))?
+            .into_report(self.name(), pair)
+    }
+
+    async fn get_open_orders(&self, pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        let inst_id = to_okx_inst_id(pair);
+        let orders: Vec<OkxOrder> = self
+            .private_request(
+                Method::GET,
+                &format!(
+                    "/api/v5/trade/orders-pending?instType=SPOT&instId={}",
+                    inst_id
+                ),
+                None,
+                &inst_id,
+            )
+            .await?;
+        orders
+            .into_iter()
+            .map(|order| order.into_report(self.name(), pair))
+            .collect()
+    }
+
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        let inst_id = to_okx_inst_id(pair);
+        let orders: Vec<OkxOrder> = match self
+            .private_request(
+                Method::GET,
+                &format!(
+                    "/api/v5/trade/order?instId={}&clOrdId={}",
+                    inst_id,
+                    okx_client_id(client_order_id)
+                ),
+                None,
+                &inst_id,
+            )
+            .await
+        {
+            // Order does not exist
+            Err(ExchangeError::Api {
+                code: Some(51603), ..
+            }) => return Ok(None),
+            result => result?,
+        };
+        orders
+            .into_iter()
+            .next()
+            .map(|order| {
+                let mut report = order.into_report(self.name(), pair)?;
+                report.client_order_id = client_order_id.to_string();
+                Ok(report)
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(server: &mockito::Server) -> OkxExchange {
+        OkxExchange::with_config(ConnectorConfig::new(server.url())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_liquidity_parses_books() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/api/v5/market/books?instId=BTC-USDT&sz=10")
+            .with_body(include_str!("../../tests/fixtures/okx/books_btc_usdt.json"))
+            .create_async()
+            .await;
+
+        let book = exchange(&server)
+            .get_liquidity(&TradingPair::new("BTC", "USDT"))
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(
+            book.best_bid(),
+            Some(&PriceLevel::new(dec!(67012.3), dec!(1.20416)))
+        );
+        assert_eq!(book.best_ask().unwrap().price, dec!(67012.4));
+        assert_eq!(book.asks.len(), 3);
+        assert_eq!(book.exchange_time, None);
+    }
+
+    #[tokio::test]
+    async fn test_error_codes_are_classified() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v5/market/books?instId=FOO-USDT&sz=10")
+            .with_body(include_str!(
+                "../../tests/fixtures/okx/unknown_instrument.json"
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/v5/market/books?instId=BTC-USDT&sz=10")
+            .with_status(429)
+            .with_body(r#"{"code":"50011","msg":"Too Many Requests","data":[]}"#)
+            .create_async()
+            .await;
+        let okx = exchange(&server);
+
+        let error = okx
+            .get_liquidity(&TradingPair::new("FOO", "USDT"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ExchangeError::UnknownSymbol { symbol, .. } if symbol == "FOO-USDT"
+        ));
+
+        let error = okx
+            .get_liquidity(&TradingPair::new("BTC", "USDT"))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ExchangeError::RateLimited { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_instruments_give_rules_and_listings() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v5/public/instruments")
+            .match_query(mockito::Matcher::Any)
+            .with_body(include_str!("../../tests/fixtures/okx/instruments.json"))
+            .create_async()
+            .await;
+        let okx = exchange(&server);
+
+        let listings = okx.list_pairs().await.unwrap();
+        assert_eq!(
+            listings,
+            vec![
+                PairListing {
+                    pair: TradingPair::new("BTC", "USDT"),
+                    symbol: "BTC-USDT".to_string(),
+                    status: TradingStatus::Trading,
+                },
+                PairListing {
+                    pair: TradingPair::new("LUNA", "USDT"),
+                    symbol: "LUNA-USDT".to_string(),
+                    status: TradingStatus::Halted,
+                },
+            ]
+        );
+
+        let rules = okx
+            .instrument_rules(&TradingPair::new("BTC", "USDT"))
+            .await
+            .unwrap();
+        assert_eq!(
+            rules,
+            InstrumentRules::new(dec!(0.1), dec!(0.00000001)).with_min_quantity(dec!(0.00001))
+        );
+    }
+
+    fn trading_exchange(server: &mockito::Server) -> OkxExchange {
+        OkxExchange::with_config(
+            ConnectorConfig::new(server.url()).with_credentials(
+                Credentials::new("okx-key", "okx-secret").with_passphrase("phrase"),
+            ),
+        )
+        .unwrap()
+    }
+
+    fn limit_buy() -> OrderRequest {
+        OrderRequest {
+            client_order_id: "sor-1-0".to_string(),
+            pair: TradingPair::new("BTC", "USDT"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Limit,
+            quantity: dec!(0.5),
+            limit_price: Some(dec!(67000)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_place_order_is_signed_and_reports_the_order() {
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/api/v5/trade/order")
+            .match_header("OK-ACCESS-KEY", "okx-key")
+            .match_header("OK-ACCESS-PASSPHRASE", "phrase")
+            .match_header(
+                "OK-ACCESS-SIGN",
+                mockito::Matcher::Regex("^[A-Za-z0-9+/]{43}=$".into()),
+            )
+            .match_header(
+                "OK-ACCESS-TIMESTAMP",
+                mockito::Matcher::Regex(r"^\d{4}-\d\d-\d\dT\d\d:\d\d:\d\d\.\d{3}Z$".into()),
+            )
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "instId": "BTC-USDT",
+                "tdMode": "cash",
+                "clOrdId": okx_client_id("sor-1-0"),
+                "side": "buy",
+                "ordType": "limit",
+                "sz": "0.5",
+                "px": "67000",
+            })))
+            .with_body(
+                r#"{"code":"0","msg":"","data":[{"ordId":"1966471237851410432","clOrdId":"","sCode":"0","sMsg":""}]}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock(
+                "GET",
+                "/api/v5/trade/order?instId=BTC-USDT&ordId=1966471237851410432",
+            )
+            .with_body(include_str!(
+                "../../tests/fixtures/okx/order_partially_filled.json"
+            ))
+            .create_async()
+            .await;
+
+        let report = trading_exchange(&server)
+            .place_order(&limit_buy())
+            .await
+            .unwrap();
+
+        create.assert_async().await;
+        assert_eq!(report.order_id, "1966471237851410432");
+        assert_eq!(report.client_order_id, "sor-1-0");
+        assert_eq!(report.status, OrderStatus::PartiallyFilled);
+        assert_eq!(report.quantity, dec!(0.5));
+        assert_eq!(report.filled_quantity, dec!(0.2));
+        assert_eq!(report.average_price, dec!(66990));
+        // 0.0002 BTC at the fill price
+        assert_eq!(report.fees, dec!(13.398));
+    }
+
+    #[tokio::test]
+    async fn test_refused_order_is_an_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/v5/trade/order")
+            .with_body(
+                r#"{"code":"1","msg":"All operations failed","data":[{"ordId":"","clOrdId":"",
+                    "sCode":"51008","sMsg":"Order failed. Insufficient USDT balance in account."}]}"#,
+            )
+            .create_async()
+            .await;
+
+        let error = trading_exchange(&server)
+            .place_order(&limit_buy())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ExchangeError>(),
+            Some(ExchangeError::Api { code: Some(51008), message, .. })
+                if message.contains("Insufficient")
+        ));
+        // Without keys nothing is sent
+        assert!(exchange(&server).place_order(&limit_buy()).await.is_err());
+    }
+
+    #[test]
+    fn test_client_ids_okx_rejects_are_digested() {
+        assert_eq!(okx_client_id("sor1a2b"), "sor1a2b");
+        let digest = okx_client_id("sor-1-0");
+        assert_eq!(digest.len(), 32);
+        assert_eq!(digest, okx_client_id("sor-1-0"));
+    }
+}
@@ -0,0 +1,177 @@
+use super::error::ExchangeError;
+use crate::types::{PairListing, TradingPair, TradingStatus};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+struct Snapshot {
+    fetched_at: Instant,
+    listings: HashMap<TradingPair, PairListing>,
+}
+
+/// Cached copy of the pairs a venue lists.
+///
+/// The list is fetched on first use and again once it is older than the
+/// refresh interval. A refresh that fails keeps serving the previous list,
+/// so a flaky listing endpoint cannot take a venue out of routing on its
+/// own; the error only surfaces while nothing has been fetched yet.
+pub struct PairDirectory {
+    refresh_interval: Duration,
+    /// Held across the fetch so concurrent callers share one request
+    snapshot: Mutex<Option<Snapshot>>,
+}
+
+impl PairDirectory {
+    pub fn new(refresh_interval: Duration) -> Self {
+        Self {
+            refresh_interval,
+            snapshot: Mutex::new(None),
+        }
+    }
+
+    /// Every listed pair, sorted by venue symbol
+    pub async fn listings<F, Fut>(
+        &self,
+        exchange: &str,
+        fetch: F,
+    ) -> Result<Vec<PairListing>, ExchangeError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<PairListing>, ExchangeError>>,
+    {
+        self.with_fresh(exchange, fetch, |listings| {
+            let mut listings: Vec<_> = listings.values().cloned().collect();
+            listings.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+            listings
+        })
+        .await
+    }
+
+    /// Status of `pair`, or `None` if the venue does not list it
+    pub async fn status<F, Fut>(
+        &self,
+        exchange: &str,
+        pair: &TradingPair,
+        fetch: F,
+    ) -> Result<Option<TradingStatus>, ExchangeError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<PairListing>, ExchangeError>>,
+    {
+        let pair = TradingPair::new(pair.base.to_uppercase(), pair.quote.to_uppercase());
+        self.with_fresh(exchange, fetch, |listings| {
+            listings.get(&pair).map(|listing| listing.status)
+        })
+        .await
+    }
+
+    /// Drop the cached list so the next lookup fetches it again
+    pub async fn invalidate(&self) {
+        *self.snapshot.lock().await = None;
+    }
+
+    async fn with_fresh<F, Fut, R>(
+        &self,
+        exchange: &str,
+        fetch: F,
+        read: impl FnOnce(&HashMap<TradingPair, PairListing>) -> R,
+    ) -> Result<R, ExchangeError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<PairListing>, ExchangeError>>,
+    {
+        let mut snapshot = self.snapshot.lock().await;
+        let stale = snapshot
+            .as_ref()
+            .is_none_or(|s| s.fetched_at.elapsed() >= self.refresh_interval);
+        if stale {
+            match fetch().await {
+                Ok(listings) => {
+                    *snapshot = Some(Snapshot {
+                        fetched_at: Instant::now(),
+                        listings: listings
+                            .into_iter()
+                            .map(|listing| (listing.pair.clone(), listing))
+                            .collect(),
+                    })
+                }
+                Err(e) if snapshot.is_some() => {
+                    log::warn!("Keeping stale {} pair list: {}", exchange, e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(read(&snapshot.as_ref().expect("fetched above").listings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(base: &str, quote: &str, status: TradingStatus) -> PairListing {
+        PairListing {
+            pair: TradingPair::new(base, quote),
+            symbol: format!("{}{}", base, quote),
+            status,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refreshes_when_stale_and_keeps_old_list_on_failure() {
+        let directory = PairDirectory::new(Duration::ZERO);
+        let btc = TradingPair::new("btc", "usd");
+
+        let status = directory
+            .status("Venue", &btc, || async {
+                Ok(vec![listing("BTC", "USD", TradingStatus::Trading)])
+            })
+            .await
+            .unwrap();
+        assert_eq!(status, Some(TradingStatus::Trading));
+
+        // Every lookup is stale with a zero interval, so this refetches
+        let status = directory
+            .status("Venue", &btc, || async {
+                Ok(vec![listing("BTC", "USD", TradingStatus::Halted)])
+            })
+            .await
+            .unwrap();
+        assert_eq!(status, Some(TradingStatus::Halted));
+
+        let listings = directory
+            .listings("Venue", || async {
+                Err(ExchangeError::network("Venue", "connection reset"))
+            })
+            .await
+            .unwrap();
+        assert_eq!(listings, vec![listing("BTC", "USD", TradingStatus::Halted)]);
+
+        directory.invalidate().await;
+        assert!(directory
+            .listings("Venue", || async {
+                Err(ExchangeError::network("Venue", "connection reset"))
+            })
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fresh_list_is_not_refetched() {
+        let directory = PairDirectory::new(Duration::from_secs(3600));
+        directory
+            .listings("Venue", || async { Ok(vec![]) })
+            .await
+            .unwrap();
+
+        let status = directory
+            .status("Venue", &TradingPair::new("BTC", "USD"), || async {
+                panic!("fresh list must not be refetched")
+            })
+            .await
+            .unwrap();
+        assert_eq!(status, None);
+    }
+}
@@ -0,0 +1,243 @@
+use super::fees::FeeRates;
+use crate::types::{
+    Fill, OrderBook, OrderReport, OrderRequest, OrderSide, OrderStatus, OrderType, TradingPair,
+};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// In-memory order matching against order book snapshots.
+///
+/// Used by the mock exchange behind demo mode and tests.
+/// Incoming orders take liquidity from the supplied book at the taker
+/// rate; any limit remainder rests as an open order until cancelled.
+pub struct PaperBackend {
+    exchange: String,
+    orders: Mutex<HashMap<String, OrderReport>>,
+    next_id: Mutex<u64>,
+}
+
+impl PaperBackend {
+    pub fn new(exchange: impl Into<String>) -> Self {
+        Self {
+            exchange: exchange.into(),
+            orders: Mutex::new(HashMap::new()),
+            next_id: Mutex::new(1),
+        }
+    }
+
+    /// Match an order against `book` and record the result
+    pub fn place_order(
+        &self,
+        request: &OrderRequest,
+        book: &OrderBook,
+        fees: FeeRates,
+    ) -> Result<OrderReport> {
+        let limit_price = match request.order_type {
+            OrderType::Market => None,
+            OrderType::Limit => Some(
+                request
+                    .limit_price
+                    .context("Limit order has no limit price")?,
+            ),
+        };
+
+        let levels = match request.side {
+            OrderSide::Buy => &book.asks,
+            OrderSide::Sell => &book.bids,
+        };
+
+        let now = Utc::now();
+        let mut fills = Vec::new();
+        let mut remaining = request.quantity;
+        for level in levels {
+            if remaining <= dec!(0) {
+                break;
+            }
+            let crosses = match (request.side, limit_price) {
+                (_, None) => true,
+                (OrderSide::Buy, Some(limit)) => level.price <= limit,
+                (OrderSide::Sell, Some(limit)) => level.price >= limit,
+            };
+            if !crosses {
+                break;
+            }
+
+            let quantity = remaining.min(level.quantity);
+            // The per-order charge is paid once, with the first fill
+            let fixed = if fills.is_empty() {
+                fees.per_order
+            } else {
+                dec!(0)
+            };
+            fills.push(Fill {
+                price: level.price,
+                quantity,
+                fee: quantity * level.price * fees.taker + fixed,
+                timestamp: now,
+            });
+            remaining -= quantity;
+        }
+
+        let filled_quantity = request.quantity - remaining;
+        let status = if remaining <= dec!(0) {
+            OrderStatus::Filled
+        } else if limit_price.is_none() {
+            // Market orders never rest; whatever the book could not absorb expires
+            OrderStatus::Expired
+        } else if filled_quantity > dec!(0) {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::New
+        };
+
+        let report = OrderReport {
+            order_id: self.next_order_id(),
+            client_order_id: request.client_order_id.clone(),
+            exchange: self.exchange.clone(),
+            pair: request.pair.clone(),
+            side: request.side,
+            status,
+            quantity: request.quantity,
+            filled_quantity,
+            average_price: average_price(&fills),
+            fees: fills.iter().map(|f| f.fee).sum(),
+            fills,
+            updated_at: now,
+        };
+
+        self.orders
+            .lock()
+            .unwrap()
+            .insert(report.order_id.clone(), report.clone());
+        Ok(report)
+    }
+
+    /// Cancel an open order
+    pub fn cancel_order(&self, order_id: &str) -> Result<OrderReport> {
+        let mut orders = self.orders.lock().unwrap();
+        let report = orders
+            .get_mut(order_id)
+            .with_context(|| format!("Unknown order {}", order_id))?;
+        if !report.status.is_open() {
+            anyhow::bail!("Order {} is already {:?}", order_id, report.status);
+        }
+        report.status = OrderStatus::Cancelled;
+        report.updated_at = Utc::now();
+        Ok(report.clone())
+    }
+
+    /// Look up an order by id
+    pub fn get_order_status(&self, order_id: &str) -> Result<OrderReport> {
+        self.orders
+            .lock()
+            .unwrap()
+            .get(order_id)
+            .cloned()
+            .with_context(|| format!("Unknown order {}", order_id))
+    }
+
+    /// Look up an order by the client order ID it was sent with
+    pub fn find_order(&self, pair: &TradingPair, client_order_id: &str) -> Option<OrderReport> {
+        self.orders
+            .lock()
+            .unwrap()
+            .values()
+            .find(|report| &report.pair == pair && report.client_order_id == client_order_id)
+            .cloned()
+    }
+
+    /// All orders still working for a pair
+    pub fn get_open_orders(&self, pair: &TradingPair) -> Vec<OrderReport> {
+        self.orders
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|report| report.status.is_open() && &report.pair == pair)
+            .cloned()
+            .collect()
+    }
+
+    fn next_order_id(&self) -> String {
+        let mut next_id = self.next_id.lock().unwrap();
+        let id = format!("{}-{}", self.exchange.to_uppercase(), *next_id);
+        *next_id += 1;
+        id
+    }
+}
+
+fn average_price(fills: &[Fill]) -> Decimal {
+    let quantity: Decimal = fills.iter().map(|f| f.quantity).sum();
+    if quantity > dec!(0) {
+        fills.iter().map(|f| f.price * f.quantity).sum::<Decimal>() / quantity
+    } else {
+        dec!(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PriceLevel;
+
+    fn book() -> OrderBook {
+        OrderBook::new(
+            "Paper",
+            TradingPair::new("BTC", "USD"),
+            vec![PriceLevel::new(dec!(99), dec!(1))],
+            vec![
+                PriceLevel::new(dec!(100), dec!(1)),
+                PriceLevel::new(dec!(102), dec!(1)),
+            ],
+        )
+    }
+
+    fn request(order_type: OrderType, limit_price: Option<Decimal>) -> OrderRequest {
+        OrderRequest {
+            client_order_id: "child-1".to_string(),
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type,
+            quantity: dec!(1.5),
+            limit_price,
+        }
+    }
+
+    #[test]
+    fn test_market_order_fills_across_levels() {
+        let backend = PaperBackend::new("Paper");
+        let fees = FeeRates::new(dec!(0), dec!(0.001));
+
+        let report = backend
+            .place_order(&request(OrderType::Market, None), &book(), fees)
+            .unwrap();
+
+        assert_eq!(report.status, OrderStatus::Filled);
+        assert_eq!(report.fills.len(), 2);
+        assert_eq!(report.average_price.round_dp(2), dec!(100.67));
+        assert_eq!(report.fees, dec!(0.151));
+        assert_eq!(report.execution_result().executed_quantity, dec!(1.5));
+    }
+
+    #[test]
+    fn test_limit_remainder_rests_until_cancelled() {
+        let backend = PaperBackend::new("Paper");
+        let fees = FeeRates::new(dec!(0), dec!(0));
+        let pair = TradingPair::new("BTC", "USD");
+
+        let report = backend
+            .place_order(&request(OrderType::Limit, Some(dec!(101))), &book(), fees)
+            .unwrap();
+        assert_eq!(report.status, OrderStatus::PartiallyFilled);
+        assert_eq!(report.remaining_quantity(), dec!(0.5));
+        assert_eq!(backend.get_open_orders(&pair).len(), 1);
+
+        let cancelled = backend.cancel_order(&report.order_id).unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert!(backend.get_open_orders(&pair).is_empty());
+        assert!(backend.cancel_order(&report.order_id).is_err());
+    }
+}
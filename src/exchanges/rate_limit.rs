@@ -0,0 +1,203 @@
+use super::error::ExchangeError;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Back-off applied when a venue throttles us without saying for how long
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Request allowance a venue grants per rolling window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Total request weight that may be spent per window
+    pub weight: u32,
+    pub window: Duration,
+}
+
+impl RateLimit {
+    pub fn new(weight: u32, window: Duration) -> Self {
+        Self { weight, window }
+    }
+
+    fn per_second(&self) -> f64 {
+        self.weight as f64 / self.window.as_secs_f64()
+    }
+}
+
+struct Bucket {
+    /// Negative while waiters have reserved capacity not yet refilled
+    tokens: f64,
+    refreshed: Instant,
+    /// Set once the venue has throttled us
+    blocked_until: Option<Instant>,
+}
+
+/// Token bucket shared by every request a connector makes.
+///
+/// Requests spend their endpoint weight and queue for capacity, but never
+/// longer than `max_wait`: past that they fail as
+/// [`ExchangeError::RateLimited`] so callers can fall back to cached data
+/// instead of stalling. Usage reported by the venue and 429/418 answers
+/// tighten the bucket further.
+pub struct RateLimiter {
+    exchange: String,
+    limit: RateLimit,
+    max_wait: Duration,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(exchange: impl Into<String>, limit: RateLimit, max_wait: Duration) -> Self {
+        Self {
+            exchange: exchange.into(),
+            limit,
+            max_wait,
+            bucket: Mutex::new(Bucket {
+                tokens: limit.weight as f64,
+                refreshed: Instant::now(),
+                blocked_until: None,
+            }),
+        }
+    }
+
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Spend `weight`, waiting for capacity if that takes at most
+    /// `max_wait`
+    pub async fn acquire(&self, weight: u32) -> Result<(), ExchangeError> {
+        let weight = weight.min(self.limit.weight) as f64;
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            self.refill(&mut bucket, now);
+
+            let blocked = bucket
+                .blocked_until
+                .map(|until| until.saturating_duration_since(now))
+                .unwrap_or_default();
+            let deficit = (weight - bucket.tokens).max(0.0);
+            let wait = blocked.max(Duration::from_secs_f64(deficit / self.limit.per_second()));
+            if wait > self.max_wait {
+                return Err(ExchangeError::RateLimited {
+                    exchange: self.exchange.clone(),
+                    retry_after: Some(wait),
+                });
+            }
+            bucket.tokens -= weight;
+            wait
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    /// Weight still available right now
+    pub fn available(&self) -> u32 {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        self.refill(&mut bucket, now);
+        if bucket.blocked_until.is_some_and(|until| until > now) {
+            return 0;
+        }
+        bucket.tokens.max(0.0) as u32
+    }
+
+    /// Align with the weight the venue says we have already used this window
+    pub fn observe_usage(&self, used: u32) {
+        let mut bucket = self.bucket.lock().unwrap();
+        self.refill(&mut bucket, Instant::now());
+        let remaining = self.limit.weight.saturating_sub(used) as f64;
+        bucket.tokens = bucket.tokens.min(remaining);
+    }
+
+    /// Stop sending until `retry_after` (or a default back-off) has passed
+    pub fn back_off(&self, retry_after: Option<Duration>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        let until = Instant::now() + retry_after.unwrap_or(DEFAULT_BACKOFF);
+        bucket.blocked_until = Some(bucket.blocked_until.map_or(until, |u| u.max(until)));
+        bucket.tokens = bucket.tokens.min(0.0);
+        log::warn!(
+            "{} throttled us; pausing requests for {:?}",
+            self.exchange,
+            until - Instant::now()
+        );
+    }
+
+    /// Back off if a venue response was a rate-limit rejection
+    pub fn observe(&self, error: &ExchangeError) {
+        if let ExchangeError::RateLimited { retry_after, .. } = error {
+            self.back_off(*retry_after);
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.refreshed);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.limit.per_second())
+            .min(self.limit.weight as f64);
+        bucket.refreshed = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_requests_queue_briefly_then_fail_fast() {
+        // 10 weight per second: one unit every 100ms
+        let limiter = RateLimiter::new(
+            "Venue",
+            RateLimit::new(10, Duration::from_secs(1)),
+            Duration::from_millis(250),
+        );
+
+        limiter.acquire(8).await.unwrap();
+        assert_eq!(limiter.available(), 2);
+
+        // Needs one unit more than is left: waits about 100ms
+        let started = std::time::Instant::now();
+        limiter.acquire(3).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(80));
+
+        // Needs five more units, which would take 500ms
+        let error = limiter.acquire(5).await.unwrap_err();
+        assert!(matches!(
+            error,
+            ExchangeError::RateLimited {
+                retry_after: Some(wait),
+                ..
+            } if wait > Duration::from_millis(250)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_usage_and_throttling_tighten_the_bucket() {
+        let limiter = RateLimiter::new(
+            "Venue",
+            RateLimit::new(6000, Duration::from_secs(60)),
+            Duration::ZERO,
+        );
+
+        limiter.observe_usage(5990);
+        assert!(limiter.available() <= 10);
+        assert!(limiter.acquire(20).await.is_err());
+
+        limiter.observe(&ExchangeError::RateLimited {
+            exchange: "Venue".to_string(),
+            retry_after: Some(Duration::from_secs(30)),
+        });
+        assert_eq!(limiter.available(), 0);
+        let error = limiter.acquire(1).await.unwrap_err();
+        assert!(matches!(
+            error,
+            ExchangeError::RateLimited {
+                retry_after: Some(wait),
+                ..
+            } if wait > Duration::from_secs(29)
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+use super::error::ExchangeError;
+use crate::types::{
+    Fill, OrderReport, OrderSide, OrderStatus, QuoteRequest, RfqQuote, TradingPair,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A market maker or OTC desk that prices blocks on request instead of
+/// showing a public book
+#[async_trait]
+pub trait RfqVenue: Send + Sync {
+    /// Get the venue name
+    fn name(&self) -> &str;
+
+    /// Ask for a firm quote, or `None` if the venue declines to quote
+    async fn request_quote(
+        &self,
+        request: &QuoteRequest,
+    ) -> Result<Option<RfqQuote>, ExchangeError>;
+
+    /// Trade the whole quote at its price. Fails once the quote has expired.
+    async fn accept_quote(&self, quote: &RfqQuote) -> Result<OrderReport>;
+}
+
+/// RFQ venue quoting fixed prices, for demos and tests.
+///
+/// Every request for a priced pair and side is quoted at that price for
+/// the requested size, up to an optional maximum. Accepted quotes fill in
+/// full straight away.
+pub struct MockRfqVenue {
+    name: String,
+    prices: HashMap<(TradingPair, OrderSide), Decimal>,
+    max_quantity: Option<Decimal>,
+    quote_ttl: Duration,
+    /// Delay before every request or acceptance is answered
+    latency: Duration,
+    issued: Mutex<Vec<String>>,
+    accepted: Mutex<Vec<RfqQuote>>,
+    next_id: AtomicU64,
+}
+
+impl MockRfqVenue {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            prices: HashMap::new(),
+            max_quantity: None,
+            quote_ttl: Duration::from_secs(5),
+            latency: Duration::ZERO,
+            issued: Mutex::new(Vec::new()),
+            accepted: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Quote orders on `side` of `pair` at `price`
+    pub fn with_price(mut self, pair: TradingPair, side: OrderSide, price: Decimal) -> Self {
+        self.prices.insert((pair, side), price);
+        self
+    }
+
+    /// Quote at most this size, whatever is asked for
+    pub fn with_max_quantity(mut self, max_quantity: Decimal) -> Self {
+        self.max_quantity = Some(max_quantity);
+        self
+    }
+
+    /// How long each quote stays firm
+    pub fn with_quote_ttl(mut self, quote_ttl: Duration) -> Self {
+        self.quote_ttl = quote_ttl;
+        self
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Quotes accepted so far, in order
+    pub fn accepted_quotes(&self) -> Vec<RfqQuote> {
+        self.accepted.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl RfqVenue for MockRfqVenue {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn request_quote(
+        &self,
+        request: &QuoteRequest,
+    ) -> Result<Option<RfqQuote>, ExchangeError> {
+        tokio::time::sleep(self.latency).await;
+        let Some(&price) = self.prices.get(&(request.pair.clone(), request.side)) else {
+            return Ok(None);
+        };
+        let quantity = match self.max_quantity {
+            Some(max) => request.quantity.min(max),
+            None => request.quantity,
+        };
+        let quote_id = format!(
+            "{}-q{}",
+            self.name,
+            self.next_id.fetch_add(1, Ordering::SeqCst)
+        );
+        self.issued.lock().unwrap().push(quote_id.clone());
+        Ok(Some(RfqQuote {
+            quote_id,
+            request_id: request.request_id.clone(),
+            venue: self.name.clone(),
+            pair: request.pair.clone(),
+            side: request.side,
+            quantity,
+            price,
+            expires_at: Utc::now() + self.quote_ttl,
+        }))
+    }
+
+    async fn accept_quote(&self, quote: &RfqQuote) -> Result<OrderReport> {
+        tokio::time::sleep(self.latency).await;
+        let now = Utc::now();
+        if !self.issued.lock().unwrap().contains(&quote.quote_id) {
+            anyhow::bail!("{} never issued quote {}", self.name, quote.quote_id);
+        }
+        if quote.expires_at <= now {
+            anyhow::bail!("Quote {} has expired", quote.quote_id);
+        }
+        self.accepted.lock().unwrap().push(quote.clone());
+
+        Ok(OrderReport {
+            order_id: quote.quote_id.clone(),
+            client_order_id: quote.request_id.clone(),
+            exchange: self.name.clone(),
+            pair: quote.pair.clone(),
+            side: quote.side,
+            status: OrderStatus::Filled,
+            quantity: quote.quantity,
+            filled_quantity: quote.quantity,
+            average_price: quote.price,
+            fees: Decimal::ZERO,
+            fills: vec![Fill {
+                price: quote.price,
+                quantity: quote.quantity,
+                fee: Decimal::ZERO,
+                timestamp: now,
+            }],
+            updated_at: now,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn test_quotes_are_firm_until_they_expire() {
+        let pair = TradingPair::new("BTC", "USD");
+        let desk = MockRfqVenue::new("Desk")
+            .with_price(pair.clone(), OrderSide::Buy, dec!(50100))
+            .with_max_quantity(dec!(10))
+            .with_quote_ttl(Duration::from_millis(50));
+        let request = |side| QuoteRequest {
+            request_id: "rfq-1".to_string(),
+            pair: pair.clone(),
+            side,
+            quantity: dec!(25),
+        };
+
+        assert_eq!(
+            desk.request_quote(&request(OrderSide::Sell)).await.unwrap(),
+            None
+        );
+        let quote = desk
+            .request_quote(&request(OrderSide::Buy))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(quote.quantity, dec!(10));
+
+        let report = desk.accept_quote(&quote).await.unwrap();
+        assert_eq!(report.status, OrderStatus::Filled);
+        assert_eq!(report.execution_result().executed_price, dec!(50100));
+
+        let late = desk
+            .request_quote(&request(OrderSide::Buy))
+            .await
+            .unwrap()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(desk.accept_quote(&late).await.is_err());
+        assert_eq!(desk.accepted_quotes(), vec![quote]);
+    }
+}
@@ -0,0 +1,1271 @@
+use crate::exchanges::{Exchange, ExchangeError};
+use crate::router::SmartOrderRouter;
+use crate::types::{
+    BalanceSnapshot, ExecutionResult, InstrumentRules, Order, OrderReport, OrderRequest, OrderSide,
+    OrderSplit, OrderStatus, OrderType, PriceLevel, QuoteConversion, RfqQuote, RoutingResult,
+    SyntheticLeg, SyntheticRoute,
+};
+use anyhow::Result;
+use chrono::Utc;
+use futures::future::join_all;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Limits on how hard the executor works to complete a parent order
+#[derive(Debug, Clone, Copy)]
+pub struct ExecutorConfig {
+    /// Maximum number of times unfilled remainder is re-routed
+    pub max_reroutes: usize,
+    /// Delay between status polls of working child orders
+    pub poll_interval: Duration,
+    /// Status polls before a working child order is cancelled; also the
+    /// number of lookups for an order the venue gave no answer about
+    pub max_status_polls: usize,
+}
+
+impl Default for ExecutorConfig {
+    fn default() -> Self {
+        Self {
+            max_reroutes: 2,
+            poll_interval: Duration::from_millis(200),
+            max_status_polls: 5,
+        }
+    }
+}
+
+/// A child order sent to one exchange on behalf of the parent order
+#[derive(Debug, Clone)]
+pub struct ChildOrder {
+    pub request: OrderRequest,
+    pub exchange: String,
+    /// Routing round the child was sent in (0 for the initial plan)
+    pub round: usize,
+    pub status: OrderStatus,
+    /// Latest report from the exchange, if the order was acknowledged
+    pub report: Option<OrderReport>,
+    /// Why the exchange or executor rejected the order
+    pub reject_reason: Option<String>,
+    /// How the child's pair maps onto the parent's, when the venue
+    /// trades the base in another quote currency
+    pub conversion: Option<QuoteConversion>,
+}
+
+impl ChildOrder {
+    pub fn filled_quantity(&self) -> Decimal {
+        self.report
+            .as_ref()
+            .map(|r| r.filled_quantity)
+            .unwrap_or(dec!(0))
+    }
+
+    /// Quantity that may still fill: the rest of an order left open, or
+    /// all of one whose outcome is unknown
+    pub fn working_quantity(&self) -> Decimal {
+        if self.status.is_open() || self.status == OrderStatus::Unknown {
+            (self.request.quantity - self.filled_quantity()).max(dec!(0))
+        } else {
+            dec!(0)
+        }
+    }
+
+    /// Asset the child pays with, and how much of it is spent or still
+    /// committed: quote for buys, base for sells, on the child's own pair
+    pub fn spent(&self) -> (&str, Decimal) {
+        let working = self.working_quantity();
+        match self.request.side {
+            OrderSide::Buy => {
+                let (traded, fees, price) = self
+                    .report
+                    .as_ref()
+                    .map_or((dec!(0), dec!(0), dec!(0)), |r| {
+                        (r.filled_quantity * r.average_price, r.fees, r.average_price)
+                    });
+                let working_price = self.request.limit_price.unwrap_or(price);
+                (
+                    &self.request.pair.quote,
+                    traded + fees + working * working_price,
+                )
+            }
+            OrderSide::Sell => (&self.request.pair.base, self.filled_quantity() + working),
+        }
+    }
+
+    /// Units of the parent's quote per unit of the child's
+    pub fn quote_rate(&self) -> Decimal {
+        self.conversion
+            .as_ref()
+            .map_or(dec!(1), |conversion| conversion.rate)
+    }
+}
+
+/// What a synthetic route delivered, in terms of the parent order
+#[derive(Debug, Clone)]
+pub struct SyntheticFill {
+    /// The intermediate asset
+    pub via: String,
+    /// Child orders of each leg, in the order the legs ran
+    pub legs: Vec<Vec<ChildOrder>>,
+    /// Orders trading intermediate asset a later leg left behind back
+    /// into what the first leg spent
+    pub unwind: Vec<ChildOrder>,
+    /// Base asset bought or sold for the parent, through every leg
+    pub filled_quantity: Decimal,
+    /// Quote asset spent for buys, or received for sells, with the fees of
+    /// every leg and any unwind included
+    pub quote_amount: Decimal,
+    /// Intermediate asset the route still holds: rounding dust, or what a
+    /// later leg failed to trade and the unwind could not either
+    pub leftover: Decimal,
+    /// Part of the parent the leftover stands for when a later leg fell
+    /// short; it is neither filled nor re-routed
+    pub stranded_quantity: Decimal,
+    /// What trading the leftover back lost, in the asset the first leg
+    /// spent: quote for buys, already part of `quote_amount`, or base for
+    /// sells, sold without proceeds and not counted as filled
+    pub unwind_loss: Decimal,
+}
+
+impl SyntheticFill {
+    /// Price the route achieved per unit of base, all-in
+    pub fn average_price(&self) -> Decimal {
+        if self.filled_quantity > dec!(0) {
+            self.quote_amount / self.filled_quantity
+        } else {
+            dec!(0)
+        }
+    }
+}
+
+/// Consolidated fills for a parent order across all child orders
+#[derive(Debug, Clone)]
+pub struct ParentFillReport {
+    pub parent: Order,
+    pub children: Vec<ChildOrder>,
+    /// Fills through intermediate assets
+    pub synthetic: Vec<SyntheticFill>,
+    pub filled_quantity: Decimal,
+    pub unfilled_quantity: Decimal,
+    /// Part of the unfilled quantity in child orders that could not be
+    /// cancelled or whose outcome is unknown; it may still fill
+    pub working_quantity: Decimal,
+    /// Part of the unfilled quantity held as an intermediate asset by
+    /// synthetic routes that could not finish or unwind
+    pub stranded_quantity: Decimal,
+    pub average_price: Decimal,
+    /// Fees of the direct child orders, in the parent's quote; synthetic
+    /// fills carry theirs in their quote amounts
+    pub total_fees: Decimal,
+}
+
+impl ParentFillReport {
+    fn new(parent: Order, children: Vec<ChildOrder>, synthetic: Vec<SyntheticFill>) -> Self {
+        // Reports paired with the rate that brings them into the parent's quote
+        let filled: Vec<(&OrderReport, Decimal)> = children
+            .iter()
+            .filter_map(|c| Some((c.report.as_ref()?, c.quote_rate())))
+            .filter(|(r, _)| r.filled_quantity > dec!(0))
+            .collect();
+        let filled_quantity: Decimal = filled
+            .iter()
+            .map(|(r, _)| r.filled_quantity)
+            .sum::<Decimal>()
+            + synthetic.iter().map(|s| s.filled_quantity).sum::<Decimal>();
+        let notional: Decimal = filled
+            .iter()
+            .map(|(r, rate)| r.filled_quantity * r.average_price * rate)
+            .chain(synthetic.iter().map(|s| s.quote_amount))
+            .sum();
+
+        Self {
+            unfilled_quantity: parent.quantity - filled_quantity,
+            working_quantity: children.iter().map(ChildOrder::working_quantity).sum(),
+            stranded_quantity: synthetic.iter().map(|s| s.stranded_quantity).sum(),
+            average_price: if filled_quantity > dec!(0) {
+                notional / filled_quantity
+            } else {
+                dec!(0)
+            },
+            total_fees: filled.iter().map(|(r, rate)| r.fees * rate).sum(),
+            filled_quantity,
+            parent,
+            children,
+            synthetic,
+        }
+    }
+
+    /// Overall state of the parent order
+    pub fn status(&self) -> OrderStatus {
+        if self.unfilled_quantity <= dec!(0) {
+            OrderStatus::Filled
+        } else if self.filled_quantity > dec!(0) {
+            OrderStatus::PartiallyFilled
+        } else if self
+            .children
+            .iter()
+            .any(|c| c.status == OrderStatus::Unknown)
+        {
+            OrderStatus::Unknown
+        } else if self.working_quantity > dec!(0) {
+            OrderStatus::New
+        } else {
+            OrderStatus::Rejected
+        }
+    }
+
+    /// One execution result per child order that traded
+    pub fn execution_results(&self) -> Vec<ExecutionResult> {
+        self.children
+            .iter()
+            .filter_map(|c| c.report.as_ref())
+            .filter(|r| r.filled_quantity > dec!(0))
+            .map(OrderReport::execution_result)
+            .collect()
+    }
+}
+
+/// Turns routing plans into live child orders
+pub struct Executor<'a> {
+    router: &'a SmartOrderRouter,
+    config: ExecutorConfig,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(router: &'a SmartOrderRouter) -> Self {
+        Self::with_config(router, ExecutorConfig::default())
+    }
+
+    pub fn with_config(router: &'a SmartOrderRouter, config: ExecutorConfig) -> Self {
+        Self { router, config }
+    }
+
+    /// Execute a routing plan.
+    ///
+    /// Splits are dispatched, RFQ quotes accepted and synthetic routes
+    /// started concurrently. Child orders left working are polled and then
+    /// cancelled; any quantity still unfilled is re-routed on fresh
+    /// liquidity, leaving out venues that rejected orders, until the parent
+    /// is filled or `max_reroutes` is exhausted. Quantity in orders that
+    /// could not be cancelled, or that the venue never confirmed either
+    /// way, is not re-routed.
+    pub async fn execute(&self, routing: &RoutingResult) -> Result<ParentFillReport> {
+        self.run(routing, None).await
+    }
+
+    /// Execute a plan routed with
+    /// [`SmartOrderRouter::route_order_with_balances`], keeping re-routes
+    /// within `balances` less what earlier child orders spent
+    pub async fn execute_with_balances(
+        &self,
+        routing: &RoutingResult,
+        balances: &BalanceSnapshot,
+    ) -> Result<ParentFillReport> {
+        self.run(routing, Some(balances.clone())).await
+    }
+
+    async fn run(
+        &self,
+        routing: &RoutingResult,
+        mut balances: Option<BalanceSnapshot>,
+    ) -> Result<ParentFillReport> {
+        let parent = routing.original_order.clone();
+        let tag = parent_tag();
+        let mut children = Vec::new();
+        let mut synthetic = Vec::new();
+        let mut splits = routing.splits.clone();
+        let mut quotes = routing.quotes.clone();
+        let mut routes = routing.synthetic_routes.clone();
+        let mut excluded: Vec<String> = Vec::new();
+
+        for round in 0..=self.config.max_reroutes {
+            let synthetic_runs = routes
+                .iter()
+                .map(|route| self.run_synthetic(route, &tag, round));
+            let (round_children, round_synthetic) = tokio::join!(
+                self.run_round(&parent, &splits, &quotes, &tag, round, children.len()),
+                join_all(synthetic_runs)
+            );
+            let leg_children = round_synthetic
+                .iter()
+                .flat_map(|fill| fill.legs.iter().flatten().chain(&fill.unwind));
+            for child in round_children.iter().chain(leg_children) {
+                let failed = matches!(child.status, OrderStatus::Rejected | OrderStatus::Unknown);
+                if failed && !excluded.contains(&child.exchange) {
+                    excluded.push(child.exchange.clone());
+                }
+                if let Some(balances) = &mut balances {
+                    let (asset, amount) = child.spent();
+                    balances.spend(&child.exchange, asset, amount);
+                }
+            }
+            children.extend(round_children);
+            synthetic.extend(round_synthetic);
+
+            let filled: Decimal = children
+                .iter()
+                .map(ChildOrder::filled_quantity)
+                .sum::<Decimal>()
+                + synthetic.iter().map(|s| s.filled_quantity).sum::<Decimal>();
+            let working: Decimal = children.iter().map(ChildOrder::working_quantity).sum();
+            let stranded: Decimal = synthetic.iter().map(|s| s.stranded_quantity).sum();
+            let remaining = parent.quantity - filled - working - stranded;
+            if remaining <= dec!(0) || round == self.config.max_reroutes {
+                break;
+            }
+
+            log::info!("Re-routing {} unfilled after round {}", remaining, round);
+            let remainder = Order {
+                quantity: remaining,
+                ..parent.clone()
+            };
+            match self
+                .router
+                .route_order_excluding(&remainder, &excluded, balances.as_ref())
+                .await
+            {
+                Ok(rerouted)
+                    if !rerouted.splits.is_empty()
+                        || !rerouted.quotes.is_empty()
+                        || !rerouted.synthetic_routes.is_empty() =>
+                {
+                    splits = rerouted.splits;
+                    quotes = rerouted.quotes;
+                    routes = rerouted.synthetic_routes;
+                }
+                Ok(_) => break,
+                Err(e) => {
+                    log::warn!("Could not re-route remainder: {}", e);
+                    break;
+                }
+            }
+        }
+
+        Ok(ParentFillReport::new(parent, children, synthetic))
+    }
+
+    /// Run the legs of a synthetic route one after another, each as market
+    /// orders on the venues routing chose.
+    ///
+    /// A leg that fills short scales the legs after it down to match, since
+    /// they can only trade what it produced. When a later leg falls short,
+    /// the intermediate asset it left is traded back on the first leg's
+    /// venues; whatever the unwind cannot trade stays as leftover, and the
+    /// part of the parent it stands for as stranded.
+    async fn run_synthetic(
+        &self,
+        route: &SyntheticRoute,
+        tag: &str,
+        round: usize,
+    ) -> SyntheticFill {
+        let client_order_id = |leg: &str, i: usize| {
+            format!(
+                "sor-{}-{}-{}-{}-{}",
+                tag,
+                round,
+                route.via.to_lowercase(),
+                leg,
+                i + 1
+            )
+        };
+        let mut legs: Vec<Vec<ChildOrder>> = Vec::new();
+        let mut scale = dec!(1);
+        let mut finished = true;
+        for (leg_index, leg) in route.legs.iter().enumerate() {
+            if scale <= dec!(0) {
+                break;
+            }
+            let leg_order = Order {
+                pair: leg.pair.clone(),
+                side: leg.side,
+                order_type: OrderType::Market,
+                quantity: leg.quantity * scale,
+                limit_price: None,
+            };
+            let dispatches = leg.splits.iter().enumerate().map(|(i, split)| {
+                let split = OrderSplit {
+                    quantity: split.quantity * scale,
+                    ..split.clone()
+                };
+                let client_order_id = client_order_id(&(leg_index + 1).to_string(), i);
+                self.dispatch(&leg_order, split, client_order_id, round)
+            });
+            let children = join_all(dispatches).await;
+
+            let filled: Decimal = children.iter().map(ChildOrder::filled_quantity).sum();
+            if leg_index > 0 && filled < leg_order.quantity {
+                finished = false;
+            }
+            scale = if leg.quantity > dec!(0) {
+                (filled / leg.quantity).min(dec!(1))
+            } else {
+                dec!(0)
+            };
+            legs.push(children);
+        }
+
+        let first_side = route.legs.first().map(|leg| leg.side);
+        let (first_quantity, first_notional, first_fees) = traded(legs.first());
+        let (last_quantity, last_notional, last_fees) = if legs.len() == route.legs.len() {
+            traded(legs.last())
+        } else {
+            (dec!(0), dec!(0), dec!(0))
+        };
+        // Intermediate asset the first leg produced, and what the last used
+        let (produced, used) = match first_side {
+            Some(OrderSide::Buy) => (first_quantity, last_notional + last_fees),
+            Some(OrderSide::Sell) => (first_notional - first_fees, last_quantity),
+            None => (dec!(0), dec!(0)),
+        };
+        let mut leftover = (produced - used).max(dec!(0));
+
+        let unwind = match (finished, first_side, route.legs.first(), legs.first()) {
+            (false, Some(side), Some(leg), Some(children)) if leftover > dec!(0) => {
+                self.unwind(leg, side, children, leftover, &client_order_id, round)
+                    .await
+            }
+            _ => Vec::new(),
+        };
+        let (unwound_quantity, unwound_notional, unwound_fees) = traded(Some(&unwind));
+
+        let (filled_quantity, quote_amount, stranded_quantity, unwind_loss) = match first_side {
+            // Quote spent on the intermediate asset that was used, less what
+            // selling the rest back fetched
+            Some(OrderSide::Buy) => {
+                leftover = (leftover - unwound_quantity).max(dec!(0));
+                let kept = if produced > dec!(0) {
+                    (produced - leftover) / produced
+                } else {
+                    dec!(0)
+                };
+                let unwound_cost = if produced > dec!(0) {
+                    (first_notional + first_fees) * unwound_quantity / produced
+                } else {
+                    dec!(0)
+                };
+                let unwind_loss = unwound_cost - (unwound_notional - unwound_fees);
+                let quote_amount =
+                    (first_notional + first_fees) * kept - (unwound_notional - unwound_fees);
+                // Base the leftover would have bought, at the planned rate
+                let via_per_base = match (route.legs.first(), route.legs.last()) {
+                    (Some(via_leg), Some(base_leg)) if base_leg.quantity > dec!(0) => {
+                        via_leg.quantity / base_leg.quantity
+                    }
+                    _ => dec!(0),
+                };
+                let stranded = if finished || via_per_base <= dec!(0) {
+                    dec!(0)
+                } else {
+                    leftover / via_per_base
+                };
+                (last_quantity, quote_amount, stranded, unwind_loss)
+            }
+            // Base sold on the first leg whose proceeds made it through
+            // the last; of the rest, what was not bought back is the
+            // unwind's loss
+            Some(OrderSide::Sell) => {
+                leftover = (leftover - unwound_notional - unwound_fees).max(dec!(0));
+                if finished || produced <= dec!(0) {
+                    (first_quantity, last_notional - last_fees, dec!(0), dec!(0))
+                } else {
+                    let filled = first_quantity * used / produced;
+                    let stranded = first_quantity * leftover / produced;
+                    let unwind_loss =
+                        (first_quantity - filled - stranded - unwound_quantity).max(dec!(0));
+                    (filled, last_notional - last_fees, stranded, unwind_loss)
+                }
+            }
+            None => (dec!(0), dec!(0), dec!(0), dec!(0)),
+        };
+        if stranded_quantity > dec!(0) {
+            log::error!(
+                "Synthetic route via {} left {} {} stranded",
+                route.via,
+                leftover,
+                route.via
+            );
+        }
+
+        SyntheticFill {
+            via: route.via.clone(),
+            legs,
+            unwind,
+            filled_quantity: filled_quantity.max(dec!(0)),
+            quote_amount,
+            leftover,
+            stranded_quantity,
+            unwind_loss,
+        }
+    }
+
+    /// Trade `leftover` intermediate asset back into what the first leg of
+    /// a route spent, on the venues it filled on in proportion to their
+    /// fills: selling it back for quote after buys, or buying base back
+    /// with it after sells
+    async fn unwind(
+        &self,
+        leg: &SyntheticLeg,
+        side: OrderSide,
+        children: &[ChildOrder],
+        leftover: Decimal,
+        client_order_id: &impl Fn(&str, usize) -> String,
+        round: usize,
+    ) -> Vec<ChildOrder> {
+        let filled: Decimal = children.iter().map(ChildOrder::filled_quantity).sum();
+        let order = Order {
+            pair: leg.pair.clone(),
+            side: match side {
+                OrderSide::Buy => OrderSide::Sell,
+                OrderSide::Sell => OrderSide::Buy,
+            },
+            order_type: OrderType::Market,
+            quantity: dec!(0),
+            limit_price: None,
+        };
+        let dispatches = children
+            .iter()
+            .filter_map(|child| Some((child, child.report.as_ref()?)))
+            .filter(|(_, report)| report.filled_quantity > dec!(0))
+            .enumerate()
+            .map(|(i, (child, report))| {
+                let share = leftover * report.filled_quantity / filled;
+                let notional = report.filled_quantity * report.average_price;
+                let fee_rate = if notional > dec!(0) {
+                    report.fees / notional
+                } else {
+                    dec!(0)
+                };
+                let order = order.clone();
+                async move {
+                    // The intermediate is the leg's base after buys, and
+                    // after sells its quote, which buys as much base as the
+                    // venue's asks cover
+                    let quantity = match side {
+                        OrderSide::Buy => share,
+                        OrderSide::Sell => {
+                            let exchange = self.router.exchange(&child.exchange)?;
+                            match exchange.get_liquidity(&order.pair).await {
+                                Ok(book) => affordable_quantity(&book.asks, share, fee_rate),
+                                Err(e) => {
+                                    log::warn!(
+                                        "No book from {} to unwind into: {}",
+                                        child.exchange,
+                                        e
+                                    );
+                                    return None;
+                                }
+                            }
+                        }
+                    };
+                    if quantity <= dec!(0) {
+                        return None;
+                    }
+                    let split = OrderSplit {
+                        exchange: child.exchange.clone(),
+                        quantity,
+                        expected_price: report.average_price,
+                        expected_fee: quantity * report.average_price * fee_rate,
+                        fee_rate: Some(fee_rate),
+                        conversion: None,
+                    };
+                    let order = Order { quantity, ..order };
+                    let client_order_id = client_order_id("unwind", i);
+                    Some(self.dispatch(&order, split, client_order_id, round).await)
+                }
+            });
+        join_all(dispatches).await.into_iter().flatten().collect()
+    }
+
+    /// Send one round of splits, accept its quotes and drive each child to
+    /// a final state
+    async fn run_round(
+        &self,
+        parent: &Order,
+        splits: &[OrderSplit],
+        quotes: &[RfqQuote],
+        tag: &str,
+        round: usize,
+        sequence_start: usize,
+    ) -> Vec<ChildOrder> {
+        let dispatches = splits.iter().enumerate().map(|(i, split)| {
+            let client_order_id = format!("sor-{}-{}-{}", tag, round, sequence_start + i + 1);
+            self.dispatch(parent, split.clone(), client_order_id, round)
+        });
+        let acceptances = quotes.iter().enumerate().map(|(i, quote)| {
+            let sequence = sequence_start + splits.len() + i + 1;
+            let client_order_id = format!("sor-{}-{}-{}", tag, round, sequence);
+            self.accept(parent, quote, client_order_id, round)
+        });
+        let (mut children, accepted) = tokio::join!(join_all(dispatches), join_all(acceptances));
+        children.extend(accepted);
+        children
+    }
+
+    /// Trade on an RFQ quote. The child is recorded as a limit order at
+    /// the quoted price for the quoted size.
+    async fn accept(
+        &self,
+        parent: &Order,
+        quote: &RfqQuote,
+        client_order_id: String,
+        round: usize,
+    ) -> ChildOrder {
+        let mut child = ChildOrder {
+            request: OrderRequest {
+                client_order_id,
+                pair: parent.pair.clone(),
+                side: parent.side,
+                order_type: OrderType::Limit,
+                quantity: quote.quantity,
+                limit_price: Some(quote.price),
+            },
+            exchange: quote.venue.clone(),
+            round,
+            status: OrderStatus::New,
+            report: None,
+            reject_reason: None,
+            conversion: None,
+        };
+
+        let Some(venue) = self.router.rfq_venue(&quote.venue) else {
+            child.status = OrderStatus::Rejected;
+            child.reject_reason = Some("RFQ venue not connected".to_string());
+            return child;
+        };
+
+        match venue.accept_quote(quote).await {
+            Ok(report) => {
+                child.status = report.status;
+                child.report = Some(report);
+            }
+            Err(e) => {
+                log::warn!("{} refused quote {}: {}", quote.venue, quote.quote_id, e);
+                child.status = OrderStatus::Rejected;
+                child.reject_reason = Some(e.to_string());
+            }
+        }
+        child
+    }
+
+    /// Send `split` of `order` as a child order rounded to the venue's
+    /// rules, and drive it to a final state
+    async fn dispatch(
+        &self,
+        order: &Order,
+        split: OrderSplit,
+        client_order_id: String,
+        round: usize,
+    ) -> ChildOrder {
+        let exchange = self.router.exchange(&split.exchange);
+        let pair = split
+            .conversion
+            .as_ref()
+            .map_or(&order.pair, |conversion| &conversion.pair);
+        let rules = match exchange {
+            Some(exchange) => exchange.instrument_rules(pair).await.unwrap_or_else(|e| {
+                log::warn!("No instrument rules from {}: {}", split.exchange, e);
+                InstrumentRules::default()
+            }),
+            None => InstrumentRules::default(),
+        };
+        let mut child = ChildOrder {
+            request: OrderRequest::from_split(order, &split, &rules, client_order_id),
+            exchange: split.exchange,
+            round,
+            status: OrderStatus::New,
+            report: None,
+            reject_reason: None,
+            conversion: split.conversion,
+        };
+
+        let Some(exchange) = exchange else {
+            child.status = OrderStatus::Rejected;
+            child.reject_reason = Some("exchange not connected".to_string());
+            return child;
+        };
+        if child.request.quantity <= dec!(0) {
+            child.status = OrderStatus::Rejected;
+            child.reject_reason = Some("quantity rounds to nothing on the venue".to_string());
+            return child;
+        }
+
+        let mut report = match exchange.place_order(&child.request).await {
+            Ok(report) => report,
+            Err(e) if rejected_outright(&e) => {
+                log::warn!(
+                    "{} rejected {}: {}",
+                    child.exchange,
+                    child.request.client_order_id,
+                    e
+                );
+                child.status = OrderStatus::Rejected;
+                child.reject_reason = Some(e.to_string());
+                return child;
+            }
+            // The venue may have taken the order, so find out before
+            // treating it as rejected
+            Err(e) => match self.locate(exchange, &child.request).await {
+                Ok(Some(report)) => report,
+                Ok(None) => {
+                    log::warn!(
+                        "{} has no order {}: {}",
+                        child.exchange,
+                        child.request.client_order_id,
+                        e
+                    );
+                    child.status = OrderStatus::Rejected;
+                    child.reject_reason = Some(e.to_string());
+                    return child;
+                }
+                Err(lookup) => {
+                    log::error!(
+                        "Outcome of {} on {} is unknown: {} ({})",
+                        child.request.client_order_id,
+                        child.exchange,
+                        e,
+                        lookup
+                    );
+                    child.status = OrderStatus::Unknown;
+                    return child;
+                }
+            },
+        };
+
+        // Give working orders a chance to fill before pulling them
+        let mut polls = 0;
+        while report.status.is_open() && polls < self.config.max_status_polls {
+            tokio::time::sleep(self.config.poll_interval).await;
+            match exchange
+                .get_order_status(&report.pair, &report.order_id)
+                .await
+            {
+                Ok(latest) => report = latest,
+                Err(e) => log::warn!("Status poll for {} failed: {}", report.order_id, e),
+            }
+            polls += 1;
+        }
+
+        if report.status.is_open() {
+            match exchange.cancel_order(&report.pair, &report.order_id).await {
+                Ok(cancelled) => report = cancelled,
+                Err(e) => {
+                    // The order may have finished meanwhile; if not it is
+                    // still working and reported as such
+                    log::warn!("Cancel of {} failed: {}", report.order_id, e);
+                    if let Ok(latest) = exchange
+                        .get_order_status(&report.pair, &report.order_id)
+                        .await
+                    {
+                        report = latest;
+                    }
+                }
+            }
+        }
+
+        child.status = report.status;
+        child.report = Some(report);
+        child
+    }
+
+    /// Look an order up by client order ID, retrying while the venue
+    /// cannot say
+    async fn locate(
+        &self,
+        exchange: &dyn Exchange,
+        request: &OrderRequest,
+    ) -> Result<Option<OrderReport>> {
+        let mut attempts = 0;
+        loop {
+            tokio::time::sleep(self.config.poll_interval).await;
+            attempts += 1;
+            match exchange
+                .find_order(&request.pair, &request.client_order_id)
+                .await
+            {
+                Err(e) if attempts < self.config.max_status_polls => {
+                    log::warn!("Lookup of {} failed: {}", request.client_order_id, e)
+                }
+                found => return found,
+            }
+        }
+    }
+}
+
+/// Whether a failed order was certainly never taken by the venue
+fn rejected_outright(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<ExchangeError>()
+        .is_some_and(ExchangeError::is_definite)
+}
+
+/// Tag unique to one parent order, so client order IDs never repeat
+/// across parents: the start time and a per-process counter, kept apart
+/// by a separator so one pair can never read as another
+fn parent_tag() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{:x}-{}",
+        Utc::now().timestamp_millis(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Base a market buy can take from `asks` for `budget` of quote, fees
+/// included
+fn affordable_quantity(asks: &[PriceLevel], budget: Decimal, fee_rate: Decimal) -> Decimal {
+    let mut budget = budget;
+    let mut quantity = dec!(0);
+    for level in asks {
+        let unit_cost = level.price * (dec!(1) + fee_rate);
+        if budget <= dec!(0) || unit_cost <= dec!(0) {
+            break;
+        }
+        let taken = level.quantity.min(budget / unit_cost);
+        quantity += taken;
+        budget -= taken * unit_cost;
+    }
+    quantity
+}
+
+/// Quantity, notional and fees a leg's child orders traded
+fn traded(leg: Option<&Vec<ChildOrder>>) -> (Decimal, Decimal, Decimal) {
+    leg.into_iter()
+        .flatten()
+        .filter_map(|child| child.report.as_ref())
+        .fold((dec!(0), dec!(0), dec!(0)), |(q, n, f), r| {
+            (
+                q + r.filled_quantity,
+                n + r.filled_quantity * r.average_price,
+                f + r.fees,
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::mock::{FillBehavior, MockExchange};
+    use crate::exchanges::Exchange;
+    use crate::types::{OrderSide, OrderType, TradingPair};
+
+    /// Venue quoting `ask` that either rejects or paper-fills orders
+    fn venue(name: &str, ask: Decimal, rejects: bool) -> Box<dyn Exchange> {
+        let behavior = if rejects {
+            FillBehavior::Reject("insufficient balance".to_string())
+        } else {
+            FillBehavior::Book
+        };
+        Box::new(
+            MockExchange::new(name)
+                .with_book(
+                    TradingPair::new("BTC", "USD"),
+                    &[(ask - dec!(1), dec!(10))],
+                    &[(ask, dec!(10))],
+                )
+                .with_fill_behavior(behavior),
+        )
+    }
+
+    #[test]
+    fn test_parent_tags_keep_time_and_counter_apart() {
+        let first = parent_tag();
+        let second = parent_tag();
+        assert_ne!(first, second);
+        for tag in [&first, &second] {
+            let (millis, counter) = tag.split_once('-').unwrap();
+            assert!(i64::from_str_radix(millis, 16).is_ok());
+            assert!(counter.parse::<u64>().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_split_is_rerouted() {
+        let router = SmartOrderRouter::new(vec![
+            venue("Cheap", dec!(100), true),
+            venue("Backup", dec!(101), false),
+        ]);
+        let order = Order {
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(2),
+            limit_price: None,
+        };
+
+        let routing = router.route_order(&order).await.unwrap();
+        assert_eq!(routing.splits[0].exchange, "Cheap");
+
+        let report = Executor::new(&router).execute(&routing).await.unwrap();
+
+        assert_eq!(report.status(), OrderStatus::Filled);
+        assert_eq!(report.children.len(), 2);
+        assert_eq!(report.children[0].status, OrderStatus::Rejected);
+        assert_eq!(report.children[1].exchange, "Backup");
+        assert_eq!(report.children[1].round, 1);
+        assert_eq!(report.average_price, dec!(101));
+        assert_eq!(report.execution_results().len(), 1);
+    }
+
+    fn fast_polls() -> ExecutorConfig {
+        ExecutorConfig {
+            poll_interval: Duration::from_millis(1),
+            max_status_polls: 2,
+            ..ExecutorConfig::default()
+        }
+    }
+
+    fn limit_buy(quantity: Decimal) -> Order {
+        Order {
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Limit,
+            quantity,
+            limit_price: Some(dec!(105)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_fill_is_polled_cancelled_and_rerouted() {
+        let router = SmartOrderRouter::new(vec![Box::new(
+            MockExchange::new("Thin")
+                .with_book(
+                    TradingPair::new("BTC", "USD"),
+                    &[],
+                    &[(dec!(100), dec!(10))],
+                )
+                .with_fill_behavior(FillBehavior::Partial(dec!(0.5))),
+        )]);
+        let routing = router.route_order(&limit_buy(dec!(2))).await.unwrap();
+        let executor = Executor::with_config(&router, fast_polls());
+
+        let report = executor.execute(&routing).await.unwrap();
+
+        // Each round fills half of what it sends and cancels the rest
+        let quantities: Vec<_> = report.children.iter().map(|c| c.request.quantity).collect();
+        assert_eq!(quantities, vec![dec!(2), dec!(1), dec!(0.5)]);
+        assert!(report
+            .children
+            .iter()
+            .all(|c| c.status == OrderStatus::Cancelled));
+        assert_eq!(report.filled_quantity, dec!(1.75));
+        assert_eq!(report.working_quantity, dec!(0));
+        assert_eq!(report.status(), OrderStatus::PartiallyFilled);
+
+        // A second parent never reuses a client order ID
+        let again = executor.execute(&routing).await.unwrap();
+        for child in &again.children {
+            assert!(report
+                .children
+                .iter()
+                .all(|c| c.request.client_order_id != child.request.client_order_id));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quantity_left_working_is_not_rerouted() {
+        let router = SmartOrderRouter::new(vec![
+            Box::new(
+                MockExchange::new("Stuck")
+                    .with_book(
+                        TradingPair::new("BTC", "USD"),
+                        &[],
+                        &[(dec!(100), dec!(10))],
+                    )
+                    .with_fill_behavior(FillBehavior::Partial(dec!(0.5)))
+                    .with_failing_cancels(),
+            ),
+            venue("Backup", dec!(101), false),
+        ]);
+        let routing = router.route_order(&limit_buy(dec!(2))).await.unwrap();
+        assert_eq!(routing.splits.len(), 1);
+
+        let report = Executor::with_config(&router, fast_polls())
+            .execute(&routing)
+            .await
+            .unwrap();
+
+        assert_eq!(report.children.len(), 1);
+        assert_eq!(report.children[0].status, OrderStatus::PartiallyFilled);
+        assert_eq!(report.filled_quantity, dec!(1));
+        assert_eq!(report.unfilled_quantity, dec!(1));
+        assert_eq!(report.working_quantity, dec!(1));
+    }
+
+    #[tokio::test]
+    async fn test_unacknowledged_order_is_found_by_client_order_id() {
+        let router = SmartOrderRouter::new(vec![
+            Box::new(
+                MockExchange::new("Flaky")
+                    .with_book(
+                        TradingPair::new("BTC", "USD"),
+                        &[],
+                        &[(dec!(100), dec!(10))],
+                    )
+                    .with_fill_behavior(FillBehavior::Unacknowledged),
+            ),
+            venue("Backup", dec!(101), false),
+        ]);
+        let routing = router.route_order(&limit_buy(dec!(2))).await.unwrap();
+
+        let report = Executor::with_config(&router, fast_polls())
+            .execute(&routing)
+            .await
+            .unwrap();
+
+        // The fill is picked up instead of buying the quantity again
+        assert_eq!(report.children.len(), 1);
+        assert_eq!(report.children[0].status, OrderStatus::Filled);
+        assert_eq!(report.filled_quantity, dec!(2));
+        assert_eq!(report.status(), OrderStatus::Filled);
+    }
+
+    #[tokio::test]
+    async fn test_reroutes_stay_within_what_is_left_of_the_balances() {
+        use crate::exchanges::fees::FeeSchedule;
+        use crate::types::Balance;
+
+        let pair = TradingPair::new("BTC", "USD");
+        let router = SmartOrderRouter::new(vec![
+            Box::new(
+                MockExchange::new("Thin")
+                    .with_book(pair.clone(), &[], &[(dec!(100), dec!(10))])
+                    .with_fees(FeeSchedule::flat(dec!(0), dec!(0)))
+                    .with_fill_behavior(FillBehavior::Partial(dec!(0.5)))
+                    .with_balance(Balance::new("USD", dec!(100), dec!(0))),
+            ),
+            venue("Broken", dec!(101), true),
+        ]);
+        let order = limit_buy(dec!(4));
+        let balances = router.fetch_balances().await;
+        let routing = router
+            .route_order_with_balances(&order, &balances)
+            .await
+            .unwrap();
+
+        let report = Executor::with_config(&router, fast_polls())
+            .execute_with_balances(&routing, &balances)
+            .await
+            .unwrap();
+
+        // Each round Thin fills half its split, spending that much of its
+        // 100 USD, and the next round may only use what is left
+        let thin: Vec<_> = report
+            .children
+            .iter()
+            .filter(|c| c.exchange == "Thin")
+            .map(|c| (c.round, c.request.quantity))
+            .collect();
+        assert_eq!(thin, vec![(0, dec!(1)), (1, dec!(0.5)), (2, dec!(0.25))]);
+        assert_eq!(report.filled_quantity, dec!(0.875));
+    }
+
+    #[tokio::test]
+    async fn test_quotes_are_accepted_alongside_splits() {
+        use crate::exchanges::rfq::MockRfqVenue;
+
+        let pair = TradingPair::new("BTC", "USD");
+        let router = SmartOrderRouter::new(vec![Box::new(MockExchange::new("Lit").with_book(
+            pair.clone(),
+            &[],
+            &[(dec!(100), dec!(2)), (dec!(110), dec!(10))],
+        ))])
+        .with_rfq_venues(vec![Box::new(
+            MockRfqVenue::new("Desk")
+                .with_price(pair.clone(), OrderSide::Buy, dec!(103))
+                .with_max_quantity(dec!(3)),
+        )]);
+        let order = Order {
+            pair,
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(5),
+            limit_price: None,
+        };
+
+        let routing = router.route_order(&order).await.unwrap();
+        assert_eq!(routing.splits[0].quantity, dec!(2));
+        assert_eq!(routing.quotes[0].quantity, dec!(3));
+
+        let report = Executor::new(&router).execute(&routing).await.unwrap();
+
+        assert_eq!(report.status(), OrderStatus::Filled);
+        assert_eq!(report.children.len(), 2);
+        assert_eq!(report.children[1].exchange, "Desk");
+        assert_eq!(report.children[1].request.limit_price, Some(dec!(103)));
+        assert_eq!(report.average_price, dec!(101.8));
+    }
+
+    #[tokio::test]
+    async fn test_synthetic_route_runs_its_legs_in_order() {
+        use crate::exchanges::fees::FeeSchedule;
+
+        let venue = |name, base, quote, asks: &[(Decimal, Decimal)]| -> Box<dyn Exchange> {
+            Box::new(
+                MockExchange::new(name)
+                    .with_book(TradingPair::new(base, quote), &[], asks)
+                    .with_fees(FeeSchedule::flat(dec!(0), dec!(0))),
+            )
+        };
+        let router = SmartOrderRouter::new(vec![
+            venue("Direct", "ETH", "USD", &[(dec!(100.3), dec!(3))]),
+            venue("Alts", "ETH", "BTC", &[(dec!(0.05), dec!(10))]),
+            venue("Majors", "BTC", "USD", &[(dec!(2000), dec!(0.3))]),
+        ])
+        .with_intermediate_assets(["BTC"]);
+        let order = Order {
+            pair: TradingPair::new("ETH", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(8),
+            limit_price: None,
+        };
+
+        let routing = router.route_order(&order).await.unwrap();
+        let report = Executor::new(&router).execute(&routing).await.unwrap();
+
+        assert_eq!(report.status(), OrderStatus::Filled);
+        assert_eq!(report.children.len(), 1);
+        assert_eq!(report.synthetic.len(), 1);
+        let synthetic = &report.synthetic[0];
+        assert_eq!(synthetic.legs[0][0].exchange, "Majors");
+        assert_eq!(
+            synthetic.legs[0][0].request.pair,
+            TradingPair::new("BTC", "USD")
+        );
+        assert_eq!(synthetic.legs[1][0].exchange, "Alts");
+        assert_eq!(synthetic.legs[1][0].request.quantity, dec!(6));
+        assert_eq!(synthetic.filled_quantity, dec!(6));
+        assert_eq!(synthetic.quote_amount, dec!(600));
+        assert_eq!(report.filled_quantity, dec!(8));
+        assert_eq!(report.average_price, dec!(100.075));
+    }
+
+    #[tokio::test]
+    async fn test_synthetic_leftover_is_unwound_or_reported() {
+        use crate::exchanges::fees::FeeSchedule;
+
+        let pair = TradingPair::new("ETH", "BTC");
+        let router = |alts_asks: &[(Decimal, Decimal)]| {
+            SmartOrderRouter::new(vec![
+                Box::new(
+                    MockExchange::new("Alts")
+                        .with_book(pair.clone(), &[(dec!(0.05), dec!(10))], alts_asks)
+                        .with_fees(FeeSchedule::flat(dec!(0), dec!(0))),
+                ),
+                Box::new(
+                    MockExchange::new("Majors")
+                        .with_book(
+                            TradingPair::new("BTC", "USD"),
+                            &[(dec!(2000), dec!(1))],
+                            &[],
+                        )
+                        .with_fees(FeeSchedule::flat(dec!(0), dec!(0)))
+                        .with_fill_behavior(FillBehavior::Reject("halted".to_string())),
+                ),
+                Box::new(
+                    MockExchange::new("Direct")
+                        .with_book(TradingPair::new("ETH", "USD"), &[(dec!(99), dec!(10))], &[])
+                        .with_fees(FeeSchedule::flat(dec!(0), dec!(0))),
+                ),
+            ])
+            .with_intermediate_assets(["BTC"])
+        };
+        let order = Order {
+            pair: TradingPair::new("ETH", "USD"),
+            side: OrderSide::Sell,
+            order_type: OrderType::Market,
+            quantity: dec!(6),
+            limit_price: None,
+        };
+
+        // The BTC the sold ETH fetched buys back 5.94 ETH: the rest is what
+        // the round trip cost, and none of it counts as sold
+        let router_with_asks = router(&[(dec!(0.0505), dec!(10))]);
+        let routing = router_with_asks.route_order(&order).await.unwrap();
+        let report = Executor::new(&router_with_asks)
+            .execute(&routing)
+            .await
+            .unwrap();
+        let synthetic = &report.synthetic[0];
+        assert_eq!(synthetic.legs[0][0].filled_quantity(), dec!(6));
+        assert_eq!(synthetic.legs[1][0].status, OrderStatus::Rejected);
+        assert_eq!(synthetic.unwind.len(), 1);
+        assert_eq!(synthetic.unwind[0].request.side, OrderSide::Buy);
+        assert_eq!(synthetic.unwind[0].request.pair, pair);
+        assert_eq!(synthetic.leftover.round_dp(12), dec!(0));
+        assert_eq!(synthetic.stranded_quantity, dec!(0));
+        assert_eq!(synthetic.filled_quantity, dec!(0));
+        assert_eq!(synthetic.quote_amount, dec!(0));
+        assert_eq!(synthetic.unwind_loss.round_dp(4), dec!(0.0594));
+        // The whole 6 ETH is re-routed to the direct book
+        assert_eq!(routing.synthetic_routes[0].quantity, dec!(6));
+        assert_eq!(report.children.len(), 1);
+        assert_eq!(report.children[0].exchange, "Direct");
+        assert_eq!(report.children[0].request.quantity, dec!(6));
+        assert_eq!(report.filled_quantity, dec!(6));
+        assert_eq!(report.average_price, dec!(99));
+
+        // With nothing to buy back, the BTC is left over and the ETH it
+        // came from is neither filled nor re-routed
+        let router_without_asks = router(&[]);
+        let routing = router_without_asks.route_order(&order).await.unwrap();
+        let report = Executor::new(&router_without_asks)
+            .execute(&routing)
+            .await
+            .unwrap();
+        let synthetic = &report.synthetic[0];
+        assert!(synthetic.unwind.is_empty());
+        assert_eq!(synthetic.leftover, dec!(0.3));
+        assert_eq!(synthetic.stranded_quantity, dec!(6));
+        assert_eq!(synthetic.filled_quantity, dec!(0));
+        assert!(report.children.is_empty());
+        assert_eq!(report.filled_quantity, dec!(0));
+        assert_eq!(report.stranded_quantity, dec!(6));
+        assert_eq!(report.unfilled_quantity, dec!(6));
+    }
+
+    #[tokio::test]
+    async fn test_converted_splits_trade_the_venues_own_pair() {
+        use crate::exchanges::fees::FeeSchedule;
+        use crate::router::conversion::QuoteEquivalence;
+
+        let venue = |name, quote, asks: &[(Decimal, Decimal)]| -> Box<dyn Exchange> {
+            Box::new(
+                MockExchange::new(name)
+                    .with_book(TradingPair::new("BTC", quote), &[], asks)
+                    .with_rules(
+                        TradingPair::new("BTC", quote),
+                        InstrumentRules::new(dec!(0.01), dec!(0.001)),
+                    )
+                    .with_fees(FeeSchedule::flat(dec!(0), dec!(0))),
+            )
+        };
+        let router =
+            SmartOrderRouter::new(vec![
+                venue("Direct", "USD", &[(dec!(50100), dec!(5))]),
+                venue("Tether", "USDT", &[(dec!(50000), dec!(2))]),
+            ])
+            .with_quote_equivalence(
+                QuoteEquivalence::stablecoins().with_fallback_rate("USDT", "USD", dec!(0.999)),
+            );
+        let order = Order {
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Limit,
+            quantity: dec!(2.5),
+            limit_price: Some(dec!(50200)),
+        };
+
+        let routing = router.route_order(&order).await.unwrap();
+        let report = Executor::new(&router).execute(&routing).await.unwrap();
+
+        assert_eq!(report.status(), OrderStatus::Filled);
+        let tether = &report.children[0];
+        assert_eq!(tether.exchange, "Tether");
+        assert_eq!(tether.request.pair, TradingPair::new("BTC", "USDT"));
+        // 50200 / 0.999 = 50250.2502..., rounded down to the tick
+        assert_eq!(tether.request.limit_price, Some(dec!(50250.25)));
+        // 2 BTC for 100000 USDT worth 99900 USD, then 0.5 BTC for 25050 USD
+        assert_eq!(report.average_price, dec!(49980));
+    }
+}
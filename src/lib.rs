@@ -4,10 +4,13 @@
 //!
 //! ## Features
 //!
-//! - Multi-exchange connectivity (Binance, Coinbase, Kraken)
+//! - Multi-exchange connectivity (Binance, Coinbase, Kraken, OKX, Bybit, Bitstamp)
 //! - Intelligent order routing algorithms (VWAP, TWAP, Implementation Shortfall)
 //! - Real-time liquidity analysis
+//! - WebSocket depth streaming into locally maintained order books
+//! - Venue health tracking with a circuit breaker for degraded exchanges
 //! - Order splitting to minimize slippage
+//! - Concurrent child-order execution with re-routing of unfilled quantity
 //! - Comprehensive execution analytics
 //! - Backtesting framework
 //!
@@ -38,7 +41,9 @@
 pub mod analytics;
 pub mod backtesting;
 pub mod exchanges;
+pub mod executor;
 pub mod router;
+pub mod streaming;
 pub mod types;
 
 // Re-export commonly used types
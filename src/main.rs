@@ -8,12 +8,42 @@ async fn main() -> Result<()> {
 
     println!("=== Smart Order Router Demo ===\n");
 
-    // Create exchanges
-    let exchanges = exchanges::create_exchanges();
+    // Create exchanges; simulated venues only when explicitly requested
+    let args: Vec<String> = std::env::args().collect();
+    let config_path = args
+        .iter()
+        .position(|arg| arg == "--config")
+        .and_then(|i| args.get(i + 1));
+    let demo = args.iter().any(|arg| arg == "--demo");
+    // Live venues only receive orders when asked to; otherwise the plan is
+    // printed and nothing is sent
+    let execute = demo || args.iter().any(|arg| arg == "--execute");
+    let exchanges = if demo {
+        println!("Demo mode: routing against simulated exchanges");
+        exchanges::create_demo_exchanges()
+    } else if let Some(path) = config_path {
+        let config = exchanges::config::ExchangesConfig::from_file(path)?;
+        exchanges::create_exchanges_from_config(&config)?
+    } else {
+        exchanges::create_exchanges()
+    };
     println!("Connected to {} exchanges", exchanges.len());
 
-    // Create router
-    let router = router::SmartOrderRouter::new(exchanges);
+    // Create router, letting USD, USDT and USDC books fill one another's orders
+    let router = router::SmartOrderRouter::new(exchanges)
+        .with_quote_equivalence(router::conversion::QuoteEquivalence::stablecoins());
+
+    if args.iter().any(|arg| arg == "--list-pairs") {
+        for (exchange, listings) in router.list_pairs().await {
+            for listing in listings {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    exchange, listing.symbol, listing.pair, listing.status
+                );
+            }
+        }
+        return Ok(());
+    }
 
     // Example 1: Buy order
     println!("\n--- Example 1: Buy Order ---");
@@ -34,32 +64,108 @@ async fn main() -> Result<()> {
             println!("\nSplits:");
             for (i, split) in routing.splits.iter().enumerate() {
                 println!(
-                    "  {}. {} - Quantity: {}, Price: ${:.2}",
+                    "  {}. {} - Quantity: {}, Price: ${:.2}, Fee: ${:.2}",
                     i + 1,
                     split.exchange,
                     split.quantity,
-                    split.expected_price
+                    split.expected_price,
+                    split.expected_fee
                 );
+                if let Some(conversion) = &split.conversion {
+                    println!(
+                        "     traded on {} at {} {} per {}",
+                        conversion.pair,
+                        conversion.rate,
+                        buy_order.pair.quote,
+                        conversion.pair.quote
+                    );
+                }
             }
-
-            // Simulate execution
-            println!("\n--- Simulating Execution ---");
-            let mut analytics = analytics::ExecutionAnalytics::new();
-            
-            for split in &routing.splits {
-                let execution = backtesting::simulator::simulate_execution(split);
+            for route in &routing.synthetic_routes {
+                println!(
+                    "  Via {} - Quantity: {}, All-in price: ${:.2}",
+                    route.via, route.quantity, route.expected_price
+                );
+                for leg in &route.legs {
+                    for split in &leg.splits {
+                        println!(
+                            "     {} {:?} {} on {}",
+                            leg.pair, leg.side, split.quantity, split.exchange
+                        );
+                    }
+                }
+            }
+            for quote in &routing.quotes {
+                println!(
+                    "  RFQ {} - Quantity: {}, Price: ${:.2}, firm until {}",
+                    quote.venue, quote.quantity, quote.price, quote.expires_at
+                );
+            }
+            for skipped in &routing.skipped_venues {
+                println!("  Skipped {}: {}", skipped.exchange, skipped.reason);
+            }
+            for venue in &routing.cached_venues {
+                println!("  Priced {} from a cached book (rate limited)", venue);
+            }
+            for limit in &routing.balance_limited_venues {
                 println!(
-                    "Executed {} {} on {} at ${:.2} (fees: ${:.2})",
-                    execution.executed_quantity,
-                    buy_order.pair.base,
-                    execution.exchange,
-                    execution.executed_price,
-                    execution.fees
+                    "  Balance-limited on {}: {} {} available",
+                    limit.exchange, limit.available, limit.asset
                 );
-                analytics.add_result(execution);
             }
 
-            println!("\n{}", analytics.generate_report(&routing));
+            if execute {
+                // Execute the routing plan
+                println!("\n--- Executing ---");
+                let executor = executor::Executor::new(&router);
+                let fill_report = executor.execute(&routing).await?;
+                let mut analytics = analytics::ExecutionAnalytics::new();
+
+                for child in &fill_report.children {
+                    match &child.reject_reason {
+                        Some(reason) => println!("Rejected on {}: {}", child.exchange, reason),
+                        None => println!(
+                            "{:?} {} {} on {} at ${:.2}",
+                            child.status,
+                            child.filled_quantity(),
+                            buy_order.pair.base,
+                            child.exchange,
+                            child
+                                .report
+                                .as_ref()
+                                .map(|r| r.average_price)
+                                .unwrap_or_default()
+                        ),
+                    }
+                }
+                for execution in fill_report.execution_results() {
+                    analytics.add_result(execution);
+                }
+                println!(
+                    "Parent order {:?}: {} filled, {} unfilled",
+                    fill_report.status(),
+                    fill_report.filled_quantity,
+                    fill_report.unfilled_quantity
+                );
+                if fill_report.working_quantity > rust_decimal::Decimal::ZERO {
+                    println!(
+                        "  {} still working or unconfirmed at the venues",
+                        fill_report.working_quantity
+                    );
+                }
+                for fill in &fill_report.synthetic {
+                    if fill.stranded_quantity > rust_decimal::Decimal::ZERO {
+                        println!(
+                            "  {} {} left over from a synthetic route, standing for {} {}",
+                            fill.leftover, fill.via, fill.stranded_quantity, buy_order.pair.base
+                        );
+                    }
+                }
+
+                println!("\n{}", analytics.generate_report(&routing));
+            } else {
+                println!("\nDry run: pass --execute to send this plan to the exchanges");
+            }
         }
         Err(e) => println!("Error routing order: {}", e),
     }
@@ -83,11 +189,12 @@ async fn main() -> Result<()> {
             println!("\nSplits:");
             for (i, split) in routing.splits.iter().enumerate() {
                 println!(
-                    "  {}. {} - Quantity: {}, Price: ${:.2}",
+                    "  {}. {} - Quantity: {}, Price: ${:.2}, Fee: ${:.2}",
                     i + 1,
                     split.exchange,
                     split.quantity,
-                    split.expected_price
+                    split.expected_price,
+                    split.expected_fee
                 );
             }
         }
@@ -97,23 +204,27 @@ async fn main() -> Result<()> {
     // Example 3: Backtesting
     println!("\n\n--- Example 3: Backtesting ---");
     let mut backtest = backtesting::BacktestEngine::new();
-    
+
     for i in 0..5 {
         let order = Order {
             pair: TradingPair::new("BTC", "USD"),
-            side: if i % 2 == 0 { OrderSide::Buy } else { OrderSide::Sell },
+            side: if i % 2 == 0 {
+                OrderSide::Buy
+            } else {
+                OrderSide::Sell
+            },
             order_type: types::OrderType::Market,
             quantity: dec!(0.5) + dec!(0.1) * rust_decimal::Decimal::from(i),
             limit_price: None,
         };
-        
+
         if let Ok(routing) = router.route_order(&order).await {
             let executions: Vec<_> = routing
                 .splits
                 .iter()
                 .map(backtesting::simulator::simulate_execution)
                 .collect();
-            
+
             backtest.add_order(order);
             backtest.add_result(routing, executions);
         }
@@ -121,6 +232,11 @@ async fn main() -> Result<()> {
 
     println!("{}", backtest.summary());
 
+    println!("\n--- Venue Health ---");
+    for report in router.health_report() {
+        println!("  {}", report);
+    }
+
     println!("\n=== Demo Complete ===");
     Ok(())
 }
@@ -0,0 +1,214 @@
+use super::optimizer::VenueBook;
+use crate::types::{OrderBook, PriceLevel, QuoteConversion, TradingPair};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+
+/// Which quote assets may stand in for one another, and at what cost.
+///
+/// A BTC/USD order can then also take liquidity from BTC/USDT and
+/// BTC/USDC books, priced in USD at the live conversion rate. The rate is
+/// the mid of the conversion market (USDT/USD, or USD/USDT inverted) on
+/// the router's venues, falling back to a configured rate where no venue
+/// quotes one. Converted prices are worsened by `buffer` for the cost of
+/// converting and the risk of the rate moving.
+#[derive(Debug, Clone, Default)]
+pub struct QuoteEquivalence {
+    groups: Vec<Vec<String>>,
+    buffer: Decimal,
+    /// Units of the second asset per unit of the first
+    fallback_rates: HashMap<(String, String), Decimal>,
+}
+
+impl QuoteEquivalence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// USD, USDT and USDC as one group with a 10 basis point buffer and
+    /// no fallback rates
+    pub fn stablecoins() -> Self {
+        Self::new()
+            .with_group(["USD", "USDT", "USDC"])
+            .with_buffer(dec!(0.001))
+    }
+
+    /// Treat these assets as interchangeable quotes
+    pub fn with_group(mut self, assets: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.groups.push(
+            assets
+                .into_iter()
+                .map(|asset| asset.into().to_uppercase())
+                .collect(),
+        );
+        self
+    }
+
+    /// Fraction each converted price is worsened by
+    pub fn with_buffer(mut self, buffer: Decimal) -> Self {
+        self.buffer = buffer;
+        self
+    }
+
+    /// Rate used when no venue quotes a market between `from` and `to`:
+    /// `rate` units of `to` per unit of `from`
+    pub fn with_fallback_rate(mut self, from: &str, to: &str, rate: Decimal) -> Self {
+        self.fallback_rates
+            .insert((from.to_uppercase(), to.to_uppercase()), rate);
+        self
+    }
+
+    pub fn buffer(&self) -> Decimal {
+        self.buffer
+    }
+
+    /// Other quote assets that may stand in for `quote`
+    pub fn alternatives(&self, quote: &str) -> Vec<String> {
+        let quote = quote.to_uppercase();
+        self.groups
+            .iter()
+            .filter(|group| group.contains(&quote))
+            .flatten()
+            .filter(|asset| **asset != quote)
+            .fold(Vec::new(), |mut alternatives, asset| {
+                if !alternatives.contains(asset) {
+                    alternatives.push(asset.clone());
+                }
+                alternatives
+            })
+    }
+
+    /// Configured units of `to` per unit of `from`, set either way round
+    pub fn fallback_rate(&self, from: &str, to: &str) -> Option<Decimal> {
+        let (from, to) = (from.to_uppercase(), to.to_uppercase());
+        if let Some(rate) = self.fallback_rates.get(&(from.clone(), to.clone())) {
+            return Some(*rate);
+        }
+        self.fallback_rates
+            .get(&(to, from))
+            .filter(|rate| **rate > dec!(0))
+            .map(|rate| dec!(1) / rate)
+    }
+}
+
+/// Mid price across the best bid and ask of several books of one pair, or
+/// the one side there is
+pub fn mid_rate(venues: &[VenueBook]) -> Option<Decimal> {
+    let best_bid = venues
+        .iter()
+        .filter_map(|venue| venue.book.best_bid())
+        .map(|level| level.price)
+        .max();
+    let best_ask = venues
+        .iter()
+        .filter_map(|venue| venue.book.best_ask())
+        .map(|level| level.price)
+        .min();
+    match (best_bid, best_ask) {
+        (Some(bid), Some(ask)) => Some((bid + ask) / dec!(2)),
+        (bid, ask) => bid.or(ask),
+    }
+    .filter(|rate| *rate > dec!(0))
+}
+
+/// A venue book quoted in another currency, converted for the order's pair
+#[derive(Debug, Clone)]
+pub struct ConvertedVenue {
+    /// The book in the order's quote, under a name of its own
+    pub venue: VenueBook,
+    /// The venue the book came from
+    pub exchange: String,
+    pub conversion: QuoteConversion,
+}
+
+impl ConvertedVenue {
+    /// Price `venue`'s book in `pair`'s quote at `rate`, worsening every
+    /// level by `buffer`: asks cost more and bids fetch less
+    pub fn new(venue: &VenueBook, pair: &TradingPair, rate: Decimal, buffer: Decimal) -> Self {
+        let convert = |levels: &[PriceLevel], factor: Decimal| {
+            levels
+                .iter()
+                .map(|level| PriceLevel::new(level.price * rate * factor, level.quantity))
+                .collect()
+        };
+        let exchange = venue.book.exchange.clone();
+        let book = OrderBook::new(
+            Self::venue_name(&exchange, &venue.book.pair.quote),
+            pair.clone(),
+            convert(&venue.book.bids, dec!(1) - buffer),
+            convert(&venue.book.asks, dec!(1) + buffer),
+        )
+        .with_exchange_time(venue.book.exchange_time);
+
+        let mut fees = venue.fees;
+        fees.per_order *= rate;
+        let mut converted = VenueBook::new(book, fees)
+            .with_rules(venue.rules.clone())
+            .with_staleness_penalty(venue.staleness_penalty);
+        converted.available = venue.available;
+
+        Self {
+            venue: converted,
+            exchange,
+            conversion: QuoteConversion {
+                pair: venue.book.pair.clone(),
+                rate,
+            },
+        }
+    }
+
+    /// Name a converted book goes by among the venues
+    pub fn venue_name(exchange: &str, quote: &str) -> String {
+        format!("{} ({})", exchange, quote.to_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::fees::FeeRates;
+
+    #[test]
+    fn test_foreign_quoted_book_is_converted_with_a_buffer() {
+        let equivalence =
+            QuoteEquivalence::stablecoins().with_fallback_rate("USDC", "USD", dec!(1));
+        assert_eq!(equivalence.alternatives("usd"), vec!["USDT", "USDC"]);
+        assert!(equivalence.alternatives("EUR").is_empty());
+        assert_eq!(equivalence.fallback_rate("USD", "USDC"), Some(dec!(1)));
+        assert_eq!(equivalence.fallback_rate("USDT", "USD"), None);
+
+        let book = OrderBook::new(
+            "Binance",
+            TradingPair::new("BTC", "USDT"),
+            vec![PriceLevel::new(dec!(50000), dec!(2))],
+            vec![PriceLevel::new(dec!(50010), dec!(3))],
+        );
+        let venue = VenueBook::new(book, FeeRates::new(dec!(0.001), dec!(0.001)));
+        let usdt_usd = VenueBook::new(
+            OrderBook::new(
+                "Kraken",
+                TradingPair::new("USDT", "USD"),
+                vec![PriceLevel::new(dec!(0.9997), dec!(100000))],
+                vec![PriceLevel::new(dec!(0.9999), dec!(100000))],
+            ),
+            FeeRates::new(dec!(0), dec!(0)),
+        );
+        let rate = mid_rate(&[usdt_usd]).unwrap();
+        assert_eq!(rate, dec!(0.9998));
+
+        let converted = ConvertedVenue::new(
+            &venue,
+            &TradingPair::new("BTC", "USD"),
+            rate,
+            equivalence.buffer(),
+        );
+
+        assert_eq!(converted.exchange, "Binance");
+        assert_eq!(converted.venue.book.exchange, "Binance (USDT)");
+        assert_eq!(converted.conversion.pair, TradingPair::new("BTC", "USDT"));
+        // 50000 × 0.9998 × 0.999 and 50010 × 0.9998 × 1.001
+        assert_eq!(converted.venue.book.bids[0].price, dec!(49940.01));
+        assert_eq!(converted.venue.book.asks[0].price, dec!(50049.997998));
+        assert_eq!(converted.venue.book.asks[0].quantity, dec!(3));
+    }
+}
@@ -0,0 +1,468 @@
+use crate::types::{OrderBook, TradingPair};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Thresholds for venue health tracking and the circuit breaker
+#[derive(Debug, Clone, Copy)]
+pub struct HealthConfig {
+    /// Number of recent requests error rate and latency are computed over
+    pub window: usize,
+    /// Requests needed in the window before the breaker may trip
+    pub min_samples: usize,
+    /// Error rate (0 to 1) at which the breaker opens
+    pub max_error_rate: f64,
+    /// How long an open breaker keeps a venue out before probing it
+    pub cooldown: Duration,
+    /// A book that has not changed for this long is treated as stale
+    pub stale_after: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            min_samples: 5,
+            max_error_rate: 0.5,
+            cooldown: Duration::from_secs(30),
+            stale_after: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Circuit breaker position for one venue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Routed normally
+    Closed,
+    /// Left out of routing until the cooldown ends
+    Open { remaining: Duration },
+    /// A single probe request decides whether the venue comes back
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::Open { remaining } => {
+                write!(f, "open ({}s left)", remaining.as_secs())
+            }
+            CircuitState::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+/// Health summary of one venue, as shown by the CLI
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueHealthReport {
+    pub exchange: String,
+    pub state: CircuitState,
+    /// Requests in the current window
+    pub samples: usize,
+    /// Share of failed requests in the window, 0 to 1
+    pub error_rate: f64,
+    pub latency_p50: Option<Duration>,
+    pub latency_p95: Option<Duration>,
+    pub latency_p99: Option<Duration>,
+    /// How often the breaker has opened
+    pub trips: u32,
+    pub last_error: Option<String>,
+}
+
+impl fmt::Display for VenueHealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |latency: Option<Duration>| {
+            latency.map_or("-".to_string(), |l| format!("{}ms", l.as_millis()))
+        };
+        write!(
+            f,
+            "{}: {}, {:.1}% errors over {} requests, latency p50 {} p95 {} p99 {}",
+            self.exchange,
+            self.state,
+            self.error_rate * 100.0,
+            self.samples,
+            ms(self.latency_p50),
+            ms(self.latency_p95),
+            ms(self.latency_p99),
+        )?;
+        if let Some(error) = &self.last_error {
+            write!(f, " (last error: {})", error)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    ok: bool,
+    latency: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum Breaker {
+    #[default]
+    Closed,
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        probing: bool,
+    },
+}
+
+#[derive(Default)]
+struct VenueState {
+    samples: VecDeque<Sample>,
+    breaker: Breaker,
+    trips: u32,
+    last_error: Option<String>,
+    /// Fingerprint of the last book of each pair and when it was first seen
+    books: HashMap<TradingPair, (u64, Instant)>,
+}
+
+impl VenueState {
+    fn error_rate(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let failures = self.samples.iter().filter(|s| !s.ok).count();
+        failures as f64 / self.samples.len() as f64
+    }
+}
+
+/// Tracks request outcomes per venue and trips a circuit breaker when a
+/// venue degrades.
+///
+/// An open breaker keeps the venue out of routing for the cooldown; the
+/// first request after that is a probe that either closes the breaker or
+/// opens it for another cooldown.
+pub struct HealthMonitor {
+    config: HealthConfig,
+    venues: Mutex<HashMap<String, VenueState>>,
+}
+
+impl HealthMonitor {
+    pub fn new(config: HealthConfig) -> Self {
+        Self {
+            config,
+            venues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `venue` may be queried now. Claims the probe slot when a
+    /// breaker is ready to half-open.
+    pub fn admit(&self, venue: &str) -> bool {
+        let mut venues = self.venues.lock().unwrap();
+        let state = venues.entry(venue.to_string()).or_default();
+        match state.breaker {
+            Breaker::Closed => true,
+            Breaker::Open { until } if Instant::now() >= until => {
+                log::info!("Probing {} after circuit cooldown", venue);
+                state.breaker = Breaker::HalfOpen { probing: true };
+                true
+            }
+            Breaker::Open { .. } | Breaker::HalfOpen { probing: true } => false,
+            Breaker::HalfOpen { probing: false } => {
+                state.breaker = Breaker::HalfOpen { probing: true };
+                true
+            }
+        }
+    }
+
+    /// Whether `venue`'s breaker is closed. Unlike [`Self::admit`] this
+    /// never claims the probe slot.
+    pub fn is_closed(&self, venue: &str) -> bool {
+        let venues = self.venues.lock().unwrap();
+        venues
+            .get(venue)
+            .is_none_or(|state| matches!(state.breaker, Breaker::Closed))
+    }
+
+    /// Record a book fetched in `latency`. Crossed or stale books count as
+    /// failures; the reason is returned so the caller can drop the book.
+    pub fn record_book(
+        &self,
+        venue: &str,
+        book: &OrderBook,
+        latency: Duration,
+    ) -> Result<(), String> {
+        let mut venues = self.venues.lock().unwrap();
+        let state = venues.entry(venue.to_string()).or_default();
+
+        let now = Instant::now();
+        let fingerprint = fingerprint(book);
+        let unchanged_since = match state.books.get(&book.pair) {
+            Some(&(previous, since)) if previous == fingerprint => since,
+            _ => now,
+        };
+        state
+            .books
+            .insert(book.pair.clone(), (fingerprint, unchanged_since));
+
+        let problem = match (book.best_bid(), book.best_ask()) {
+            (Some(bid), Some(ask)) if bid.price >= ask.price => Some("crossed book".to_string()),
+            _ if now.duration_since(unchanged_since) >= self.config.stale_after => Some(format!(
+                "book unchanged for {}s",
+                now.duration_since(unchanged_since).as_secs()
+            )),
+            _ => None,
+        };
+        match problem {
+            Some(problem) => {
+                self.push(venue, state, false, latency, Some(problem.clone()));
+                Err(problem)
+            }
+            None => {
+                self.push(venue, state, true, latency, None);
+                Ok(())
+            }
+        }
+    }
+
+    /// Record a failed request
+    pub fn record_failure(&self, venue: &str, latency: Duration, error: impl Into<String>) {
+        let mut venues = self.venues.lock().unwrap();
+        let state = venues.entry(venue.to_string()).or_default();
+        self.push(venue, state, false, latency, Some(error.into()));
+    }
+
+    /// Record an admitted request that says nothing about the venue's
+    /// health, such as one we throttled ourselves; frees the probe slot
+    pub fn record_neutral(&self, venue: &str) {
+        let mut venues = self.venues.lock().unwrap();
+        let state = venues.entry(venue.to_string()).or_default();
+        if let Breaker::HalfOpen { probing: true } = state.breaker {
+            state.breaker = Breaker::HalfOpen { probing: false };
+        }
+    }
+
+    pub fn report(&self, venue: &str) -> VenueHealthReport {
+        let venues = self.venues.lock().unwrap();
+        let empty = VenueState::default();
+        let state = venues.get(venue).unwrap_or(&empty);
+
+        let mut latencies: Vec<_> = state.samples.iter().map(|s| s.latency).collect();
+        latencies.sort();
+        let percentile = |p: f64| {
+            let rank = (p * latencies.len() as f64).ceil() as usize;
+            latencies.get(rank.saturating_sub(1)).copied()
+        };
+
+        VenueHealthReport {
+            exchange: venue.to_string(),
+            state: match state.breaker {
+                Breaker::Closed => CircuitState::Closed,
+                Breaker::Open { until } => CircuitState::Open {
+                    remaining: until.saturating_duration_since(Instant::now()),
+                },
+                Breaker::HalfOpen { .. } => CircuitState::HalfOpen,
+            },
+            samples: state.samples.len(),
+            error_rate: state.error_rate(),
+            latency_p50: percentile(0.50),
+            latency_p95: percentile(0.95),
+            latency_p99: percentile(0.99),
+            trips: state.trips,
+            last_error: state.last_error.clone(),
+        }
+    }
+
+    fn push(
+        &self,
+        venue: &str,
+        state: &mut VenueState,
+        ok: bool,
+        latency: Duration,
+        error: Option<String>,
+    ) {
+        if error.is_some() {
+            state.last_error = error;
+        }
+        state.samples.push_back(Sample { ok, latency });
+        while state.samples.len() > self.config.window {
+            state.samples.pop_front();
+        }
+
+        let open = Breaker::Open {
+            until: Instant::now() + self.config.cooldown,
+        };
+        match state.breaker {
+            Breaker::HalfOpen { .. } if ok => {
+                log::info!("{} recovered; closing circuit", venue);
+                // Start afresh so old failures cannot trip it straight away
+                state.samples.clear();
+                state.samples.push_back(Sample { ok, latency });
+                state.breaker = Breaker::Closed;
+            }
+            Breaker::HalfOpen { .. } => {
+                log::warn!("{} probe failed; circuit stays open", venue);
+                state.breaker = open;
+                state.trips += 1;
+            }
+            Breaker::Closed
+                if state.samples.len() >= self.config.min_samples
+                    && state.error_rate() >= self.config.max_error_rate =>
+            {
+                log::warn!(
+                    "{} failing {:.0}% of requests; opening circuit",
+                    venue,
+                    state.error_rate() * 100.0
+                );
+                state.breaker = open;
+                state.trips += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Hash of every level, to notice a book that stops changing
+fn fingerprint(book: &OrderBook) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for level in book.bids.iter().chain(&book.asks) {
+        level.price.hash(&mut hasher);
+        level.quantity.hash(&mut hasher);
+    }
+    book.bids.len().hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{PriceLevel, TradingPair};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn book(bid: Decimal, ask: Decimal) -> OrderBook {
+        OrderBook::new(
+            "Venue",
+            TradingPair::new("BTC", "USD"),
+            vec![PriceLevel::new(bid, dec!(1))],
+            vec![PriceLevel::new(ask, dec!(1))],
+        )
+    }
+
+    fn monitor(cooldown: Duration) -> HealthMonitor {
+        HealthMonitor::new(HealthConfig {
+            window: 4,
+            min_samples: 2,
+            max_error_rate: 0.5,
+            cooldown,
+            stale_after: Duration::from_secs(60),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_breaker_opens_probes_and_closes() {
+        let health = monitor(Duration::from_millis(20));
+        let latency = Duration::from_millis(10);
+
+        assert!(health.admit("Venue"));
+        health
+            .record_book("Venue", &book(dec!(99), dec!(100)), latency)
+            .unwrap();
+        health.record_failure("Venue", latency, "timed out");
+        assert!(matches!(
+            health.report("Venue").state,
+            CircuitState::Open { .. }
+        ));
+        assert!(!health.admit("Venue"));
+
+        // After the cooldown exactly one probe goes through
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        assert!(health.admit("Venue"));
+        assert!(!health.admit("Venue"));
+        health.record_failure("Venue", latency, "timed out");
+        assert!(!health.admit("Venue"));
+        assert_eq!(health.report("Venue").trips, 2);
+
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        assert!(health.admit("Venue"));
+        health
+            .record_book("Venue", &book(dec!(99), dec!(101)), latency)
+            .unwrap();
+        let report = health.report("Venue");
+        assert_eq!(report.state, CircuitState::Closed);
+        assert_eq!(report.error_rate, 0.0);
+        assert_eq!(report.last_error.as_deref(), Some("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_crossed_and_unchanging_books_are_failures() {
+        let health = HealthMonitor::new(HealthConfig {
+            stale_after: Duration::from_millis(20),
+            ..HealthConfig::default()
+        });
+        let latency = Duration::from_millis(1);
+
+        assert_eq!(
+            health.record_book("Venue", &book(dec!(101), dec!(100)), latency),
+            Err("crossed book".to_string())
+        );
+
+        health
+            .record_book("Venue", &book(dec!(99), dec!(100)), latency)
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        assert!(health
+            .record_book("Venue", &book(dec!(99), dec!(100)), latency)
+            .is_err());
+        // Any change resets the clock
+        health
+            .record_book("Venue", &book(dec!(99.5), dec!(100)), latency)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_books_of_other_pairs_do_not_reset_staleness() {
+        let health = HealthMonitor::new(HealthConfig {
+            stale_after: Duration::from_millis(20),
+            ..HealthConfig::default()
+        });
+        let latency = Duration::from_millis(1);
+        let eth = |bid| {
+            OrderBook::new(
+                "Venue",
+                TradingPair::new("ETH", "USD"),
+                vec![PriceLevel::new(bid, dec!(1))],
+                vec![PriceLevel::new(dec!(3000), dec!(1))],
+            )
+        };
+
+        health
+            .record_book("Venue", &book(dec!(99), dec!(100)), latency)
+            .unwrap();
+        health
+            .record_book("Venue", &eth(dec!(2999)), latency)
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        health
+            .record_book("Venue", &eth(dec!(2998)), latency)
+            .unwrap();
+        assert!(health
+            .record_book("Venue", &book(dec!(99), dec!(100)), latency)
+            .is_err());
+    }
+
+    #[test]
+    fn test_report_latency_percentiles() {
+        let health = HealthMonitor::new(HealthConfig::default());
+        for ms in 1..=20 {
+            health.record_failure("Venue", Duration::from_millis(ms), "error");
+        }
+        health.record_neutral("Venue");
+
+        let report = health.report("Venue");
+        assert_eq!(report.samples, 20);
+        assert_eq!(report.latency_p50, Some(Duration::from_millis(10)));
+        assert_eq!(report.latency_p95, Some(Duration::from_millis(19)));
+        assert_eq!(report.latency_p99, Some(Duration::from_millis(20)));
+        assert_eq!(health.report("Unknown").latency_p50, None);
+    }
+}
@@ -1,47 +1,1366 @@
+pub mod conversion;
+pub mod health;
 pub mod optimizer;
 pub mod splitter;
+pub mod synthetic;
 
-use crate::exchanges::Exchange;
-use crate::types::{Order, OrderSide, RoutingResult};
+use crate::exchanges::rfq::RfqVenue;
+use crate::exchanges::{Exchange, ExchangeError};
+use crate::types::{
+    BalanceSnapshot, Order, OrderBook, OrderSide, OrderSplit, OrderType, PairListing, QuoteRequest,
+    RfqQuote, RoutingResult, SkipReason, SkippedVenue, TradingPair,
+};
 use anyhow::Result;
+use chrono::Utc;
+use conversion::{mid_rate, ConvertedVenue, QuoteEquivalence};
+use futures::future::join_all;
+use health::{HealthConfig, HealthMonitor, VenueHealthReport};
+use optimizer::VenueBook;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use synthetic::SyntheticBook;
+use tokio::time::{timeout_at, Instant};
+
+/// Router behaviour and timing limits applied while gathering liquidity
+#[derive(Debug, Clone, Copy)]
+pub struct RouterConfig {
+    /// Maximum time a single venue may take to answer
+    pub venue_timeout: Duration,
+    /// Maximum time spent gathering liquidity across all venues
+    pub routing_budget: Duration,
+    /// Propose resting the unfilled remainder of limit orders passively
+    pub rest_unfilled_limit: bool,
+    /// Oldest cached book used in place of a venue that is throttling us,
+    /// never more than the venue's quote age limit
+    pub max_cached_book_age: Duration,
+    /// Oldest quote routed on, measured from the venue's own timestamp
+    /// where it gives one. Venues whose books age differently (AMM pools
+    /// move once a block) set their own limit.
+    pub max_quote_age: Duration,
+    /// Fraction of price a quote is ranked worse by per second of age, so
+    /// fresher books win close calls
+    pub staleness_penalty_per_second: Decimal,
+    /// Circuit breaker thresholds
+    pub health: HealthConfig,
+    /// Smallest order quantity worth asking RFQ venues to quote
+    pub min_rfq_quantity: Decimal,
+    /// Maximum time spent collecting RFQ quotes, within the routing budget
+    pub rfq_timeout: Duration,
+    /// Quotes expiring sooner than this after routing are not used, so
+    /// there is time left to accept them
+    pub min_quote_life: Duration,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            venue_timeout: Duration::from_millis(2000),
+            routing_budget: Duration::from_millis(3000),
+            rest_unfilled_limit: false,
+            max_cached_book_age: Duration::from_secs(2),
+            max_quote_age: Duration::from_secs(2),
+            // One basis point per second
+            staleness_penalty_per_second: dec!(0.0001),
+            health: HealthConfig::default(),
+            min_rfq_quantity: dec!(0),
+            rfq_timeout: Duration::from_millis(1000),
+            min_quote_life: Duration::from_millis(500),
+        }
+    }
+}
 
 /// Smart Order Router
 pub struct SmartOrderRouter {
     exchanges: Vec<Box<dyn Exchange>>,
+    /// Market makers asked to quote alongside the books
+    rfq_venues: Vec<Box<dyn RfqVenue>>,
+    /// Assets synthetic routes may pass through
+    intermediate_assets: Vec<String>,
+    /// Quote assets whose books may fill orders quoted in one another
+    quote_equivalence: Option<QuoteEquivalence>,
+    config: RouterConfig,
+    health: HealthMonitor,
+    /// Last book fetched from each venue, for when it throttles us
+    book_cache: Mutex<HashMap<(String, TradingPair), (Instant, OrderBook)>>,
+    next_quote_request: AtomicU64,
 }
 
 impl SmartOrderRouter {
     pub fn new(exchanges: Vec<Box<dyn Exchange>>) -> Self {
-        Self { exchanges }
+        Self::with_config(exchanges, RouterConfig::default())
+    }
+
+    pub fn with_config(exchanges: Vec<Box<dyn Exchange>>, config: RouterConfig) -> Self {
+        Self {
+            exchanges,
+            rfq_venues: Vec::new(),
+            intermediate_assets: Vec::new(),
+            quote_equivalence: None,
+            health: HealthMonitor::new(config.health),
+            config,
+            book_cache: Mutex::new(HashMap::new()),
+            next_quote_request: AtomicU64::new(1),
+        }
+    }
+
+    /// Ask these venues for firm quotes on every order of at least
+    /// `min_rfq_quantity`, and route to their quotes where they beat the
+    /// books
+    pub fn with_rfq_venues(mut self, rfq_venues: Vec<Box<dyn RfqVenue>>) -> Self {
+        self.rfq_venues = rfq_venues;
+        self
+    }
+
+    /// Also route orders through these assets: an ETH/USD order may then
+    /// buy ETH/BTC with BTC bought on BTC/USD, on any venues
+    pub fn with_intermediate_assets(
+        mut self,
+        assets: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.intermediate_assets = assets
+            .into_iter()
+            .map(|asset| asset.into().to_uppercase())
+            .collect();
+        self
+    }
+
+    /// Also route orders to books quoted in an equivalent currency: a
+    /// BTC/USD order may then fill on BTC/USDT, priced in USD at the live
+    /// USDT/USD rate
+    pub fn with_quote_equivalence(mut self, equivalence: QuoteEquivalence) -> Self {
+        self.quote_equivalence = Some(equivalence);
+        self
     }
 
     /// Route an order across multiple exchanges
     pub async fn route_order(&self, order: &Order) -> Result<RoutingResult> {
+        self.route_order_excluding(order, &[], None).await
+    }
+
+    /// Route an order, leaving out the named exchanges and, given
+    /// `balances`, keeping each venue within them
+    pub async fn route_order_excluding(
+        &self,
+        order: &Order,
+        excluded: &[String],
+        balances: Option<&BalanceSnapshot>,
+    ) -> Result<RoutingResult> {
+        self.route(order, excluded, balances).await
+    }
+
+    /// Route an order so no venue is asked to spend more than it holds.
+    ///
+    /// Venues missing from `balances` are not constrained. Venues whose
+    /// share was cut short are listed in
+    /// [`RoutingResult::balance_limited_venues`].
+    pub async fn route_order_with_balances(
+        &self,
+        order: &Order,
+        balances: &BalanceSnapshot,
+    ) -> Result<RoutingResult> {
+        self.route(order, &[], Some(balances)).await
+    }
+
+    /// Query balances on every exchange concurrently.
+    ///
+    /// Exchanges that cannot report balances are left out of the snapshot,
+    /// and so are not constrained by it.
+    pub async fn fetch_balances(&self) -> BalanceSnapshot {
+        let deadline = Instant::now() + self.config.venue_timeout;
+        let queries = self.exchanges.iter().map(|exchange| async move {
+            let balances = timeout_at(deadline, exchange.get_balances()).await;
+            (exchange.name(), balances)
+        });
+
+        let mut snapshot = BalanceSnapshot::new();
+        for (name, balances) in join_all(queries).await {
+            match balances {
+                Ok(Ok(balances)) => snapshot.insert(name, balances),
+                Ok(Err(e)) => log::warn!("No balances from {}: {:#}", name, e),
+                Err(_) => log::warn!("No balances from {}: timed out", name),
+            }
+        }
+        snapshot
+    }
+
+    async fn route(
+        &self,
+        order: &Order,
+        excluded: &[String],
+        balances: Option<&BalanceSnapshot>,
+    ) -> Result<RoutingResult> {
         log::info!("Routing order: {:?}", order);
 
-        // Fetch liquidity from all exchanges
-        let mut liquidities = Vec::new();
-        for exchange in &self.exchanges {
-            if exchange.supports_pair(&order.pair).await {
-                match exchange.get_liquidity(&order.pair).await {
-                    Ok(liquidity) => liquidities.push(liquidity),
-                    Err(e) => log::warn!("Failed to get liquidity from {}: {}", exchange.name(), e),
+        let (
+            (mut venues, mut skipped_venues, cached_venues),
+            (quotes, skipped_quoters),
+            synthetic_books,
+            mut converted_venues,
+        ) = tokio::join!(
+            self.fetch_venues(order, excluded),
+            self.collect_quotes(order, excluded),
+            self.synthetic_books(order, excluded),
+            self.converted_venues(order, excluded)
+        );
+        skipped_venues.extend(skipped_quoters);
+        if let Some(balances) = balances {
+            let spent_asset = match order.side {
+                OrderSide::Buy => &order.pair.quote,
+                OrderSide::Sell => &order.pair.base,
+            };
+            for venue in &mut venues {
+                venue.available = balances.available(&venue.book.exchange, spent_asset);
+            }
+            // Converted venues buy with their own quote, counted in the
+            // order's, but sell the same base as the exchange's direct book
+            for converted in &mut converted_venues {
+                match order.side {
+                    OrderSide::Buy => {
+                        converted.venue.available = balances
+                            .available(&converted.exchange, &converted.conversion.pair.quote)
+                            .map(|available| available * converted.conversion.rate);
+                    }
+                    OrderSide::Sell => {
+                        converted.venue.available =
+                            balances.available(&converted.exchange, spent_asset);
+                        converted.venue.budget = Some(converted.exchange.clone());
+                    }
                 }
             }
         }
 
-        // Use optimizer to find best routing
-        let routing = match order.side {
-            OrderSide::Buy => optimizer::optimize_buy_order(order, &liquidities)?,
-            OrderSide::Sell => optimizer::optimize_sell_order(order, &liquidities)?,
+        // Converted books compete with the direct ones under names of their
+        // own, and synthetic books as fee-free venues
+        let direct_venues = venues.len();
+        venues.extend(
+            converted_venues
+                .iter()
+                .map(|converted| converted.venue.clone()),
+        );
+        venues.extend(synthetic_books.iter().map(|book| book.venue().clone()));
+
+        // Use optimizer to find best routing, weighing quotes against the books
+        let mut routing = optimizer::optimize_with_quotes(order, &venues, &quotes)?;
+        let (splits, synthetic_splits): (Vec<OrderSplit>, Vec<OrderSplit>) =
+            routing.splits.into_iter().partition(|split| {
+                !synthetic_books
+                    .iter()
+                    .any(|book| book.venue().book.exchange == split.exchange)
+            });
+        routing.splits = splits;
+        for split in &synthetic_splits {
+            let Some(book) = synthetic_books
+                .iter()
+                .find(|book| book.venue().book.exchange == split.exchange)
+            else {
+                continue;
+            };
+            let route = book.route(split.quantity);
+            if route.quantity > dec!(0) {
+                routing.synthetic_routes.push(route);
+            }
+        }
+        // Whatever the legs' rounding left out goes unfilled
+        optimizer::summarize(&mut routing, &venues);
+        let converted = |exchange: &str| {
+            converted_venues
+                .iter()
+                .find(|converted| converted.venue.book.exchange == exchange)
         };
+        for split in &mut routing.splits {
+            if let Some(converted) = converted(&split.exchange) {
+                split.exchange = converted.exchange.clone();
+                split.conversion = Some(converted.conversion.clone());
+            }
+        }
+        for limit in &mut routing.balance_limited_venues {
+            if let Some(converted) = converted(&limit.exchange) {
+                limit.exchange = converted.exchange.clone();
+                if order.side == OrderSide::Buy {
+                    limit.asset = converted.conversion.pair.quote.to_uppercase();
+                    limit.available /= converted.conversion.rate;
+                }
+            }
+        }
+        // Books sharing one balance report it once
+        let mut reported: Vec<(String, String)> = Vec::new();
+        routing.balance_limited_venues.retain(|limit| {
+            let key = (limit.exchange.clone(), limit.asset.clone());
+            let first = !reported.contains(&key);
+            reported.push(key);
+            first
+        });
+        if self.config.rest_unfilled_limit {
+            routing.resting_order =
+                optimizer::propose_resting_order(&routing, &venues[..direct_venues]);
+        }
+        routing.skipped_venues = skipped_venues;
+        routing.cached_venues = cached_venues;
 
-        log::info!("Routing complete: {} splits", routing.splits.len());
+        log::info!(
+            "Routing complete: {} splits, {} quotes, {} synthetic routes",
+            routing.splits.len(),
+            routing.quotes.len(),
+            routing.synthetic_routes.len()
+        );
         Ok(routing)
     }
 
+    /// Fetch order books and fee rates from all exchanges concurrently.
+    ///
+    /// Each venue gets `venue_timeout`, capped by the overall
+    /// `routing_budget`; venues that miss their deadline or fail are
+    /// reported alongside the books that did arrive. A venue that is
+    /// throttling us is priced from its last book if that is recent enough,
+    /// rather than queueing more requests. Quotes older than the venue's
+    /// quote age limit are skipped, and younger ones are ranked a little
+    /// worse the older they are.
+    async fn fetch_venues(
+        &self,
+        order: &Order,
+        excluded: &[String],
+    ) -> (Vec<VenueBook>, Vec<SkippedVenue>, Vec<String>) {
+        self.collect_venues(order, excluded, true).await
+    }
+
+    /// Fetch the books a synthetic leg or a quote conversion needs, like
+    /// [`Self::fetch_venues`] but without touching venue health: these are
+    /// pairs the venue may not even list, so they neither claim a
+    /// half-open breaker's probe nor count as its outcome, and venues whose
+    /// breaker is not closed are left out
+    async fn fetch_auxiliary_venues(&self, order: &Order, excluded: &[String]) -> Vec<VenueBook> {
+        self.collect_venues(order, excluded, false).await.0
+    }
+
+    async fn collect_venues(
+        &self,
+        order: &Order,
+        excluded: &[String],
+        track_health: bool,
+    ) -> (Vec<VenueBook>, Vec<SkippedVenue>, Vec<String>) {
+        let started = Instant::now();
+        let budget_deadline = started + self.config.routing_budget;
+        let venue_deadline = (started + self.config.venue_timeout).min(budget_deadline);
+
+        let fetches = self.exchanges.iter().map(|exchange| async move {
+            let name = exchange.name();
+            if excluded.iter().any(|excluded| excluded == name) {
+                return (name, Err(SkipReason::Excluded));
+            }
+            let admitted = if track_health {
+                self.health.admit(name)
+            } else {
+                self.health.is_closed(name)
+            };
+            if !admitted {
+                return (name, Err(SkipReason::CircuitOpen));
+            }
+
+            let outcome = timeout_at(venue_deadline, self.fetch_venue(exchange.as_ref(), order))
+                .await
+                .unwrap_or(Err(SkipReason::Timeout));
+            if track_health {
+                (name, self.record_health(name, outcome, started.elapsed()))
+            } else {
+                (name, outcome)
+            }
+        });
+
+        let mut venues = Vec::new();
+        let mut skipped = Vec::new();
+        let mut cached = Vec::new();
+        for (name, outcome) in join_all(fetches).await {
+            match outcome {
+                Ok((venue, false)) => venues.push(venue),
+                Ok((venue, true)) => {
+                    log::info!("Pricing {} from its cached book: rate limited", name);
+                    venues.push(venue);
+                    cached.push(name.to_string());
+                }
+                Err(reason) => {
+                    log::warn!("Skipping {}: {}", name, reason);
+                    skipped.push(SkippedVenue {
+                        exchange: name.to_string(),
+                        reason,
+                    });
+                }
+            }
+        }
+
+        (venues, skipped, cached)
+    }
+
+    /// One venue's book for the order's pair, with its fees and rules, and
+    /// whether it came from the cache
+    async fn fetch_venue(
+        &self,
+        exchange: &dyn Exchange,
+        order: &Order,
+    ) -> Result<(VenueBook, bool), SkipReason> {
+        match exchange
+            .pair_status(&order.pair)
+            .await
+            .map_err(skip_reason)?
+        {
+            None => return Err(SkipReason::UnsupportedPair),
+            Some(status) if !status.accepts(order.order_type) => {
+                return Err(SkipReason::NotTrading(status))
+            }
+            Some(_) => {}
+        }
+        let (book, rules) = tokio::join!(
+            exchange.get_liquidity(&order.pair),
+            exchange.instrument_rules(&order.pair)
+        );
+        let max_quote_age = exchange
+            .max_quote_age()
+            .unwrap_or(self.config.max_quote_age);
+        let (book, cached) = match book {
+            Ok(book) => (book, false),
+            Err(e @ ExchangeError::RateLimited { .. }) => {
+                match self.cached_book(exchange.name(), &order.pair, max_quote_age) {
+                    Some(book) => (book, true),
+                    None => return Err(skip_reason(e)),
+                }
+            }
+            Err(e) => return Err(skip_reason(e)),
+        };
+        let age = book.quote_age(Utc::now());
+        if age > max_quote_age {
+            return Err(SkipReason::StaleQuote(age));
+        }
+        let fees = exchange.fee_schedule().rates_for(&order.pair);
+        let venue = VenueBook::new(book, fees)
+            .with_rules(rules.map_err(skip_reason)?)
+            .with_staleness_penalty(self.staleness_penalty(age));
+        Ok((venue, cached))
+    }
+
+    /// Fetch both legs of every synthetic route for the order's pair and
+    /// chain them into books.
+    ///
+    /// Legs are fetched like direct books, for market orders since that is
+    /// how they are sent. A leg nobody quotes only means there is no route
+    /// through that asset, so leg venues that fail are not reported.
+    /// Currencies equivalent to the order's quote are not passed through:
+    /// their books are converted into the route instead.
+    async fn synthetic_books(&self, order: &Order, excluded: &[String]) -> Vec<SyntheticBook> {
+        let pair = &order.pair;
+        let equivalents = self
+            .quote_equivalence
+            .as_ref()
+            .map(|equivalence| equivalence.alternatives(&pair.quote))
+            .unwrap_or_default();
+        let routes = self
+            .intermediate_assets
+            .iter()
+            .filter(|via| **via != pair.base.to_uppercase() && **via != pair.quote.to_uppercase())
+            .filter(|via| !equivalents.contains(via))
+            .map(|via| async move {
+                let leg = |pair| Order {
+                    pair,
+                    order_type: OrderType::Market,
+                    limit_price: None,
+                    ..order.clone()
+                };
+                let first = leg(TradingPair::new(&pair.base, via));
+                let second = leg(TradingPair::new(via, &pair.quote));
+                let (base_via, via_quote) = tokio::join!(
+                    self.fetch_auxiliary_venues(&first, excluded),
+                    self.fetch_auxiliary_venues(&second, excluded)
+                );
+                SyntheticBook::compose(pair, order.side, via, &base_via, &via_quote)
+            });
+        join_all(routes).await.into_iter().flatten().collect()
+    }
+
+    /// Fetch the order's base quoted in every equivalent currency and
+    /// price those books in the order's quote.
+    ///
+    /// The rate for each currency is the mid of its market against the
+    /// order's quote across all venues, either way round, or the
+    /// configured fallback where no venue quotes one. Currencies with no
+    /// rate are left out. Like synthetic legs, converted venues that fail
+    /// are not reported.
+    async fn converted_venues(&self, order: &Order, excluded: &[String]) -> Vec<ConvertedVenue> {
+        let Some(equivalence) = &self.quote_equivalence else {
+            return Vec::new();
+        };
+        let pair = &order.pair;
+        let conversions =
+            equivalence
+                .alternatives(&pair.quote)
+                .into_iter()
+                .map(|quote| async move {
+                    let market = |pair| Order {
+                        pair,
+                        order_type: OrderType::Market,
+                        limit_price: None,
+                        ..order.clone()
+                    };
+                    let books = Order {
+                        pair: TradingPair::new(&pair.base, &quote),
+                        ..order.clone()
+                    };
+                    let direct = market(TradingPair::new(&quote, &pair.quote));
+                    let inverse = market(TradingPair::new(&pair.quote, &quote));
+                    let (venues, direct, inverse) = tokio::join!(
+                        self.fetch_auxiliary_venues(&books, excluded),
+                        self.fetch_auxiliary_venues(&direct, excluded),
+                        self.fetch_auxiliary_venues(&inverse, excluded)
+                    );
+                    let rate = mid_rate(&direct)
+                        .or_else(|| mid_rate(&inverse).map(|rate| dec!(1) / rate))
+                        .or_else(|| equivalence.fallback_rate(&quote, &pair.quote));
+                    let Some(rate) = rate else {
+                        log::warn!(
+                            "No {}/{} rate, leaving out {} books",
+                            quote,
+                            pair.quote,
+                            quote
+                        );
+                        return Vec::new();
+                    };
+                    venues
+                        .iter()
+                        .map(|venue| ConvertedVenue::new(venue, pair, rate, equivalence.buffer()))
+                        .collect()
+                });
+        join_all(conversions).await.into_iter().flatten().collect()
+    }
+
+    /// Broadcast a quote request to every RFQ venue and collect the firm
+    /// quotes that arrive within `rfq_timeout`.
+    ///
+    /// Orders under `min_rfq_quantity` are not quoted. Quotes that do not
+    /// match the request or that would expire within `min_quote_life` are
+    /// dropped, and every venue without a usable quote is reported.
+    async fn collect_quotes(
+        &self,
+        order: &Order,
+        excluded: &[String],
+    ) -> (Vec<RfqQuote>, Vec<SkippedVenue>) {
+        if self.rfq_venues.is_empty() || order.quantity < self.config.min_rfq_quantity {
+            return (Vec::new(), Vec::new());
+        }
+        let deadline = Instant::now() + self.config.rfq_timeout.min(self.config.routing_budget);
+        let request = QuoteRequest {
+            request_id: format!(
+                "rfq-{}",
+                self.next_quote_request.fetch_add(1, Ordering::SeqCst)
+            ),
+            pair: order.pair.clone(),
+            side: order.side,
+            quantity: order.quantity,
+        };
+
+        let requests = self.rfq_venues.iter().map(|venue| {
+            let request = &request;
+            async move {
+                let name = venue.name();
+                if excluded.iter().any(|excluded| excluded == name) {
+                    return (name, Err(SkipReason::Excluded));
+                }
+                let quote = match timeout_at(deadline, venue.request_quote(request)).await {
+                    Err(_) => Err(SkipReason::Timeout),
+                    Ok(Err(e)) => Err(SkipReason::Error(e.to_string())),
+                    Ok(Ok(None)) => Err(SkipReason::QuoteDeclined),
+                    Ok(Ok(Some(quote))) => {
+                        if quote.pair != request.pair || quote.side != request.side {
+                            Err(SkipReason::Error(
+                                "quote does not match request".to_string(),
+                            ))
+                        } else if quote.time_to_expiry(Utc::now()) < self.config.min_quote_life {
+                            Err(SkipReason::QuoteExpired)
+                        } else {
+                            Ok(quote)
+                        }
+                    }
+                };
+                (name, quote)
+            }
+        });
+
+        let mut quotes = Vec::new();
+        let mut skipped = Vec::new();
+        for (name, quote) in join_all(requests).await {
+            match quote {
+                Ok(quote) => quotes.push(quote),
+                Err(reason) => {
+                    log::info!("No quote from {}: {}", name, reason);
+                    skipped.push(SkippedVenue {
+                        exchange: name.to_string(),
+                        reason,
+                    });
+                }
+            }
+        }
+        (quotes, skipped)
+    }
+
+    /// Feed a fetch outcome to the health monitor, turning books it
+    /// rejects into skips
+    fn record_health(
+        &self,
+        name: &str,
+        outcome: Result<(VenueBook, bool), SkipReason>,
+        latency: Duration,
+    ) -> Result<(VenueBook, bool), SkipReason> {
+        match outcome {
+            Ok((venue, false)) => {
+                self.health
+                    .record_book(name, &venue.book, latency)
+                    .map_err(SkipReason::InvalidBook)?;
+                self.remember_book(&venue.book);
+                Ok((venue, false))
+            }
+            Err(
+                reason @ (SkipReason::Timeout | SkipReason::Maintenance | SkipReason::Error(_)),
+            ) => {
+                self.health
+                    .record_failure(name, latency, reason.to_string());
+                Err(reason)
+            }
+            // Cached books, throttling, stale quotes and unlisted pairs say
+            // nothing about the venue's health; a lagging venue shows up as
+            // latency instead
+            other => {
+                self.health.record_neutral(name);
+                other
+            }
+        }
+    }
+
+    /// Circuit state, error rate and latency of every exchange
+    pub fn health_report(&self) -> Vec<VenueHealthReport> {
+        self.exchanges
+            .iter()
+            .map(|exchange| self.health.report(exchange.name()))
+            .collect()
+    }
+
+    /// Ranking penalty for a quote `age` old
+    fn staleness_penalty(&self, age: Duration) -> Decimal {
+        Decimal::from(age.as_millis() as u64) / dec!(1000)
+            * self.config.staleness_penalty_per_second
+    }
+
+    fn remember_book(&self, book: &OrderBook) {
+        self.book_cache.lock().unwrap().insert(
+            (book.exchange.clone(), book.pair.clone()),
+            (Instant::now(), book.clone()),
+        );
+    }
+
+    fn cached_book(
+        &self,
+        exchange: &str,
+        pair: &TradingPair,
+        max_quote_age: Duration,
+    ) -> Option<OrderBook> {
+        let cache = self.book_cache.lock().unwrap();
+        let (fetched_at, book) = cache.get(&(exchange.to_string(), pair.clone()))?;
+        let max_age = self.config.max_cached_book_age.min(max_quote_age);
+        (fetched_at.elapsed() <= max_age).then(|| book.clone())
+    }
+
+    /// Pairs listed by every exchange, keyed by exchange name.
+    ///
+    /// Exchanges that cannot list their pairs in time are left out.
+    pub async fn list_pairs(&self) -> BTreeMap<String, Vec<PairListing>> {
+        let deadline = Instant::now() + self.config.venue_timeout;
+        let queries = self.exchanges.iter().map(|exchange| async move {
+            let listings = timeout_at(deadline, exchange.list_pairs()).await;
+            (exchange.name(), listings)
+        });
+
+        let mut pairs = BTreeMap::new();
+        for (name, listings) in join_all(queries).await {
+            match listings {
+                Ok(Ok(listings)) => {
+                    pairs.insert(name.to_string(), listings);
+                }
+                Ok(Err(e)) => log::warn!("No pair list from {}: {:#}", name, e),
+                Err(_) => log::warn!("No pair list from {}: timed out", name),
+            }
+        }
+        pairs
+    }
+
+    /// Look up a connected exchange by name
+    pub fn exchange(&self, name: &str) -> Option<&dyn Exchange> {
+        self.exchanges
+            .iter()
+            .find(|exchange| exchange.name() == name)
+            .map(|exchange| exchange.as_ref())
+    }
+
+    /// Look up an RFQ venue by name
+    pub fn rfq_venue(&self, name: &str) -> Option<&dyn RfqVenue> {
+        self.rfq_venues
+            .iter()
+            .find(|venue| venue.name() == name)
+            .map(|venue| venue.as_ref())
+    }
+
     /// Get number of connected exchanges
     pub fn exchange_count(&self) -> usize {
         self.exchanges.len()
     }
 }
+
+/// Router policy for a venue whose liquidity could not be fetched.
+///
+/// Every failure leaves the venue out of the current routing; nothing is
+/// ever substituted for the missing book.
+fn skip_reason(error: ExchangeError) -> SkipReason {
+    match error {
+        ExchangeError::UnknownSymbol { .. } => SkipReason::UnsupportedPair,
+        ExchangeError::RateLimited { .. } => SkipReason::RateLimited,
+        ExchangeError::Maintenance { .. } => SkipReason::Maintenance,
+        other => SkipReason::Error(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::mock::MockExchange;
+    use crate::types::{OrderType, QuoteConversion, TradingPair};
+
+    /// Venue quoting BTC/USD that answers after `delay_ms`, or one that
+    /// lists nothing
+    fn stub(name: &str, delay_ms: u64, supported: bool) -> Box<dyn Exchange> {
+        let venue = MockExchange::new(name).with_latency(Duration::from_millis(delay_ms));
+        if !supported {
+            return Box::new(venue);
+        }
+        Box::new(venue.with_book(
+            TradingPair::new("BTC", "USD"),
+            &[(dec!(99), dec!(10))],
+            &[(dec!(100), dec!(10))],
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_slow_and_unsupported_venues_are_skipped() {
+        let config = RouterConfig {
+            venue_timeout: Duration::from_millis(100),
+            routing_budget: Duration::from_millis(500),
+            ..RouterConfig::default()
+        };
+        let router = SmartOrderRouter::with_config(
+            vec![
+                stub("Fast", 10, true),
+                stub("Slow", 1000, true),
+                stub("Other", 0, false),
+            ],
+            config,
+        );
+        let order = Order {
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(1),
+            limit_price: None,
+        };
+
+        let routing = router.route_order(&order).await.unwrap();
+
+        assert_eq!(routing.splits.len(), 1);
+        assert_eq!(routing.splits[0].exchange, "Fast");
+        assert_eq!(
+            routing.skipped_venues,
+            vec![
+                SkippedVenue {
+                    exchange: "Slow".to_string(),
+                    reason: SkipReason::Timeout,
+                },
+                SkippedVenue {
+                    exchange: "Other".to_string(),
+                    reason: SkipReason::UnsupportedPair,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failing_venue_is_taken_out_by_circuit_breaker() {
+        let config = RouterConfig {
+            venue_timeout: Duration::from_millis(20),
+            health: HealthConfig {
+                min_samples: 2,
+                ..HealthConfig::default()
+            },
+            ..RouterConfig::default()
+        };
+        let router = SmartOrderRouter::with_config(
+            vec![stub("Fast", 0, true), stub("Slow", 100, true)],
+            config,
+        );
+        let order = Order {
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(1),
+            limit_price: None,
+        };
+
+        for _ in 0..2 {
+            let routing = router.route_order(&order).await.unwrap();
+            assert_eq!(routing.skipped_venues[0].reason, SkipReason::Timeout);
+        }
+        let started = Instant::now();
+        let routing = router.route_order(&order).await.unwrap();
+        assert_eq!(routing.skipped_venues[0].reason, SkipReason::CircuitOpen);
+        // Nobody waited for the open venue
+        assert!(started.elapsed() < Duration::from_millis(20));
+
+        let report = router.health_report();
+        assert_eq!(report[0].state, health::CircuitState::Closed);
+        assert_eq!(report[0].samples, 3);
+        assert!(matches!(report[1].state, health::CircuitState::Open { .. }));
+        assert_eq!(report[1].error_rate, 1.0);
+        assert_eq!(report[1].last_error.as_deref(), Some("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_leg_fetches_leave_venue_health_alone() {
+        let config = RouterConfig {
+            health: HealthConfig {
+                min_samples: 2,
+                ..HealthConfig::default()
+            },
+            ..RouterConfig::default()
+        };
+        // The order's own book is fine; the ETH/BTC leg always fails
+        let venue = MockExchange::new("Flaky")
+            .with_book(
+                TradingPair::new("ETH", "USD"),
+                &[],
+                &[(dec!(100), dec!(10))],
+            )
+            .with_book(
+                TradingPair::new("BTC", "USD"),
+                &[],
+                &[(dec!(2000), dec!(10))],
+            )
+            .then_fail(
+                TradingPair::new("ETH", "BTC"),
+                ExchangeError::network("Flaky", "connection reset"),
+            );
+        let router = SmartOrderRouter::with_config(vec![Box::new(venue)], config)
+            .with_intermediate_assets(["BTC"])
+            .with_quote_equivalence(QuoteEquivalence::stablecoins());
+        let order = Order {
+            pair: TradingPair::new("ETH", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(1),
+            limit_price: None,
+        };
+
+        for _ in 0..3 {
+            let routing = router.route_order(&order).await.unwrap();
+            assert!(routing.skipped_venues.is_empty());
+        }
+
+        let report = router.health_report();
+        assert_eq!(report[0].state, health::CircuitState::Closed);
+        assert_eq!(report[0].samples, 3);
+        assert_eq!(report[0].error_rate, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_throttled_venue_is_priced_from_cached_book() {
+        let order = Order {
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(1),
+            limit_price: None,
+        };
+        // Answers once, then reports every request as rate limited
+        let throttled = || {
+            Box::new(
+                MockExchange::new("Throttled")
+                    .with_book(order.pair.clone(), &[], &[(dec!(100), dec!(10))])
+                    .then_fail(
+                        order.pair.clone(),
+                        ExchangeError::RateLimited {
+                            exchange: "Throttled".to_string(),
+                            retry_after: None,
+                        },
+                    ),
+            )
+        };
+
+        let router = SmartOrderRouter::new(vec![throttled()]);
+        assert!(router
+            .route_order(&order)
+            .await
+            .unwrap()
+            .cached_venues
+            .is_empty());
+        let routing = router.route_order(&order).await.unwrap();
+        assert_eq!(routing.cached_venues, vec!["Throttled".to_string()]);
+        assert_eq!(routing.splits[0].quantity, dec!(1));
+
+        // Too old to trust: the venue is skipped instead
+        let config = RouterConfig {
+            max_cached_book_age: Duration::ZERO,
+            ..RouterConfig::default()
+        };
+        let router = SmartOrderRouter::with_config(vec![throttled()], config);
+        router.route_order(&order).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(router.route_order(&order).await.is_err());
+
+        // A cached book is never older than the venue would quote
+        let config = RouterConfig {
+            max_cached_book_age: Duration::from_secs(60),
+            ..RouterConfig::default()
+        };
+        let capped = throttled().with_max_quote_age(Duration::from_millis(20));
+        let router = SmartOrderRouter::with_config(vec![Box::new(capped)], config);
+        router.route_order(&order).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(router.route_order(&order).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_balances_constrain_routing() {
+        use crate::exchanges::fees::FeeSchedule;
+        use crate::types::Balance;
+
+        let pair = TradingPair::new("BTC", "USD");
+        let funded = MockExchange::new("Cheap")
+            .with_book(pair.clone(), &[], &[(dec!(100), dec!(5))])
+            .with_fees(FeeSchedule::flat(dec!(0), dec!(0)))
+            .with_balance(Balance::new("USD", dec!(80), dec!(30)));
+        let unknown = MockExchange::new("Dear")
+            .with_book(pair.clone(), &[], &[(dec!(110), dec!(5))])
+            .with_fees(FeeSchedule::flat(dec!(0), dec!(0)));
+        let router = SmartOrderRouter::new(vec![Box::new(funded), Box::new(unknown)]);
+        let order = Order {
+            pair,
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(1),
+            limit_price: None,
+        };
+
+        let balances = router.fetch_balances().await;
+        let routing = router
+            .route_order_with_balances(&order, &balances)
+            .await
+            .unwrap();
+
+        // 80 USD held, 30 reserved: 50 USD buys 0.5 BTC on the cheap venue
+        assert_eq!(routing.splits[0].exchange, "Cheap");
+        assert_eq!(routing.splits[0].quantity, dec!(0.5));
+        assert_eq!(routing.splits[1].exchange, "Dear");
+        assert_eq!(routing.splits[1].quantity, dec!(0.5));
+        assert_eq!(routing.balance_limited_venues.len(), 1);
+        assert_eq!(routing.balance_limited_venues[0].available, dec!(50));
+    }
+
+    #[tokio::test]
+    async fn test_venues_not_trading_the_pair_are_skipped() {
+        use crate::types::TradingStatus;
+
+        let pair = TradingPair::new("BTC", "USD");
+        let book =
+            |name| MockExchange::new(name).with_book(pair.clone(), &[], &[(dec!(100), dec!(5))]);
+        let router = SmartOrderRouter::new(vec![
+            Box::new(book("Open")),
+            Box::new(book("Halted").with_status(pair.clone(), TradingStatus::Halted)),
+            Box::new(book("LimitOnly").with_status(pair.clone(), TradingStatus::LimitOnly)),
+            Box::new(MockExchange::new("Unlisted")),
+        ]);
+        let order = Order {
+            pair: pair.clone(),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(1),
+            limit_price: None,
+        };
+
+        let routing = router.route_order(&order).await.unwrap();
+
+        assert_eq!(routing.splits.len(), 1);
+        assert_eq!(routing.splits[0].exchange, "Open");
+        assert_eq!(
+            routing.skipped_venues,
+            vec![
+                SkippedVenue {
+                    exchange: "Halted".to_string(),
+                    reason: SkipReason::NotTrading(TradingStatus::Halted),
+                },
+                SkippedVenue {
+                    exchange: "LimitOnly".to_string(),
+                    reason: SkipReason::NotTrading(TradingStatus::LimitOnly),
+                },
+                SkippedVenue {
+                    exchange: "Unlisted".to_string(),
+                    reason: SkipReason::UnsupportedPair,
+                },
+            ]
+        );
+
+        let pairs = router.list_pairs().await;
+        assert_eq!(pairs.len(), 4);
+        assert_eq!(pairs["Halted"][0].status, TradingStatus::Halted);
+        assert!(pairs["Unlisted"].is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_block_orders_are_quoted_by_rfq_venues() {
+        use crate::exchanges::rfq::MockRfqVenue;
+
+        let pair = TradingPair::new("BTC", "USD");
+        let desk =
+            |name, price| MockRfqVenue::new(name).with_price(pair.clone(), OrderSide::Buy, price);
+        let config = RouterConfig {
+            min_rfq_quantity: dec!(5),
+            rfq_timeout: Duration::from_millis(100),
+            ..RouterConfig::default()
+        };
+        let router = SmartOrderRouter::with_config(
+            vec![Box::new(MockExchange::new("Lit").with_book(
+                pair.clone(),
+                &[],
+                &[(dec!(100), dec!(2)), (dec!(110), dec!(10))],
+            ))],
+            config,
+        )
+        .with_rfq_venues(vec![
+            Box::new(desk("Desk", dec!(104))),
+            Box::new(desk("Sharp", dec!(101)).with_latency(Duration::from_millis(500))),
+            Box::new(desk("Fleeting", dec!(101)).with_quote_ttl(Duration::from_millis(100))),
+            Box::new(MockRfqVenue::new("Quiet")),
+        ]);
+        let order = |quantity| Order {
+            pair: pair.clone(),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity,
+            limit_price: None,
+        };
+
+        let routing = router.route_order(&order(dec!(5))).await.unwrap();
+
+        assert!(routing.splits.is_empty());
+        assert_eq!(routing.quotes.len(), 1);
+        assert_eq!(routing.quotes[0].venue, "Desk");
+        assert_eq!(routing.quotes[0].quantity, dec!(5));
+        assert_eq!(routing.average_price, dec!(104));
+        let skipped: Vec<_> = routing
+            .skipped_venues
+            .iter()
+            .map(|skipped| (skipped.exchange.as_str(), skipped.reason.clone()))
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![
+                ("Sharp", SkipReason::Timeout),
+                ("Fleeting", SkipReason::QuoteExpired),
+                ("Quiet", SkipReason::QuoteDeclined),
+            ]
+        );
+
+        // Small orders go to the book without asking for quotes
+        let routing = router.route_order(&order(dec!(1))).await.unwrap();
+        assert!(routing.quotes.is_empty());
+        assert!(routing.skipped_venues.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_orders_split_across_direct_and_synthetic_routes() {
+        use crate::exchanges::fees::FeeSchedule;
+        use crate::types::InstrumentRules;
+
+        let venue = |name, base, quote, asks: &[(Decimal, Decimal)]| -> Box<dyn Exchange> {
+            Box::new(
+                MockExchange::new(name)
+                    .with_book(TradingPair::new(base, quote), &[], asks)
+                    .with_fees(FeeSchedule::flat(dec!(0), dec!(0))),
+            )
+        };
+        let router = SmartOrderRouter::new(vec![
+            venue(
+                "Direct",
+                "ETH",
+                "USD",
+                &[(dec!(100.3), dec!(3)), (dec!(105), dec!(10))],
+            ),
+            venue("Alts", "ETH", "BTC", &[(dec!(0.05), dec!(10))]),
+            venue("Majors", "BTC", "USD", &[(dec!(2000), dec!(0.3))]),
+        ])
+        .with_intermediate_assets(["btc", "usd"]);
+        let order = Order {
+            pair: TradingPair::new("ETH", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(8),
+            limit_price: None,
+        };
+
+        let routing = router.route_order(&order).await.unwrap();
+
+        // 6 ETH through BTC at 100, then 2 direct at 100.3
+        assert_eq!(routing.total_quantity, dec!(8));
+        assert_eq!(routing.average_price, dec!(100.075));
+        assert_eq!(routing.splits.len(), 1);
+        assert_eq!(routing.splits[0].exchange, "Direct");
+        assert_eq!(routing.splits[0].quantity, dec!(2));
+        assert_eq!(routing.synthetic_routes.len(), 1);
+        let route = &routing.synthetic_routes[0];
+        assert_eq!(route.via, "BTC");
+        assert_eq!(route.quantity, dec!(6));
+        assert_eq!(route.legs[0].splits[0].exchange, "Majors");
+        assert_eq!(route.legs[0].quantity, dec!(0.3));
+        assert_eq!(route.legs[1].splits[0].exchange, "Alts");
+        assert_eq!(route.legs[1].quantity, dec!(6));
+
+        // ETH/BTC trades in tenths: the odd 0.05 routed through BTC at 102
+        // goes unfilled rather than being sent, and stops counting against
+        // the price
+        let tenths: Box<dyn Exchange> = Box::new(
+            MockExchange::new("Alts")
+                .with_book(
+                    TradingPair::new("ETH", "BTC"),
+                    &[],
+                    &[(dec!(0.05), dec!(5)), (dec!(0.051), dec!(10))],
+                )
+                .with_fees(FeeSchedule::flat(dec!(0), dec!(0)))
+                .with_rules(
+                    TradingPair::new("ETH", "BTC"),
+                    InstrumentRules::new(dec!(0.0001), dec!(0.1)),
+                ),
+        );
+        let tenths = SmartOrderRouter::new(vec![
+            tenths,
+            venue("Majors", "BTC", "USD", &[(dec!(2000), dec!(1))]),
+        ])
+        .with_intermediate_assets(["btc"]);
+        let routing = tenths
+            .route_order(&Order {
+                quantity: dec!(5.05),
+                ..order.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(routing.synthetic_routes[0].quantity, dec!(5));
+        assert_eq!(routing.total_quantity, dec!(5));
+        assert_eq!(routing.unfilled_quantity, dec!(0.05));
+        assert_eq!(routing.average_price, dec!(100));
+        assert_eq!(routing.estimated_slippage, dec!(0));
+
+        // Without intermediate assets only the direct book is used
+        let direct_only = SmartOrderRouter::new(vec![venue(
+            "Direct",
+            "ETH",
+            "USD",
+            &[(dec!(100.3), dec!(3)), (dec!(105), dec!(10))],
+        )]);
+        let routing = direct_only.route_order(&order).await.unwrap();
+        assert!(routing.synthetic_routes.is_empty());
+        assert_eq!(routing.average_price, dec!(103.2375));
+    }
+
+    #[tokio::test]
+    async fn test_books_in_equivalent_quotes_are_converted_into_the_route() {
+        use crate::exchanges::fees::FeeSchedule;
+
+        let venue = |name, pair: TradingPair, bids: &[(Decimal, Decimal)], asks: &[_]| {
+            MockExchange::new(name)
+                .with_book(pair, bids, asks)
+                .with_fees(FeeSchedule::flat(dec!(0), dec!(0)))
+        };
+        let exchanges = || -> Vec<Box<dyn Exchange>> {
+            vec![
+                Box::new(venue(
+                    "Direct",
+                    TradingPair::new("BTC", "USD"),
+                    &[],
+                    &[(dec!(50100), dec!(1)), (dec!(50300), dec!(5))],
+                )),
+                Box::new(venue(
+                    "Tether",
+                    TradingPair::new("BTC", "USDT"),
+                    &[],
+                    &[(dec!(50000), dec!(2))],
+                )),
+                Box::new(venue(
+                    "Rates",
+                    TradingPair::new("USDT", "USD"),
+                    &[(dec!(0.9997), dec!(1000000))],
+                    &[(dec!(0.9999), dec!(1000000))],
+                )),
+            ]
+        };
+        let order = Order {
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(2.5),
+            limit_price: None,
+        };
+
+        let router = SmartOrderRouter::new(exchanges())
+            .with_quote_equivalence(QuoteEquivalence::stablecoins());
+        let routing = router.route_order(&order).await.unwrap();
+
+        // USDT at 0.9998 with a 10 basis point buffer puts the 50000 ask at
+        // 50039.99, under the direct book
+        assert_eq!(routing.splits.len(), 2);
+        assert_eq!(routing.splits[0].exchange, "Tether");
+        assert_eq!(routing.splits[0].quantity, dec!(2));
+        assert_eq!(routing.splits[0].expected_price, dec!(50039.99));
+        assert_eq!(
+            routing.splits[0].conversion,
+            Some(QuoteConversion {
+                pair: TradingPair::new("BTC", "USDT"),
+                rate: dec!(0.9998),
+            })
+        );
+        assert_eq!(routing.splits[1].exchange, "Direct");
+        assert_eq!(routing.splits[1].conversion, None);
+        assert_eq!(routing.average_price, dec!(50051.992));
+
+        // An equivalent quote is converted, never chained through
+        let routing = SmartOrderRouter::new(exchanges())
+            .with_quote_equivalence(QuoteEquivalence::stablecoins())
+            .with_intermediate_assets(["usdt"])
+            .route_order(&order)
+            .await
+            .unwrap();
+        assert!(routing.synthetic_routes.is_empty());
+        assert_eq!(routing.average_price, dec!(50051.992));
+
+        // Without equivalence the USDT book is not used
+        let routing = SmartOrderRouter::new(exchanges())
+            .route_order(&order)
+            .await
+            .unwrap();
+        assert_eq!(routing.splits.len(), 1);
+        assert_eq!(routing.splits[0].exchange, "Direct");
+    }
+
+    #[tokio::test]
+    async fn test_converted_sells_share_the_exchanges_base_balance() {
+        use crate::exchanges::fees::FeeSchedule;
+        use crate::types::Balance;
+
+        let both = MockExchange::new("Both")
+            .with_book(
+                TradingPair::new("BTC", "USD"),
+                &[(dec!(50000), dec!(2))],
+                &[],
+            )
+            .with_book(
+                TradingPair::new("BTC", "USDT"),
+                &[(dec!(50100), dec!(2))],
+                &[],
+            )
+            .with_book(
+                TradingPair::new("USDT", "USD"),
+                &[(dec!(0.9999), dec!(1000000))],
+                &[(dec!(1.0001), dec!(1000000))],
+            )
+            .with_fees(FeeSchedule::flat(dec!(0), dec!(0)))
+            .with_balance(Balance::new("BTC", dec!(2), dec!(0)));
+        let other = MockExchange::new("Other")
+            .with_book(
+                TradingPair::new("BTC", "USD"),
+                &[(dec!(49000), dec!(5))],
+                &[],
+            )
+            .with_fees(FeeSchedule::flat(dec!(0), dec!(0)))
+            .with_balance(Balance::new("BTC", dec!(5), dec!(0)));
+        let router = SmartOrderRouter::new(vec![Box::new(both), Box::new(other)])
+            .with_quote_equivalence(QuoteEquivalence::stablecoins());
+        let order = Order {
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Sell,
+            order_type: OrderType::Market,
+            quantity: dec!(3),
+            limit_price: None,
+        };
+
+        let balances = router.fetch_balances().await;
+        let routing = router
+            .route_order_with_balances(&order, &balances)
+            .await
+            .unwrap();
+
+        // Both's USDT and USD books sell the same 2 BTC between them
+        let on_both: Decimal = routing
+            .splits
+            .iter()
+            .filter(|split| split.exchange == "Both")
+            .map(|split| split.quantity)
+            .sum();
+        assert_eq!(on_both, dec!(2));
+        assert!(routing
+            .splits
+            .iter()
+            .any(|split| split.exchange == "Both" && split.conversion.is_some()));
+        let other = routing
+            .splits
+            .iter()
+            .find(|split| split.exchange == "Other")
+            .unwrap();
+        assert_eq!(other.quantity, dec!(1));
+        assert_eq!(routing.balance_limited_venues.len(), 1);
+        assert_eq!(routing.balance_limited_venues[0].exchange, "Both");
+        assert_eq!(routing.balance_limited_venues[0].available, dec!(2));
+    }
+
+    #[tokio::test]
+    async fn test_stale_quotes_are_skipped_and_aged_quotes_ranked_worse() {
+        let pair = TradingPair::new("BTC", "USD");
+        let venue =
+            |name, ask| MockExchange::new(name).with_book(pair.clone(), &[], &[(ask, dec!(1))]);
+        let router = SmartOrderRouter::new(vec![
+            Box::new(venue("Fresh", dec!(100))),
+            // Half a basis point cheaper but a second old: one basis point worse
+            Box::new(venue("Lagging", dec!(99.995)).with_quote_age(Duration::from_secs(1))),
+            Box::new(venue("Stale", dec!(90)).with_quote_age(Duration::from_secs(5))),
+            // Same age, but a pool that only moves once a block
+            Box::new(
+                venue("Pool", dec!(101))
+                    .with_quote_age(Duration::from_secs(5))
+                    .with_max_quote_age(Duration::from_secs(30)),
+            ),
+        ]);
+        let order = Order {
+            pair: pair.clone(),
+            side: OrderSide::Buy,
+            order_type: OrderType::Market,
+            quantity: dec!(1.5),
+            limit_price: None,
+        };
+
+        let routing = router.route_order(&order).await.unwrap();
+
+        assert_eq!(routing.splits.len(), 2);
+        assert_eq!(routing.splits[0].exchange, "Fresh");
+        assert_eq!(routing.splits[0].quantity, dec!(1));
+        assert_eq!(routing.splits[1].exchange, "Lagging");
+        // The penalty only reorders venues; fills are priced as quoted
+        assert_eq!(routing.splits[1].expected_price, dec!(99.995));
+        // The pool is within its own limit, just priced out
+        assert_eq!(routing.skipped_venues.len(), 1);
+        assert_eq!(routing.skipped_venues[0].exchange, "Stale");
+        assert!(matches!(
+            routing.skipped_venues[0].reason,
+            SkipReason::StaleQuote(age) if age >= Duration::from_secs(5)
+        ));
+    }
+}
@@ -1,103 +1,947 @@
-use crate::types::{Liquidity, Order, OrderSplit, RoutingResult};
+use crate::exchanges::fees::FeeRates;
+use crate::types::{
+    BalanceLimit, InstrumentRules, Order, OrderBook, OrderSide, OrderSplit, OrderType, PriceLevel,
+    RestingOrder, RfqQuote, RoutingResult,
+};
 use anyhow::{Context, Result};
+use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// An exchange's order book together with the fees it charges
+#[derive(Debug, Clone)]
+pub struct VenueBook {
+    pub book: OrderBook,
+    pub fees: FeeRates,
+    /// Balance of the asset the order spends here (quote for buys, base
+    /// for sells), net of reservations; `None` if unknown
+    pub available: Option<Decimal>,
+    /// Name of the balance `available` counts, when other books spend it
+    /// too; fills on all of them draw on it once. Defaults to the book's
+    /// own exchange name.
+    pub budget: Option<String>,
+    /// Size and increment rules every split sent here must satisfy
+    pub rules: InstrumentRules,
+    /// Fraction of price by which this book's levels are ranked worse
+    /// than they are quoted, to allow for the market moving since
+    pub staleness_penalty: Decimal,
+}
+
+impl VenueBook {
+    pub fn new(book: OrderBook, fees: FeeRates) -> Self {
+        Self {
+            book,
+            fees,
+            available: None,
+            budget: None,
+            rules: InstrumentRules::default(),
+            staleness_penalty: dec!(0),
+        }
+    }
+
+    pub fn with_rules(mut self, rules: InstrumentRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn with_available(mut self, available: Decimal) -> Self {
+        self.available = Some(available);
+        self
+    }
+
+    pub fn with_budget(mut self, budget: impl Into<String>) -> Self {
+        self.budget = Some(budget.into());
+        self
+    }
+
+    /// Name of the balance this book's fills draw on
+    fn budget_name(&self) -> &str {
+        self.budget.as_deref().unwrap_or(&self.book.exchange)
+    }
+
+    pub fn with_staleness_penalty(mut self, staleness_penalty: Decimal) -> Self {
+        self.staleness_penalty = staleness_penalty;
+        self
+    }
+}
+
+/// A price level annotated with its venue and all-in price
+#[derive(Debug, Clone, Copy)]
+struct RankedLevel<'a> {
+    exchange: &'a str,
+    /// Balance fills on this level draw on
+    budget: &'a str,
+    level: PriceLevel,
+    fee_rate: Decimal,
+    /// Fixed fee charged once per split on this level's venue
+    per_order_fee: Decimal,
+    effective_price: Decimal,
+    /// Effective price worsened by the book's staleness penalty; used only
+    /// to order levels, never to price fills
+    rank_price: Decimal,
+}
 
 /// Optimize routing for a buy order
-pub fn optimize_buy_order(order: &Order, liquidities: &[Liquidity]) -> Result<RoutingResult> {
-    // Sort exchanges by best ask price (lowest first)
-    let mut sorted_liquidities = liquidities.to_vec();
-    sorted_liquidities.sort_by(|a, b| a.ask_price.cmp(&b.ask_price));
+pub fn optimize_buy_order(order: &Order, venues: &[VenueBook]) -> Result<RoutingResult> {
+    optimize(order, venues, OrderSide::Buy)
+}
 
-    let mut splits = Vec::new();
-    let mut remaining_quantity = order.quantity;
-    let mut total_cost = dec!(0);
+/// Optimize routing for a sell order
+pub fn optimize_sell_order(order: &Order, venues: &[VenueBook]) -> Result<RoutingResult> {
+    optimize(order, venues, OrderSide::Sell)
+}
 
-    // Greedy algorithm: fill from cheapest exchange first
-    for liquidity in sorted_liquidities {
-        if remaining_quantity <= dec!(0) {
-            break;
+/// Optimize routing across order books and firm RFQ quotes.
+///
+/// Quotes are taken whole or not at all. Best price first, each quote that
+/// still fits the order is added to the ones before it and the books are
+/// walked for what is left; the plan that fills the most at the lowest
+/// all-in cost wins. With no quote worth taking this is the book-only
+/// routing, and quotes can fill an order the books alone cannot.
+pub fn optimize_with_quotes(
+    order: &Order,
+    venues: &[VenueBook],
+    quotes: &[RfqQuote],
+) -> Result<RoutingResult> {
+    let side = order.side;
+    let mut usable: Vec<&RfqQuote> = quotes
+        .iter()
+        .filter(|quote| quote.pair == order.pair && quote.side == side)
+        .filter(|quote| quote.quantity > dec!(0) && quote.quantity <= order.quantity)
+        .filter(|quote| match (order.order_type, order.limit_price, side) {
+            (OrderType::Limit, Some(limit), OrderSide::Buy) => quote.price <= limit,
+            (OrderType::Limit, Some(limit), OrderSide::Sell) => quote.price >= limit,
+            _ => true,
+        })
+        .collect();
+    match side {
+        OrderSide::Buy => usable.sort_by_key(|quote| quote.price),
+        OrderSide::Sell => usable.sort_by_key(|quote| Reverse(quote.price)),
+    }
+
+    let book_only = optimize(order, venues, side);
+    let mut best = book_only.as_ref().ok().cloned();
+    let mut taken: Vec<RfqQuote> = Vec::new();
+    for quote in usable {
+        let quoted: Decimal = taken.iter().map(|q| q.quantity).sum();
+        if quoted + quote.quantity > order.quantity {
+            continue;
+        }
+        // A quote is only kept once the plan using it wins
+        let mut trial = taken.clone();
+        trial.push(quote.clone());
+        let Ok(candidate) = blend_quotes(order, venues, &trial) else {
+            continue;
+        };
+        let better = match &best {
+            None => true,
+            Some(best) => {
+                candidate.total_quantity > best.total_quantity
+                    || (candidate.total_quantity == best.total_quantity
+                        && routing_cost(&candidate) < routing_cost(best))
+            }
+        };
+        if better {
+            taken = trial;
+            best = Some(candidate);
         }
+    }
 
-        let available = liquidity.ask_quantity;
-        let fill_quantity = remaining_quantity.min(available);
+    match best {
+        Some(routing) => Ok(routing),
+        None => book_only,
+    }
+}
 
-        splits.push(OrderSplit {
-            exchange: liquidity.exchange.clone(),
-            quantity: fill_quantity,
-            expected_price: liquidity.ask_price,
-        });
+/// Fill `quotes` in full and route whatever they leave through the books
+fn blend_quotes(order: &Order, venues: &[VenueBook], quotes: &[RfqQuote]) -> Result<RoutingResult> {
+    let side = order.side;
+    let quoted_quantity: Decimal = quotes.iter().map(|q| q.quantity).sum();
+    let remainder = order.quantity - quoted_quantity;
 
-        total_cost += fill_quantity * liquidity.ask_price;
-        remaining_quantity -= fill_quantity;
+    let mut routing = if remainder > dec!(0) {
+        let remainder_order = Order {
+            quantity: remainder,
+            ..order.clone()
+        };
+        optimize(&remainder_order, venues, side)?
+    } else {
+        RoutingResult {
+            original_order: order.clone(),
+            splits: Vec::new(),
+            quotes: Vec::new(),
+            synthetic_routes: Vec::new(),
+            total_quantity: dec!(0),
+            average_price: dec!(0),
+            estimated_slippage: dec!(0),
+            unfilled_quantity: dec!(0),
+            resting_order: None,
+            skipped_venues: Vec::new(),
+            balance_limited_venues: Vec::new(),
+            cached_venues: Vec::new(),
+        }
+    };
+
+    routing.original_order = order.clone();
+    routing.quotes = quotes.to_vec();
+    summarize(&mut routing, venues);
+    Ok(routing)
+}
+
+/// Recompute a routing's total, average price, slippage and unfilled
+/// quantity from its splits, quotes and synthetic routes, after they have
+/// been put together or trimmed.
+///
+/// Slippage is measured from the best price shown anywhere, book or quote.
+pub fn summarize(routing: &mut RoutingResult, venues: &[VenueBook]) {
+    let side = routing.original_order.side;
+    let fills = routing
+        .splits
+        .iter()
+        .map(|split| (split.quantity, split.expected_price))
+        .chain(
+            routing
+                .quotes
+                .iter()
+                .map(|quote| (quote.quantity, quote.price)),
+        )
+        .chain(
+            routing
+                .synthetic_routes
+                .iter()
+                .map(|route| (route.quantity, route.expected_price)),
+        );
+    let (total_quantity, notional) = fills.fold(
+        (dec!(0), dec!(0)),
+        |(quantity, notional), (fill_quantity, price)| {
+            (quantity + fill_quantity, notional + fill_quantity * price)
+        },
+    );
+
+    let top_of_books = venues.iter().filter_map(|venue| match side {
+        OrderSide::Buy => venue.book.best_ask().map(|level| level.price),
+        OrderSide::Sell => venue.book.best_bid().map(|level| level.price),
+    });
+    let quote_prices = routing.quotes.iter().map(|quote| quote.price);
+    let best_price = match side {
+        OrderSide::Buy => top_of_books.chain(quote_prices).min(),
+        OrderSide::Sell => top_of_books.chain(quote_prices).max(),
+    };
+
+    routing.total_quantity = total_quantity;
+    routing.unfilled_quantity = routing.original_order.quantity - total_quantity;
+    (routing.average_price, routing.estimated_slippage) = match best_price {
+        Some(best_price) if total_quantity > dec!(0) && best_price > dec!(0) => {
+            let average_price = notional / total_quantity;
+            let slippage = match side {
+                OrderSide::Buy => (average_price - best_price) / best_price,
+                OrderSide::Sell => (best_price - average_price) / best_price,
+            };
+            (average_price, slippage * dec!(100))
+        }
+        _ => (dec!(0), dec!(0)),
+    };
+}
+
+/// Quote spent including fees for buys, or minus proceeds net of fees for
+/// sells, so that lower is better either way
+fn routing_cost(routing: &RoutingResult) -> Decimal {
+    let notional: Decimal = routing
+        .splits
+        .iter()
+        .map(|split| split.quantity * split.expected_price)
+        .chain(
+            routing
+                .quotes
+                .iter()
+                .map(|quote| quote.quantity * quote.price),
+        )
+        .sum();
+    let fees: Decimal = routing.splits.iter().map(|split| split.expected_fee).sum();
+    match routing.original_order.side {
+        OrderSide::Buy => notional + fees,
+        OrderSide::Sell => fees - notional,
     }
+}
 
-    if remaining_quantity > dec!(0) {
-        anyhow::bail!("Insufficient liquidity to fill order");
+/// Propose resting the unfilled remainder of a limit order as a passive
+/// order at its limit price.
+///
+/// The venue with the lowest maker fee is chosen, preferring the deepest
+/// book on the order's own side when fees tie. Price and quantity are
+/// rounded to the venue's rules, and venues that would not accept the
+/// rounded order are passed over.
+pub fn propose_resting_order(
+    routing: &RoutingResult,
+    venues: &[VenueBook],
+) -> Option<RestingOrder> {
+    let order = &routing.original_order;
+    let price = order.limit_price?;
+    if order.order_type != OrderType::Limit || routing.unfilled_quantity <= dec!(0) {
+        return None;
     }
 
-    let average_price = total_cost / order.quantity;
-    let best_price = liquidities
+    let rounded = |venue: &VenueBook| {
+        (
+            venue.rules.round_quantity(routing.unfilled_quantity),
+            venue.rules.round_price(price, order.side),
+        )
+    };
+    let venue = venues
         .iter()
-        .map(|l| l.ask_price)
-        .min()
-        .context("No liquidity available")?;
-    let estimated_slippage = ((average_price - best_price) / best_price) * dec!(100);
+        .filter(|venue| {
+            let (quantity, price) = rounded(venue);
+            venue.rules.accepts(quantity, price)
+        })
+        .min_by_key(|venue| {
+            let passive_side = match order.side {
+                OrderSide::Buy => &venue.book.bids,
+                OrderSide::Sell => &venue.book.asks,
+            };
+            let depth: Decimal = passive_side.iter().map(|level| level.quantity).sum();
+            (venue.fees.maker, Reverse(depth))
+        })?;
+
+    let (quantity, price) = rounded(venue);
+    Some(RestingOrder {
+        exchange: venue.book.exchange.clone(),
+        quantity,
+        price,
+    })
+}
+
+fn optimize(order: &Order, venues: &[VenueBook], side: OrderSide) -> Result<RoutingResult> {
+    let limit_price = match order.order_type {
+        OrderType::Market => None,
+        OrderType::Limit => Some(
+            order
+                .limit_price
+                .context("Limit order has no limit price")?,
+        ),
+    };
+
+    // Interleave levels from every exchange, best all-in price first
+    let mut levels = collect_levels(venues, side);
+    match side {
+        OrderSide::Buy => levels.sort_by_key(|level| level.rank_price),
+        OrderSide::Sell => levels.sort_by_key(|level| Reverse(level.rank_price)),
+    }
+
+    // Slippage is measured from the best quoted price, not from whichever
+    // level ranks first once fees and staleness are counted
+    let quoted_prices = levels.iter().map(|level| level.level.price);
+    let best_price = match side {
+        OrderSide::Buy => quoted_prices.min(),
+        OrderSide::Sell => quoted_prices.max(),
+    }
+    .context("No liquidity available")?;
+
+    // Never take a level priced through the limit
+    if let Some(limit) = limit_price {
+        levels.retain(|level| match side {
+            OrderSide::Buy => level.level.price <= limit,
+            OrderSide::Sell => level.level.price >= limit,
+        });
+    }
+
+    let mut caps = HashMap::new();
+    let fill = walk_levels(order.quantity, &levels, venues, side, &caps);
+    if fill.quantity < order.quantity && limit_price.is_none() {
+        if fill.limited.is_empty() {
+            anyhow::bail!("Insufficient liquidity to fill order");
+        }
+        anyhow::bail!(
+            "Insufficient balance to fill order (limited on {})",
+            fill.limited.join(", ")
+        );
+    }
+
+    let fill = drop_uneconomic_splits(order.quantity, &levels, venues, side, &mut caps, fill);
+    let fill = enforce_instrument_rules(order.quantity, &levels, venues, side, &mut caps, fill);
+    // Dust below every venue's quantity step stays unrouted
+    let unfilled_quantity = order.quantity - fill.quantity;
+
+    let spent_asset = match side {
+        OrderSide::Buy => &order.pair.quote,
+        OrderSide::Sell => &order.pair.base,
+    };
+    let balance_limited_venues = fill
+        .limited
+        .iter()
+        .filter_map(|&exchange| {
+            let venue = venues.iter().find(|v| v.book.exchange == exchange)?;
+            Some(BalanceLimit {
+                exchange: exchange.to_string(),
+                asset: spent_asset.to_uppercase(),
+                available: venue.available?,
+            })
+        })
+        .collect();
+
+    let (average_price, estimated_slippage) = if fill.quantity > dec!(0) {
+        let average_price = fill.notional / fill.quantity;
+        let slippage = match side {
+            OrderSide::Buy => (average_price - best_price) / best_price,
+            OrderSide::Sell => (best_price - average_price) / best_price,
+        };
+        (average_price, slippage * dec!(100))
+    } else {
+        (dec!(0), dec!(0))
+    };
 
     Ok(RoutingResult {
         original_order: order.clone(),
-        splits,
-        total_quantity: order.quantity,
+        splits: fill.splits,
+        quotes: Vec::new(),
+        synthetic_routes: Vec::new(),
+        total_quantity: fill.quantity,
         average_price,
         estimated_slippage,
+        unfilled_quantity,
+        resting_order: None,
+        skipped_venues: Vec::new(),
+        balance_limited_venues,
+        cached_venues: Vec::new(),
     })
 }
 
-/// Optimize routing for a sell order
-pub fn optimize_sell_order(order: &Order, liquidities: &[Liquidity]) -> Result<RoutingResult> {
-    // Sort exchanges by best bid price (highest first)
-    let mut sorted_liquidities = liquidities.to_vec();
-    sorted_liquidities.sort_by(|a, b| b.bid_price.cmp(&a.bid_price));
+/// Flatten the side of every book an order would take from, pricing each
+/// level net of the venue's taker fee and ranking it net of staleness
+fn collect_levels(venues: &[VenueBook], side: OrderSide) -> Vec<RankedLevel<'_>> {
+    venues
+        .iter()
+        .flat_map(|venue| {
+            let levels = match side {
+                OrderSide::Buy => &venue.book.asks,
+                OrderSide::Sell => &venue.book.bids,
+            };
+            let fee_rate = venue.fees.taker;
+            let per_order_fee = venue.fees.per_order;
+            let penalty = venue.staleness_penalty;
+            levels
+                .iter()
+                .filter(|level| level.quantity > dec!(0))
+                .map(move |level| {
+                    let (effective_price, rank_price) = match side {
+                        OrderSide::Buy => {
+                            let price = level.price * (dec!(1) + fee_rate);
+                            (price, price * (dec!(1) + penalty))
+                        }
+                        OrderSide::Sell => {
+                            let price = level.price * (dec!(1) - fee_rate);
+                            (price, price * (dec!(1) - penalty))
+                        }
+                    };
+                    RankedLevel {
+                        exchange: venue.book.exchange.as_str(),
+                        budget: venue.budget_name(),
+                        level: *level,
+                        fee_rate,
+                        per_order_fee,
+                        effective_price,
+                        rank_price,
+                    }
+                })
+        })
+        .collect()
+}
+
+/// Re-walk the book without venues whose per-order fee costs more than
+/// their split saves.
+///
+/// Levels are ranked on their proportional price alone, so a venue with a
+/// fixed charge (gas on an AMM) can win a sliver of the order that the
+/// next-best levels would have filled more cheaply all-in. Each such venue
+/// is tried at a cap of zero and stays there if the order fills just as
+/// far for less.
+fn drop_uneconomic_splits<'a>(
+    quantity: Decimal,
+    levels: &[RankedLevel<'a>],
+    venues: &'a [VenueBook],
+    side: OrderSide,
+    caps: &mut HashMap<&'a str, Decimal>,
+    mut fill: Fill<'a>,
+) -> Fill<'a> {
+    loop {
+        let candidates: Vec<&'a str> = levels
+            .iter()
+            .filter(|level| level.per_order_fee > dec!(0))
+            .filter(|level| fill.splits.iter().any(|s| s.exchange == level.exchange))
+            .map(|level| level.exchange)
+            .fold(Vec::new(), |mut seen, exchange| {
+                if !seen.contains(&exchange) {
+                    seen.push(exchange);
+                }
+                seen
+            });
+
+        let mut improved = false;
+        for exchange in candidates {
+            let mut trial_caps = caps.clone();
+            trial_caps.insert(exchange, dec!(0));
+            let trial = walk_levels(quantity, levels, venues, side, &trial_caps);
+            if trial.quantity >= fill.quantity && trial.all_in_cost(side) < fill.all_in_cost(side) {
+                *caps = trial_caps;
+                fill = trial;
+                improved = true;
+                break;
+            }
+        }
+        if !improved {
+            return fill;
+        }
+    }
+}
+
+/// Re-walk the book until every split is one its venue would accept.
+///
+/// A split that breaks its venue's instrument rules is rounded down to the
+/// quantity step, or dropped if that leaves it under the minimums, and the
+/// venue is capped there. The next walk hands the dust to the next-best
+/// levels elsewhere. Caps only ever shrink to step multiples, so this
+/// settles after a few passes.
+fn enforce_instrument_rules<'a>(
+    quantity: Decimal,
+    levels: &[RankedLevel<'a>],
+    venues: &'a [VenueBook],
+    side: OrderSide,
+    caps: &mut HashMap<&'a str, Decimal>,
+    mut fill: Fill<'a>,
+) -> Fill<'a> {
+    loop {
+        let mut capped = false;
+        for split in &fill.splits {
+            let Some(venue) = venues.iter().find(|v| v.book.exchange == split.exchange) else {
+                continue;
+            };
+            let sendable = venue
+                .rules
+                .sendable_quantity(split.quantity, split.expected_price);
+            if sendable != split.quantity {
+                caps.insert(venue.book.exchange.as_str(), sendable);
+                capped = true;
+            }
+        }
+        if !capped {
+            return fill;
+        }
+        fill = walk_levels(quantity, levels, venues, side, caps);
+    }
+}
+
+/// Splits produced by walking the book, with the quantity and notional they fill
+struct Fill<'a> {
+    splits: Vec<OrderSplit>,
+    quantity: Decimal,
+    notional: Decimal,
+    /// Exchanges whose balance ran out before their liquidity did
+    limited: Vec<&'a str>,
+}
+
+impl Fill<'_> {
+    /// Quote spent including fees for buys, or minus proceeds net of fees
+    /// for sells, so that lower is better either way
+    fn all_in_cost(&self, side: OrderSide) -> Decimal {
+        let fees: Decimal = self.splits.iter().map(|s| s.expected_fee).sum();
+        match side {
+            OrderSide::Buy => self.notional + fees,
+            OrderSide::Sell => fees - self.notional,
+        }
+    }
+}
 
-    let mut splits = Vec::new();
-    let mut remaining_quantity = order.quantity;
-    let mut total_revenue = dec!(0);
+/// Greedily consume ranked levels until `quantity` is filled or the levels
+/// run out.
+///
+/// Fills on the same exchange are merged into one split priced at the
+/// volume-weighted average of the levels it consumed. Each exchange's fills
+/// are capped by its available balance: base quantity for sells, quote
+/// notional including the taker fee for buys. An exchange's per-order fee
+/// is charged once, when its split opens. Books that share a budget draw
+/// on it together. `caps` bounds the total base quantity given to an
+/// exchange.
+fn walk_levels<'a>(
+    quantity: Decimal,
+    levels: &[RankedLevel<'a>],
+    venues: &[VenueBook],
+    side: OrderSide,
+    caps: &HashMap<&str, Decimal>,
+) -> Fill<'a> {
+    let mut splits: Vec<OrderSplit> = Vec::new();
+    let mut remaining_quantity = quantity;
+    let mut total_notional = dec!(0);
+    let mut budgets: HashMap<&str, Decimal> = HashMap::new();
+    for venue in venues {
+        if let Some(available) = venue.available {
+            budgets.entry(venue.budget_name()).or_insert(available);
+        }
+    }
+    let mut limited: Vec<&'a str> = Vec::new();
 
-    // Greedy algorithm: sell to highest bidder first
-    for liquidity in sorted_liquidities {
+    for ranked in levels {
         if remaining_quantity <= dec!(0) {
             break;
         }
 
-        let available = liquidity.bid_quantity;
-        let fill_quantity = remaining_quantity.min(available);
+        let mut fill_quantity = remaining_quantity.min(ranked.level.quantity);
+        if let Some(cap) = caps.get(ranked.exchange) {
+            let taken: Decimal = splits
+                .iter()
+                .filter(|s| s.exchange == ranked.exchange)
+                .map(|s| s.quantity)
+                .sum();
+            fill_quantity = fill_quantity.min(*cap - taken);
+            if fill_quantity <= dec!(0) {
+                continue;
+            }
+        }
+        let opens_split = !splits.iter().any(|s| s.exchange == ranked.exchange);
+        let fixed_fee = if opens_split {
+            ranked.per_order_fee
+        } else {
+            dec!(0)
+        };
+        if let Some(budget) = budgets.get_mut(ranked.budget) {
+            let affordable = match side {
+                OrderSide::Buy => (*budget - fixed_fee) / ranked.effective_price,
+                OrderSide::Sell => *budget,
+            }
+            .max(dec!(0));
+            if affordable < fill_quantity {
+                fill_quantity = affordable;
+                if !limited.contains(&ranked.exchange) {
+                    limited.push(ranked.exchange);
+                }
+            }
+            if fill_quantity > dec!(0) {
+                *budget -= match side {
+                    OrderSide::Buy => fill_quantity * ranked.effective_price + fixed_fee,
+                    OrderSide::Sell => fill_quantity,
+                };
+            }
+        }
+        if fill_quantity <= dec!(0) {
+            continue;
+        }
 
-        splits.push(OrderSplit {
-            exchange: liquidity.exchange.clone(),
-            quantity: fill_quantity,
-            expected_price: liquidity.bid_price,
-        });
+        let fill_notional = fill_quantity * ranked.level.price;
+        let fill_fee = fill_notional * ranked.fee_rate + fixed_fee;
 
-        total_revenue += fill_quantity * liquidity.bid_price;
+        match splits.iter_mut().find(|s| s.exchange == ranked.exchange) {
+            Some(split) => {
+                let notional = split.quantity * split.expected_price + fill_notional;
+                split.quantity += fill_quantity;
+                split.expected_price = notional / split.quantity;
+                split.expected_fee += fill_fee;
+            }
+            None => splits.push(OrderSplit {
+                exchange: ranked.exchange.to_string(),
+                quantity: fill_quantity,
+                expected_price: ranked.level.price,
+                expected_fee: fill_fee,
+                fee_rate: Some(ranked.fee_rate),
+                conversion: None,
+            }),
+        }
+
+        total_notional += fill_notional;
         remaining_quantity -= fill_quantity;
     }
 
-    if remaining_quantity > dec!(0) {
-        anyhow::bail!("Insufficient liquidity to fill order");
+    Fill {
+        splits,
+        quantity: quantity - remaining_quantity,
+        notional: total_notional,
+        limited,
     }
+}
 
-    let average_price = total_revenue / order.quantity;
-    let best_price = liquidities
-        .iter()
-        .map(|l| l.bid_price)
-        .max()
-        .context("No liquidity available")?;
-    let estimated_slippage = ((best_price - average_price) / best_price) * dec!(100);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TradingPair;
 
-    Ok(RoutingResult {
-        original_order: order.clone(),
-        splits,
-        total_quantity: order.quantity,
-        average_price,
-        estimated_slippage,
-    })
+    fn book(exchange: &str, bids: &[(Decimal, Decimal)], asks: &[(Decimal, Decimal)]) -> VenueBook {
+        let levels =
+            |l: &[(Decimal, Decimal)]| l.iter().map(|&(p, q)| PriceLevel::new(p, q)).collect();
+        let book = OrderBook::new(
+            exchange,
+            TradingPair::new("BTC", "USD"),
+            levels(bids),
+            levels(asks),
+        );
+        VenueBook::new(book, FeeRates::new(dec!(0), dec!(0)))
+    }
+
+    fn order(side: OrderSide, quantity: Decimal) -> Order {
+        Order {
+            pair: TradingPair::new("BTC", "USD"),
+            side,
+            order_type: OrderType::Market,
+            quantity,
+            limit_price: None,
+        }
+    }
+
+    #[test]
+    fn test_buy_walks_interleaved_levels() {
+        let books = vec![
+            book("A", &[], &[(dec!(100), dec!(1)), (dec!(103), dec!(5))]),
+            book("B", &[], &[(dec!(101), dec!(1)), (dec!(102), dec!(1))]),
+        ];
+
+        let result = optimize_buy_order(&order(OrderSide::Buy, dec!(4)), &books).unwrap();
+
+        // 1 @ 100 (A), 1 @ 101 (B), 1 @ 102 (B), 1 @ 103 (A)
+        assert_eq!(result.splits.len(), 2);
+        assert_eq!(result.splits[0].exchange, "A");
+        assert_eq!(result.splits[0].quantity, dec!(2));
+        assert_eq!(result.splits[0].expected_price, dec!(101.5));
+        assert_eq!(result.splits[1].exchange, "B");
+        assert_eq!(result.splits[1].quantity, dec!(2));
+        assert_eq!(result.splits[1].expected_price, dec!(101.5));
+        assert_eq!(result.average_price, dec!(101.5));
+        assert_eq!(result.estimated_slippage, dec!(1.5));
+    }
+
+    #[test]
+    fn test_sell_walks_interleaved_levels() {
+        let books = vec![
+            book("A", &[(dec!(99), dec!(1)), (dec!(97), dec!(5))], &[]),
+            book("B", &[(dec!(98), dec!(2))], &[]),
+        ];
+
+        let result = optimize_sell_order(&order(OrderSide::Sell, dec!(4)), &books).unwrap();
+
+        assert_eq!(result.splits[0].exchange, "A");
+        assert_eq!(result.splits[0].quantity, dec!(2));
+        assert_eq!(result.splits[0].expected_price, dec!(98));
+        assert_eq!(result.splits[1].exchange, "B");
+        assert_eq!(result.splits[1].quantity, dec!(2));
+        assert_eq!(result.average_price, dec!(98));
+    }
+
+    #[test]
+    fn test_insufficient_depth() {
+        let books = vec![book("A", &[], &[(dec!(100), dec!(1))])];
+        assert!(optimize_buy_order(&order(OrderSide::Buy, dec!(2)), &books).is_err());
+    }
+
+    #[test]
+    fn test_balances_cap_splits() {
+        let books = vec![
+            book("A", &[(dec!(100), dec!(5))], &[(dec!(100), dec!(5))]),
+            book("B", &[(dec!(99), dec!(5))], &[(dec!(101), dec!(5))]),
+        ];
+
+        // 150 USD on A buys 1.5 BTC; B has no known balance
+        let mut capped = books.clone();
+        capped[0] = capped[0].clone().with_available(dec!(150));
+        let result = optimize_buy_order(&order(OrderSide::Buy, dec!(3)), &capped).unwrap();
+        assert_eq!(result.splits[0].exchange, "A");
+        assert_eq!(result.splits[0].quantity, dec!(1.5));
+        assert_eq!(result.splits[1].exchange, "B");
+        assert_eq!(result.splits[1].quantity, dec!(1.5));
+        assert_eq!(
+            result.balance_limited_venues,
+            vec![BalanceLimit {
+                exchange: "A".to_string(),
+                asset: "USD".to_string(),
+                available: dec!(150),
+            }]
+        );
+
+        // Sells are capped in base units; no BTC anywhere cannot fill
+        let mut empty = books.clone();
+        empty[0] = empty[0].clone().with_available(dec!(0.5));
+        empty[1] = empty[1].clone().with_available(dec!(0));
+        let error = optimize_sell_order(&order(OrderSide::Sell, dec!(1)), &empty).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Insufficient balance to fill order (limited on A, B)"
+        );
+    }
+
+    #[test]
+    fn test_instrument_rules_move_dust_to_other_venues() {
+        let books = vec![
+            book("A", &[], &[(dec!(100), dec!(5))])
+                .with_rules(InstrumentRules::new(dec!(0.01), dec!(0.01))),
+            book("B", &[], &[(dec!(101), dec!(5))])
+                .with_rules(InstrumentRules::new(dec!(0.01), dec!(0.001))),
+            book("C", &[], &[(dec!(102), dec!(5))]).with_rules(
+                InstrumentRules::new(dec!(0.01), dec!(0.0001)).with_min_notional(dec!(10)),
+            ),
+        ];
+
+        // A can only take 1.23 of 1.2345; B takes the 0.004; the last
+        // 0.0005 would be a 0.051 USD order on C, below its minimum
+        let result = optimize_buy_order(&order(OrderSide::Buy, dec!(1.2345)), &books).unwrap();
+
+        assert_eq!(result.splits.len(), 2);
+        assert_eq!(result.splits[0].quantity, dec!(1.23));
+        assert_eq!(result.splits[1].exchange, "B");
+        assert_eq!(result.splits[1].quantity, dec!(0.004));
+        assert_eq!(result.total_quantity, dec!(1.234));
+        assert_eq!(result.unfilled_quantity, dec!(0.0005));
+    }
+
+    #[test]
+    fn test_fees_change_venue_ranking() {
+        let mut cheap = book("Cheap", &[], &[(dec!(10000), dec!(1))]);
+        cheap.fees = FeeRates::new(dec!(0.002), dec!(0.002));
+        let mut low_fee = book("LowFee", &[], &[(dec!(10001), dec!(1))]);
+        low_fee.fees = FeeRates::new(dec!(0.0001), dec!(0.0001));
+
+        let result =
+            optimize_buy_order(&order(OrderSide::Buy, dec!(1)), &[cheap, low_fee]).unwrap();
+
+        assert_eq!(result.splits.len(), 1);
+        assert_eq!(result.splits[0].exchange, "LowFee");
+        assert_eq!(result.splits[0].expected_fee, dec!(1.0001));
+        assert_eq!(result.splits[0].fee_rate, Some(dec!(0.0001)));
+    }
+
+    #[test]
+    fn test_slippage_is_measured_from_the_best_quoted_price() {
+        let stale =
+            book("Stale", &[], &[(dec!(99.99), dec!(1))]).with_staleness_penalty(dec!(0.001));
+        let fresh = book("Fresh", &[], &[(dec!(100), dec!(2))]);
+
+        let result = optimize_buy_order(&order(OrderSide::Buy, dec!(2)), &[stale, fresh]).unwrap();
+
+        // The stale venue ranks behind at 100.09 but still shows the best
+        // price
+        assert_eq!(result.splits.len(), 1);
+        assert_eq!(result.splits[0].exchange, "Fresh");
+        assert_eq!(result.average_price, dec!(100));
+        assert_eq!(
+            result.estimated_slippage,
+            dec!(0.01) / dec!(99.99) * dec!(100)
+        );
+    }
+
+    #[test]
+    fn test_limit_buy_stops_at_limit_price() {
+        let books = vec![
+            book(
+                "A",
+                &[(dec!(98), dec!(3))],
+                &[(dec!(100), dec!(1)), (dec!(102), dec!(5))],
+            ),
+            book("B", &[(dec!(99), dec!(1))], &[(dec!(101), dec!(1))]),
+        ];
+        let mut limit = order(OrderSide::Buy, dec!(5));
+        limit.order_type = OrderType::Limit;
+        limit.limit_price = Some(dec!(101));
+
+        let result = optimize_buy_order(&limit, &books).unwrap();
+
+        assert_eq!(result.total_quantity, dec!(2));
+        assert_eq!(result.unfilled_quantity, dec!(3));
+        assert_eq!(result.average_price, dec!(100.5));
+
+        let resting = propose_resting_order(&result, &books).unwrap();
+        assert_eq!(resting.exchange, "A");
+        assert_eq!(resting.quantity, dec!(3));
+        assert_eq!(resting.price, dec!(101));
+    }
+
+    #[test]
+    fn test_resting_order_is_rounded_to_the_venue_rules() {
+        let books = vec![book("A", &[(dec!(100), dec!(1))], &[])
+            .with_rules(InstrumentRules::new(dec!(0.5), dec!(0.1)))];
+        let mut limit = order(OrderSide::Sell, dec!(3.456));
+        limit.order_type = OrderType::Limit;
+        limit.limit_price = Some(dec!(101.3));
+
+        let result = optimize_sell_order(&limit, &books).unwrap();
+        assert_eq!(result.unfilled_quantity, dec!(3.456));
+
+        // A sell rests no lower than its limit
+        let resting = propose_resting_order(&result, &books).unwrap();
+        assert_eq!(resting.quantity, dec!(3.4));
+        assert_eq!(resting.price, dec!(101.5));
+
+        // Nothing is proposed where the rounded remainder is too small
+        let strict = vec![books[0]
+            .clone()
+            .with_rules(InstrumentRules::new(dec!(0.5), dec!(0.1)).with_min_quantity(dec!(5)))];
+        assert!(propose_resting_order(&result, &strict).is_none());
+    }
+
+    #[test]
+    fn test_quotes_compete_with_the_book() {
+        let quote = |venue: &str, price, quantity| RfqQuote {
+            quote_id: format!("{venue}-1"),
+            request_id: "rfq-1".to_string(),
+            venue: venue.to_string(),
+            pair: TradingPair::new("BTC", "USD"),
+            side: OrderSide::Buy,
+            quantity,
+            price,
+            expires_at: chrono::Utc::now() + chrono::Duration::seconds(5),
+        };
+        let books = vec![book(
+            "A",
+            &[],
+            &[(dec!(100), dec!(2)), (dec!(110), dec!(10))],
+        )];
+        let buy = order(OrderSide::Buy, dec!(5));
+
+        // The book alone costs 2 @ 100 + 3 @ 110 = 530. Taking 3 @ 103
+        // from Small and the top of the book costs 509, beating Full's 520.
+        let small = quote("Small", dec!(103), dec!(3));
+        let full = quote("Full", dec!(104), dec!(5));
+        let result = optimize_with_quotes(&buy, &books, &[full.clone(), small.clone()]).unwrap();
+        assert_eq!(result.quotes, vec![small]);
+        assert_eq!(result.splits.len(), 1);
+        assert_eq!(result.splits[0].quantity, dec!(2));
+        assert_eq!(result.total_quantity, dec!(5));
+        assert_eq!(result.average_price, dec!(101.8));
+        assert_eq!(result.estimated_slippage, dec!(1.8));
+
+        // A quote worse than the book is left alone
+        let dear = quote("Dear", dec!(107), dec!(5));
+        let result = optimize_with_quotes(&buy, &books, std::slice::from_ref(&dear)).unwrap();
+        assert!(result.quotes.is_empty());
+        assert_eq!(result.average_price, dec!(106));
+
+        // A quote can fill what the book cannot, but never through the limit
+        let thin = vec![book("A", &[], &[(dec!(100), dec!(1))])];
+        let result = optimize_with_quotes(&buy, &thin, std::slice::from_ref(&dear)).unwrap();
+        assert!(result.splits.is_empty());
+        assert_eq!(result.quotes, vec![dear.clone()]);
+        assert_eq!(result.unfilled_quantity, dec!(0));
+        let mut limit = buy.clone();
+        limit.order_type = OrderType::Limit;
+        limit.limit_price = Some(dec!(105));
+        let result = optimize_with_quotes(&limit, &thin, &[dear]).unwrap();
+        assert!(result.quotes.is_empty());
+        assert_eq!(result.unfilled_quantity, dec!(4));
+
+        // A quote whose plan fails does not crowd out a later one: 2 @ 101
+        // leaves more than the thin book holds, 4 @ 102 does not
+        let partial = quote("Partial", dec!(101), dec!(2));
+        let most = quote("Most", dec!(102), dec!(4));
+        let result = optimize_with_quotes(&buy, &thin, &[partial, most.clone()]).unwrap();
+        assert_eq!(result.quotes, vec![most]);
+        assert_eq!(result.splits[0].quantity, dec!(1));
+        assert_eq!(result.total_quantity, dec!(5));
+    }
+
+    #[test]
+    fn test_limit_sell_entirely_through_book() {
+        let books = vec![book("A", &[(dec!(99), dec!(3))], &[])];
+        let mut limit = order(OrderSide::Sell, dec!(1));
+        limit.order_type = OrderType::Limit;
+        limit.limit_price = Some(dec!(100));
+
+        let result = optimize_sell_order(&limit, &books).unwrap();
+
+        assert!(result.splits.is_empty());
+        assert_eq!(result.unfilled_quantity, dec!(1));
+        assert_eq!(result.average_price, dec!(0));
+    }
 }
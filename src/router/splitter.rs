@@ -1,50 +1,124 @@
-use crate::types::{Order, OrderSplit};
+use crate::types::{InstrumentRules, Order, OrderSplit};
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
 /// Split order using VWAP (Volume Weighted Average Price) strategy
-pub fn vwap_split(order: &Order, num_splits: usize) -> Vec<OrderSplit> {
-    let quantity_per_split = order.quantity / Decimal::from(num_splits);
-    
-    (0..num_splits)
-        .map(|i| OrderSplit {
+pub fn vwap_split(order: &Order, num_splits: usize, rules: &InstrumentRules) -> Vec<OrderSplit> {
+    let price = dec!(50000.0); // Mock price
+
+    equal_slices(order.quantity, num_splits, rules, price)
+        .into_iter()
+        .enumerate()
+        .map(|(i, quantity)| OrderSplit {
             exchange: format!("Exchange_{}", i + 1),
-            quantity: quantity_per_split,
-            expected_price: dec!(50000.0), // Mock price
+            quantity,
+            expected_price: price,
+            expected_fee: dec!(0),
+            fee_rate: None,
+            conversion: None,
         })
         .collect()
 }
 
 /// Split order using TWAP (Time Weighted Average Price) strategy
-pub fn twap_split(order: &Order, time_intervals: usize) -> Vec<OrderSplit> {
-    let quantity_per_interval = order.quantity / Decimal::from(time_intervals);
-    
-    (0..time_intervals)
-        .map(|i| OrderSplit {
+pub fn twap_split(
+    order: &Order,
+    time_intervals: usize,
+    rules: &InstrumentRules,
+) -> Vec<OrderSplit> {
+    let price = dec!(50000.0); // Mock price
+
+    equal_slices(order.quantity, time_intervals, rules, price)
+        .into_iter()
+        .enumerate()
+        .map(|(i, quantity)| OrderSplit {
             exchange: format!("Interval_{}", i + 1),
-            quantity: quantity_per_interval,
-            expected_price: dec!(50000.0), // Mock price
+            quantity,
+            expected_price: price,
+            expected_fee: dec!(0),
+            fee_rate: None,
+            conversion: None,
         })
         .collect()
 }
 
+/// Divide `quantity` into at most `parts` near-equal slices the venue
+/// accepts at `price`.
+///
+/// Slices are rounded down to the quantity step and the dust is handed out
+/// a step at a time, so no slice carries sub-step precision. When equal
+/// slices would fall under the venue's minimums, fewer slices are used.
+fn equal_slices(
+    quantity: Decimal,
+    parts: usize,
+    rules: &InstrumentRules,
+    price: Decimal,
+) -> Vec<Decimal> {
+    let minimum = rules.min_sendable_quantity(price);
+    let parts = if minimum > dec!(0) {
+        let affordable = (quantity / minimum)
+            .floor()
+            .to_usize()
+            .unwrap_or(usize::MAX);
+        parts.min(affordable)
+    } else {
+        parts
+    };
+    if parts == 0 {
+        return Vec::new();
+    }
+
+    let slice = rules.round_quantity(quantity / Decimal::from(parts));
+    let mut slices = vec![slice; parts];
+    let step = rules.step_size;
+    if step > dec!(0) {
+        let mut dust = quantity - slice * Decimal::from(parts);
+        for slice in slices.iter_mut() {
+            if dust < step || rules.round_quantity(*slice + step) != *slice + step {
+                break;
+            }
+            *slice += step;
+            dust -= step;
+        }
+    }
+    slices
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::types::{OrderSide, OrderType, TradingPair};
 
-    #[test]
-    fn test_vwap_split() {
-        let order = Order {
+    fn order(quantity: Decimal) -> Order {
+        Order {
             pair: TradingPair::new("BTC", "USD"),
             side: OrderSide::Buy,
             order_type: OrderType::Market,
-            quantity: dec!(10.0),
+            quantity,
             limit_price: None,
-        };
+        }
+    }
 
-        let splits = vwap_split(&order, 5);
+    #[test]
+    fn test_vwap_split() {
+        let splits = vwap_split(&order(dec!(10.0)), 5, &InstrumentRules::default());
         assert_eq!(splits.len(), 5);
         assert_eq!(splits[0].quantity, dec!(2.0));
     }
+
+    #[test]
+    fn test_splits_respect_instrument_rules() {
+        let rules = InstrumentRules::new(dec!(0.01), dec!(0.001)).with_min_notional(dec!(100));
+
+        // 1 / 3 rounds to 0.333; the leftover 0.001 goes to the first slice
+        let splits = vwap_split(&order(dec!(1)), 3, &rules);
+        let quantities: Vec<_> = splits.iter().map(|s| s.quantity).collect();
+        assert_eq!(quantities, vec![dec!(0.334), dec!(0.333), dec!(0.333)]);
+
+        // At 50,000 the 100 USD minimum needs 0.002 per slice
+        let splits = twap_split(&order(dec!(0.005)), 10, &rules);
+        let quantities: Vec<_> = splits.iter().map(|s| s.quantity).collect();
+        assert_eq!(quantities, vec![dec!(0.003), dec!(0.002)]);
+    }
 }
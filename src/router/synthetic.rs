@@ -0,0 +1,399 @@
+use super::optimizer::VenueBook;
+use crate::exchanges::fees::FeeRates;
+use crate::types::{
+    InstrumentRules, OrderBook, OrderSide, OrderSplit, PriceLevel, SyntheticLeg, SyntheticRoute,
+    TradingPair,
+};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+/// Most levels a synthetic book is built with
+const MAX_LEVELS: usize = 100;
+
+/// A taker fill on one venue's level within a leg
+#[derive(Debug, Clone)]
+struct Hop {
+    exchange: String,
+    price: Decimal,
+    fee_rate: Decimal,
+    /// The venue's rules for the leg's pair
+    rules: InstrumentRules,
+}
+
+impl Hop {
+    /// Price per unit net of the taker fee: paid for buys, received for sells
+    fn all_in(&self, side: OrderSide) -> Decimal {
+        match side {
+            OrderSide::Buy => self.price * (dec!(1) + self.fee_rate),
+            OrderSide::Sell => self.price * (dec!(1) - self.fee_rate),
+        }
+    }
+}
+
+/// A level of the synthetic book and the leg levels it is made of
+#[derive(Debug, Clone)]
+struct CompositeLevel {
+    /// Base asset available at this level
+    quantity: Decimal,
+    /// Quote per unit of base, both legs' fees included
+    price: Decimal,
+    /// Fill on the BASE/VIA leg
+    first: Hop,
+    /// Fill on the VIA/QUOTE leg
+    second: Hop,
+    /// Intermediate asset exchanged per unit of base, first leg's fee included
+    via_per_base: Decimal,
+}
+
+impl CompositeLevel {
+    /// What each leg sends to fill up to `quantity` of base here: base
+    /// rounded down to the first leg's step, and the intermediate rounded
+    /// to the second's, up for buys so there is enough to spend and down
+    /// for sells so no more is sold than was bought. `None` if either leg
+    /// would fall under its venue's minimums.
+    fn leg_quantities(&self, quantity: Decimal, side: OrderSide) -> Option<(Decimal, Decimal)> {
+        let base = self.first.rules.round_quantity(quantity);
+        let via = base * self.via_per_base;
+        let via = match side {
+            OrderSide::Buy => ceil_to_step(via, self.second.rules.step_size),
+            OrderSide::Sell => self.second.rules.round_quantity(via),
+        };
+        let sendable = self.first.rules.accepts(base, self.first.price)
+            && self.second.rules.accepts(via, self.second.price);
+        sendable.then_some((base, via))
+    }
+}
+
+/// Book for an order's pair made by chaining two legs through an
+/// intermediate asset: BASE/VIA, then VIA/QUOTE.
+///
+/// Both legs' levels are merged across venues and walked together, so each
+/// synthetic level is a fill on one venue per leg at an all-in price. The
+/// levels carry their fees in the price, like an AMM curve, so the
+/// optimizer can rank them straight against direct venues. Leg quantities
+/// are rounded to each venue's instrument rules and levels too small for
+/// them are left out; per-order fees are not taken into account.
+#[derive(Debug, Clone)]
+pub struct SyntheticBook {
+    via: String,
+    pair: TradingPair,
+    side: OrderSide,
+    levels: Vec<CompositeLevel>,
+    venue: VenueBook,
+}
+
+impl SyntheticBook {
+    /// Chain the side of each leg's books that an order on `side` takes
+    /// from, or `None` when either leg has no liquidity
+    pub fn compose(
+        pair: &TradingPair,
+        side: OrderSide,
+        via: &str,
+        base_via: &[VenueBook],
+        via_quote: &[VenueBook],
+    ) -> Option<Self> {
+        let first = leg_levels(base_via, side);
+        let second = leg_levels(via_quote, side);
+        let (mut i, mut j) = (0, 0);
+        let mut first_left = first.first()?.1;
+        let mut second_left = second.first()?.1;
+
+        let mut levels = Vec::new();
+        while i < first.len() && j < second.len() && levels.len() < MAX_LEVELS {
+            let (first_hop, _) = &first[i];
+            let (second_hop, _) = &second[j];
+            let via_per_base = first_hop.all_in(side);
+            // How much base the rest of this second-leg level can carry
+            let second_capacity = second_left / via_per_base;
+            let quantity = first_left.min(second_capacity);
+            let mut level = CompositeLevel {
+                quantity,
+                price: via_per_base * second_hop.all_in(side),
+                first: first_hop.clone(),
+                second: second_hop.clone(),
+                via_per_base,
+            };
+            if let Some((base, _)) = level.leg_quantities(quantity, side) {
+                level.quantity = base;
+                levels.push(level);
+            }
+
+            // Move past whichever level ran out, or both
+            if first_left < second_capacity {
+                second_left -= first_left * via_per_base;
+                i += 1;
+                first_left = first.get(i).map_or(dec!(0), |(_, quantity)| *quantity);
+            } else {
+                first_left -= second_capacity;
+                j += 1;
+                second_left = second.get(j).map_or(dec!(0), |(_, quantity)| *quantity);
+                if first_left <= dec!(0) {
+                    i += 1;
+                    first_left = first.get(i).map_or(dec!(0), |(_, quantity)| *quantity);
+                }
+            }
+        }
+        if levels.is_empty() {
+            return None;
+        }
+
+        let book_levels = levels
+            .iter()
+            .map(|level| PriceLevel::new(level.price, level.quantity))
+            .collect();
+        let (bids, asks) = match side {
+            OrderSide::Buy => (Vec::new(), book_levels),
+            OrderSide::Sell => (book_levels, Vec::new()),
+        };
+        let book = OrderBook::new(Self::venue_name(via), pair.clone(), bids, asks);
+        // A route is only as fresh as its stalest leg
+        let staleness_penalty = base_via
+            .iter()
+            .chain(via_quote)
+            .map(|venue| venue.staleness_penalty)
+            .max()
+            .unwrap_or_default();
+        let venue = VenueBook::new(book, FeeRates::new(dec!(0), dec!(0)))
+            .with_staleness_penalty(staleness_penalty);
+
+        Some(Self {
+            via: via.to_uppercase(),
+            pair: pair.clone(),
+            side,
+            levels,
+            venue,
+        })
+    }
+
+    /// Name the synthetic book goes by among the venues
+    pub fn venue_name(via: &str) -> String {
+        format!("synthetic via {}", via.to_uppercase())
+    }
+
+    pub fn via(&self) -> &str {
+        &self.via
+    }
+
+    /// The book as a fee-free venue the optimizer can route to
+    pub fn venue(&self) -> &VenueBook {
+        &self.venue
+    }
+
+    /// Break the best `quantity` of the book into the leg routings that
+    /// fill it. The route may come out a little short where a level's leg
+    /// quantities had to be rounded down.
+    pub fn route(&self, quantity: Decimal) -> SyntheticRoute {
+        let mut first_splits = Vec::new();
+        let mut second_splits = Vec::new();
+        let mut remaining = quantity;
+        let mut notional = dec!(0);
+        for level in &self.levels {
+            if remaining <= dec!(0) {
+                break;
+            }
+            let Some((taken, via)) = level.leg_quantities(remaining.min(level.quantity), self.side)
+            else {
+                continue;
+            };
+            add_fill(&mut first_splits, &level.first, taken);
+            add_fill(&mut second_splits, &level.second, via);
+            notional += taken * level.price;
+            remaining -= taken;
+        }
+
+        let filled = quantity - remaining;
+        let leg = |pair, splits: Vec<OrderSplit>| SyntheticLeg {
+            pair,
+            side: self.side,
+            quantity: splits.iter().map(|split| split.quantity).sum(),
+            splits,
+        };
+        let first = leg(TradingPair::new(&self.pair.base, &self.via), first_splits);
+        let second = leg(TradingPair::new(&self.via, &self.pair.quote), second_splits);
+        SyntheticRoute {
+            via: self.via.clone(),
+            quantity: filled,
+            expected_price: if filled > dec!(0) {
+                notional / filled
+            } else {
+                dec!(0)
+            },
+            // Buys need the intermediate asset before spending it; sells
+            // only have it once the base is sold
+            legs: match self.side {
+                OrderSide::Buy => vec![second, first],
+                OrderSide::Sell => vec![first, second],
+            },
+        }
+    }
+}
+
+/// Every venue's levels on the side an order takes from, best all-in
+/// price first, with the quantity each offers
+fn leg_levels(venues: &[VenueBook], side: OrderSide) -> Vec<(Hop, Decimal)> {
+    let mut levels: Vec<(Hop, Decimal)> = venues
+        .iter()
+        .flat_map(|venue| {
+            let levels = match side {
+                OrderSide::Buy => &venue.book.asks,
+                OrderSide::Sell => &venue.book.bids,
+            };
+            levels
+                .iter()
+                .filter(|level| level.quantity > dec!(0))
+                .map(move |level| {
+                    let hop = Hop {
+                        exchange: venue.book.exchange.clone(),
+                        price: level.price,
+                        fee_rate: venue.fees.taker,
+                        rules: venue.rules.clone(),
+                    };
+                    (hop, level.quantity)
+                })
+        })
+        .collect();
+    match side {
+        OrderSide::Buy => levels.sort_by_key(|(hop, _)| hop.all_in(side)),
+        OrderSide::Sell => levels.sort_by_key(|(hop, _)| std::cmp::Reverse(hop.all_in(side))),
+    }
+    levels
+}
+
+/// Round a quantity up to a multiple of `step`, or leave it if there is none
+fn ceil_to_step(quantity: Decimal, step: Decimal) -> Decimal {
+    if step <= dec!(0) {
+        return quantity;
+    }
+    ((quantity / step).ceil() * step).normalize()
+}
+
+/// Merge a fill into the split for its venue, priced at the
+/// volume-weighted average
+fn add_fill(splits: &mut Vec<OrderSplit>, hop: &Hop, quantity: Decimal) {
+    let notional = quantity * hop.price;
+    let fee = notional * hop.fee_rate;
+    match splits
+        .iter_mut()
+        .find(|split| split.exchange == hop.exchange)
+    {
+        Some(split) => {
+            let total = split.quantity * split.expected_price + notional;
+            split.quantity += quantity;
+            split.expected_price = total / split.quantity;
+            split.expected_fee += fee;
+        }
+        None => splits.push(OrderSplit {
+            exchange: hop.exchange.clone(),
+            quantity,
+            expected_price: hop.price,
+            expected_fee: fee,
+            fee_rate: Some(hop.fee_rate),
+            conversion: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn venue(exchange: &str, base: &str, quote: &str, asks: &[(Decimal, Decimal)]) -> VenueBook {
+        let asks = asks.iter().map(|&(p, q)| PriceLevel::new(p, q)).collect();
+        let book = OrderBook::new(exchange, TradingPair::new(base, quote), Vec::new(), asks);
+        VenueBook::new(book, FeeRates::new(dec!(0), dec!(0)))
+    }
+
+    #[test]
+    fn test_legs_chain_into_an_all_in_book() {
+        let eth_btc = vec![
+            venue("A", "ETH", "BTC", &[(dec!(0.05), dec!(10))]),
+            venue("B", "ETH", "BTC", &[(dec!(0.051), dec!(10))]),
+        ];
+        let mut cheap_btc = venue("C", "BTC", "USD", &[(dec!(2000), dec!(0.3))]);
+        cheap_btc.fees = FeeRates::new(dec!(0.001), dec!(0.001));
+        let btc_usd = vec![
+            cheap_btc,
+            venue("D", "BTC", "USD", &[(dec!(2010), dec!(10))]),
+        ];
+        let pair = TradingPair::new("ETH", "USD");
+
+        let book =
+            SyntheticBook::compose(&pair, OrderSide::Buy, "btc", &eth_btc, &btc_usd).unwrap();
+
+        // 0.3 BTC on C carries 6 ETH from A; A's other 4 ETH and all of B's
+        // are paid for with BTC from D
+        assert_eq!(book.venue().book.exchange, "synthetic via BTC");
+        assert_eq!(
+            book.venue().book.asks,
+            vec![
+                PriceLevel::new(dec!(100.1), dec!(6)),
+                PriceLevel::new(dec!(100.5), dec!(4)),
+                PriceLevel::new(dec!(102.51), dec!(10)),
+            ]
+        );
+
+        let route = book.route(dec!(8));
+        assert_eq!(route.via, "BTC");
+        assert_eq!(route.quantity, dec!(8));
+        assert_eq!(route.expected_price, dec!(100.2));
+        // BTC is bought first, then spent on ETH
+        assert_eq!(route.legs[0].pair, TradingPair::new("BTC", "USD"));
+        assert_eq!(route.legs[0].quantity, dec!(0.4));
+        assert_eq!(route.legs[0].splits[0].exchange, "C");
+        assert_eq!(route.legs[0].splits[0].quantity, dec!(0.3));
+        assert_eq!(route.legs[0].splits[0].expected_fee, dec!(0.6));
+        assert_eq!(route.legs[0].splits[1].exchange, "D");
+        assert_eq!(route.legs[0].splits[1].quantity, dec!(0.1));
+        assert_eq!(route.legs[1].pair, TradingPair::new("ETH", "BTC"));
+        assert_eq!(route.legs[1].splits.len(), 1);
+        assert_eq!(route.legs[1].splits[0].quantity, dec!(8));
+
+        assert!(
+            SyntheticBook::compose(&pair, OrderSide::Sell, "BTC", &eth_btc, &btc_usd).is_none()
+        );
+    }
+
+    #[test]
+    fn test_leg_quantities_follow_each_venues_rules() {
+        let eth_rules = InstrumentRules::new(dec!(0.0001), dec!(0.1)).with_min_quantity(dec!(1));
+        let eth_btc = vec![
+            venue("A", "ETH", "BTC", &[(dec!(0.05), dec!(10))]).with_rules(eth_rules.clone()),
+            venue("E", "ETH", "BTC", &[(dec!(0.06), dec!(0.5))]).with_rules(eth_rules),
+        ];
+        let btc_rules = InstrumentRules::new(dec!(1), dec!(0.01));
+        let btc_usd = vec![
+            venue("C", "BTC", "USD", &[(dec!(2000), dec!(0.3333))]).with_rules(btc_rules.clone()),
+            venue("D", "BTC", "USD", &[(dec!(2010), dec!(10))]).with_rules(btc_rules),
+        ];
+        let pair = TradingPair::new("ETH", "USD");
+
+        let book =
+            SyntheticBook::compose(&pair, OrderSide::Buy, "BTC", &eth_btc, &btc_usd).unwrap();
+
+        // ETH comes in steps of 0.1, and E's half ETH is under the minimum
+        assert_eq!(
+            book.venue().book.asks,
+            vec![
+                PriceLevel::new(dec!(100), dec!(6.6)),
+                PriceLevel::new(dec!(100.5), dec!(3.3)),
+            ]
+        );
+
+        // The 0.4 ETH left for the second level is under A's minimum, and
+        // the 0.33 BTC the first needs is bought whole
+        let route = book.route(dec!(7));
+        assert_eq!(route.quantity, dec!(6.6));
+        assert_eq!(route.legs[0].quantity, dec!(0.33));
+        assert_eq!(route.legs[1].quantity, dec!(6.6));
+
+        // Buying BTC rounds up to cover the ETH, selling it rounds down to
+        // what the ETH fetched
+        let route = book.route(dec!(9.9));
+        assert_eq!(route.legs[0].splits[1].quantity, dec!(0.17));
+        let level = &book.levels[1];
+        assert_eq!(
+            level.leg_quantities(dec!(3.3), OrderSide::Sell),
+            Some((dec!(3.3), dec!(0.16)))
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use super::book::BookUpdate;
+use super::{DepthFeed, FeedEvent};
+use crate::exchanges::binance::{parse_levels, BinanceExchange};
+use crate::exchanges::Exchange;
+use crate::types::TradingPair;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+/// Binance global spot market-data stream
+pub const DEFAULT_WS_URL: &str = "wss://stream.binance.com:9443";
+/// Binance.US market-data stream
+pub const BINANCE_US_WS_URL: &str = "wss://stream.binance.us:9443";
+
+#[derive(Debug, Deserialize)]
+struct DepthUpdateEvent {
+    /// Event time in milliseconds
+    #[serde(rename = "E")]
+    event_time: i64,
+    #[serde(rename = "U")]
+    first_update_id: u64,
+    #[serde(rename = "u")]
+    last_update_id: u64,
+    #[serde(rename = "b")]
+    bids: Vec<(String, String)>,
+    #[serde(rename = "a")]
+    asks: Vec<(String, String)>,
+}
+
+/// Binance `<symbol>@depth` diff stream, synchronised from a REST snapshot
+/// as described in Binance's "manage a local order book" guide. Event times
+/// are corrected by the REST connector's clock offset.
+pub struct BinanceDepthFeed {
+    ws_url: String,
+    rest: BinanceExchange,
+}
+
+impl BinanceDepthFeed {
+    pub fn new(ws_url: impl Into<String>, rest: BinanceExchange) -> Self {
+        Self {
+            ws_url: ws_url.into(),
+            rest,
+        }
+    }
+}
+
+#[async_trait]
+impl DepthFeed for BinanceDepthFeed {
+    fn exchange(&self) -> &str {
+        self.rest.name()
+    }
+
+    fn url(&self, pair: &TradingPair) -> String {
+        format!(
+            "{}/ws/{}@depth@100ms",
+            self.ws_url,
+            self.rest.format_symbol(pair).to_lowercase()
+        )
+    }
+
+    fn subscribe_message(&self, _pair: &TradingPair) -> Option<String> {
+        // The stream is selected by the URL path
+        None
+    }
+
+    fn parse(&self, text: &str) -> Result<Vec<FeedEvent>> {
+        let event: DepthUpdateEvent = serde_json::from_str(text)?;
+        Ok(vec![FeedEvent::Update(BookUpdate {
+            first_sequence: event.first_update_id,
+            last_sequence: event.last_update_id,
+            bids: parse_levels(&event.bids)?,
+            asks: parse_levels(&event.asks)?,
+            event_time: chrono::DateTime::from_timestamp_millis(event.event_time)
+                .map(|time| self.rest.clock().to_local(time)),
+        })])
+    }
+
+    async fn snapshot(&self, pair: &TradingPair) -> Result<Option<FeedEvent>> {
+        // Event times are on Binance's clock; measure it on every (re)sync
+        self.rest.sync_clock().await?;
+        let (sequence, book) = self.rest.depth_snapshot(pair).await?;
+        Ok(Some(FeedEvent::Snapshot {
+            sequence,
+            bids: book.bids,
+            asks: book.asks,
+            event_time: book.exchange_time,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::config::ConnectorConfig;
+    use crate::streaming::{test_server, BookStore, BookStreamer};
+    use crate::types::PriceLevel;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_diff_stream_is_applied_on_top_of_snapshot() {
+        let mut rest = mockito::Server::new_async().await;
+        rest.mock("GET", "/api/v3/depth")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{"lastUpdateId":100,
+                    "bids":[["49990.00","1.0"],["49980.00","2.0"]],
+                    "asks":[["50010.00","1.5"]]}"#,
+            )
+            .create_async()
+            .await;
+        // Binance runs a minute ahead of us
+        rest.mock("GET", "/api/v3/time")
+            .with_body(format!(
+                r#"{{"serverTime":{}}}"#,
+                chrono::Utc::now().timestamp_millis() + 60_000
+            ))
+            .create_async()
+            .await;
+        let (ws_url, _) = test_server::spawn(
+            vec![vec![
+                // Already reflected in the snapshot, must be dropped
+                r#"{"e":"depthUpdate","E":1729260200000,"U":95,"u":100,"b":[["49990.00","9.0"]],"a":[]}"#.into(),
+                r#"{"e":"depthUpdate","E":1729260200100,"U":99,"u":102,"b":[["49990.00","0"]],"a":[["50005.00","0.5"]]}"#.into(),
+                r#"{"e":"depthUpdate","E":1729260200200,"U":103,"u":103,"b":[["49995.00","0.7"]],"a":[]}"#.into(),
+            ]],
+            false,
+        )
+        .await;
+
+        let feed = BinanceDepthFeed::new(
+            ws_url,
+            BinanceExchange::with_config(ConnectorConfig::new(rest.url())).unwrap(),
+        );
+        let store = BookStore::new();
+        let mut streamer = BookStreamer::new(store.clone());
+        let pair = TradingPair::new("BTC", "USDT");
+        streamer.subscribe(Arc::new(feed), pair.clone());
+
+        let expected_bids = vec![
+            PriceLevel::new(dec!(49995.00), dec!(0.7)),
+            PriceLevel::new(dec!(49980.00), dec!(2.0)),
+        ];
+        let mut book = None;
+        for _ in 0..100 {
+            book = store.get("Binance", &pair, 10);
+            if book.as_ref().is_some_and(|b| b.bids == expected_bids) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let book = book.expect("book never synchronised");
+        assert_eq!(book.bids, expected_bids);
+        assert_eq!(
+            book.asks,
+            vec![
+                PriceLevel::new(dec!(50005.00), dec!(0.5)),
+                PriceLevel::new(dec!(50010.00), dec!(1.5)),
+            ]
+        );
+        // Stamped with the last applied event, on our clock
+        let lag = 1729260200200 - 60_000 - book.exchange_time.unwrap().timestamp_millis();
+        assert!(lag.abs() < 1_000, "{lag}ms off");
+    }
+}
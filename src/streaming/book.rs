@@ -0,0 +1,216 @@
+use crate::types::{OrderBook, PriceLevel, TradingPair};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Incremental change to a book covering sequence numbers
+/// `first_sequence..=last_sequence`.
+///
+/// A level with zero quantity removes that price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookUpdate {
+    pub first_sequence: u64,
+    pub last_sequence: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+    /// When the venue emitted the update, if it says
+    pub event_time: Option<DateTime<Utc>>,
+}
+
+/// An update arrived that does not follow on from the book's sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("sequence gap: expected {expected}, received {received}")]
+pub struct SequenceGap {
+    pub expected: u64,
+    pub received: u64,
+}
+
+/// Order book maintained locally from a snapshot plus sequenced updates
+#[derive(Debug, Clone)]
+pub struct LocalOrderBook {
+    exchange: String,
+    pair: TradingPair,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    sequence: u64,
+    synced: bool,
+    /// Venue time of the last snapshot or update applied
+    exchange_time: Option<DateTime<Utc>>,
+    /// When the last snapshot or update was applied
+    applied_at: DateTime<Utc>,
+}
+
+impl LocalOrderBook {
+    pub fn new(exchange: impl Into<String>, pair: TradingPair) -> Self {
+        Self {
+            exchange: exchange.into(),
+            pair,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            sequence: 0,
+            synced: false,
+            exchange_time: None,
+            applied_at: Utc::now(),
+        }
+    }
+
+    /// Replace the book with a full snapshot taken at `sequence`
+    pub fn apply_snapshot(
+        &mut self,
+        sequence: u64,
+        bids: &[PriceLevel],
+        asks: &[PriceLevel],
+        event_time: Option<DateTime<Utc>>,
+    ) {
+        self.bids.clear();
+        self.asks.clear();
+        Self::apply_levels(&mut self.bids, bids);
+        Self::apply_levels(&mut self.asks, asks);
+        self.sequence = sequence;
+        self.synced = true;
+        self.exchange_time = event_time;
+        self.applied_at = Utc::now();
+    }
+
+    /// Apply an incremental update.
+    ///
+    /// Returns `Ok(false)` for updates the book has already seen or that
+    /// arrive before the first snapshot, and an error when updates were
+    /// missed and the book must be resynchronised.
+    pub fn apply_update(&mut self, update: &BookUpdate) -> Result<bool, SequenceGap> {
+        if !self.synced || update.last_sequence <= self.sequence {
+            return Ok(false);
+        }
+        if update.first_sequence > self.sequence + 1 {
+            self.synced = false;
+            return Err(SequenceGap {
+                expected: self.sequence + 1,
+                received: update.first_sequence,
+            });
+        }
+
+        Self::apply_levels(&mut self.bids, &update.bids);
+        Self::apply_levels(&mut self.asks, &update.asks);
+        self.sequence = update.last_sequence;
+        self.exchange_time = update.event_time.or(self.exchange_time);
+        self.applied_at = Utc::now();
+        Ok(true)
+    }
+
+    /// Mark the book as unusable until the next snapshot
+    pub fn invalidate(&mut self) {
+        self.synced = false;
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Copy out the best `depth` levels of each side
+    pub fn to_order_book(&self, depth: usize) -> OrderBook {
+        let level = |(&price, &quantity)| PriceLevel::new(price, quantity);
+        OrderBook {
+            exchange: self.exchange.clone(),
+            pair: self.pair.clone(),
+            bids: self.bids.iter().rev().take(depth).map(level).collect(),
+            asks: self.asks.iter().take(depth).map(level).collect(),
+            exchange_time: self.exchange_time,
+            received_at: self.applied_at,
+        }
+    }
+
+    fn apply_levels(side: &mut BTreeMap<Decimal, Decimal>, levels: &[PriceLevel]) {
+        for level in levels {
+            if level.quantity <= dec!(0) {
+                side.remove(&level.price);
+            } else {
+                side.insert(level.price, level.quantity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(first: u64, last: u64, bids: &[(Decimal, Decimal)]) -> BookUpdate {
+        BookUpdate {
+            first_sequence: first,
+            last_sequence: last,
+            bids: bids.iter().map(|&(p, q)| PriceLevel::new(p, q)).collect(),
+            asks: Vec::new(),
+            event_time: None,
+        }
+    }
+
+    fn synced_book() -> LocalOrderBook {
+        let mut book = LocalOrderBook::new("Test", TradingPair::new("BTC", "USD"));
+        book.apply_snapshot(
+            100,
+            &[
+                PriceLevel::new(dec!(99), dec!(1)),
+                PriceLevel::new(dec!(98), dec!(2)),
+            ],
+            &[PriceLevel::new(dec!(101), dec!(1))],
+            None,
+        );
+        book
+    }
+
+    #[test]
+    fn test_updates_are_sequenced() {
+        let mut book = synced_book();
+
+        // Already covered by the snapshot
+        assert_eq!(
+            book.apply_update(&update(95, 100, &[(dec!(97), dec!(5))])),
+            Ok(false)
+        );
+        // Straddles the snapshot sequence
+        assert_eq!(
+            book.apply_update(&update(
+                99,
+                101,
+                &[(dec!(99), dec!(0)), (dec!(100), dec!(3))]
+            )),
+            Ok(true)
+        );
+        assert_eq!(
+            book.apply_update(&update(102, 102, &[(dec!(98), dec!(4))])),
+            Ok(true)
+        );
+
+        let snapshot = book.to_order_book(10);
+        assert_eq!(
+            snapshot.bids,
+            vec![
+                PriceLevel::new(dec!(100), dec!(3)),
+                PriceLevel::new(dec!(98), dec!(4)),
+            ]
+        );
+        assert_eq!(book.sequence(), 102);
+    }
+
+    #[test]
+    fn test_gap_unsyncs_book() {
+        let mut book = synced_book();
+
+        assert_eq!(
+            book.apply_update(&update(105, 106, &[])),
+            Err(SequenceGap {
+                expected: 101,
+                received: 105
+            })
+        );
+        assert!(!book.is_synced());
+        // Further updates are ignored until a new snapshot arrives
+        assert_eq!(book.apply_update(&update(107, 107, &[])), Ok(false));
+    }
+}
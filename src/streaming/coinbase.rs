@@ -0,0 +1,218 @@
+use super::book::BookUpdate;
+use super::{DepthFeed, FeedEvent};
+use crate::types::{PriceLevel, TradingPair};
+use anyhow::Result;
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Coinbase Advanced Trade market-data WebSocket
+pub const DEFAULT_WS_URL: &str = "wss://advanced-trade-ws.coinbase.com";
+
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    channel: String,
+    sequence_num: u64,
+    #[serde(default)]
+    events: Vec<Level2Event>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Level2Event {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    updates: Vec<Level2Update>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Level2Update {
+    side: String,
+    price_level: String,
+    new_quantity: String,
+}
+
+/// Coinbase `level2` channel.
+///
+/// The channel opens with a full snapshot, so no REST call is needed.
+/// `sequence_num` counts every message on the connection, including
+/// subscription acknowledgements and heartbeats, so each message advances
+/// the book's sequence even when it carries no levels. Message timestamps
+/// are on Coinbase's clock, which the feed cannot measure, so books are aged
+/// from when each message was applied.
+pub struct CoinbaseDepthFeed {
+    ws_url: String,
+}
+
+impl CoinbaseDepthFeed {
+    pub fn new(ws_url: impl Into<String>) -> Self {
+        Self {
+            ws_url: ws_url.into(),
+        }
+    }
+}
+
+impl Default for CoinbaseDepthFeed {
+    fn default() -> Self {
+        Self::new(DEFAULT_WS_URL)
+    }
+}
+
+#[async_trait]
+impl DepthFeed for CoinbaseDepthFeed {
+    fn exchange(&self) -> &str {
+        "Coinbase"
+    }
+
+    fn url(&self, _pair: &TradingPair) -> String {
+        self.ws_url.clone()
+    }
+
+    fn subscribe_message(&self, pair: &TradingPair) -> Option<String> {
+        let product_id = format!("{}-{}", pair.base.to_uppercase(), pair.quote.to_uppercase());
+        Some(
+            serde_json::json!({
+                "type": "subscribe",
+                "product_ids": [product_id],
+                "channel": "level2",
+            })
+            .to_string(),
+        )
+    }
+
+    fn parse(&self, text: &str) -> Result<Vec<FeedEvent>> {
+        let envelope: Envelope = serde_json::from_str(text)?;
+        let sequence = envelope.sequence_num;
+
+        let mut is_snapshot = false;
+        let mut bids = Vec::new();
+        let mut asks = Vec::new();
+        if envelope.channel == "l2_data" {
+            for event in &envelope.events {
+                is_snapshot |= event.kind == "snapshot";
+                for update in &event.updates {
+                    let level = PriceLevel::new(
+                        Decimal::from_str(&update.price_level)?,
+                        Decimal::from_str(&update.new_quantity)?,
+                    );
+                    match update.side.as_str() {
+                        "bid" => bids.push(level),
+                        "offer" | "ask" => asks.push(level),
+                        other => anyhow::bail!("Unknown Coinbase book side {}", other),
+                    }
+                }
+            }
+        }
+
+        Ok(vec![if is_snapshot {
+            FeedEvent::Snapshot {
+                sequence,
+                bids,
+                asks,
+                event_time: None,
+            }
+        } else {
+            FeedEvent::Update(BookUpdate {
+                first_sequence: sequence,
+                last_sequence: sequence,
+                bids,
+                asks,
+                event_time: None,
+            })
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::{test_server, BookStore, BookStreamer};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn l2(sequence: u64, kind: &str, updates: &[(&str, &str, &str)]) -> String {
+        let updates: Vec<_> = updates
+            .iter()
+            .map(|(side, price, quantity)| {
+                serde_json::json!({
+                    "side": side,
+                    "event_time": "2024-01-01T00:00:00Z",
+                    "price_level": price,
+                    "new_quantity": quantity,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "channel": "l2_data",
+            "client_id": "",
+            "timestamp": "2024-01-01T00:00:00Z",
+            "sequence_num": sequence,
+            "events": [{"type": kind, "product_id": "BTC-USD", "updates": updates}],
+        })
+        .to_string()
+    }
+
+    async fn wait_for_sequence(store: &BookStore, pair: &TradingPair, sequence: u64) {
+        for _ in 0..100 {
+            let synced = store
+                .books
+                .read()
+                .unwrap()
+                .values()
+                .any(|b| b.is_synced() && b.sequence() == sequence);
+            if synced {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("{} never reached sequence {}", pair, sequence);
+    }
+
+    #[tokio::test]
+    async fn test_gap_triggers_resubscribe() {
+        let (ws_url, mut connections) = test_server::spawn(
+            vec![
+                vec![
+                    r#"{"channel":"subscriptions","sequence_num":0,"events":[]}"#.into(),
+                    l2(1, "snapshot", &[("bid", "100", "1"), ("offer", "101", "1")]),
+                    l2(2, "update", &[("bid", "100", "0"), ("bid", "99", "2")]),
+                    // Message 3 went missing
+                    l2(4, "update", &[("offer", "101", "0")]),
+                ],
+                vec![
+                    l2(0, "snapshot", &[("bid", "98", "5"), ("offer", "102", "3")]),
+                    r#"{"channel":"heartbeats","sequence_num":1,"events":[]}"#.into(),
+                    r#"{"channel":"heartbeats","sequence_num":2,"events":[]}"#.into(),
+                    l2(3, "update", &[("offer", "101.5", "0.5")]),
+                ],
+            ],
+            true,
+        )
+        .await;
+
+        let store = BookStore::new();
+        let mut streamer =
+            BookStreamer::new(store.clone()).with_reconnect_delay(Duration::from_millis(10));
+        let pair = TradingPair::new("BTC", "USD");
+        streamer.subscribe(Arc::new(CoinbaseDepthFeed::new(ws_url)), pair.clone());
+
+        let subscribe = connections.recv().await.unwrap().unwrap();
+        assert!(subscribe.contains(r#""product_ids":["BTC-USD"]"#));
+        assert!(subscribe.contains(r#""channel":"level2""#));
+        // The gap forces a second connection
+        assert!(connections.recv().await.unwrap().is_some());
+
+        wait_for_sequence(&store, &pair, 3).await;
+        let book = store.get("Coinbase", &pair, 10).unwrap();
+        assert_eq!(book.bids, vec![PriceLevel::new(dec!(98), dec!(5))]);
+        assert_eq!(
+            book.asks,
+            vec![
+                PriceLevel::new(dec!(101.5), dec!(0.5)),
+                PriceLevel::new(dec!(102), dec!(3)),
+            ]
+        );
+    }
+}
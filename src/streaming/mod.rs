@@ -0,0 +1,363 @@
+pub mod binance;
+pub mod book;
+pub mod coinbase;
+
+use crate::exchanges::fees::FeeSchedule;
+use crate::exchanges::{Exchange, ExchangeError};
+use crate::types::{
+    Balance, InstrumentRules, OrderBook, OrderReport, OrderRequest, PairListing, PriceLevel,
+    TradingPair, TradingStatus,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use book::{BookUpdate, LocalOrderBook};
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+/// A decoded depth-feed message
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedEvent {
+    /// Full book as of `sequence`
+    Snapshot {
+        sequence: u64,
+        bids: Vec<PriceLevel>,
+        asks: Vec<PriceLevel>,
+        /// When the venue took the snapshot, if it says
+        event_time: Option<DateTime<Utc>>,
+    },
+    /// Incremental change
+    Update(BookUpdate),
+}
+
+/// Venue-specific depth WebSocket protocol
+#[async_trait]
+pub trait DepthFeed: Send + Sync {
+    /// Exchange name books are stored under; matches the REST connector
+    fn exchange(&self) -> &str;
+
+    /// WebSocket URL to connect to for `pair`
+    fn url(&self, pair: &TradingPair) -> String;
+
+    /// Message to send after connecting, if the venue needs one
+    fn subscribe_message(&self, pair: &TradingPair) -> Option<String>;
+
+    /// Decode a text frame into zero or more events
+    fn parse(&self, text: &str) -> Result<Vec<FeedEvent>>;
+
+    /// Snapshot fetched out of band, for venues whose stream only carries
+    /// diffs. Called after subscribing so no diff is missed.
+    async fn snapshot(&self, _pair: &TradingPair) -> Result<Option<FeedEvent>> {
+        Ok(None)
+    }
+}
+
+/// Shared in-memory books keyed by exchange and pair
+#[derive(Clone, Default)]
+pub struct BookStore {
+    books: Arc<RwLock<HashMap<(String, TradingPair), LocalOrderBook>>>,
+}
+
+impl BookStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Best `depth` levels of a synchronised book, if one is available
+    pub fn get(&self, exchange: &str, pair: &TradingPair, depth: usize) -> Option<OrderBook> {
+        let books = self.books.read().unwrap();
+        books
+            .get(&(exchange.to_string(), pair.clone()))
+            .filter(|book| book.is_synced())
+            .map(|book| book.to_order_book(depth))
+    }
+
+    fn apply(&self, exchange: &str, pair: &TradingPair, event: &FeedEvent) -> Result<()> {
+        let mut books = self.books.write().unwrap();
+        let book = books
+            .entry((exchange.to_string(), pair.clone()))
+            .or_insert_with(|| LocalOrderBook::new(exchange, pair.clone()));
+        match event {
+            FeedEvent::Snapshot {
+                sequence,
+                bids,
+                asks,
+                event_time,
+            } => book.apply_snapshot(*sequence, bids, asks, *event_time),
+            FeedEvent::Update(update) => {
+                book.apply_update(update)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn invalidate(&self, exchange: &str, pair: &TradingPair) {
+        let mut books = self.books.write().unwrap();
+        if let Some(book) = books.get_mut(&(exchange.to_string(), pair.clone())) {
+            book.invalidate();
+        }
+    }
+}
+
+/// Runs one background task per subscribed feed, keeping a [`BookStore`]
+/// up to date
+pub struct BookStreamer {
+    store: BookStore,
+    reconnect_delay: Duration,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl BookStreamer {
+    pub fn new(store: BookStore) -> Self {
+        Self {
+            store,
+            reconnect_delay: Duration::from_secs(1),
+            tasks: Vec::new(),
+        }
+    }
+
+    pub fn with_reconnect_delay(mut self, reconnect_delay: Duration) -> Self {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    pub fn store(&self) -> &BookStore {
+        &self.store
+    }
+
+    /// Start maintaining the book for `pair` from `feed`.
+    ///
+    /// The task reconnects and resynchronises after disconnects and
+    /// sequence gaps until the streamer is dropped.
+    pub fn subscribe(&mut self, feed: Arc<dyn DepthFeed>, pair: TradingPair) {
+        let store = self.store.clone();
+        let reconnect_delay = self.reconnect_delay;
+        self.tasks.push(tokio::spawn(async move {
+            loop {
+                if let Err(e) = stream_book(feed.as_ref(), &pair, &store).await {
+                    log::warn!("{} {} depth stream: {:#}", feed.exchange(), pair, e);
+                }
+                store.invalidate(feed.exchange(), &pair);
+                tokio::time::sleep(reconnect_delay).await;
+            }
+        }));
+    }
+}
+
+impl Drop for BookStreamer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Connect, subscribe, synchronise and apply events until the connection
+/// drops or a sequence gap forces a resync
+async fn stream_book(feed: &dyn DepthFeed, pair: &TradingPair, store: &BookStore) -> Result<()> {
+    let (mut socket, _) = tokio_tungstenite::connect_async(feed.url(pair))
+        .await
+        .context("Failed to connect")?;
+    if let Some(subscribe) = feed.subscribe_message(pair) {
+        socket
+            .send(Message::Text(subscribe))
+            .await
+            .context("Failed to subscribe")?;
+    }
+    // Diffs received while the snapshot is in flight queue up on the socket
+    if let Some(snapshot) = feed.snapshot(pair).await? {
+        store.apply(feed.exchange(), pair, &snapshot)?;
+    }
+
+    while let Some(message) = socket.next().await {
+        let text = match message.context("Connection error")? {
+            Message::Text(text) => text,
+            Message::Ping(payload) => {
+                socket.send(Message::Pong(payload)).await?;
+                continue;
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        for event in feed.parse(&text)? {
+            store.apply(feed.exchange(), pair, &event)?;
+        }
+    }
+
+    anyhow::bail!("Connection closed")
+}
+
+/// Exchange whose liquidity is served from streamed books.
+///
+/// Falls back to the wrapped connector's REST call while the local book is
+/// not synchronised; every other call is delegated. Venues without a
+/// [`DepthFeed`] (Kraken, whose book channel carries checksums rather than
+/// sequence numbers) are simply left unwrapped.
+pub struct StreamingExchange {
+    inner: Box<dyn Exchange>,
+    store: BookStore,
+    depth: usize,
+}
+
+impl StreamingExchange {
+    pub fn new(inner: Box<dyn Exchange>, store: BookStore, depth: usize) -> Self {
+        Self {
+            inner,
+            store,
+            depth,
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for StreamingExchange {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn get_liquidity(&self, pair: &TradingPair) -> Result<OrderBook, ExchangeError> {
+        match self.store.get(self.inner.name(), pair, self.depth) {
+            Some(book) => Ok(book),
+            None => self.inner.get_liquidity(pair).await,
+        }
+    }
+
+    async fn supports_pair(&self, pair: &TradingPair) -> bool {
+        self.inner.supports_pair(pair).await
+    }
+
+    async fn list_pairs(&self) -> Result<Vec<PairListing>> {
+        self.inner.list_pairs().await
+    }
+
+    async fn pair_status(
+        &self,
+        pair: &TradingPair,
+    ) -> Result<Option<TradingStatus>, ExchangeError> {
+        self.inner.pair_status(pair).await
+    }
+
+    fn fee_schedule(&self) -> FeeSchedule {
+        self.inner.fee_schedule()
+    }
+
+    fn max_quote_age(&self) -> Option<Duration> {
+        self.inner.max_quote_age()
+    }
+
+    async fn instrument_rules(&self, pair: &TradingPair) -> Result<InstrumentRules, ExchangeError> {
+        self.inner.instrument_rules(pair).await
+    }
+
+    async fn place_order(&self, request: &OrderRequest) -> Result<OrderReport> {
+        self.inner.place_order(request).await
+    }
+
+    async fn cancel_order(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        self.inner.cancel_order(pair, order_id).await
+    }
+
+    async fn get_order_status(&self, pair: &TradingPair, order_id: &str) -> Result<OrderReport> {
+        self.inner.get_order_status(pair, order_id).await
+    }
+
+    async fn get_open_orders(&self, pair: &TradingPair) -> Result<Vec<OrderReport>> {
+        self.inner.get_open_orders(pair).await
+    }
+
+    async fn find_order(
+        &self,
+        pair: &TradingPair,
+        client_order_id: &str,
+    ) -> Result<Option<OrderReport>> {
+        self.inner.find_order(pair, client_order_id).await
+    }
+
+    async fn get_balances(&self) -> Result<Vec<Balance>> {
+        self.inner.get_balances().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::mock::MockExchange;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn test_streaming_exchange_prefers_synced_book() {
+        let pair = TradingPair::new("BTC", "USD");
+        let rest = MockExchange::new("Venue").with_book(
+            pair.clone(),
+            &[(dec!(99), dec!(1))],
+            &[(dec!(101), dec!(1))],
+        );
+        let store = BookStore::new();
+        let exchange = StreamingExchange::new(Box::new(rest), store.clone(), 10);
+
+        // Nothing streamed yet: REST answers
+        let book = exchange.get_liquidity(&pair).await.unwrap();
+        assert_eq!(book.best_ask().unwrap().price, dec!(101));
+
+        let snapshot = FeedEvent::Snapshot {
+            sequence: 1,
+            bids: vec![PriceLevel::new(dec!(100), dec!(2))],
+            asks: vec![PriceLevel::new(dec!(100.5), dec!(2))],
+            event_time: None,
+        };
+        store.apply("Venue", &pair, &snapshot).unwrap();
+        let book = exchange.get_liquidity(&pair).await.unwrap();
+        assert_eq!(book.best_ask().unwrap().price, dec!(100.5));
+
+        // A dropped stream falls back to REST again
+        store.invalidate("Venue", &pair);
+        let book = exchange.get_liquidity(&pair).await.unwrap();
+        assert_eq!(book.best_ask().unwrap().price, dec!(101));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_server {
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::Message;
+
+    /// Local WebSocket stand-in: each accepted connection reports the first
+    /// client frame it receives (if any) and then replays the next script
+    pub async fn spawn(
+        scripts: Vec<Vec<String>>,
+        expect_subscribe: bool,
+    ) -> (String, mpsc::UnboundedReceiver<Option<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (connections, received) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            for script in scripts {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                let subscribe = if expect_subscribe {
+                    match socket.next().await {
+                        Some(Ok(Message::Text(text))) => Some(text),
+                        _ => None,
+                    }
+                } else {
+                    None
+                };
+                let _ = connections.send(subscribe);
+                for frame in script {
+                    socket.send(Message::Text(frame)).await.unwrap();
+                }
+                // Hold the connection open until the client goes away
+                while let Some(Ok(_)) = socket.next().await {}
+            }
+        });
+
+        (url, received)
+    }
+}
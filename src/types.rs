@@ -1,6 +1,9 @@
+use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
+use std::time::Duration;
 
 /// Represents a trading pair (e.g., BTC/USD)
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -25,7 +28,7 @@ impl fmt::Display for TradingPair {
 }
 
 /// Order side: Buy or Sell
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OrderSide {
     Buy,
     Sell,
@@ -57,6 +60,100 @@ pub struct Liquidity {
     pub bid_quantity: Decimal,
     pub ask_price: Decimal,
     pub ask_quantity: Decimal,
+    /// When the venue produced the quote, if it says
+    #[serde(default)]
+    pub exchange_time: Option<DateTime<Utc>>,
+    /// When we received the quote
+    #[serde(default = "Utc::now")]
+    pub received_at: DateTime<Utc>,
+}
+
+/// A single price level in an order book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceLevel {
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+impl PriceLevel {
+    pub fn new(price: Decimal, quantity: Decimal) -> Self {
+        Self { price, quantity }
+    }
+}
+
+/// Multi-level order book snapshot from an exchange.
+///
+/// Bids are sorted best (highest) first, asks best (lowest) first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderBook {
+    pub exchange: String,
+    pub pair: TradingPair,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+    /// When the venue produced the book, if it says
+    #[serde(default)]
+    pub exchange_time: Option<DateTime<Utc>>,
+    /// When we received the book
+    #[serde(default = "Utc::now")]
+    pub received_at: DateTime<Utc>,
+}
+
+impl OrderBook {
+    /// A book received just now, without a venue timestamp
+    pub fn new(
+        exchange: impl Into<String>,
+        pair: TradingPair,
+        bids: Vec<PriceLevel>,
+        asks: Vec<PriceLevel>,
+    ) -> Self {
+        Self {
+            exchange: exchange.into(),
+            pair,
+            bids,
+            asks,
+            exchange_time: None,
+            received_at: Utc::now(),
+        }
+    }
+
+    pub fn with_exchange_time(mut self, exchange_time: Option<DateTime<Utc>>) -> Self {
+        self.exchange_time = exchange_time;
+        self
+    }
+
+    /// How old the book is at `now`.
+    ///
+    /// Measured from the venue's timestamp when there is one, since the
+    /// book may have sat in a socket buffer or cache before we read it.
+    pub fn quote_age(&self, now: DateTime<Utc>) -> Duration {
+        (now - self.exchange_time.unwrap_or(self.received_at))
+            .to_std()
+            .unwrap_or_default()
+    }
+
+    pub fn best_bid(&self) -> Option<&PriceLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&PriceLevel> {
+        self.asks.first()
+    }
+
+    /// Collapse the book to its top-of-book liquidity
+    pub fn top_of_book(&self) -> Option<Liquidity> {
+        let bid = self.best_bid()?;
+        let ask = self.best_ask()?;
+        Some(Liquidity {
+            exchange: self.exchange.clone(),
+            pair: self.pair.clone(),
+            bid_price: bid.price,
+            bid_quantity: bid.quantity,
+            ask_price: ask.price,
+            ask_quantity: ask.quantity,
+            exchange_time: self.exchange_time,
+            received_at: self.received_at,
+        })
+    }
 }
 
 /// Represents a split order sent to a specific exchange
@@ -65,6 +162,305 @@ pub struct OrderSplit {
     pub exchange: String,
     pub quantity: Decimal,
     pub expected_price: Decimal,
+    /// Taker fees expected for this split, in quote currency
+    pub expected_fee: Decimal,
+    /// Taker fee rate the venue charges on this split, where the router
+    /// knows it; zero for venues whose fee is in the price
+    #[serde(default)]
+    pub fee_rate: Option<Decimal>,
+    /// Set when the venue quotes the pair in another currency; the
+    /// expected price and fee are then converted to the order's quote
+    #[serde(default)]
+    pub conversion: Option<QuoteConversion>,
+}
+
+/// How a venue quoting the base in another currency maps onto an order,
+/// e.g. a BTC/USDT book used for a BTC/USD order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuoteConversion {
+    /// The venue's own pair
+    pub pair: TradingPair,
+    /// Units of the order's quote per unit of the venue's quote
+    pub rate: Decimal,
+}
+
+/// One leg of a synthetic route: an ordinary routing of `quantity` on
+/// `pair` across the venues in `splits`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntheticLeg {
+    pub pair: TradingPair,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub splits: Vec<OrderSplit>,
+}
+
+/// Part of an order filled through an intermediate asset, such as buying
+/// ETH/USD as ETH/BTC plus BTC/USD
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntheticRoute {
+    /// The intermediate asset
+    pub via: String,
+    /// Base asset the route buys or sells
+    pub quantity: Decimal,
+    /// Price per unit of base in the order's quote asset, with the fees of
+    /// every leg included
+    pub expected_price: Decimal,
+    /// Legs in the order they must run: the leg that funds the next
+    /// comes first
+    pub legs: Vec<SyntheticLeg>,
+}
+
+/// A request for a firm quote, broadcast to RFQ venues
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuoteRequest {
+    pub request_id: String,
+    pub pair: TradingPair,
+    /// Side of our order: a buy asks market makers for their offer
+    pub side: OrderSide,
+    pub quantity: Decimal,
+}
+
+/// A market maker's firm price for a block, good until it expires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RfqQuote {
+    pub quote_id: String,
+    pub request_id: String,
+    pub venue: String,
+    pub pair: TradingPair,
+    pub side: OrderSide,
+    /// Size the quote is firm for; it is accepted whole or not at all
+    pub quantity: Decimal,
+    /// Price per unit of base, all-in
+    pub price: Decimal,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl RfqQuote {
+    /// Time left to accept the quote at `now`
+    pub fn time_to_expiry(&self, now: DateTime<Utc>) -> Duration {
+        (self.expires_at - now).to_std().unwrap_or_default()
+    }
+}
+
+/// A passive order proposed for the unfilled remainder of a limit order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestingOrder {
+    pub exchange: String,
+    pub quantity: Decimal,
+    pub price: Decimal,
+}
+
+/// Why a venue was left out of a routing decision
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkipReason {
+    /// The venue does not list the pair
+    UnsupportedPair,
+    /// The venue did not answer before its deadline
+    Timeout,
+    /// The venue is throttling our requests
+    RateLimited,
+    /// The venue is down for maintenance
+    Maintenance,
+    /// The venue returned an error
+    Error(String),
+    /// The caller asked for the venue to be left out
+    Excluded,
+    /// The venue lists the pair but is not accepting this order for it
+    NotTrading(TradingStatus),
+    /// The venue's circuit breaker is open after repeated failures
+    CircuitOpen,
+    /// The book was crossed or had stopped updating
+    InvalidBook(String),
+    /// The freshest quote available was older than the router accepts
+    StaleQuote(Duration),
+    /// The RFQ venue chose not to quote
+    QuoteDeclined,
+    /// The RFQ venue's quote expired too soon to be accepted
+    QuoteExpired,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::UnsupportedPair => write!(f, "pair not supported"),
+            SkipReason::Timeout => write!(f, "timed out"),
+            SkipReason::RateLimited => write!(f, "rate limited"),
+            SkipReason::Maintenance => write!(f, "under maintenance"),
+            SkipReason::Error(e) => write!(f, "error: {}", e),
+            SkipReason::Excluded => write!(f, "excluded"),
+            SkipReason::NotTrading(status) => write!(f, "pair is {}", status),
+            SkipReason::CircuitOpen => write!(f, "circuit open"),
+            SkipReason::InvalidBook(problem) => write!(f, "unusable book: {}", problem),
+            SkipReason::StaleQuote(age) => write!(f, "quote {}ms old", age.as_millis()),
+            SkipReason::QuoteDeclined => write!(f, "declined to quote"),
+            SkipReason::QuoteExpired => write!(f, "quote expired"),
+        }
+    }
+}
+
+/// A venue that was not used for routing, and why
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedVenue {
+    pub exchange: String,
+    pub reason: SkipReason,
+}
+
+/// Whether a venue currently accepts orders for a pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TradingStatus {
+    Trading,
+    /// Only limit orders are accepted
+    LimitOnly,
+    /// Only orders that rest on the book are accepted; nothing can be taken
+    PostOnly,
+    /// Trading is paused; at most cancels are accepted
+    Halted,
+    /// The listing has been withdrawn
+    Delisted,
+}
+
+impl TradingStatus {
+    /// Whether an order of `order_type` can take liquidity in this state
+    pub fn accepts(&self, order_type: OrderType) -> bool {
+        match self {
+            TradingStatus::Trading => true,
+            TradingStatus::LimitOnly => order_type == OrderType::Limit,
+            TradingStatus::PostOnly | TradingStatus::Halted | TradingStatus::Delisted => false,
+        }
+    }
+}
+
+impl fmt::Display for TradingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradingStatus::Trading => write!(f, "trading"),
+            TradingStatus::LimitOnly => write!(f, "limit only"),
+            TradingStatus::PostOnly => write!(f, "post only"),
+            TradingStatus::Halted => write!(f, "halted"),
+            TradingStatus::Delisted => write!(f, "delisted"),
+        }
+    }
+}
+
+/// A pair as listed by a venue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairListing {
+    pub pair: TradingPair,
+    /// The venue's own name for the pair, e.g. `BTCUSDT` or `XXBTZUSD`
+    pub symbol: String,
+    pub status: TradingStatus,
+}
+
+/// Order constraints a venue enforces for one instrument.
+///
+/// Zero increments and minimums mean the venue imposes none.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct InstrumentRules {
+    /// Price increment
+    pub tick_size: Decimal,
+    /// Quantity increment
+    pub step_size: Decimal,
+    pub min_quantity: Decimal,
+    pub max_quantity: Option<Decimal>,
+    /// Minimum quantity × price
+    pub min_notional: Decimal,
+}
+
+impl InstrumentRules {
+    pub fn new(tick_size: Decimal, step_size: Decimal) -> Self {
+        Self {
+            tick_size,
+            step_size,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_min_quantity(mut self, min_quantity: Decimal) -> Self {
+        self.min_quantity = min_quantity;
+        self
+    }
+
+    pub fn with_max_quantity(mut self, max_quantity: Decimal) -> Self {
+        self.max_quantity = Some(max_quantity);
+        self
+    }
+
+    pub fn with_min_notional(mut self, min_notional: Decimal) -> Self {
+        self.min_notional = min_notional;
+        self
+    }
+
+    /// Round a quantity down to the step, capped at the maximum
+    pub fn round_quantity(&self, quantity: Decimal) -> Decimal {
+        let quantity = match self.max_quantity {
+            Some(max) => quantity.min(max),
+            None => quantity,
+        };
+        floor_to_increment(quantity, self.step_size)
+    }
+
+    /// Round a price to the tick without crossing it: down for buys, up
+    /// for sells
+    pub fn round_price(&self, price: Decimal, side: OrderSide) -> Decimal {
+        if self.tick_size <= Decimal::ZERO {
+            return price;
+        }
+        let ticks = price / self.tick_size;
+        let ticks = match side {
+            OrderSide::Buy => ticks.floor(),
+            OrderSide::Sell => ticks.ceil(),
+        };
+        (ticks * self.tick_size).normalize()
+    }
+
+    /// Whether the venue would accept `quantity` at `price` as is
+    pub fn accepts(&self, quantity: Decimal, price: Decimal) -> bool {
+        quantity > Decimal::ZERO
+            && quantity == self.round_quantity(quantity)
+            && quantity >= self.min_quantity
+            && quantity * price >= self.min_notional
+    }
+
+    /// The largest quantity up to `quantity` the venue would accept at
+    /// `price`, or zero if even that falls under the minimums
+    pub fn sendable_quantity(&self, quantity: Decimal, price: Decimal) -> Decimal {
+        let rounded = self.round_quantity(quantity);
+        if self.accepts(rounded, price) {
+            rounded
+        } else {
+            Decimal::ZERO
+        }
+    }
+
+    /// Smallest quantity the venue accepts at `price`
+    pub fn min_sendable_quantity(&self, price: Decimal) -> Decimal {
+        let mut minimum = self.min_quantity.max(self.step_size);
+        if price > Decimal::ZERO && self.min_notional > Decimal::ZERO {
+            minimum = minimum.max(self.min_notional / price);
+        }
+        if self.step_size > Decimal::ZERO {
+            minimum = (minimum / self.step_size).ceil() * self.step_size;
+        }
+        minimum
+    }
+}
+
+fn floor_to_increment(value: Decimal, increment: Decimal) -> Decimal {
+    if increment <= Decimal::ZERO {
+        value
+    } else {
+        ((value / increment).floor() * increment).normalize()
+    }
+}
+
+/// A venue whose split was capped by the funds held there
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceLimit {
+    pub exchange: String,
+    /// Asset the order spends: quote for buys, base for sells
+    pub asset: String,
+    /// Spendable balance at routing time
+    pub available: Decimal,
 }
 
 /// Routing result with optimal splits
@@ -72,9 +468,107 @@ pub struct OrderSplit {
 pub struct RoutingResult {
     pub original_order: Order,
     pub splits: Vec<OrderSplit>,
+    /// Firm RFQ quotes to accept, filling part or all of the order
+    /// alongside the splits
+    #[serde(default)]
+    pub quotes: Vec<RfqQuote>,
+    /// Parts of the order routed through intermediate assets
+    #[serde(default)]
+    pub synthetic_routes: Vec<SyntheticRoute>,
+    /// Quantity routed to venues, quotes and synthetic routes
     pub total_quantity: Decimal,
     pub average_price: Decimal,
     pub estimated_slippage: Decimal,
+    /// Quantity that could not be routed within the limit price
+    pub unfilled_quantity: Decimal,
+    /// Suggested passive order for `unfilled_quantity`, if requested
+    pub resting_order: Option<RestingOrder>,
+    /// Venues that could not contribute liquidity to this routing
+    pub skipped_venues: Vec<SkippedVenue>,
+    /// Venues that got less than their liquidity allowed for lack of funds
+    pub balance_limited_venues: Vec<BalanceLimit>,
+    /// Venues priced from a recently cached book because they were
+    /// throttling requests
+    pub cached_venues: Vec<String>,
+}
+
+/// Funds held in one asset on one exchange
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balance {
+    pub asset: String,
+    pub total: Decimal,
+    /// Held against open orders
+    pub reserved: Decimal,
+}
+
+impl Balance {
+    pub fn new(asset: impl Into<String>, total: Decimal, reserved: Decimal) -> Self {
+        Self {
+            asset: asset.into().to_uppercase(),
+            total,
+            reserved,
+        }
+    }
+
+    /// Funds free to back a new order
+    pub fn available(&self) -> Decimal {
+        (self.total - self.reserved).max(Decimal::ZERO)
+    }
+}
+
+/// Balances per exchange at a point in time.
+///
+/// Exchanges absent from the snapshot are treated as unconstrained; an
+/// exchange that is present but holds none of an asset has nothing to spend.
+#[derive(Debug, Clone, Default)]
+pub struct BalanceSnapshot {
+    venues: HashMap<String, HashMap<String, Balance>>,
+}
+
+impl BalanceSnapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record every balance held on `exchange`, replacing earlier ones
+    pub fn insert(&mut self, exchange: impl Into<String>, balances: Vec<Balance>) {
+        let balances = balances
+            .into_iter()
+            .map(|balance| (balance.asset.clone(), balance))
+            .collect();
+        self.venues.insert(exchange.into(), balances);
+    }
+
+    pub fn with_balances(mut self, exchange: impl Into<String>, balances: Vec<Balance>) -> Self {
+        self.insert(exchange, balances);
+        self
+    }
+
+    /// Spendable amount of `asset` on `exchange`, or `None` if the
+    /// exchange's balances are unknown
+    pub fn available(&self, exchange: &str, asset: &str) -> Option<Decimal> {
+        let balances = self.venues.get(exchange)?;
+        Some(
+            balances
+                .get(&asset.to_uppercase())
+                .map(Balance::available)
+                .unwrap_or(Decimal::ZERO),
+        )
+    }
+
+    /// Hold back `amount` of `asset` on `exchange`, e.g. what an order
+    /// spent since the snapshot was taken. Exchanges with unknown balances
+    /// stay unconstrained.
+    pub fn spend(&mut self, exchange: &str, asset: &str, amount: Decimal) {
+        let Some(balances) = self.venues.get_mut(exchange) else {
+            return;
+        };
+        let asset = asset.to_uppercase();
+        balances
+            .entry(asset.clone())
+            .or_insert_with(|| Balance::new(asset, Decimal::ZERO, Decimal::ZERO))
+            .reserved += amount;
+    }
 }
 
 /// Execution result
@@ -87,3 +581,118 @@ pub struct ExecutionResult {
     pub fees: Decimal,
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
+
+/// A child order to be sent to a single exchange
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderRequest {
+    pub client_order_id: String,
+    pub pair: TradingPair,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub quantity: Decimal,
+    pub limit_price: Option<Decimal>,
+}
+
+impl OrderRequest {
+    /// Build the child order that sends `split` for the parent `order`,
+    /// rounded to the venue's `rules`: the quantity down to the step and
+    /// the limit price to the tick on the passive side
+    pub fn from_split(
+        order: &Order,
+        split: &OrderSplit,
+        rules: &InstrumentRules,
+        client_order_id: impl Into<String>,
+    ) -> Self {
+        // Venues quoting another currency trade their own pair, with the
+        // limit price converted into that currency
+        let (pair, limit_price) = match &split.conversion {
+            Some(conversion) => (
+                conversion.pair.clone(),
+                order.limit_price.map(|price| price / conversion.rate),
+            ),
+            None => (order.pair.clone(), order.limit_price),
+        };
+        Self {
+            client_order_id: client_order_id.into(),
+            pair,
+            side: order.side,
+            order_type: order.order_type,
+            quantity: rules.round_quantity(split.quantity),
+            limit_price: limit_price.map(|price| rules.round_price(price, order.side)),
+        }
+    }
+}
+
+/// Lifecycle state of an order at an exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderStatus {
+    New,
+    PartiallyFilled,
+    /// Cancel requested but not yet confirmed; the order can still fill
+    PendingCancel,
+    Filled,
+    Cancelled,
+    Rejected,
+    Expired,
+    /// The venue gave no definite answer and the order could not be found;
+    /// it may be working or filled
+    Unknown,
+}
+
+impl OrderStatus {
+    /// Whether the order can still trade
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::New | OrderStatus::PartiallyFilled | OrderStatus::PendingCancel
+        )
+    }
+}
+
+/// A single trade against an order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fill {
+    pub price: Decimal,
+    pub quantity: Decimal,
+    /// Fee charged for this fill, in quote currency
+    pub fee: Decimal,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// An exchange's view of an order, returned when placing, cancelling or
+/// querying it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderReport {
+    pub order_id: String,
+    pub client_order_id: String,
+    pub exchange: String,
+    pub pair: TradingPair,
+    pub side: OrderSide,
+    pub status: OrderStatus,
+    pub quantity: Decimal,
+    pub filled_quantity: Decimal,
+    pub average_price: Decimal,
+    pub fees: Decimal,
+    /// Individual fills, when the exchange reports them
+    pub fills: Vec<Fill>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl OrderReport {
+    /// Quantity still working at the exchange
+    pub fn remaining_quantity(&self) -> Decimal {
+        self.quantity - self.filled_quantity
+    }
+
+    /// Summarize the filled part of the order as an execution result
+    pub fn execution_result(&self) -> ExecutionResult {
+        ExecutionResult {
+            order_id: self.order_id.clone(),
+            exchange: self.exchange.clone(),
+            executed_quantity: self.filled_quantity,
+            executed_price: self.average_price,
+            fees: self.fees,
+            timestamp: self.updated_at,
+        }
+    }
+}